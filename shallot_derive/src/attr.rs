@@ -0,0 +1,433 @@
+//! Parsing of `#[shallot(...)]` attributes and the input shapes they apply to.
+
+use quote::format_ident;
+use syn::ext::IdentExt;
+use syn::punctuated::Punctuated;
+use syn::{
+    Attribute, Data, DataEnum, DeriveInput, Field, Fields, FieldsNamed, GenericArgument, Generics,
+    Ident, LitStr, Member, Path, PathArguments, Token, Type, TypePath, WherePredicate,
+};
+
+/// Attributes on the type being derived.
+pub(crate) struct Container<'a> {
+    /// With `#[shallot(discriminant)]`, the integer type of the enum's
+    /// discriminants, which it is written as instead of variant names.
+    pub(crate) discriminant: Option<Ident>,
+    /// With `#[shallot(transparent)]`, the only field of the struct, which
+    /// it is written as.
+    pub(crate) transparent: Option<(Member, &'a Type)>,
+    /// With `#[shallot(rename_all = "...")]`, the case convention of the
+    /// names of a struct's fields or an enum's variants.
+    pub(crate) rename_all: Option<RenameRule>,
+    /// With `#[shallot(field_order = "...")]`, the variant of
+    /// `shallot::serialize::FieldOrder` that fields are written in.
+    pub(crate) field_order: Option<Ident>,
+}
+
+impl<'a> Container<'a> {
+    pub(crate) fn parse(input: &'a DeriveInput) -> syn::Result<Self> {
+        let mut discriminant = None;
+        let mut transparent = None;
+        let mut rename_all = None;
+        let mut field_order = None;
+        for attr in shallot_attrs(&input.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("discriminant") {
+                    discriminant = Some(discriminant_type(input, &meta.path)?);
+                    Ok(())
+                } else if meta.path.is_ident("transparent") {
+                    transparent = Some(only_field(input, &meta.path)?);
+                    Ok(())
+                } else if meta.path.is_ident("rename_all") {
+                    rename_all = Some(RenameRule::parse(&meta.value()?.parse()?)?);
+                    Ok(())
+                } else if meta.path.is_ident("field_order") {
+                    let literal: LitStr = meta.value()?.parse()?;
+                    let order = match literal.value().as_str() {
+                        "declaration" => "Declaration",
+                        "alphabetical" => "Alphabetical",
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                literal,
+                                "expected \"declaration\" or \"alphabetical\"",
+                            ))
+                        }
+                    };
+                    field_order = Some(Ident::new(order, literal.span()));
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown shallot attribute"))
+                }
+            })?;
+        }
+        Ok(Self {
+            discriminant,
+            transparent,
+            rename_all,
+            field_order,
+        })
+    }
+}
+
+/// A case convention for `rename_all`, applied to Rust identifiers.
+#[derive(Clone, Copy)]
+pub(crate) enum RenameRule {
+    Camel,
+    Snake,
+    Kebab,
+    ScreamingSnake,
+}
+
+impl RenameRule {
+    fn parse(literal: &LitStr) -> syn::Result<Self> {
+        match literal.value().as_str() {
+            "camelCase" => Ok(Self::Camel),
+            "snake_case" => Ok(Self::Snake),
+            "kebab-case" => Ok(Self::Kebab),
+            "SCREAMING_SNAKE_CASE" => Ok(Self::ScreamingSnake),
+            _ => Err(syn::Error::new_spanned(
+                literal,
+                "expected \"camelCase\", \"snake_case\", \"kebab-case\", or \"SCREAMING_SNAKE_CASE\"",
+            )),
+        }
+    }
+
+    /// Respell `ident`, a snake_case field or PascalCase variant name.
+    pub(crate) fn apply(self, ident: &str) -> String {
+        // Words end at underscores and before an uppercase letter that
+        // follows a lowercase letter or digit.
+        let mut words: Vec<String> = Vec::new();
+        let mut previous_lower = false;
+        for c in ident.chars() {
+            if c == '_' {
+                words.push(String::new());
+            } else {
+                if c.is_uppercase() && previous_lower || words.is_empty() {
+                    words.push(String::new());
+                }
+                words.last_mut().unwrap().push(c);
+            }
+            previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        }
+        words.retain(|word| !word.is_empty());
+        match self {
+            Self::Camel => {
+                let mut name = String::new();
+                for (index, word) in words.iter().enumerate() {
+                    let lower = word.to_lowercase();
+                    let mut chars = lower.chars();
+                    if index > 0 {
+                        name.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                    }
+                    name.extend(chars);
+                }
+                name
+            }
+            Self::Snake => words.join("_").to_lowercase(),
+            Self::Kebab => words.join("-").to_lowercase(),
+            Self::ScreamingSnake => words.join("_").to_uppercase(),
+        }
+    }
+}
+
+/// The member and type of the only field of a struct.
+fn only_field<'a>(input: &'a DeriveInput, path: &Path) -> syn::Result<(Member, &'a Type)> {
+    let field = match &input.data {
+        Data::Struct(data) if data.fields.len() == 1 => data.fields.iter().next(),
+        _ => None,
+    };
+    let field = field.ok_or_else(|| {
+        syn::Error::new_spanned(
+            path,
+            "`transparent` only applies to structs with exactly one field",
+        )
+    })?;
+    let member = match &field.ident {
+        Some(ident) => Member::Named(ident.clone()),
+        None => Member::Unnamed(0.into()),
+    };
+    Ok((member, &field.ty))
+}
+
+/// The `#[repr]` integer type of a fieldless enum, or `isize` by default.
+fn discriminant_type(input: &DeriveInput, path: &Path) -> syn::Result<Ident> {
+    let fieldless = match &input.data {
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .all(|variant| matches!(variant.fields, Fields::Unit)),
+        _ => false,
+    };
+    if !fieldless {
+        return Err(syn::Error::new_spanned(
+            path,
+            "`discriminant` only applies to enums whose variants are all units",
+        ));
+    }
+    const INTEGERS: [&str; 12] = [
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+    let mut repr = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if let Some(ident) = meta.path.get_ident() {
+                if INTEGERS.iter().any(|integer| ident == integer) {
+                    repr = Some(ident.clone());
+                }
+            }
+            // Skip the arguments of other hints such as `align(8)`.
+            if meta.input.peek(syn::token::Paren) {
+                let _content;
+                syn::parenthesized!(_content in meta.input);
+            }
+            Ok(())
+        })?;
+    }
+    Ok(repr.unwrap_or_else(|| format_ident!("isize")))
+}
+
+/// A named field and its attributes.
+pub(crate) struct NamedField<'a> {
+    pub(crate) ident: &'a Ident,
+    pub(crate) ty: &'a Type,
+    /// The name the field is written under.
+    pub(crate) name: String,
+    /// The views the field belongs to, from `#[shallot(view = "...")]`.
+    pub(crate) views: Vec<LitStr>,
+    /// Other names the field is read under, from `#[shallot(alias = "...")]`.
+    pub(crate) aliases: Vec<String>,
+    /// The function writing the field instead of its `Serialize` impl, from
+    /// `#[shallot(serialize_with = "...")]` or `#[shallot(with = "...")]`.
+    pub(crate) serialize_with: Option<Path>,
+    /// The function reading the field instead of its `Deserialize` impl,
+    /// from `#[shallot(deserialize_with = "...")]` or
+    /// `#[shallot(with = "...")]`.
+    pub(crate) deserialize_with: Option<Path>,
+}
+
+impl<'a> NamedField<'a> {
+    /// Parse `fields`, respelling their names with `rename_all` unless they
+    /// are renamed themselves, and check that no two share a name or alias.
+    pub(crate) fn parse_all(
+        fields: &'a FieldsNamed,
+        rename_all: Option<RenameRule>,
+    ) -> syn::Result<Vec<Self>> {
+        let fields = fields
+            .named
+            .iter()
+            .map(|field| Self::parse(field, rename_all))
+            .collect::<syn::Result<Vec<_>>>()?;
+        let names = fields.iter().map(|field| {
+            let names = std::iter::once(&field.name).chain(&field.aliases);
+            (field.ident, names)
+        });
+        check_unique("field", names)?;
+        Ok(fields)
+    }
+
+    /// Parse `field`, respelling its name with `rename_all` unless it is
+    /// renamed itself.
+    fn parse(field: &'a Field, rename_all: Option<RenameRule>) -> syn::Result<Self> {
+        let ident = field.ident.as_ref().expect("named fields have identifiers");
+        let mut name = renamed(ident, rename_all);
+        let mut views = Vec::new();
+        let mut aliases = Vec::new();
+        let mut serialize_with = None;
+        let mut deserialize_with = None;
+        for attr in shallot_attrs(&field.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("view") {
+                    views.push(meta.value()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else if meta.path.is_ident("alias") {
+                    aliases.push(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else if meta.path.is_ident("serialize_with") {
+                    serialize_with = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("deserialize_with") {
+                    deserialize_with = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    Ok(())
+                } else if meta.path.is_ident("with") {
+                    let module: Path = meta.value()?.parse::<LitStr>()?.parse()?;
+                    serialize_with = Some(syn::parse_quote!(#module::serialize));
+                    deserialize_with = Some(syn::parse_quote!(#module::deserialize));
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown shallot field attribute"))
+                }
+            })?;
+        }
+        Ok(Self {
+            ident,
+            ty: &field.ty,
+            name,
+            views,
+            aliases,
+            serialize_with,
+            deserialize_with,
+        })
+    }
+
+    /// Whether a missing field reads as `None` rather than an error.
+    pub(crate) fn is_option(&self) -> bool {
+        let Type::Path(TypePath { qself: None, path }) = self.ty else {
+            return false;
+        };
+        path.segments.last().is_some_and(|segment| {
+            segment.ident == "Option"
+                && matches!(&segment.arguments, PathArguments::AngleBracketed(arguments)
+                    if matches!(arguments.args.first(), Some(GenericArgument::Type(_))))
+        })
+    }
+}
+
+/// Reject attributes on the variants of an enum written as discriminants,
+/// whose names are never written.
+pub(crate) fn reject_variant_attrs(data: &DataEnum) -> syn::Result<()> {
+    for variant in &data.variants {
+        if let Some(attr) = shallot_attrs(&variant.attrs).next() {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "variants of `discriminant` enums take no shallot attributes",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `generics` with `bound` required of every type parameter.
+pub(crate) fn with_bound(generics: &Generics, bound: Path) -> Generics {
+    let mut generics = generics.clone();
+    let predicates: Punctuated<WherePredicate, Token![,]> = generics
+        .type_params()
+        .map(|param| -> WherePredicate {
+            let ident = &param.ident;
+            syn::parse_quote!(#ident: #bound)
+        })
+        .collect();
+    generics.make_where_clause().predicates.extend(predicates);
+    generics
+}
+
+/// Fail if any name is read for two of the fields or variants, given as
+/// their identifiers and the names each is read under.
+fn check_unique<'a, I>(what: &str, items: impl Iterator<Item = (&'a Ident, I)>) -> syn::Result<()>
+where
+    I: Iterator<Item = &'a String>,
+{
+    let mut seen: Vec<(&String, &Ident)> = Vec::new();
+    for (ident, names) in items {
+        let names: Vec<_> = names.collect();
+        for name in &names {
+            if let Some((_, first)) = seen.iter().find(|(seen, _)| seen == name) {
+                return Err(syn::Error::new_spanned(
+                    ident,
+                    format!("`{name}` names both {what} `{first}` and {what} `{ident}`"),
+                ));
+            }
+        }
+        seen.extend(names.into_iter().map(|name| (name, ident)));
+    }
+    Ok(())
+}
+
+/// The name of `ident`, respelled with `rename_all` if given.
+fn renamed(ident: &Ident, rename_all: Option<RenameRule>) -> String {
+    let name = ident.unraw().to_string();
+    match rename_all {
+        Some(rule) => rule.apply(&name),
+        None => name,
+    }
+}
+
+fn shallot_attrs(attrs: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attrs.iter().filter(|attr| attr.path().is_ident("shallot"))
+}
+
+/// The data carried by an enum variant.
+pub(crate) enum Shape<'a> {
+    Unit,
+    /// Unnamed fields; a single one is a newtype variant.
+    Tuple(Vec<&'a Type>),
+    Named(Vec<NamedField<'a>>),
+}
+
+/// An enum variant and the name it is written under.
+pub(crate) struct Variant<'a> {
+    pub(crate) ident: &'a Ident,
+    pub(crate) name: String,
+    pub(crate) shape: Shape<'a>,
+}
+
+impl<'a> Variant<'a> {
+    /// Parse the variants of `data`, respelling their names with
+    /// `rename_all` unless they are renamed themselves, and check that no
+    /// two share a name.
+    pub(crate) fn parse_all(
+        data: &'a DataEnum,
+        rename_all: Option<RenameRule>,
+    ) -> syn::Result<Vec<Self>> {
+        let variants = data
+            .variants
+            .iter()
+            .map(|variant| Self::parse(variant, rename_all))
+            .collect::<syn::Result<Vec<_>>>()?;
+        let names = variants
+            .iter()
+            .map(|variant| (variant.ident, std::iter::once(&variant.name)));
+        check_unique("variant", names)?;
+        Ok(variants)
+    }
+
+    fn parse(variant: &'a syn::Variant, rename_all: Option<RenameRule>) -> syn::Result<Self> {
+        let shape = match &variant.fields {
+            Fields::Unit => Shape::Unit,
+            Fields::Unnamed(fields) if fields.unnamed.is_empty() => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "variants with empty parentheses cannot be derived",
+                ))
+            }
+            Fields::Unnamed(fields) => {
+                Shape::Tuple(fields.unnamed.iter().map(|field| &field.ty).collect())
+            }
+            Fields::Named(fields) => Shape::Named(NamedField::parse_all(fields, None)?),
+        };
+        let mut name = renamed(&variant.ident, rename_all);
+        for attr in shallot_attrs(&variant.attrs) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    name = meta.value()?.parse::<LitStr>()?.value();
+                    Ok(())
+                } else {
+                    Err(meta.error("unknown shallot variant attribute"))
+                }
+            })?;
+        }
+        Ok(Self {
+            ident: &variant.ident,
+            name,
+            shape,
+        })
+    }
+
+    /// Bindings for the variant's fields, for patterns and constructors.
+    pub(crate) fn bindings(&self) -> Vec<Ident> {
+        let len = match &self.shape {
+            Shape::Unit => 0,
+            Shape::Tuple(types) => types.len(),
+            Shape::Named(fields) => fields.len(),
+        };
+        (0..len)
+            .map(|index| format_ident!("__field{index}"))
+            .collect()
+    }
+}
@@ -0,0 +1,304 @@
+//! `#[derive(Deserialize)]`.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{Data, DataEnum, DeriveInput, Fields, Ident};
+
+use crate::attr::{self, Container, NamedField, Shape, Variant};
+
+pub(crate) fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::parse(input)?;
+    let ident = &input.ident;
+    let generics = attr::with_bound(&input.generics, syn::parse_quote!(::shallot::Deserialize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = ident.unraw().to_string();
+    // Only structs whose fields can be read one by one get an in-place
+    // reader; everything else keeps the default, which replaces the value.
+    let mut in_place = None;
+    let body = match &input.data {
+        Data::Struct(_) if container.transparent.is_some() => {
+            let (member, ty) = container.transparent.as_ref().unwrap();
+            in_place = Some(quote! {
+                <#ty as ::shallot::Deserialize>::accept_in_place(deserializer, &mut place.#member)
+            });
+            quote! {
+                <#ty as ::shallot::Deserialize>::accept(deserializer)
+                    .map(|value| Self { #member: value })
+            }
+        }
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let fields = NamedField::parse_all(fields, container.rename_all)?;
+                let value = named_fields(&ident.to_string(), quote!(Self), &fields);
+                let statements = named_fields_in_place(&ident.to_string(), &fields);
+                in_place = Some(quote! {
+                    #statements
+                    ::core::result::Result::Ok(())
+                });
+                quote!(::core::result::Result::Ok(#value))
+            }
+            Fields::Unit => quote!(deserializer.visit_unit().map(|()| Self)),
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                quote! {
+                    deserializer
+                        .visit_newtype_struct::<#ty>(#name)
+                        .map(Self)
+                        .map_err(::shallot::Error::in_type::<#ty>)
+                }
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() > 16 => {
+                return Err(syn::Error::new_spanned(
+                    fields,
+                    "tuple structs with more than 16 fields cannot be derived",
+                ))
+            }
+            Fields::Unnamed(fields) => {
+                let types = fields.unnamed.iter().map(|field| &field.ty);
+                let bindings: Vec<_> = (0..fields.unnamed.len())
+                    .map(|index| format_ident!("__field{index}"))
+                    .collect();
+                quote! {
+                    deserializer
+                        .visit_tuple_struct::<(#(#types,)*)>(#name)
+                        .map(|(#(#bindings,)*)| Self(#(#bindings),*))
+                }
+            }
+        },
+        Data::Enum(data) if container.discriminant.is_some() => {
+            attr::reject_variant_attrs(data)?;
+            discriminants(ident, data)
+        }
+        Data::Enum(data) => enumeration(data, &container)?,
+        Data::Union(_) => return Err(syn::Error::new_spanned(ident, "unions cannot be derived")),
+    };
+    let in_place = in_place.map(|body| {
+        quote! {
+            fn accept_in_place<__D: ::shallot::Deserializer>(
+                deserializer: &mut __D,
+                place: &mut Self,
+            ) -> ::shallot::Result<()> {
+                #body
+            }
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics ::shallot::Deserialize for #ident #ty_generics #where_clause {
+            fn accept<__D: ::shallot::Deserializer>(
+                deserializer: &mut __D,
+            ) -> ::shallot::Result<Self> {
+                #body
+            }
+
+            #in_place
+        }
+    })
+}
+
+/// Read the variant name, then the variant's data if it has any.
+fn enumeration(data: &DataEnum, container: &Container) -> syn::Result<TokenStream> {
+    let variants = Variant::parse_all(data, container.rename_all)?;
+    let descriptions = variants.iter().map(|variant| {
+        let name = &variant.name;
+        let unit = matches!(variant.shape, Shape::Unit);
+        quote!(::shallot::deserialize::Variant { name: #name, unit: #unit })
+    });
+    let arms = variants.iter().enumerate().map(|(index, variant)| {
+        let variant_ident = variant.ident;
+        let value = match &variant.shape {
+            Shape::Unit => {
+                return quote!(#index => ::core::result::Result::Ok(Self::#variant_ident),)
+            }
+            Shape::Tuple(types) if types.len() == 1 => {
+                let ty = types[0];
+                quote!(Self::#variant_ident(<#ty as ::shallot::Deserialize>::accept(deserializer)?))
+            }
+            Shape::Tuple(types) => {
+                let bindings = variant.bindings();
+                quote! {{
+                    let (#(#bindings,)*) =
+                        <(#(#types,)*) as ::shallot::Deserialize>::accept(deserializer)?;
+                    Self::#variant_ident(#(#bindings),*)
+                }}
+            }
+            Shape::Named(fields) => {
+                named_fields(&variant.name, quote!(Self::#variant_ident), fields)
+            }
+        };
+        quote! {
+            #index => {
+                let value = #value;
+                deserializer.end_variant()?;
+                ::core::result::Result::Ok(value)
+            }
+        }
+    });
+    Ok(quote! {
+        const VARIANTS: &[::shallot::deserialize::Variant] = &[#(#descriptions),*];
+        match deserializer.begin_variant(VARIANTS)? {
+            #(#arms)*
+            _ => ::core::unreachable!("variant index out of range"),
+        }
+    })
+}
+
+/// Read a fieldless variant from its discriminant.
+fn discriminants(ident: &Ident, data: &DataEnum) -> TokenStream {
+    let idents: Vec<_> = data.variants.iter().map(|variant| &variant.ident).collect();
+    let indices = 0..idents.len();
+    quote! {
+        const DISCRIMINANTS: &[i128] = &[#(#ident::#idents as i128),*];
+        match deserializer.visit_discriminant(DISCRIMINANTS)? {
+            #(#indices => ::core::result::Result::Ok(Self::#idents),)*
+            _ => ::core::unreachable!("variant index out of range"),
+        }
+    }
+}
+
+/// An expression reading named fields in any order and building them with
+/// `path`, returning early on errors.
+///
+/// A field is accepted under its name or any of its aliases. A missing
+/// `Option` field reads as `None`; any other missing field is an error.
+fn named_fields(name: &str, path: TokenStream, fields: &[NamedField]) -> TokenStream {
+    let (names, patterns) = spellings(fields);
+    let slots: Vec<_> = (0..fields.len())
+        .map(|index| format_ident!("__field{index}"))
+        .collect();
+    let types = fields.iter().map(|field| field.ty);
+    let arms = fields.iter().zip(&slots).zip(&patterns).map(|((field, slot), pattern)| {
+        let (ty, name) = (field.ty, &field.name);
+        let read = match &field.deserialize_with {
+            Some(path) => quote!(#path(deserializer)),
+            None => quote!(<#ty as ::shallot::Deserialize>::accept(deserializer)),
+        };
+        quote! {
+            #pattern => {
+                let value: ::shallot::Result<#ty> = #read;
+                let value = value.map_err(|error| {
+                    error
+                        .in_type::<#ty>()
+                        .in_field(::core::any::type_name::<Self>(), ::core::option::Option::Some(#name))
+                })?;
+                #slot = ::core::option::Option::Some(value);
+            }
+        }
+    });
+    let values = fields.iter().zip(&slots).map(|(field, slot)| {
+        let (ident, name) = (field.ident, &field.name);
+        let missing = if field.is_option() {
+            quote!(::core::option::Option::None)
+        } else {
+            quote! {
+                return ::core::result::Result::Err(::shallot::Error::new(
+                    ::shallot::ErrorKind::MissingField { field: #name },
+                ))
+            }
+        };
+        quote! {
+            #ident: match #slot {
+                ::core::option::Option::Some(value) => value,
+                ::core::option::Option::None => #missing,
+            }
+        }
+    });
+    quote! {{
+        #names
+        #(let mut #slots: ::core::option::Option<#types> = ::core::option::Option::None;)*
+        let mut __map = deserializer.begin_struct(#name)?;
+        while let ::core::option::Option::Some(index) = deserializer.next_field(&mut __map, FIELDS)? {
+            match index {
+                #(#arms)*
+                _ => ::core::unreachable!("field index out of range"),
+            }
+        }
+        deserializer.end_struct(__map)?;
+        #path { #(#values),* }
+    }}
+}
+
+/// A statement reading named fields in any order into the matching fields of
+/// `place`, returning early on errors.
+///
+/// Fields are read in place unless they have a `deserialize_with` function.
+/// A missing `Option` field is set to `None`; any other missing field is an
+/// error.
+fn named_fields_in_place(name: &str, fields: &[NamedField]) -> TokenStream {
+    let (names, patterns) = spellings(fields);
+    let arms = fields.iter().zip(&patterns).enumerate().map(|(index, (field, pattern))| {
+        let (ident, ty, name) = (field.ident, field.ty, &field.name);
+        let read = match &field.deserialize_with {
+            Some(path) => quote! {
+                #path(deserializer).map(|value| place.#ident = value)
+            },
+            None => quote! {
+                <#ty as ::shallot::Deserialize>::accept_in_place(deserializer, &mut place.#ident)
+            },
+        };
+        quote! {
+            #pattern => {
+                let read: ::shallot::Result<()> = #read;
+                read.map_err(|error| {
+                    error
+                        .in_type::<#ty>()
+                        .in_field(::core::any::type_name::<Self>(), ::core::option::Option::Some(#name))
+                })?;
+                __seen[#index] = true;
+            }
+        }
+    });
+    let missing = fields.iter().enumerate().map(|(index, field)| {
+        let (ident, name) = (field.ident, &field.name);
+        let missing = if field.is_option() {
+            quote!(place.#ident = ::core::option::Option::None)
+        } else {
+            quote! {
+                return ::core::result::Result::Err(::shallot::Error::new(
+                    ::shallot::ErrorKind::MissingField { field: #name },
+                ))
+            }
+        };
+        quote! {
+            if !__seen[#index] {
+                #missing;
+            }
+        }
+    });
+    let len = fields.len();
+    quote! {
+        #names
+        #[allow(unused_mut)]
+        let mut __seen = [false; #len];
+        let mut __map = deserializer.begin_struct(#name)?;
+        while let ::core::option::Option::Some(index) = deserializer.next_field(&mut __map, FIELDS)? {
+            match index {
+                #(#arms)*
+                _ => ::core::unreachable!("field index out of range"),
+            }
+        }
+        deserializer.end_struct(__map)?;
+        #(#missing)*
+    }
+}
+
+/// The `FIELDS` constant listing every spelling of every field, with the
+/// aliases after all the names, and the pattern matching the indices of
+/// each field's spellings.
+fn spellings(fields: &[NamedField]) -> (TokenStream, Vec<TokenStream>) {
+    let names = fields
+        .iter()
+        .map(|field| &field.name)
+        .chain(fields.iter().flat_map(|field| &field.aliases));
+    let mut next_alias = fields.len();
+    let patterns = fields
+        .iter()
+        .enumerate()
+        .map(|(index, field)| {
+            let aliases = next_alias..next_alias + field.aliases.len();
+            next_alias = aliases.end;
+            quote!(#index #(| #aliases)*)
+        })
+        .collect();
+    (quote!(const FIELDS: &[&str] = &[#(#names),*];), patterns)
+}
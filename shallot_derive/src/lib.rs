@@ -0,0 +1,41 @@
+//! Derive macros for shallot's `Serialize`, `Deserialize` and `Metadata`
+//! traits.
+//!
+//! Enable shallot's `derive` feature and use them through the shallot crate
+//! rather than depending on this crate directly.
+
+mod attr;
+mod de;
+mod meta;
+mod ser;
+
+use proc_macro::TokenStream;
+use syn::{parse_macro_input, DeriveInput};
+
+/// Implement `shallot::Serialize`, and `shallot::serialize::Struct` for
+/// structs with named fields.
+#[proc_macro_derive(Serialize, attributes(shallot))]
+pub fn derive_serialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    ser::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `shallot::Deserialize`.
+#[proc_macro_derive(Deserialize, attributes(shallot))]
+pub fn derive_deserialize(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    de::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Implement `shallot::metadata::Metadata` for a struct with named fields.
+#[proc_macro_derive(Metadata, attributes(shallot))]
+pub fn derive_metadata(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    meta::expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
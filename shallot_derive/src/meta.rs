@@ -0,0 +1,79 @@
+//! `#[derive(Metadata)]`.
+
+use proc_macro2::TokenStream;
+use quote::{quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::{Data, DeriveInput, Fields, Type};
+
+use crate::attr::{Container, NamedField};
+
+pub(crate) fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::parse(input)?;
+    let ident = &input.ident;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields,
+            _ => return Err(not_named(ident)),
+        },
+        _ => return Err(not_named(ident)),
+    };
+    let fields = NamedField::parse_all(fields, container.rename_all)?;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let name = ident.unraw().to_string();
+    let infos = fields.iter().map(|field| {
+        let ident = field.ident.unraw().to_string();
+        let (wire_name, ty, aliases) = (&field.name, type_name(field.ty), &field.aliases);
+        quote! {
+            ::shallot::metadata::FieldInfo {
+                name: #ident,
+                wire_name: #wire_name,
+                ty: #ty,
+                aliases: &[#(#aliases),*],
+            }
+        }
+    });
+    Ok(quote! {
+        impl #impl_generics ::shallot::metadata::Metadata for #ident #ty_generics #where_clause {
+            const NAME: &'static str = #name;
+            const FIELDS: &'static [::shallot::metadata::FieldInfo] = &[#(#infos),*];
+        }
+    })
+}
+
+/// `ty` as it would be written by hand, without the spaces that printing
+/// its tokens puts between each of them.
+fn type_name(ty: &Type) -> String {
+    let word = |c: char| c.is_alphanumeric() || c == '_' || c == '\'';
+    let text = ty.to_token_stream().to_string();
+    let mut name = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => {
+                let after = chars.peek().copied();
+                if name.ends_with(word) && after.is_some_and(word) {
+                    name.push(' ');
+                }
+            }
+            ',' | ';' => name.push_str(&format!("{c} ")),
+            '+' => name.push_str(" + "),
+            '-' if chars.peek() == Some(&'>') => {
+                chars.next();
+                name.push_str(" -> ");
+            }
+            ')' | ']' | '>' => {
+                name.truncate(name.trim_end().len());
+                name.push(c);
+            }
+            _ => name.push(c),
+        }
+    }
+    name
+}
+
+fn not_named(ident: &syn::Ident) -> syn::Error {
+    syn::Error::new_spanned(
+        ident,
+        "`Metadata` can only be derived for structs with named fields",
+    )
+}
@@ -0,0 +1,242 @@
+//! `#[derive(Serialize)]`.
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::ext::IdentExt;
+use syn::{Data, DataEnum, DeriveInput, Fields, FieldsNamed, Generics, Ident};
+
+use crate::attr::{self, Container, NamedField, Shape, Variant};
+
+pub(crate) fn expand(input: &DeriveInput) -> syn::Result<TokenStream> {
+    let container = Container::parse(input)?;
+    let ident = &input.ident;
+    let generics = attr::with_bound(&input.generics, syn::parse_quote!(::shallot::Serialize));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let name = ident.unraw().to_string();
+    let (extra, body) = match &input.data {
+        Data::Struct(_) if container.transparent.is_some() => {
+            let (member, _) = container.transparent.as_ref().unwrap();
+            (
+                TokenStream::new(),
+                quote!(serializer.serialize(&self.#member)),
+            )
+        }
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => (
+                named_struct(ident, &generics, fields, &container)?,
+                quote!(serializer.visit_struct(self)),
+            ),
+            Fields::Unit => (TokenStream::new(), quote!(serializer.visit_unit())),
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => (
+                TokenStream::new(),
+                quote!(serializer.visit_newtype_struct(#name, &self.0)),
+            ),
+            Fields::Unnamed(fields) => (
+                tuple_struct(ident, &generics, fields.unnamed.len()),
+                quote!(serializer.visit_tuple_struct(#name, self)),
+            ),
+        },
+        Data::Enum(data) => match &container.discriminant {
+            Some(repr) => {
+                attr::reject_variant_attrs(data)?;
+                (TokenStream::new(), discriminants(data, repr))
+            }
+            None => enumeration(ident, &generics, data, &container)?,
+        },
+        Data::Union(_) => return Err(syn::Error::new_spanned(ident, "unions cannot be derived")),
+    };
+    // The helper types of enums only need to be visible to the impl.
+    Ok(quote! {
+        const _: () = {
+            #extra
+
+            impl #impl_generics ::shallot::Serialize for #ident #ty_generics #where_clause {
+                fn accept<__S: ::shallot::Serializer>(&self, serializer: &__S) -> ::shallot::Result<__S::Output> {
+                    #body
+                }
+            }
+        };
+    })
+}
+
+/// Implement `Struct` for a struct with named fields.
+fn named_struct(
+    ident: &Ident,
+    generics: &Generics,
+    fields: &FieldsNamed,
+    container: &Container,
+) -> syn::Result<TokenStream> {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let fields = NamedField::parse_all(fields, container.rename_all)?;
+    let accessors: Vec<_> = fields
+        .iter()
+        .map(|field| {
+            let ident = field.ident;
+            quote!(&self.#ident)
+        })
+        .collect();
+    let body = serialize_field(&fields, &accessors);
+    let items = struct_items(&ident.to_string(), &fields, body, container);
+    Ok(quote! {
+        impl #impl_generics ::shallot::serialize::Struct for #ident #ty_generics #where_clause {
+            #items
+        }
+    })
+}
+
+/// Implement `Tuple` for a struct with `len` unnamed fields.
+fn tuple_struct(ident: &Ident, generics: &Generics, len: usize) -> TokenStream {
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let members = (0..len).map(syn::Index::from);
+    quote! {
+        impl #impl_generics ::shallot::serialize::Tuple for #ident #ty_generics #where_clause {
+            fn len(&self) -> usize {
+                #len
+            }
+
+            fn serialize_elements<__S: ::shallot::Serializer>(
+                &self,
+                serializer: &__S,
+                mut element: impl ::core::ops::FnMut(__S::Output),
+            ) -> ::shallot::Result<()> {
+                #(element(serializer.serialize(&self.#members)?);)*
+                ::core::result::Result::Ok(())
+            }
+        }
+    }
+}
+
+/// Write unit variants as their names and other variants as their data
+/// tagged with their names.
+///
+/// Struct variants are written through a wrapper around a reference to the
+/// enum, which implements `Struct` for the fields of that variant.
+fn enumeration(
+    ident: &Ident,
+    generics: &Generics,
+    data: &DataEnum,
+    container: &Container,
+) -> syn::Result<(TokenStream, TokenStream)> {
+    let (_, ty_generics, where_clause) = generics.split_for_impl();
+    let mut wrapper_generics = generics.clone();
+    wrapper_generics.params.insert(0, syn::parse_quote!('__a));
+    let (wrapper_impl_generics, wrapper_ty_generics, _) = wrapper_generics.split_for_impl();
+    let variants = Variant::parse_all(data, container.rename_all)?;
+    let mut wrappers = TokenStream::new();
+    let arms = variants.iter().map(|variant| {
+        let (variant_ident, name) = (variant.ident, &variant.name);
+        let bindings = variant.bindings();
+        match &variant.shape {
+            Shape::Unit => quote! {
+                Self::#variant_ident => serializer.visit_unit_variant(#name),
+            },
+            Shape::Tuple(_) if bindings.len() == 1 => quote! {
+                Self::#variant_ident(ref __field0) => {
+                    serializer.visit_variant(#name, serializer.serialize(__field0)?)
+                }
+            },
+            Shape::Tuple(_) => quote! {
+                Self::#variant_ident(#(ref #bindings),*) => {
+                    serializer.visit_variant(#name, serializer.visit_tuple(&(#(#bindings,)*))?)
+                }
+            },
+            Shape::Named(fields) => {
+                let wrapper = format_ident!("__{}{}", ident, variant_ident);
+                let field_idents = fields.iter().map(|field| field.ident);
+                let accessors: Vec<_> = bindings.iter().map(|binding| quote!(#binding)).collect();
+                let body = serialize_field(fields, &accessors);
+                let body = quote! {
+                    match *self.0 {
+                        #ident::#variant_ident { #(#field_idents: ref #bindings),* } => #body,
+                        _ => ::core::unreachable!("wrapper around another variant"),
+                    }
+                };
+                let items = struct_items(name, fields, body, container);
+                wrappers.extend(quote! {
+                    struct #wrapper #wrapper_impl_generics (&'__a #ident #ty_generics) #where_clause;
+
+                    #[allow(unreachable_patterns)]
+                    impl #wrapper_impl_generics ::shallot::serialize::Struct
+                        for #wrapper #wrapper_ty_generics #where_clause
+                    {
+                        #items
+                    }
+                });
+                quote! {
+                    Self::#variant_ident { .. } => {
+                        serializer.visit_variant(#name, serializer.visit_struct(&#wrapper(self))?)
+                    }
+                }
+            }
+        }
+    });
+    let arms: Vec<_> = arms.collect();
+    Ok((wrappers, quote!(match *self { #(#arms)* })))
+}
+
+/// Write fieldless variants as their discriminants.
+fn discriminants(data: &DataEnum, repr: &Ident) -> TokenStream {
+    let arms = data.variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        quote!(Self::#ident => serializer.serialize(&(Self::#ident as #repr)),)
+    });
+    quote!(match *self { #(#arms)* })
+}
+
+/// The body of `Struct::serialize_field` given `fields` and an expression
+/// for a reference to each of them.
+fn serialize_field(fields: &[NamedField], accessors: &[TokenStream]) -> TokenStream {
+    let arms = fields
+        .iter()
+        .zip(accessors)
+        .enumerate()
+        .map(|(index, (field, accessor))| match &field.serialize_with {
+            Some(path) => quote!(#index => #path(#accessor, serializer),),
+            None => quote!(#index => serializer.serialize(#accessor),),
+        });
+    quote! {
+        match index {
+            #(#arms)*
+            _ => ::core::unreachable!("field index out of range"),
+        }
+    }
+}
+
+/// The items of a `Struct` impl named `name` with `fields`.
+fn struct_items(
+    name: &str,
+    fields: &[NamedField],
+    serialize_field: TokenStream,
+    container: &Container,
+) -> TokenStream {
+    let descriptions = fields.iter().map(|field| {
+        let (name, views) = (&field.name, &field.views);
+        quote!(::shallot::serialize::Field { name: #name, views: &[#(#views),*] })
+    });
+    let field_order = container.field_order.as_ref().map(|order| {
+        quote! {
+            fn field_order(&self) -> ::shallot::serialize::FieldOrder {
+                ::shallot::serialize::FieldOrder::#order
+            }
+        }
+    });
+    quote! {
+        fn name(&self) -> &'static str {
+            #name
+        }
+
+        fn fields(&self) -> &'static [::shallot::serialize::Field] {
+            &[#(#descriptions),*]
+        }
+
+        fn serialize_field<__S: ::shallot::Serializer>(
+            &self,
+            index: usize,
+            serializer: &__S,
+        ) -> ::shallot::Result<__S::Output> {
+            #serialize_field
+        }
+
+        #field_order
+    }
+}
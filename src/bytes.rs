@@ -0,0 +1,266 @@
+//! Binary data carried as text.
+//!
+//! Text formats have no native byte strings, so blobs are written as base64
+//! or hex strings. Wrapping a buffer in [`Base64`] or [`Hex`] selects the
+//! encoding; deserializers that support it decode the string straight from
+//! the input through [`Deserializer::visit_bytes`] without building the
+//! intermediate `String`.
+//!
+//! A derived type can keep a plain `Vec<u8>` field and declare it bytes
+//! with `#[shallot(with = "shallot::bytes::base64")]` or
+//! `#[shallot(with = "shallot::bytes::hex")]` instead.
+
+use crate::deserialize::{Deserialize, Deserializer};
+use crate::error::Result;
+use crate::serialize::{Serialize, Serializer};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// The value of each base64 digit, indexed by byte, with `0xff` for bytes
+/// outside the alphabet.
+const BASE64_DIGITS: [u8; 256] = {
+    let mut table = [0xff; 256];
+    let mut index = 0;
+    while index < BASE64.len() {
+        table[BASE64[index] as usize] = index as u8;
+        index += 1;
+    }
+    table
+};
+
+/// How bytes are spelled as text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    /// Standard base64 (RFC 4648) with `=` padding; padding is optional on input.
+    Base64,
+    /// Two hex digits per byte, lowercase on output and either case on input.
+    Hex,
+}
+
+impl Encoding {
+    /// A short name for the encoding, used in error messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Base64 => "base64",
+            Self::Hex => "hex",
+        }
+    }
+
+    /// Encode `bytes` as text.
+    pub fn encode(self, bytes: &[u8]) -> String {
+        let mut output = String::with_capacity(bytes.len() * 2);
+        match self {
+            Self::Base64 => {
+                for chunk in bytes.chunks(3) {
+                    let group = chunk
+                        .iter()
+                        .enumerate()
+                        .fold(0u32, |group, (index, &byte)| {
+                            group | u32::from(byte) << (16 - 8 * index)
+                        });
+                    for index in 0..4 {
+                        if index <= chunk.len() {
+                            let sextet = group >> (18 - 6 * index) & 0x3f;
+                            output.push(char::from(BASE64[sextet as usize]));
+                        } else {
+                            output.push('=');
+                        }
+                    }
+                }
+            }
+            Self::Hex => {
+                for byte in bytes {
+                    output.push(char::from(HEX[usize::from(byte >> 4)]));
+                    output.push(char::from(HEX[usize::from(byte & 0xf)]));
+                }
+            }
+        }
+        output
+    }
+
+    /// Decode all of `text`.
+    ///
+    /// On failure, returns the byte offset in `text` where decoding stopped.
+    pub fn decode(self, text: &str) -> std::result::Result<Vec<u8>, usize> {
+        let mut decoder = Decoder::new(self);
+        for (offset, byte) in text.bytes().enumerate() {
+            if !decoder.push(byte) {
+                return Err(offset);
+            }
+        }
+        decoder.finish().ok_or(text.len())
+    }
+}
+
+/// Incrementally decodes text one byte at a time.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    encoding: Encoding,
+    output: Vec<u8>,
+    buffer: u32,
+    bits: u32,
+    padding: usize,
+}
+
+impl Decoder {
+    /// Create a decoder for `encoding`.
+    pub fn new(encoding: Encoding) -> Self {
+        Self {
+            encoding,
+            output: Vec::new(),
+            buffer: 0,
+            bits: 0,
+            padding: 0,
+        }
+    }
+
+    /// Feed one byte of text, returning `false` if it cannot appear here.
+    pub fn push(&mut self, byte: u8) -> bool {
+        let (value, width) = match self.encoding {
+            Encoding::Base64 if byte == b'=' => {
+                self.padding += 1;
+                return self.padding <= 2;
+            }
+            _ if self.padding > 0 => return false,
+            Encoding::Base64 => match BASE64_DIGITS[usize::from(byte)] {
+                0xff => return false,
+                value => (u32::from(value), 6),
+            },
+            Encoding::Hex => match char::from(byte).to_digit(16) {
+                Some(value) => (value, 4),
+                None => return false,
+            },
+        };
+        self.buffer = self.buffer << width | value;
+        self.bits += width;
+        if self.bits >= 8 {
+            self.bits -= 8;
+            self.output.push((self.buffer >> self.bits) as u8);
+            self.buffer &= (1 << self.bits) - 1;
+        }
+        true
+    }
+
+    /// Finish decoding, returning `None` if the text ended mid-byte.
+    pub fn finish(self) -> Option<Vec<u8>> {
+        let complete = match self.encoding {
+            // A trailing group of 2 or 3 characters leaves 4 or 2 spare bits
+            // which must be zero, and padding must fill the group exactly.
+            Encoding::Base64 => {
+                let padded = self.padding == 0 || self.padding == self.bits as usize / 2;
+                matches!(self.bits, 0 | 2 | 4) && self.buffer == 0 && padded
+            }
+            Encoding::Hex => self.bits == 0,
+        };
+        complete.then_some(self.output)
+    }
+}
+
+/// Bytes written as a base64 string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Base64(pub Vec<u8>);
+
+/// Bytes written as a hex string.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Hex(pub Vec<u8>);
+
+impl Serialize for Base64 {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(&Encoding::Base64.encode(&self.0))
+    }
+}
+
+impl Deserialize for Base64 {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_bytes(Encoding::Base64).map(Self)
+    }
+}
+
+impl Serialize for Hex {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(&Encoding::Hex.encode(&self.0))
+    }
+}
+
+impl Deserialize for Hex {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_bytes(Encoding::Hex).map(Self)
+    }
+}
+
+/// Functions for `#[shallot(with = "shallot::bytes::base64")]`, writing a
+/// `Vec<u8>` field as a base64 string.
+pub mod base64 {
+    use super::Encoding;
+    use crate::deserialize::Deserializer;
+    use crate::error::Result;
+    use crate::serialize::Serializer;
+
+    /// Write `input` as a base64 string.
+    pub fn serialize<S: Serializer>(input: &[u8], serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(&Encoding::Base64.encode(input))
+    }
+
+    /// Read bytes from a base64 string.
+    pub fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Vec<u8>> {
+        deserializer.visit_bytes(Encoding::Base64)
+    }
+}
+
+/// Functions for `#[shallot(with = "shallot::bytes::hex")]`, writing a
+/// `Vec<u8>` field as a hex string.
+pub mod hex {
+    use super::Encoding;
+    use crate::deserialize::Deserializer;
+    use crate::error::Result;
+    use crate::serialize::Serializer;
+
+    /// Write `input` as a hex string.
+    pub fn serialize<S: Serializer>(input: &[u8], serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(&Encoding::Hex.encode(input))
+    }
+
+    /// Read bytes from a hex string.
+    pub fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Vec<u8>> {
+        deserializer.visit_bytes(Encoding::Hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trips() {
+        for (bytes, text) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (b"\xff\xfe", "//4="),
+        ] {
+            assert_eq!(Encoding::Base64.encode(bytes), text);
+            assert_eq!(Encoding::Base64.decode(text).unwrap(), bytes);
+        }
+        assert_eq!(Encoding::Base64.decode("Zm8").unwrap(), b"fo");
+    }
+
+    #[test]
+    fn base64_rejects_malformed_text() {
+        assert_eq!(Encoding::Base64.decode("Zm9v!"), Err(4));
+        assert_eq!(Encoding::Base64.decode("Z=m9"), Err(2));
+        assert_eq!(Encoding::Base64.decode("Z"), Err(1));
+        assert_eq!(Encoding::Base64.decode("Zm9="), Err(4));
+        assert_eq!(Encoding::Base64.decode("Zg="), Err(3));
+    }
+
+    #[test]
+    fn hex_round_trips() {
+        assert_eq!(Encoding::Hex.encode(b"\x00\xab\x10"), "00ab10");
+        assert_eq!(Encoding::Hex.decode("00AB10").unwrap(), b"\x00\xab\x10");
+        assert_eq!(Encoding::Hex.decode("abc"), Err(3));
+        assert_eq!(Encoding::Hex.decode("0g"), Err(1));
+    }
+}
@@ -0,0 +1,78 @@
+//! Single-byte legacy encodings.
+//!
+//! Exports from older systems often arrive as Latin-1 or Windows-1252 rather
+//! than UTF-8. Both map every byte to exactly one character, so input can be
+//! decoded up front and positions mapped back to byte offsets in the original
+//! by counting characters.
+
+use crate::error::Position;
+
+/// Windows-1252 characters for bytes `0x80..=0x9F`.
+///
+/// The five bytes the code page leaves undefined decode to the C1 control of
+/// the same value, as in the WHATWG Encoding Standard.
+const WINDOWS_1252: [char; 32] = [
+    '\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}', '\u{17e}', '\u{178}',
+];
+
+/// A single-byte character encoding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Charset {
+    /// ISO-8859-1, where every byte is the code point of the same value.
+    Latin1,
+    /// Windows code page 1252, Latin-1 with printable characters in place of
+    /// most C1 controls.
+    Windows1252,
+}
+
+impl Charset {
+    /// The character encoded by `byte`.
+    pub fn decode_byte(self, byte: u8) -> char {
+        match (self, byte) {
+            (Self::Windows1252, 0x80..=0x9f) => WINDOWS_1252[usize::from(byte - 0x80)],
+            _ => char::from(byte),
+        }
+    }
+
+    /// Decode all of `input` into UTF-8.
+    pub fn decode(self, input: &[u8]) -> String {
+        input.iter().map(|&byte| self.decode_byte(byte)).collect()
+    }
+
+    /// Map a position in text produced by [`Charset::decode`] back to the
+    /// original input.
+    ///
+    /// Rows and columns already count characters, so only the offset changes.
+    pub fn original_position(decoded: &str, position: Position) -> Position {
+        Position {
+            offset: decoded[..position.offset].chars().count(),
+            ..position
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_single_bytes() {
+        assert_eq!(Charset::Latin1.decode(b"caf\xe9 \x80"), "café \u{80}");
+        assert_eq!(Charset::Windows1252.decode(b"caf\xe9 \x80"), "café €");
+        assert_eq!(
+            Charset::Windows1252.decode(b"\x81\x93\x94\x9f"),
+            "\u{81}“”Ÿ"
+        );
+    }
+
+    #[test]
+    fn maps_offsets_back() {
+        let decoded = Charset::Windows1252.decode(b"\x93\xe9\x94x");
+        let position = Position::locate(&decoded, 8);
+        assert_eq!(Charset::original_position(&decoded, position).offset, 3);
+        assert_eq!(position.col, 4);
+    }
+}
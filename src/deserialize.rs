@@ -0,0 +1,868 @@
+//! Reading Rust values back out of a data format.
+//!
+//! A type opts in by implementing [`Deserialize`], which asks a
+//! [`Deserializer`] for the shape of value it expects via the matching
+//! `visit_*` method. The deserializer owns the input and its position.
+
+mod json;
+mod lines;
+mod logfmt;
+mod multipart;
+mod urlencoded;
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, LinkedList, VecDeque};
+use std::convert::Infallible;
+use std::ffi::OsString;
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::ops::{Bound, ControlFlow};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+
+pub use json::{
+    lexer, Feed, Json, NulPolicy, OverflowPolicy, Parser, PushParser, StreamDeserializer,
+    TrailingPolicy,
+};
+pub use lines::JsonLines;
+pub use logfmt::Logfmt;
+pub use multipart::Multipart;
+pub use urlencoded::{RepeatPolicy, Urlencoded};
+
+pub(crate) use logfmt::kind as logfmt_kind;
+
+use crate::bytes::Encoding;
+use crate::error::{Error, ErrorKind, Result, WarningKind};
+
+/// An enum variant, as described to [`Deserializer::begin_variant`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Variant {
+    /// The name the variant is written under.
+    pub name: &'static str,
+    /// Whether the variant carries no data.
+    pub unit: bool,
+}
+
+/// The kind of the next value, as reported by [`Deserializer::peek_kind`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    /// Nothing, read with [`Deserializer::visit_unit`].
+    Null,
+    /// `true` or `false`.
+    Bool,
+    /// A whole number that fits in a `u64`.
+    Unsigned,
+    /// A negative whole number that fits in an `i64`.
+    Signed,
+    /// A whole number too large for a `u64` that fits in a `u128`.
+    WideUnsigned,
+    /// A negative whole number too small for an `i64` that fits in an
+    /// `i128`.
+    WideSigned,
+    /// Any other number.
+    Float,
+    /// Text.
+    String,
+    /// A sequence, read with [`Deserializer::begin_seq`].
+    Seq,
+    /// A map, read with [`Deserializer::begin_map`].
+    Map,
+}
+
+/// A value that can be read by any [`Deserializer`].
+pub trait Deserialize: Sized {
+    /// Read a value of this type from the deserializer's current position.
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self>;
+
+    /// Read a value into `place`, reusing what it already owns.
+    ///
+    /// Parsing into the same value over and over then keeps the capacity of
+    /// its strings and vectors rather than allocating anew. If reading fails,
+    /// `place` is left valid but with unspecified contents. The default
+    /// replaces `place` with a value read by [`Deserialize::accept`].
+    fn accept_in_place<D: Deserializer>(deserializer: &mut D, place: &mut Self) -> Result<()> {
+        *place = Self::accept(deserializer)?;
+        Ok(())
+    }
+}
+
+/// A seed reading into an existing value with
+/// [`Deserialize::accept_in_place`].
+#[derive(Debug)]
+pub struct InPlace<'a, T>(pub &'a mut T);
+
+/// Something that reads a value from a [`Deserializer`] using state passed
+/// in by the caller.
+///
+/// Where [`Deserialize`] builds a value from nothing, a seed carries context
+/// into deserialization, such as a buffer to reuse, an interner, or a schema.
+/// [`PhantomData<T>`] is the seed of any `T: Deserialize`.
+///
+/// ```
+/// use shallot::deserialize::{DeserializeSeed, Json};
+/// use shallot::{Deserialize, Deserializer, Result};
+///
+/// /// Appends the elements of an array to a vector, reusing its capacity.
+/// struct Extend<'a, T>(&'a mut Vec<T>);
+///
+/// impl<T: Deserialize> DeserializeSeed for Extend<'_, T> {
+///     type Value = ();
+///
+///     fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<()> {
+///         let mut seq = deserializer.begin_seq(None)?;
+///         while deserializer.next_element(&mut seq)? {
+///             self.0.extend(deserializer.element::<T>(&mut seq)?);
+///         }
+///         deserializer.end_seq(seq)
+///     }
+/// }
+///
+/// let json = Json::new();
+/// let mut values: Vec<u32> = Vec::with_capacity(8);
+/// json.deserialize_seed("[1, 2]", Extend(&mut values))?;
+/// json.deserialize_seed("[3]", Extend(&mut values))?;
+/// assert_eq!(values, [1, 2, 3]);
+/// # Ok::<(), shallot::Error>(())
+/// ```
+pub trait DeserializeSeed: Sized {
+    /// The type of value read.
+    type Value;
+
+    /// Read a value from the deserializer's current position, consuming the
+    /// seed.
+    fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<Self::Value>;
+}
+
+impl<T: Deserialize> DeserializeSeed for PhantomData<T> {
+    type Value = T;
+
+    fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<T> {
+        T::accept(deserializer)
+    }
+}
+
+impl<T: Deserialize> DeserializeSeed for InPlace<'_, T> {
+    type Value = ();
+
+    fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<()> {
+        T::accept_in_place(deserializer, self.0).map_err(Error::in_type::<T>)
+    }
+}
+
+/// The progress through a sequence being read.
+///
+/// Created by [`Deserializer::begin_seq`] and passed back to the other
+/// sequence methods, so each nested sequence keeps its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seq {
+    /// The exact number of elements expected, if known.
+    pub len: Option<usize>,
+    /// The number of elements moved to so far.
+    pub index: usize,
+    /// Where the sequence starts, in a unit of the format's choosing.
+    pub start: usize,
+    /// Where the current element starts, in the same unit.
+    pub element: usize,
+    /// How deeply the elements are nested, for formats that limit it.
+    pub depth: usize,
+    /// Errors of the elements skipped so far, by index; see
+    /// [`Deserializer::element_failed`].
+    pub errors: Vec<(usize, Error)>,
+}
+
+impl Seq {
+    /// A sequence starting at `start`, of exactly `len` elements if given.
+    pub fn new(len: Option<usize>, start: usize) -> Self {
+        Self {
+            len,
+            index: 0,
+            start,
+            element: start,
+            depth: 0,
+            errors: Vec::new(),
+        }
+    }
+}
+
+/// The progress through a map or struct being read.
+///
+/// Created by [`Deserializer::begin_map`] or [`Deserializer::begin_struct`]
+/// and passed back to the other map methods, so each nested map keeps its
+/// own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Map {
+    /// The number of entries moved to so far.
+    pub index: usize,
+    /// Where the map starts, in a unit of the format's choosing.
+    pub start: usize,
+}
+
+impl Map {
+    /// A map starting at `start`.
+    pub fn new(start: usize) -> Self {
+        Self { index: 0, start }
+    }
+}
+
+/// A cursor over input in some data format.
+pub trait Deserializer: Sized {
+    /// Report a non-fatal finding at the current position.
+    ///
+    /// The default discards it.
+    fn warn(&mut self, _kind: WarningKind) {}
+
+    fn visit_bool(&mut self) -> Result<bool>;
+    fn visit_i8(&mut self) -> Result<i8>;
+    fn visit_i16(&mut self) -> Result<i16>;
+    fn visit_i32(&mut self) -> Result<i32>;
+    fn visit_i64(&mut self) -> Result<i64>;
+    fn visit_i128(&mut self) -> Result<i128>;
+    fn visit_isize(&mut self) -> Result<isize>;
+    fn visit_u8(&mut self) -> Result<u8>;
+    fn visit_u16(&mut self) -> Result<u16>;
+    fn visit_u32(&mut self) -> Result<u32>;
+    fn visit_u64(&mut self) -> Result<u64>;
+    fn visit_u128(&mut self) -> Result<u128>;
+    fn visit_usize(&mut self) -> Result<usize>;
+    fn visit_f32(&mut self) -> Result<f32>;
+    fn visit_f64(&mut self) -> Result<f64>;
+    fn visit_char(&mut self) -> Result<char>;
+    fn visit_string(&mut self) -> Result<String>;
+
+    /// Read a string into `buffer`, replacing its contents.
+    ///
+    /// The default reads a new string with [`Deserializer::visit_string`];
+    /// formats should override it to write into `buffer` and keep its
+    /// capacity.
+    fn visit_string_into(&mut self, buffer: &mut String) -> Result<()> {
+        *buffer = self.visit_string()?;
+        Ok(())
+    }
+
+    /// Read a platform string.
+    ///
+    /// The default only accepts strings; formats that can tell a string from
+    /// an array should also accept the array of platform code units written
+    /// by [`Serializer::visit_os_str`](crate::Serializer::visit_os_str).
+    fn visit_os_string(&mut self) -> Result<OsString> {
+        self.visit_string().map(OsString::from)
+    }
+
+    /// Read bytes spelled as text in `encoding`.
+    ///
+    /// The default reads a whole string and decodes it afterwards; text
+    /// formats should override it to decode while scanning the input.
+    fn visit_bytes(&mut self, encoding: Encoding) -> Result<Vec<u8>> {
+        let text = self.visit_string()?;
+        encoding.decode(&text).map_err(|_| {
+            Error::new(ErrorKind::Syntax {
+                expected: encoding.name(),
+            })
+        })
+    }
+
+    /// Pass the raw input text of the next value to `read` and consume it.
+    ///
+    /// This suits types embedding their own syntax, which can parse the
+    /// text without it being copied or decoded first. The default fails,
+    /// since not every format is textual.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::{Deserialize, Deserializer, Result};
+    ///
+    /// struct Raw(String);
+    ///
+    /// impl Deserialize for Raw {
+    ///     fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+    ///         deserializer.visit_raw(|text| Ok(Raw(text.to_owned())))
+    ///     }
+    /// }
+    ///
+    /// let (raw, n): (Raw, u8) = Json::new().deserialize(r#"[ {"a": [1, 2]} , 3]"#)?;
+    /// assert_eq!((raw.0.as_str(), n), (r#"{"a": [1, 2]}"#, 3));
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    fn visit_raw<T>(&mut self, read: impl FnOnce(&str) -> Result<T>) -> Result<T> {
+        let _ = read;
+        Err(Error::custom("raw input is not available from this format"))
+    }
+
+    /// Report the kind of the next value without consuming it, so that
+    /// types like [`Value`](crate::Value) can take whatever the input holds.
+    ///
+    /// The default fails, since only self-describing formats know.
+    fn peek_kind(&mut self) -> Result<Kind> {
+        Err(Error::custom("this format does not describe its values"))
+    }
+
+    /// Read the unit value `()`.
+    fn visit_unit(&mut self) -> Result<()>;
+
+    /// Read a value that may be absent.
+    fn visit_option<T: Deserialize>(&mut self) -> Result<Option<T>>;
+
+    /// Start reading a sequence, of exactly `len` elements if given.
+    ///
+    /// Follow with calls to [`Deserializer::next_element`], reading each
+    /// element with [`Deserializer::element`], and finish with
+    /// [`Deserializer::end_seq`].
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::{Deserialize, Deserializer, Result};
+    ///
+    /// struct Sum(i64);
+    ///
+    /// impl Deserialize for Sum {
+    ///     fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+    ///         let mut seq = deserializer.begin_seq(None)?;
+    ///         let mut sum = 0;
+    ///         while deserializer.next_element(&mut seq)? {
+    ///             sum += deserializer.element::<i64>(&mut seq)?.unwrap_or(0);
+    ///         }
+    ///         deserializer.end_seq(seq)?;
+    ///         Ok(Sum(sum))
+    ///     }
+    /// }
+    ///
+    /// let sum: Sum = Json::new().deserialize("[1, 2, 3]")?;
+    /// assert_eq!(sum.0, 6);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    fn begin_seq(&mut self, len: Option<usize>) -> Result<Seq>;
+
+    /// Move to the next element of `seq`, returning `false` if there are no
+    /// more.
+    ///
+    /// A sequence of known length fails if it ends early, and returns
+    /// `false` once all of its elements have been read without looking
+    /// further.
+    fn next_element(&mut self, seq: &mut Seq) -> Result<bool>;
+
+    /// Read the element just moved to as a `T`.
+    ///
+    /// Returns `None` if reading failed but
+    /// [`Deserializer::element_failed`] skipped the element.
+    fn element<T: Deserialize>(&mut self, seq: &mut Seq) -> Result<Option<T>> {
+        self.element_seed(seq, PhantomData)
+    }
+
+    /// Read the element just moved to with `seed`, like
+    /// [`Deserializer::element`].
+    fn element_seed<S: DeserializeSeed>(
+        &mut self,
+        seq: &mut Seq,
+        seed: S,
+    ) -> Result<Option<S::Value>> {
+        match seed.accept(self) {
+            Ok(element) => Ok(Some(element)),
+            Err(error) => {
+                self.element_failed(seq, error.in_type::<S::Value>())?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Handle `error` from reading the current element of `seq`.
+    ///
+    /// Returning the error stops reading. Alternatively, a format can skip
+    /// the element, add the error to [`Seq::errors`], and return `Ok` to
+    /// carry on; [`Deserializer::end_seq`] then reports every collected
+    /// error. The default returns the error.
+    fn element_failed(&mut self, seq: &mut Seq, error: Error) -> Result<()> {
+        let _ = seq;
+        Err(error)
+    }
+
+    /// Finish reading `seq`.
+    ///
+    /// Fails if a sequence of known length has more elements, or if element
+    /// errors were collected into [`Seq::errors`].
+    fn end_seq(&mut self, seq: Seq) -> Result<()>;
+
+    /// Read a homogeneous sequence of any length.
+    fn visit_array<T: Deserialize>(&mut self) -> Result<Vec<T>> {
+        let mut seq = self.begin_seq(None)?;
+        let mut elements = Vec::new();
+        while self.next_element(&mut seq)? {
+            elements.extend(self.element(&mut seq)?);
+        }
+        self.end_seq(seq)?;
+        Ok(elements)
+    }
+
+    /// Read a homogeneous sequence of exactly `N` elements.
+    fn visit_fixed_array<T: Deserialize, const N: usize>(&mut self) -> Result<[T; N]> {
+        let mut seq = self.begin_seq(Some(N))?;
+        let mut fatal = None;
+        // Built in place rather than through a `Vec`, stopping at the first
+        // error that is not collected.
+        let elements: [Option<T>; N] = std::array::from_fn(|_| match fatal {
+            Some(_) => None,
+            None => fixed_element(self, &mut seq).unwrap_or_else(|error| {
+                fatal = Some(error);
+                None
+            }),
+        });
+        if let Some(error) = fatal {
+            return Err(error);
+        }
+        self.end_seq(seq)?;
+        Ok(elements.map(|element| element.expect(SKIPPED)))
+    }
+
+    /// Start reading a map.
+    ///
+    /// Follow with calls to [`Deserializer::next_key`], each followed by
+    /// [`Deserializer::next_value`], and finish with
+    /// [`Deserializer::end_map`].
+    fn begin_map(&mut self) -> Result<Map>;
+
+    /// Move to the next entry of `map` and read its key, returning `None` if
+    /// there are no more entries.
+    fn next_key<K: Deserialize>(&mut self, map: &mut Map) -> Result<Option<K>> {
+        self.next_key_seed(map, PhantomData)
+    }
+
+    /// Move to the next entry of `map` and read its key with `seed`, like
+    /// [`Deserializer::next_key`].
+    fn next_key_seed<S: DeserializeSeed>(
+        &mut self,
+        map: &mut Map,
+        seed: S,
+    ) -> Result<Option<S::Value>>;
+
+    /// Read the value of the entry whose key was just read.
+    ///
+    /// The default reads it with [`Deserialize::accept`], for formats that
+    /// consume any separator along with the key.
+    fn next_value<V: Deserialize>(&mut self, map: &mut Map) -> Result<V> {
+        self.next_value_seed(map, PhantomData)
+    }
+
+    /// Read the value of the entry whose key was just read with `seed`, like
+    /// [`Deserializer::next_value`].
+    fn next_value_seed<S: DeserializeSeed>(&mut self, map: &mut Map, seed: S) -> Result<S::Value> {
+        let _ = map;
+        seed.accept(self).map_err(Error::in_type::<S::Value>)
+    }
+
+    /// Finish reading `map`.
+    fn end_map(&mut self, map: Map) -> Result<()>;
+
+    /// Read a collection of key-value pairs in input order.
+    ///
+    /// Duplicate keys are returned as they appear; collecting into a map
+    /// keeps the last value for each key.
+    fn visit_map<K: Deserialize, V: Deserialize>(&mut self) -> Result<Vec<(K, V)>> {
+        let mut map = self.begin_map()?;
+        let mut entries = Vec::new();
+        while let Some(key) = self.next_key(&mut map)? {
+            let value = self.next_value(&mut map)?;
+            entries.push((key, value));
+        }
+        self.end_map(map)?;
+        Ok(entries)
+    }
+
+    /// Start reading a struct named `name`, which is a map keyed by field
+    /// name.
+    ///
+    /// Follow with calls to [`Deserializer::next_field`], reading each
+    /// field's value with [`Deserialize::accept`], and finish with
+    /// [`Deserializer::end_struct`]. The default starts a map.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::{Deserialize, Deserializer, Error, ErrorKind, Result};
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// struct Point {
+    ///     x: i32,
+    ///     y: i32,
+    /// }
+    ///
+    /// impl Deserialize for Point {
+    ///     fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+    ///         const FIELDS: &[&str] = &["x", "y"];
+    ///         let (mut x, mut y) = (None, None);
+    ///         let mut map = deserializer.begin_struct("Point")?;
+    ///         while let Some(index) = deserializer.next_field(&mut map, FIELDS)? {
+    ///             match index {
+    ///                 0 => x = Some(i32::accept(deserializer)?),
+    ///                 _ => y = Some(i32::accept(deserializer)?),
+    ///             }
+    ///         }
+    ///         deserializer.end_struct(map)?;
+    ///         let missing = |field| Error::new(ErrorKind::MissingField { field });
+    ///         Ok(Point {
+    ///             x: x.ok_or_else(|| missing("x"))?,
+    ///             y: y.ok_or_else(|| missing("y"))?,
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let point: Point = Json::new().deserialize(r#"{"y": -2, "x": 1}"#)?;
+    /// assert_eq!(point, Point { x: 1, y: -2 });
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    fn begin_struct(&mut self, name: &'static str) -> Result<Map> {
+        let _ = name;
+        self.begin_map()
+    }
+
+    /// Move to the next field of the struct being read, returning the index
+    /// of its name in `fields`, or `None` if there are no more fields.
+    ///
+    /// Fields not named in `fields` are skipped with a
+    /// [`WarningKind::UnknownField`] warning. A field that appears twice is
+    /// returned twice.
+    fn next_field(
+        &mut self,
+        map: &mut Map,
+        fields: &'static [&'static str],
+    ) -> Result<Option<usize>>;
+
+    /// Finish reading a struct. The default finishes a map.
+    fn end_struct(&mut self, map: Map) -> Result<()> {
+        self.end_map(map)
+    }
+
+    /// Read a struct named `name` with unnamed fields, given as the tuple
+    /// `T` of their types.
+    ///
+    /// The default reads the tuple as is, matching
+    /// [`Serializer::visit_tuple_struct`](crate::Serializer::visit_tuple_struct).
+    fn visit_tuple_struct<T: Deserialize>(&mut self, name: &'static str) -> Result<T> {
+        let _ = name;
+        T::accept(self)
+    }
+
+    /// Read a struct named `name` wrapping a single value of type `T`.
+    ///
+    /// The default reads the wrapped value as is, matching
+    /// [`Serializer::visit_newtype_struct`](crate::Serializer::visit_newtype_struct).
+    fn visit_newtype_struct<T: Deserialize>(&mut self, name: &'static str) -> Result<T> {
+        let _ = name;
+        T::accept(self)
+    }
+
+    /// Read the `T` behind an `Rc` or `Arc`, which `new` wraps it in.
+    ///
+    /// Formats that write a value several pointers share once and refer
+    /// back to it elsewhere hand out a clone of the same pointer for each
+    /// reference. The default reads a `T` and wraps it, matching
+    /// [`Serializer::visit_shared`](crate::Serializer::visit_shared).
+    fn visit_shared<T: Deserialize, P: Clone + 'static>(&mut self, new: fn(T) -> P) -> Result<P> {
+        T::accept(self).map(new)
+    }
+
+    /// Start reading an enum variant, returning its index in `variants`.
+    ///
+    /// Unit variants are complete once this returns. For any other variant,
+    /// read its data with [`Deserialize::accept`] and then call
+    /// [`Deserializer::end_variant`].
+    fn begin_variant(&mut self, variants: &'static [Variant]) -> Result<usize>;
+
+    /// Finish reading a variant that carries data.
+    fn end_variant(&mut self) -> Result<()>;
+
+    /// Read the integer discriminant of a fieldless enum variant, returning
+    /// its index in `discriminants`.
+    ///
+    /// The default reads an `i128` and looks it up, so the error for an
+    /// unknown discriminant carries no position.
+    fn visit_discriminant(&mut self, discriminants: &'static [i128]) -> Result<usize> {
+        let value = self.visit_i128()?;
+        discriminants
+            .iter()
+            .position(|discriminant| *discriminant == value)
+            .ok_or_else(|| {
+                let variant = value.to_string();
+                Error::new(ErrorKind::UnknownVariant { variant })
+            })
+    }
+}
+
+/// Why an element missing after [`Deserializer::end_seq`] succeeded is a bug.
+const SKIPPED: &str = "end_seq fails after an element is skipped";
+
+/// Move to and read the next element of a sequence of known length.
+fn fixed_element<T: Deserialize, D: Deserializer>(
+    deserializer: &mut D,
+    seq: &mut Seq,
+) -> Result<Option<T>> {
+    if !deserializer.next_element(seq)? {
+        let kind = ErrorKind::Length {
+            expected: seq.len.unwrap_or(seq.index),
+            found: seq.index,
+        };
+        return Err(Error::new(kind));
+    }
+    deserializer.element(seq)
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty => $method:ident,)+) => {
+        $(
+            impl Deserialize for $ty {
+                fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+                    deserializer.$method()
+                }
+            }
+        )+
+    };
+}
+
+impl_primitive! {
+    bool => visit_bool,
+    i8 => visit_i8,
+    i16 => visit_i16,
+    i32 => visit_i32,
+    i64 => visit_i64,
+    i128 => visit_i128,
+    isize => visit_isize,
+    u8 => visit_u8,
+    u16 => visit_u16,
+    u32 => visit_u32,
+    u64 => visit_u64,
+    u128 => visit_u128,
+    usize => visit_usize,
+    f32 => visit_f32,
+    f64 => visit_f64,
+    char => visit_char,
+    () => visit_unit,
+}
+
+impl Deserialize for String {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_string()
+    }
+
+    fn accept_in_place<D: Deserializer>(deserializer: &mut D, place: &mut Self) -> Result<()> {
+        deserializer.visit_string_into(place)
+    }
+}
+
+impl Deserialize for OsString {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_os_string()
+    }
+}
+
+/// There are no values to read, so this always fails without consuming input.
+impl Deserialize for Infallible {
+    fn accept<D: Deserializer>(_deserializer: &mut D) -> Result<Self> {
+        Err(Error::custom("Infallible has no values to deserialize"))
+    }
+}
+
+impl<T: ?Sized> Deserialize for PhantomData<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_unit().map(|()| PhantomData)
+    }
+}
+
+impl<T: Deserialize> Deserialize for Option<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_option()
+    }
+}
+
+impl<T: Deserialize> Deserialize for Bound<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        const VARIANTS: &[Variant] = &[
+            Variant {
+                name: "Included",
+                unit: false,
+            },
+            Variant {
+                name: "Excluded",
+                unit: false,
+            },
+            Variant {
+                name: "Unbounded",
+                unit: true,
+            },
+        ];
+        let value = match deserializer.begin_variant(VARIANTS)? {
+            0 => Bound::Included(T::accept(deserializer).map_err(Error::in_type::<T>)?),
+            1 => Bound::Excluded(T::accept(deserializer).map_err(Error::in_type::<T>)?),
+            _ => return Ok(Bound::Unbounded),
+        };
+        deserializer.end_variant()?;
+        Ok(value)
+    }
+}
+
+impl<B: Deserialize, C: Deserialize> Deserialize for ControlFlow<B, C> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        const VARIANTS: &[Variant] = &[
+            Variant {
+                name: "Continue",
+                unit: false,
+            },
+            Variant {
+                name: "Break",
+                unit: false,
+            },
+        ];
+        let value = match deserializer.begin_variant(VARIANTS)? {
+            0 => ControlFlow::Continue(C::accept(deserializer).map_err(Error::in_type::<C>)?),
+            _ => ControlFlow::Break(B::accept(deserializer).map_err(Error::in_type::<B>)?),
+        };
+        deserializer.end_variant()?;
+        Ok(value)
+    }
+}
+
+impl<T: Deserialize> Deserialize for Box<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        T::accept(deserializer).map(Box::new)
+    }
+
+    fn accept_in_place<D: Deserializer>(deserializer: &mut D, place: &mut Self) -> Result<()> {
+        T::accept_in_place(deserializer, place)
+    }
+}
+
+impl<T: Deserialize + 'static> Deserialize for Rc<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_shared(Rc::new)
+    }
+}
+
+impl<T: Deserialize + 'static> Deserialize for Arc<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_shared(Arc::new)
+    }
+}
+
+impl<T: Deserialize> Deserialize for Cell<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        T::accept(deserializer).map(Cell::new)
+    }
+}
+
+impl<T: Deserialize> Deserialize for RefCell<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        T::accept(deserializer).map(RefCell::new)
+    }
+}
+
+impl<T: Deserialize> Deserialize for Mutex<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        T::accept(deserializer).map(Mutex::new)
+    }
+}
+
+impl<T: Deserialize> Deserialize for RwLock<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        T::accept(deserializer).map(RwLock::new)
+    }
+}
+
+impl<T: Deserialize> Deserialize for Vec<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_array()
+    }
+
+    /// Reads the leading elements into those already in `place`, then
+    /// pushes or truncates to the new length.
+    fn accept_in_place<D: Deserializer>(deserializer: &mut D, place: &mut Self) -> Result<()> {
+        let mut seq = deserializer.begin_seq(None)?;
+        let mut len = 0;
+        while deserializer.next_element(&mut seq)? {
+            let read = match place.get_mut(len) {
+                Some(element) => deserializer.element_seed(&mut seq, InPlace(element))?,
+                None => deserializer
+                    .element(&mut seq)?
+                    .map(|element| place.push(element)),
+            };
+            len += usize::from(read.is_some());
+        }
+        place.truncate(len);
+        deserializer.end_seq(seq)
+    }
+}
+
+impl<T: Deserialize, const N: usize> Deserialize for [T; N] {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_fixed_array()
+    }
+}
+
+impl<T: Deserialize> Deserialize for VecDeque<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_array().map(VecDeque::from)
+    }
+}
+
+impl<T: Deserialize> Deserialize for LinkedList<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        let elements: Vec<T> = deserializer.visit_array()?;
+        Ok(elements.into_iter().collect())
+    }
+}
+
+impl<T: Deserialize + Ord> Deserialize for BinaryHeap<T> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_array().map(BinaryHeap::from)
+    }
+}
+
+impl<K, V, H> Deserialize for HashMap<K, V, H>
+where
+    K: Deserialize + Eq + Hash,
+    V: Deserialize,
+    H: BuildHasher + Default,
+{
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        let entries: Vec<(K, V)> = deserializer.visit_map()?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl<K: Deserialize + Ord, V: Deserialize> Deserialize for BTreeMap<K, V> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        let entries: Vec<(K, V)> = deserializer.visit_map()?;
+        Ok(entries.into_iter().collect())
+    }
+}
+
+macro_rules! impl_tuple {
+    ($(($($name:ident),+))+) => {
+        $(
+            impl<$($name: Deserialize),+> Deserialize for ($($name,)+) {
+                #[allow(non_snake_case)]
+                fn accept<De: Deserializer>(deserializer: &mut De) -> Result<Self> {
+                    const LEN: usize = [$(stringify!($name)),+].len();
+                    let mut seq = deserializer.begin_seq(Some(LEN))?;
+                    $(let $name = fixed_element::<$name, De>(deserializer, &mut seq)?;)+
+                    deserializer.end_seq(seq)?;
+                    Ok(($($name.expect(SKIPPED),)+))
+                }
+            }
+        )+
+    };
+}
+
+impl_tuple! {
+    (A)
+    (A, B)
+    (A, B, C)
+    (A, B, C, D)
+    (A, B, C, D, E)
+    (A, B, C, D, E, F)
+    (A, B, C, D, E, F, G)
+    (A, B, C, D, E, F, G, H)
+    (A, B, C, D, E, F, G, H, I)
+    (A, B, C, D, E, F, G, H, I, J)
+    (A, B, C, D, E, F, G, H, I, J, K)
+    (A, B, C, D, E, F, G, H, I, J, K, L)
+    (A, B, C, D, E, F, G, H, I, J, K, L, M)
+    (A, B, C, D, E, F, G, H, I, J, K, L, M, N)
+    (A, B, C, D, E, F, G, H, I, J, K, L, M, N, O)
+    (A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P)
+}
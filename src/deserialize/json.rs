@@ -0,0 +1,3637 @@
+//! JSON input.
+
+pub mod lexer;
+mod push;
+mod scan;
+mod shared;
+
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::fmt::{self, Display, Write};
+use std::marker::PhantomData;
+use std::num::{IntErrorKind, ParseIntError};
+use std::str::FromStr;
+
+use super::{Deserialize, DeserializeSeed, Deserializer, InPlace, Kind, Map, Seq, Variant};
+use crate::bytes::{Decoder, Encoding};
+#[cfg(feature = "charset")]
+use crate::charset::Charset;
+use crate::error::{Error, ErrorKind, Position, Result, Warning, WarningKind};
+use crate::os_str;
+
+pub use push::{Feed, PushParser};
+use shared::SharedValues;
+
+/// Deserializes values from JSON text.
+///
+/// ```
+/// use shallot::deserialize::Json;
+///
+/// let json = Json::new();
+/// let value: (u8, String, Vec<f64>) = json.deserialize(r#"[1, "two", [3.5]]"#)?;
+/// assert_eq!(value, (1, String::from("two"), vec![3.5]));
+/// # Ok::<(), shallot::Error>(())
+/// ```
+///
+/// [`Json::new`] reads strict JSON, as does [`Json::builder`], which starts
+/// a configuration. Each option is set by a method taking and returning the
+/// deserializer, so they chain in any order and only the ones that differ
+/// from the default need naming:
+///
+/// - strictness: [`Json::nul_policy`], [`Json::overflow_policy`] and
+///   [`Json::trailing_policy`];
+/// - limits: [`Json::max_depth`], [`Json::max_input_len`],
+///   [`Json::max_string_len`] and [`Json::max_elements`];
+/// - error reporting: [`Json::collect_errors`];
+/// - shared values: [`Json::references`];
+/// - lenient syntax: [`Json::comments`], [`Json::single_quotes`],
+///   [`Json::trailing_commas`], [`Json::unquoted_keys`],
+///   [`Json::control_characters`], [`Json::radix_integers`],
+///   [`Json::non_finite`] and [`Json::quote_wide_integers`], or most of
+///   them at once with [`Json::json5`].
+///
+/// ```
+/// use shallot::deserialize::{Json, NulPolicy, TrailingPolicy};
+///
+/// let config = Json::builder()
+///     .comments(true)
+///     .trailing_commas(true)
+///     .nul_policy(NulPolicy::Reject)
+///     .trailing_policy(TrailingPolicy::Newline);
+/// let ports: Vec<u16> = config.deserialize("[80, 443, // tls\n]\n")?;
+/// assert_eq!(ports, [80, 443]);
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Json {
+    nul: NulPolicy,
+    overflow: OverflowPolicy,
+    trailing: TrailingPolicy,
+    collect_errors: bool,
+    non_finite: bool,
+    quote_wide_integers: bool,
+    comments: bool,
+    single_quotes: bool,
+    trailing_commas: bool,
+    radix_integers: bool,
+    unquoted_keys: bool,
+    control_characters: bool,
+    json5: bool,
+    references: bool,
+    max_depth: Option<usize>,
+    max_input_len: Option<usize>,
+    max_string_len: Option<usize>,
+    max_elements: Option<usize>,
+}
+
+/// The nesting depth [`Json::max_depth`] allows unless set.
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+/// What to do with U+0000 inside strings.
+///
+/// Many consumers written in C, and databases such as PostgreSQL, cannot
+/// store embedded NULs, so they are best caught while parsing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NulPolicy {
+    /// Keep the NUL as-is.
+    #[default]
+    Accept,
+    /// Fail with [`ErrorKind::Nul`].
+    Reject,
+    /// Substitute U+FFFD REPLACEMENT CHARACTER.
+    Replace,
+}
+
+/// What to do with integers outside the range of the target type.
+///
+/// Floats too large for their type fail with [`ErrorKind::Overflow`]
+/// whatever the policy, rather than becoming infinite.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Fail with [`ErrorKind::Overflow`].
+    #[default]
+    Error,
+    /// Saturate to the target's minimum or maximum and record a
+    /// [`WarningKind::Clamped`] warning.
+    Clamp,
+}
+
+/// What may follow the value in [`Json::deserialize`] and
+/// [`Parser::finish`].
+///
+/// The same rule applies to every type, so `42\n` and `"text"\n` are
+/// treated alike.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrailingPolicy {
+    /// Any amount of JSON whitespace.
+    #[default]
+    Whitespace,
+    /// At most one line ending, `\n` or `\r\n`, as written by line-oriented
+    /// tools.
+    Newline,
+    /// Nothing at all.
+    Exact,
+}
+
+impl Json {
+    /// Create a JSON deserializer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start configuring a JSON deserializer, with every option at its
+    /// default; chain the option methods on the result and use it as is.
+    ///
+    /// This is the same as [`Json::new`], which stays for deserializers
+    /// that need no options.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::builder().max_depth(8).json5(true);
+    /// let ports: Vec<u16> = json.deserialize("[80, 0x1bb,]")?;
+    /// assert_eq!(ports, [80, 443]);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn builder() -> Self {
+        Self::default()
+    }
+
+    /// Set how NUL characters inside strings are handled.
+    ///
+    /// ```
+    /// use shallot::deserialize::{Json, NulPolicy};
+    ///
+    /// let json = Json::new().nul_policy(NulPolicy::Replace);
+    /// assert_eq!(json.deserialize::<String>("\"a\0b\"")?, "a\u{fffd}b");
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn nul_policy(mut self, policy: NulPolicy) -> Self {
+        self.nul = policy;
+        self
+    }
+
+    /// Set how out-of-range integers are handled.
+    ///
+    /// ```
+    /// use shallot::deserialize::{Json, OverflowPolicy};
+    ///
+    /// let json = Json::new().overflow_policy(OverflowPolicy::Clamp);
+    /// let (value, warnings) = json.deserialize_with_warnings::<Vec<u8>>("[-3, 7, 300]")?;
+    /// assert_eq!(value, [0, 7, 255]);
+    /// assert_eq!(warnings.len(), 2);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.overflow = policy;
+        self
+    }
+
+    /// Set what may follow the value.
+    ///
+    /// ```
+    /// use shallot::deserialize::{Json, TrailingPolicy};
+    ///
+    /// let json = Json::new().trailing_policy(TrailingPolicy::Newline);
+    /// assert_eq!(json.deserialize::<u8>("42\n")?, 42);
+    /// assert!(json.deserialize::<u8>("42\n\n").is_err());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn trailing_policy(mut self, policy: TrailingPolicy) -> Self {
+        self.trailing = policy;
+        self
+    }
+
+    /// Set whether sequences and tuples report every failed element in one
+    /// [`ErrorKind::Elements`] error rather than stopping at the first.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::ErrorKind;
+    ///
+    /// let json = Json::new().collect_errors(true);
+    /// let error = json.deserialize::<Vec<u8>>("[1, -2, 3, 400]").unwrap_err();
+    /// let ErrorKind::Elements { errors } = error.kind() else {
+    ///     unreachable!()
+    /// };
+    /// let indices: Vec<usize> = errors.iter().map(|(index, _)| *index).collect();
+    /// assert_eq!(indices, [1, 3]);
+    /// ```
+    pub fn collect_errors(mut self, collect: bool) -> Self {
+        self.collect_errors = collect;
+        self
+    }
+
+    /// Set whether float targets accept the bare tokens `NaN`, `Infinity`
+    /// and `-Infinity`, as written by JavaScript, Python and JSON5, though
+    /// they are not JSON.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new().non_finite(true);
+    /// let value: Vec<f64> = json.deserialize("[1.5, -Infinity]")?;
+    /// assert_eq!(value, [1.5, f64::NEG_INFINITY]);
+    /// assert!(json.deserialize::<f32>("NaN")?.is_nan());
+    /// assert!(Json::new().deserialize::<f32>("NaN").is_err());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn non_finite(mut self, accept: bool) -> Self {
+        self.non_finite = accept;
+        self
+    }
+
+    /// Set whether `i128` and `u128` also accept their digits as a string,
+    /// as written by [`serialize::Json::quote_wide_integers`].
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new().quote_wide_integers(true);
+    /// let value: Vec<u128> = json.deserialize(r#"["1", 2]"#)?;
+    /// assert_eq!(value, [1, 2]);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    ///
+    /// [`serialize::Json::quote_wide_integers`]: crate::serialize::Json::quote_wide_integers
+    pub fn quote_wide_integers(mut self, accept: bool) -> Self {
+        self.quote_wide_integers = accept;
+        self
+    }
+
+    /// Set whether `//` line comments and `/* */` block comments are
+    /// skipped wherever whitespace may appear, as in JSONC configuration
+    /// files.
+    ///
+    /// Positions in errors and warnings still count the comments.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new().comments(true);
+    /// let input = "{\n  // the port\n  \"port\": /* default */ 8080\n}";
+    /// let value: std::collections::BTreeMap<String, u16> = json.deserialize(input)?;
+    /// assert_eq!(value["port"], 8080);
+    /// assert!(Json::new().deserialize::<u16>("1 // one").is_err());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn comments(mut self, accept: bool) -> Self {
+        self.comments = accept;
+        self
+    }
+
+    /// Set whether strings may also be enclosed in single quotes, as
+    /// JavaScript allows.
+    ///
+    /// Inside single quotes, `"` needs no escape and `'` is written `\'`.
+    /// Serializers never write single-quoted strings.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new().single_quotes(true);
+    /// let value: Vec<String> = json.deserialize(r#"['say "hi"', 'it\'s', "plain"]"#)?;
+    /// assert_eq!(value, [r#"say "hi""#, "it's", "plain"]);
+    /// assert!(Json::new().deserialize::<String>("'a'").is_err());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn single_quotes(mut self, accept: bool) -> Self {
+        self.single_quotes = accept;
+        self
+    }
+
+    /// Set whether arrays and objects may have a comma after their last
+    /// element or entry.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new().trailing_commas(true);
+    /// assert_eq!(json.deserialize::<Vec<u8>>("[1, 2,]")?, [1, 2]);
+    /// assert!(json.deserialize::<Vec<u8>>("[1, 2,,]").is_err());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn trailing_commas(mut self, accept: bool) -> Self {
+        self.trailing_commas = accept;
+        self
+    }
+
+    /// Set whether integers may be written in hexadecimal, octal or binary,
+    /// as in `0xFF`, `0o17` and `-0b1010`.
+    ///
+    /// Values out of range of the target fail with [`ErrorKind::Overflow`]
+    /// like decimal ones, or are clamped by [`OverflowPolicy::Clamp`].
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::ErrorKind;
+    ///
+    /// let json = Json::new().radix_integers(true);
+    /// assert_eq!(json.deserialize::<Vec<i16>>("[0xFF, 0o17, -0b1010]")?, [255, 15, -10]);
+    /// let error = json.deserialize::<u8>("0x100").unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::Overflow { target: "u8" });
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn radix_integers(mut self, accept: bool) -> Self {
+        self.radix_integers = accept;
+        self
+    }
+
+    /// Set whether object keys may be bare identifiers, as in JavaScript
+    /// object literals like `{port: 80}`.
+    ///
+    /// An identifier starts with a letter, `$` or `_`, and goes on with
+    /// those or digits. Keys of other types are read from the identifier
+    /// itself, so `{true: 1}` has a `bool` key.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new().unquoted_keys(true);
+    /// let map: BTreeMap<String, u16> = json.deserialize(r#"{port: 80, "max-age": 60}"#)?;
+    /// assert_eq!(map["port"], 80);
+    /// assert!(json.deserialize::<BTreeMap<String, u16>>("{max-age: 60}").is_err());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn unquoted_keys(mut self, accept: bool) -> Self {
+        self.unquoted_keys = accept;
+        self
+    }
+
+    /// Set whether strings may hold the control characters U+0001 to
+    /// U+001F, such as tabs and line breaks, as they are rather than
+    /// escaped.
+    ///
+    /// JSON requires them to be escaped, so by default they fail with
+    /// [`ErrorKind::ControlCharacter`]. U+0000 is handled by
+    /// [`Json::nul_policy`] instead.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::ErrorKind;
+    ///
+    /// let json = Json::new().control_characters(true);
+    /// assert_eq!(json.deserialize::<String>("\"a\tb\"")?, "a\tb");
+    /// let error = Json::new().deserialize::<String>("\"a\tb\"").unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::ControlCharacter);
+    /// assert_eq!(error.position().unwrap().col, 3);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn control_characters(mut self, accept: bool) -> Self {
+        self.control_characters = accept;
+        self
+    }
+
+    /// Whether a string may hold the control character `char` as it is.
+    fn accepts_control(&self, char: char) -> bool {
+        char == '\0' || self.control_characters || self.json5 && !matches!(char, '\n' | '\r')
+    }
+
+    /// Set whether input is read as [JSON5](https://json5.org), the
+    /// superset of JSON written by hand in configuration files.
+    ///
+    /// This accepts everything [`Json::comments`], [`Json::single_quotes`],
+    /// [`Json::trailing_commas`], [`Json::unquoted_keys`] and
+    /// [`Json::non_finite`] do, whatever they are set to, and also:
+    ///
+    /// - hexadecimal integers, like `0xFF`,
+    /// - numbers with a leading `+`, or a leading or trailing decimal point,
+    /// - strings continued over several lines by ending each with `\`,
+    /// - control characters other than line breaks inside strings,
+    /// - the escapes `\'`, `\v`, `\0` and `\xFF`, and a backslash before any
+    ///   other character standing for the character itself,
+    /// - Unicode whitespace such as U+00A0 and the byte order mark.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::Value;
+    ///
+    /// let input = r#"{
+    ///     // comments
+    ///     unquoted: 'and you can quote me on that',
+    ///     lineBreaks: 'Look, Mom! \
+    /// No \\n\'s!',
+    ///     hexadecimal: 0xdecaf,
+    ///     leadingDecimalPoint: .8675309, andTrailing: 8675309.,
+    ///     positiveSign: +1,
+    ///     trailingComma: 'in objects', andIn: ['arrays',],
+    /// }"#;
+    /// let value: Value = Json::new().json5(true).deserialize(input)?;
+    /// let get = |key| value.get(key).unwrap();
+    /// assert_eq!(get("lineBreaks").as_str(), Some(r"Look, Mom! No \n's!"));
+    /// assert_eq!(get("hexadecimal").as_u64(), Some(0xdecaf));
+    /// assert_eq!(get("andTrailing").as_f64(), Some(8675309.0));
+    /// assert!(Json::new().deserialize::<Value>(input).is_err());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn json5(mut self, accept: bool) -> Self {
+        self.json5 = accept;
+        self
+    }
+
+    /// Set whether a value several `Rc` or `Arc` pointers share, written
+    /// once with [`SharedMode::Reference`], is read back as one value
+    /// shared by them again.
+    ///
+    /// An `Rc` or `Arc` is then read from `{"$id": 1, "$value": ...}`, and
+    /// each later `{"$ref": 1}` gives another pointer to the same value;
+    /// any other value is read as usual. A reference to an id not read yet
+    /// fails, including one from within the value itself, since a value
+    /// cannot be made to contain itself through an `Rc`. The values are
+    /// looked up in the [`Parser`] reading the document.
+    ///
+    /// [`SharedMode::Reference`]: crate::serialize::SharedMode::Reference
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    ///
+    /// use shallot::deserialize::Json;
+    ///
+    /// let input = r#"[{"$id": 1, "$value": [1, 2]}, {"$ref": 1}, [3, 4]]"#;
+    /// let points: Vec<Rc<(i32, i32)>> = Json::new().references(true).deserialize(input)?;
+    /// assert!(Rc::ptr_eq(&points[0], &points[1]));
+    /// assert_eq!(*points[2], (3, 4));
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn references(mut self, accept: bool) -> Self {
+        self.references = accept;
+        self
+    }
+
+    /// Set how deeply arrays and objects may be nested, 128 unless set.
+    ///
+    /// Reading nested values recurses, so without a limit a document of
+    /// many opening brackets can overflow the stack. Deeper input fails
+    /// with [`ErrorKind::DepthLimit`] at the bracket that passes the limit;
+    /// values that are skipped count as well.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::ErrorKind;
+    ///
+    /// let json = Json::new().max_depth(2);
+    /// assert_eq!(json.deserialize::<Vec<Vec<u8>>>("[[1], []]")?, [vec![1], vec![]]);
+    /// let error = json.deserialize::<Vec<Vec<Vec<u8>>>>("[[[1]]]").unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::DepthLimit { limit: 2 });
+    /// assert_eq!(error.position().unwrap().col, 3);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Set how many bytes of input may be read at once, unlimited unless
+    /// set.
+    ///
+    /// Longer input fails with [`ErrorKind::SizeLimit`] before any of it is
+    /// parsed.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::ErrorKind;
+    ///
+    /// let json = Json::new().max_input_len(8);
+    /// assert_eq!(json.deserialize::<Vec<u8>>("[1, 2]")?, [1, 2]);
+    /// let error = json.deserialize::<Vec<u8>>("[1, 2, 3, 4]").unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::SizeLimit { what: "input", limit: 8 });
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn max_input_len(mut self, len: usize) -> Self {
+        self.max_input_len = Some(len);
+        self
+    }
+
+    /// Set how many bytes a string, including an object key, may take once
+    /// its escapes are resolved, unlimited unless set.
+    ///
+    /// Longer strings fail with [`ErrorKind::SizeLimit`] at their start,
+    /// without being read further.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::ErrorKind;
+    ///
+    /// let json = Json::new().max_string_len(3);
+    /// assert_eq!(json.deserialize::<String>(r#""ét""#)?, "ét");
+    /// let error = json.deserialize::<Vec<String>>(r#"["abc", "abcd"]"#).unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::SizeLimit { what: "string", limit: 3 });
+    /// assert_eq!(error.position().unwrap().col, 9);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn max_string_len(mut self, len: usize) -> Self {
+        self.max_string_len = Some(len);
+        self
+    }
+
+    /// Set how many elements an array, or entries an object, may have,
+    /// unlimited unless set.
+    ///
+    /// Larger arrays and objects fail with [`ErrorKind::SizeLimit`] at the
+    /// first item past the limit; values that are skipped count as well.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::ErrorKind;
+    ///
+    /// let json = Json::new().max_elements(2);
+    /// assert_eq!(json.deserialize::<Vec<Vec<u8>>>("[[1, 2], [3]]")?, [vec![1, 2], vec![3]]);
+    /// let error = json.deserialize::<Vec<u8>>("[1, 2, 3]").unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::SizeLimit { what: "array", limit: 2 });
+    /// assert_eq!(error.position().unwrap().col, 8);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn max_elements(mut self, len: usize) -> Self {
+        self.max_elements = Some(len);
+        self
+    }
+
+    /// Deserialize a single value spanning all of `input`.
+    ///
+    /// Whitespace before the value is ignored; what may follow it is set by
+    /// [`Json::trailing_policy`], and anything else is an
+    /// [`ErrorKind::TrailingCharacters`] error.
+    pub fn deserialize<T: Deserialize>(&self, input: &str) -> Result<T> {
+        self.deserialize_seed(input, PhantomData)
+    }
+
+    /// Deserialize like [`Json::deserialize`], reading the value with `seed`.
+    pub fn deserialize_seed<S: DeserializeSeed>(&self, input: &str, seed: S) -> Result<S::Value> {
+        self.check_input_len(input.len())?;
+        let mut parser = self.parser(input).without_warnings();
+        let value = seed
+            .accept(&mut parser)
+            .map_err(Error::in_type::<S::Value>)?;
+        parser.finish()?;
+        Ok(value)
+    }
+
+    /// Deserialize a single value from the start of `input`, returning it
+    /// with the rest of the input that follows it.
+    ///
+    /// Whitespace before the value is ignored, and nothing after it is
+    /// checked, so a value embedded in a larger buffer can be read and
+    /// parsing continued after it. Error positions are within `input`.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new();
+    /// let (numbers, rest) = json.deserialize_partial::<Vec<u8>>(" [1, 2] tail")?;
+    /// assert_eq!(numbers, [1, 2]);
+    /// assert_eq!(rest, " tail");
+    /// let (word, rest) = json.deserialize_partial::<String>(r#""a""b""#)?;
+    /// assert_eq!((word.as_str(), rest), ("a", r#""b""#));
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn deserialize_partial<'a, T: Deserialize>(&self, input: &'a str) -> Result<(T, &'a str)> {
+        self.check_input_len(input.len())?;
+        let mut parser = self.parser(input).without_warnings();
+        let value = T::accept(&mut parser).map_err(Error::in_type::<T>)?;
+        Ok((value, &input[parser.offset..]))
+    }
+
+    /// Deserialize like [`Json::deserialize`] from `input` as UTF-8 bytes,
+    /// such as data read from a file or a socket, without copying it.
+    ///
+    /// `input` is validated before it is parsed, and invalid UTF-8 fails
+    /// with [`ErrorKind::InvalidUtf8`] at the first offending byte, whose
+    /// position counts the characters before it in the valid part.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    /// use shallot::ErrorKind;
+    ///
+    /// let json = Json::new();
+    /// assert_eq!(json.deserialize_bytes::<Vec<String>>(b"[\"caf\xc3\xa9\"]")?, ["café"]);
+    /// let error = json.deserialize_bytes::<Vec<String>>(b"[\"\xc3\xa9\xe9\"]").unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::InvalidUtf8);
+    /// assert_eq!(error.position().unwrap().offset, 4);
+    /// assert_eq!(error.to_string(), "invalid UTF-8 at line 1, column 4");
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn deserialize_bytes<T: Deserialize>(&self, input: &[u8]) -> Result<T> {
+        self.deserialize(from_utf8(input)?)
+    }
+
+    /// Deserialize like [`Json::deserialize`] into `place`, reusing its
+    /// allocations; see [`Deserialize::accept_in_place`].
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new();
+    /// let mut names: Vec<String> = Vec::new();
+    /// json.deserialize_in_place(r#"["ada", "grace"]"#, &mut names)?;
+    /// let capacity = names[1].capacity();
+    /// json.deserialize_in_place(r#"["bo", "cy"]"#, &mut names)?;
+    /// assert_eq!(names, ["bo", "cy"]);
+    /// assert_eq!(names[1].capacity(), capacity);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn deserialize_in_place<T: Deserialize>(&self, input: &str, place: &mut T) -> Result<()> {
+        self.deserialize_seed(input, InPlace(place))
+    }
+
+    /// Deserialize like [`Json::deserialize`], also returning the warnings
+    /// collected along the way.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let (value, warnings) = Json::new().deserialize_with_warnings::<f32>("0.123456789")?;
+    /// assert_eq!(value, 0.12345679);
+    /// assert_eq!(
+    ///     warnings[0].to_string(),
+    ///     "number cannot be represented exactly as f32 at line 1, column 1"
+    /// );
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn deserialize_with_warnings<T: Deserialize>(
+        &self,
+        input: &str,
+    ) -> Result<(T, Vec<Warning>)> {
+        self.check_input_len(input.len())?;
+        let mut parser = self.parser(input);
+        let value = T::accept(&mut parser).map_err(Error::in_type::<T>)?;
+        parser.finish()?;
+        Ok((value, parser.take_warnings()))
+    }
+
+    /// Deserialize like [`Json::deserialize_with_warnings`] from `input` in a
+    /// legacy single-byte `charset`.
+    ///
+    /// Error and warning positions are byte offsets into `input`.
+    ///
+    /// ```
+    /// use shallot::charset::Charset;
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new();
+    /// let (value, _) = json.deserialize_charset::<String>(b"\"\x93caf\xe9\x94\"", Charset::Windows1252)?;
+    /// assert_eq!(value, "“café”");
+    ///
+    /// let error = json.deserialize_charset::<Vec<u8>>(b"[\"\xe9\"]", Charset::Latin1).unwrap_err();
+    /// assert_eq!(error.position().unwrap().offset, 1);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    #[cfg(feature = "charset")]
+    pub fn deserialize_charset<T: Deserialize>(
+        &self,
+        input: &[u8],
+        charset: Charset,
+    ) -> Result<(T, Vec<Warning>)> {
+        let decoded = charset.decode(input);
+        let original = |position| Charset::original_position(&decoded, position);
+        match self.deserialize_with_warnings(&decoded) {
+            Ok((value, mut warnings)) => {
+                for warning in &mut warnings {
+                    warning.position = warning.position.map(original);
+                }
+                Ok((value, warnings))
+            }
+            Err(error) => Err(match error.position() {
+                Some(position) => error.at(original(position)),
+                None => error,
+            }),
+        }
+    }
+
+    /// Iterate over the values of `input`, one JSON document after another
+    /// as in a log or a pipe, each read as a `T`.
+    ///
+    /// Whitespace may separate the values, and must where one would
+    /// otherwise run into the next, as between two numbers. Error positions
+    /// are within `input`, and iteration stops after the first error, since
+    /// nothing after malformed input can be trusted.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let json = Json::new();
+    /// let values = json.stream::<Vec<u8>>("[1]\n[2, 3]  []").collect::<Result<Vec<_>, _>>()?;
+    /// assert_eq!(values, [vec![1], vec![2, 3], vec![]]);
+    ///
+    /// let mut stream = json.stream::<u8>("1 2 x 4");
+    /// assert_eq!(stream.next().transpose()?, Some(1));
+    /// assert_eq!(stream.next().transpose()?, Some(2));
+    /// assert_eq!(stream.next().unwrap().unwrap_err().position().unwrap().col, 5);
+    /// assert!(stream.next().is_none());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn stream<'a, T: Deserialize>(&'a self, input: &'a str) -> StreamDeserializer<'a, T> {
+        StreamDeserializer {
+            pending: self.check_input_len(input.len()).err(),
+            parser: self.parser(input),
+            done: false,
+            marker: PhantomData,
+        }
+    }
+
+    /// Fail if `len` bytes of input are more than [`Json::max_input_len`]
+    /// allows.
+    fn check_input_len(&self, len: usize) -> Result<()> {
+        match self.max_input_len {
+            Some(limit) if len > limit => Err(Error::new(ErrorKind::SizeLimit {
+                what: "input",
+                limit,
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    /// Create a parser positioned at the start of `input`.
+    pub fn parser<'a>(&'a self, input: &'a str) -> Parser<'a> {
+        Parser {
+            json: self,
+            input,
+            offset: 0,
+            located: Cell::new(Position {
+                offset: 0,
+                row: 1,
+                col: 1,
+            }),
+            warnings: Some(Vec::new()),
+            key: false,
+            depth: 0,
+            shared: SharedValues::default(),
+        }
+    }
+}
+
+/// A cursor over JSON input, created by [`Json::parser`].
+///
+/// The parser holds the values it read for `$id`s with [`Json::references`],
+/// which may be `Rc`s, so it stays on the thread that created it.
+#[derive(Debug, Clone)]
+pub struct Parser<'a> {
+    json: &'a Json,
+    input: &'a str,
+    offset: usize,
+    /// The position last located, which later ones are counted on from.
+    located: Cell<Position>,
+    /// The warnings collected, or `None` if nothing takes them.
+    warnings: Option<Vec<Warning>>,
+    /// Whether an object key is being read, so scalars must be quoted.
+    key: bool,
+    /// How many arrays and objects are being read.
+    depth: usize,
+    /// The values read for `$id`s, with [`Json::references`].
+    shared: SharedValues,
+}
+
+impl<'a> Parser<'a> {
+    /// Remove and return the warnings collected so far.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.warnings
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    /// Drop warnings rather than collecting them.
+    fn without_warnings(mut self) -> Self {
+        self.warnings = None;
+        self
+    }
+
+    /// Collect a warning for the input at byte `offset`.
+    fn warn_at(&mut self, kind: WarningKind, offset: usize) {
+        if self.warnings.is_none() {
+            return;
+        }
+        let position = Some(self.locate(offset));
+        if let Some(warnings) = &mut self.warnings {
+            warnings.push(Warning { kind, position });
+        }
+    }
+
+    /// Check that only what [`Json::trailing_policy`] allows remains in the
+    /// input.
+    pub fn finish(&mut self) -> Result<()> {
+        match self.json.trailing {
+            TrailingPolicy::Whitespace => self.skip_whitespace(),
+            TrailingPolicy::Newline => {
+                let rest = self.rest();
+                let newline = ["\r\n", "\n"]
+                    .iter()
+                    .find(|newline| rest.starts_with(**newline));
+                self.offset += newline.map_or(0, |newline| newline.len());
+            }
+            TrailingPolicy::Exact => {}
+        }
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(self.error(ErrorKind::TrailingCharacters)),
+        }
+    }
+
+    /// The current position in the input.
+    pub fn position(&self) -> Position {
+        self.locate(self.offset)
+    }
+
+    /// The position of byte `offset` of the input.
+    ///
+    /// Finding the line and column takes a pass over the input, so the
+    /// parser keeps offsets and only locates them for errors and warnings,
+    /// counting on from the last position located. Those come in input
+    /// order, so the input is passed over once however many there are.
+    fn locate(&self, offset: usize) -> Position {
+        let last = self.located.get();
+        let position = match offset >= last.offset {
+            true => last.after(&self.input[last.offset..offset]),
+            false => Position::locate(self.input, offset),
+        };
+        self.located.set(position);
+        position
+    }
+
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error::new(kind).at(self.position())
+    }
+
+    /// An error for input that is not the `expected` kind of value.
+    fn syntax(&self, expected: &'static str) -> Error {
+        match self.peek() {
+            Some(_) => self.error(ErrorKind::Syntax { expected }),
+            None => self.error(ErrorKind::Eof),
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.offset..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.as_bytes().get(self.offset).copied()
+    }
+
+    /// Skip whitespace, and comments if they are accepted.
+    ///
+    /// An unterminated block comment is left in place, to fail as
+    /// unexpected input where it starts.
+    fn skip_whitespace(&mut self) {
+        loop {
+            self.offset += lexer::whitespace(self.json, self.rest());
+            if !self.json.comments && !self.json.json5 {
+                return;
+            }
+            match lexer::comment(self.rest()) {
+                Some(len) => self.offset += len,
+                None => return,
+            }
+        }
+    }
+
+    /// Consume `byte`, or fail expecting `expected`.
+    fn expect(&mut self, byte: u8, expected: &'static str) -> Result<()> {
+        if self.peek() != Some(byte) {
+            return Err(self.syntax(expected));
+        }
+        self.offset += 1;
+        Ok(())
+    }
+
+    /// Consume `literal` after any whitespace, or fail expecting `expected`.
+    fn literal(&mut self, literal: &str, expected: &'static str) -> Result<()> {
+        self.skip_whitespace();
+        if !self.rest().starts_with(literal) {
+            return Err(self.syntax(expected));
+        }
+        self.offset += literal.len();
+        Ok(())
+    }
+
+    /// Run `read`, first consuming an opening quote and afterwards a closing
+    /// one if an object key is being read.
+    fn quoted<T>(
+        &mut self,
+        expected: &'static str,
+        read: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        if !self.key {
+            return read(self);
+        }
+        self.skip_whitespace();
+        if self.at_identifier() {
+            self.key = false;
+            let value = read(self);
+            self.key = true;
+            return value;
+        }
+        let quote = self.open_quote(expected)?;
+        self.key = false;
+        let value = read(self);
+        self.key = true;
+        let value = value?;
+        self.close_quote(quote)?;
+        Ok(value)
+    }
+
+    /// Whether a string starts here, with `"` or an accepted `'`.
+    fn at_quote(&self) -> bool {
+        match self.peek() {
+            Some(b'"') => true,
+            Some(b'\'') => self.json.single_quotes || self.json.json5,
+            _ => false,
+        }
+    }
+
+    /// Whether an object key that is a bare identifier starts here, if they
+    /// are accepted.
+    fn at_identifier(&self) -> bool {
+        self.key
+            && (self.json.unquoted_keys || self.json.json5)
+            && lexer::identifier(self.rest()).is_some()
+    }
+
+    /// Consume an object key that is a bare identifier, if one starts here.
+    fn identifier(&mut self) -> Option<&'a str> {
+        if !self.at_identifier() {
+            return None;
+        }
+        let rest = self.rest();
+        let end = lexer::identifier(rest)?;
+        self.offset += end;
+        Some(&rest[..end])
+    }
+
+    /// Run `read` as when reading an object key.
+    fn as_key<T>(&mut self, read: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        let key = std::mem::replace(&mut self.key, true);
+        let value = read(self);
+        self.key = key;
+        value
+    }
+
+    /// Consume the quote opening a string and return it.
+    fn open_quote(&mut self, expected: &'static str) -> Result<char> {
+        if !self.at_quote() {
+            return Err(self.syntax(expected));
+        }
+        self.offset += 1;
+        Ok(char::from(self.input.as_bytes()[self.offset - 1]))
+    }
+
+    /// Consume the quote closing a string opened with `quote`.
+    fn close_quote(&mut self, quote: char) -> Result<()> {
+        match quote {
+            '"' => self.expect(b'"', "`\"`"),
+            _ => self.expect(b'\'', "`'`"),
+        }
+    }
+
+    /// Consume a number token, returning it and whether it is a plain
+    /// integer, without a fraction or an exponent.
+    fn number(&mut self, expected: &'static str) -> Result<(&'a str, bool)> {
+        self.skip_whitespace();
+        let start = self.offset;
+        match lexer::number(self.json, self.rest()) {
+            Ok((len, integral)) => {
+                self.offset += len;
+                Ok((&self.input[start..self.offset], integral))
+            }
+            Err(at) => {
+                self.offset += at;
+                Err(self.syntax(expected))
+            }
+        }
+    }
+
+    /// Consume an integer of a type whose range is `min..=max`.
+    ///
+    /// Numbers with a fraction or an exponent, like `1.0`, `1e3` or
+    /// `2.5E2`, are accepted as long as the value is whole. Negative zero
+    /// is zero, even for unsigned types.
+    fn integer<T>(&mut self, expected: &'static str, (min, max): (T, T)) -> Result<T>
+    where
+        T: FromStr<Err = ParseIntError>,
+    {
+        self.quoted(expected, |parser| {
+            parser.unquoted_integer(expected, (min, max))
+        })
+    }
+
+    /// Consume an `i128` or `u128`, which may be quoted if configured.
+    fn wide_integer<T>(&mut self, expected: &'static str, range: (T, T)) -> Result<T>
+    where
+        T: FromStr<Err = ParseIntError>,
+    {
+        self.skip_whitespace();
+        if !self.json.quote_wide_integers || !self.at_quote() {
+            return self.integer(expected, range);
+        }
+        let quote = self.open_quote(expected)?;
+        let value = self.unquoted_integer(expected, range)?;
+        self.close_quote(quote)?;
+        Ok(value)
+    }
+
+    fn unquoted_integer<T>(&mut self, expected: &'static str, (min, max): (T, T)) -> Result<T>
+    where
+        T: FromStr<Err = ParseIntError>,
+    {
+        self.skip_whitespace();
+        let start = self.offset;
+        let (token, integral) = self.number(expected)?;
+        let error = |kind| Error::new(kind).at(self.locate(start));
+        let parse = parse_integer::<T>;
+        let mut expanded = StackBuffer::<48>::new();
+        let parsed = match radix_integer(token) {
+            Some(Ok((negative, magnitude))) => {
+                let sign = if negative { "-" } else { "" };
+                write!(expanded, "{sign}{magnitude}").expect("every u128 fits");
+                parse(expanded.as_str())
+            }
+            Some(Err(cause)) => Err(cause),
+            None if integral => parse(token),
+            None => {
+                let decimal = Decimal::parse(token);
+                if decimal.exponent < 0 {
+                    return Err(error(ErrorKind::Syntax { expected }));
+                }
+                // Anything that doesn't fit the buffer is out of range of
+                // every integer type.
+                match decimal.write_integer(&mut expanded) {
+                    Ok(()) => parse(expanded.as_str()),
+                    Err(_) if decimal.negative => Err(IntErrorKind::NegOverflow),
+                    Err(_) => Err(IntErrorKind::PosOverflow),
+                }
+            }
+        };
+        let clamp = self.json.overflow == OverflowPolicy::Clamp;
+        let clamped = match parsed {
+            Ok(value) => return Ok(value),
+            Err(cause) => match cause {
+                IntErrorKind::PosOverflow if clamp => max,
+                IntErrorKind::NegOverflow if clamp => min,
+                IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                    return Err(error(ErrorKind::Overflow { target: expected }))
+                }
+                _ => return Err(error(ErrorKind::Syntax { expected })),
+            },
+        };
+        self.warn_at(WarningKind::Clamped { target: expected }, start);
+        Ok(clamped)
+    }
+
+    fn float<T: FromStr + Display + Into<f64> + Copy>(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<T> {
+        self.quoted(expected, |parser| parser.unquoted_float(expected))
+    }
+
+    /// Consume `NaN`, `Infinity` or `-Infinity` if they are accepted.
+    fn non_finite(&mut self) -> Option<&'a str> {
+        let token = lexer::non_finite(self.json, self.rest())?;
+        self.offset += token.len();
+        Some(token)
+    }
+
+    fn unquoted_float<T: FromStr + Display + Into<f64> + Copy>(
+        &mut self,
+        expected: &'static str,
+    ) -> Result<T> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let error =
+            |parser: &Self| Error::new(ErrorKind::Syntax { expected }).at(parser.locate(start));
+        if let Some(token) = self.non_finite() {
+            return token.parse().map_err(|_| error(self));
+        }
+        let (token, _) = self.number(expected)?;
+        let overflow = |parser: &Self| {
+            Error::new(ErrorKind::Overflow { target: expected }).at(parser.locate(start))
+        };
+        if let Some((negative, digits, radix)) = radix_digits(token) {
+            // Exact up to 2^53, and rounded at each step beyond, which only
+            // matters for hand-written numbers wider than that.
+            let magnitude = digits.chars().fold(0.0, |value: f64, digit| {
+                value * f64::from(radix) + f64::from(digit.to_digit(radix).unwrap_or_default())
+            });
+            let value = if negative { -magnitude } else { magnitude };
+            let value: T = value.to_string().parse().map_err(|_| error(self))?;
+            return match value.into().is_infinite() {
+                true => Err(overflow(self)),
+                false => Ok(value),
+            };
+        }
+        let value: T = token.parse().map_err(|_| error(self))?;
+        // Only the tokens above stand for infinities; numbers too large for
+        // the type round to them.
+        if value.into().is_infinite() {
+            return Err(overflow(self));
+        }
+        if self.warnings.is_none() {
+            return Ok(value);
+        }
+        // Floats print without an exponent, so the longest, f64::MIN_POSITIVE
+        // subnormals and f64::MAX, take a little over 320 bytes.
+        let mut printed = StackBuffer::<400>::new();
+        let lossy = match write!(printed, "{value}") {
+            Ok(()) => Decimal::parse(token) != Decimal::parse(printed.as_str()),
+            Err(_) => Decimal::parse(token) != Decimal::parse(&value.to_string()),
+        };
+        if lossy {
+            self.warn_at(WarningKind::LossyFloat { target: expected }, start);
+        }
+        Ok(value)
+    }
+
+    /// Consume a quoted string, resolving escapes.
+    ///
+    /// Strings without escapes are copied from the input whole rather than
+    /// a character at a time.
+    fn string(&mut self, expected: &'static str) -> Result<String> {
+        self.borrowed_string(expected).map(Cow::into_owned)
+    }
+
+    /// Consume a quoted string, borrowing it from the input unless it
+    /// contains escapes or NULs to replace.
+    fn borrowed_string(&mut self, expected: &'static str) -> Result<Cow<'a, str>> {
+        if let Some(string) = self.plain_string()? {
+            return Ok(Cow::Borrowed(string));
+        }
+        let mut output = String::new();
+        self.scan_string(expected, |char| output.push(char))?;
+        Ok(Cow::Owned(output))
+    }
+
+    /// Consume a quoted string and return it if it has no escapes or NULs
+    /// to replace, so it can be borrowed from the input as it is.
+    fn plain_string(&mut self) -> Result<Option<&'a str>> {
+        self.skip_whitespace();
+        let start = self.offset;
+        if let Some(identifier) = self.identifier() {
+            self.check_string_len(start, identifier.len())?;
+            return Ok(Some(identifier));
+        }
+        if !self.at_quote() {
+            return Ok(None);
+        }
+        let rest = self.rest();
+        let quote = rest.as_bytes()[0];
+        let body = &rest[1..];
+        let end = scan::string_special(body.as_bytes(), quote);
+        match end.filter(|&end| body.as_bytes()[end] == quote) {
+            Some(end) => {
+                self.check_string_len(start, end)?;
+                self.offset += end + 2;
+                Ok(Some(&body[..end]))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Consume a quoted string, passing each character to `push` after
+    /// resolving escapes and applying the NUL policy.
+    fn scan_string(&mut self, expected: &'static str, mut push: impl FnMut(char)) -> Result<()> {
+        self.skip_whitespace();
+        let start = self.offset;
+        if let Some(identifier) = self.identifier() {
+            self.check_string_len(start, identifier.len())?;
+            identifier.chars().for_each(push);
+            return Ok(());
+        }
+        let quote = self.open_quote(expected)?;
+        let limit = self.json.max_string_len.unwrap_or(usize::MAX);
+        let mut len = 0;
+        let mut chars = self.rest().char_indices();
+        while let Some((index, char)) = chars.next() {
+            let char = match char {
+                _ if char == quote => {
+                    self.offset += index + 1;
+                    return Ok(());
+                }
+                '\\' => match lexer::escape(self.json, quote, &mut chars) {
+                    Ok(Some(unescaped)) => unescaped,
+                    Ok(None) => continue,
+                    Err(ErrorKind::Eof) => break,
+                    Err(kind) => {
+                        self.offset += index;
+                        return Err(self.error(kind));
+                    }
+                },
+                char if char < ' ' && !self.json.accepts_control(char) => {
+                    self.offset += index;
+                    return Err(self.error(ErrorKind::ControlCharacter));
+                }
+                char => char,
+            };
+            let char = match (char, self.json.nul) {
+                ('\0', NulPolicy::Replace) => char::REPLACEMENT_CHARACTER,
+                ('\0', NulPolicy::Reject) => {
+                    self.offset += index;
+                    return Err(self.error(ErrorKind::Nul));
+                }
+                _ => char,
+            };
+            len += char.len_utf8();
+            if len > limit {
+                self.check_string_len(start, len)?;
+            }
+            push(char);
+        }
+        self.offset = self.input.len();
+        Err(self.error(ErrorKind::Eof))
+    }
+
+    /// Fail at the string starting at `start` if `len` bytes are more than
+    /// [`Json::max_string_len`] allows.
+    fn check_string_len(&mut self, start: usize, len: usize) -> Result<()> {
+        match self.json.max_string_len {
+            Some(limit) if len > limit => {
+                self.offset = start;
+                Err(self.error(ErrorKind::SizeLimit {
+                    what: "string",
+                    limit,
+                }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Consume the opening bracket of an array.
+    fn begin_array(&mut self, expected: &'static str) -> Result<()> {
+        self.skip_whitespace();
+        self.open(b'[', expected)
+    }
+
+    /// Consume the opening `bracket` of an array or object, checking that
+    /// it is within the depth limit.
+    fn open(&mut self, bracket: u8, expected: &'static str) -> Result<()> {
+        let limit = self.json.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+        if self.peek() == Some(bracket) && self.depth >= limit {
+            return Err(self.error(ErrorKind::DepthLimit { limit }));
+        }
+        self.expect(bracket, expected)?;
+        self.depth += 1;
+        Ok(())
+    }
+
+    /// Consume the closing `bracket` of the innermost array or object.
+    fn close(&mut self, bracket: u8, expected: &'static str) -> Result<()> {
+        self.expect(bracket, expected)?;
+        self.depth = self.depth.saturating_sub(1);
+        Ok(())
+    }
+
+    /// Move to the element at `index`, returning `false` once the closing
+    /// bracket has been consumed instead.
+    fn next_in_array(&mut self, index: usize) -> Result<bool> {
+        if self.separator(index, b']', "`,` or `]`")? {
+            self.close(b']', "`]`")?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Consume the comma before the item at `index` of an array or object
+    /// ending with `close`, returning whether `close` follows instead, which
+    /// is left in place.
+    fn separator(&mut self, index: usize, close: u8, expected: &'static str) -> Result<bool> {
+        self.skip_whitespace();
+        if index > 0 && self.peek() != Some(close) {
+            self.expect(b',', expected)?;
+            self.skip_whitespace();
+            if !self.json.trailing_commas && !self.json.json5 {
+                self.check_elements(index, close)?;
+                return Ok(false);
+            }
+        }
+        let closed = self.peek() == Some(close);
+        if !closed {
+            self.check_elements(index, close)?;
+        }
+        Ok(closed)
+    }
+
+    /// Fail if the item at `index` of an array or object ending with
+    /// `close` is past [`Json::max_elements`].
+    fn check_elements(&self, index: usize, close: u8) -> Result<()> {
+        match self.json.max_elements {
+            Some(limit) if index >= limit => {
+                let what = if close == b']' { "array" } else { "object" };
+                Err(self.error(ErrorKind::SizeLimit { what, limit }))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Move to the next element of `seq`, as in
+    /// [`Deserializer::next_element`].
+    fn advance(&mut self, seq: &mut Seq) -> Result<bool> {
+        if seq.len == Some(seq.index) {
+            return Ok(false);
+        }
+        if self.separator(seq.index, b']', "`,` or `]`")? {
+            let Some(len) = seq.len else {
+                return Ok(false);
+            };
+            let kind = ErrorKind::Length {
+                expected: len,
+                found: seq.index,
+            };
+            return Err(self.error(kind));
+        }
+        seq.element = self.offset;
+        seq.index += 1;
+        Ok(true)
+    }
+
+    /// Consume the closing bracket of `seq`, counting any surplus elements
+    /// of a sequence of known length for the error.
+    fn close_array(&mut self, seq: &Seq) -> Result<()> {
+        self.skip_whitespace();
+        if seq.len.is_none() {
+            return self.close(b']', "`]`");
+        }
+        let position = self.offset;
+        let mut found = seq.index;
+        while self.next_in_array(found)? {
+            self.skip_value()?;
+            found += 1;
+        }
+        if found == seq.index {
+            return Ok(());
+        }
+        let kind = ErrorKind::Length {
+            expected: seq.index,
+            found,
+        };
+        Err(Error::new(kind).at(self.locate(position)))
+    }
+
+    /// The error to return from a sequence for `error`, which is combined
+    /// with any element errors already collected, blaming element `index`.
+    fn seq_error(&self, seq: &mut Seq, index: usize, error: Error) -> Error {
+        if seq.errors.is_empty() {
+            return error;
+        }
+        seq.errors.push((index, error));
+        self.elements_error(seq)
+    }
+
+    /// The errors collected in `seq` combined into one.
+    fn elements_error(&self, seq: &mut Seq) -> Error {
+        let errors = std::mem::take(&mut seq.errors);
+        Error::new(ErrorKind::Elements { errors }).at(self.locate(seq.start))
+    }
+
+    /// Consume the opening brace of an object.
+    fn begin_object(&mut self, expected: &'static str) -> Result<()> {
+        self.skip_whitespace();
+        self.open(b'{', expected)
+    }
+
+    /// Move to the entry at `index`, returning `false` once the closing
+    /// brace has been consumed instead.
+    fn next_entry(&mut self, index: usize) -> Result<bool> {
+        if self.separator(index, b'}', "`,` or `}`")? {
+            self.close(b'}', "`}`")?;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    /// Move to the next entry of `map`, returning `false` at the closing
+    /// brace without consuming it.
+    fn advance_entry(&mut self, map: &mut Map) -> Result<bool> {
+        if self.separator(map.index, b'}', "`,` or `}`")? {
+            return Ok(false);
+        }
+        map.index += 1;
+        Ok(true)
+    }
+
+    /// Read an object key and the colon after it.
+    fn key<S: DeserializeSeed>(&mut self, seed: S) -> Result<S::Value> {
+        let key = self.as_key(|parser| seed.accept(parser).map_err(Error::in_type::<S::Value>))?;
+        self.skip_whitespace();
+        self.expect(b':', "`:`")?;
+        Ok(key)
+    }
+
+    /// Consume the start of an object written for a shared value, up to
+    /// the colon after its `"$id"` or `"$ref"` key, and return the key; or
+    /// consume nothing and return `None` if no such object starts here.
+    fn shared_key(&mut self) -> Option<&'static str> {
+        let (offset, depth) = (self.offset, self.depth);
+        let mut read = || -> Result<Option<&'static str>> {
+            self.begin_object("object")?;
+            self.skip_whitespace();
+            let key = match &*self.as_key(|parser| parser.borrowed_string("string"))? {
+                "$id" => "$id",
+                "$ref" => "$ref",
+                _ => return Ok(None),
+            };
+            self.skip_whitespace();
+            self.expect(b':', "`:`")?;
+            Ok(Some(key))
+        };
+        let key = read().ok().flatten();
+        if key.is_none() {
+            (self.offset, self.depth) = (offset, depth);
+        }
+        key
+    }
+
+    /// Consume any single JSON value without interpreting it.
+    fn skip_value(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        match self.peek() {
+            _ if self.at_quote() => self.scan_string("value", drop),
+            Some(b'[') => {
+                self.begin_array("value")?;
+                let mut index = 0;
+                while self.next_in_array(index)? {
+                    self.skip_value()?;
+                    index += 1;
+                }
+                Ok(())
+            }
+            Some(b'{') => {
+                self.begin_object("value")?;
+                let mut index = 0;
+                while self.next_entry(index)? {
+                    self.as_key(|parser| parser.scan_string("string", drop))?;
+                    self.skip_whitespace();
+                    self.expect(b':', "`:`")?;
+                    self.skip_value()?;
+                    index += 1;
+                }
+                Ok(())
+            }
+            Some(b't') => self.literal("true", "value"),
+            Some(b'f') => self.literal("false", "value"),
+            Some(b'n') => self.literal("null", "value"),
+            _ if self.non_finite().is_some() => Ok(()),
+            _ => self.number("value").map(drop),
+        }
+    }
+}
+
+/// Borrow `input` as a `str`, failing at the first byte that is not valid
+/// UTF-8.
+pub(super) fn from_utf8(input: &[u8]) -> Result<&str> {
+    std::str::from_utf8(input).map_err(|error| {
+        let valid = &input[..error.valid_up_to()];
+        let valid = std::str::from_utf8(valid).expect("the prefix is valid");
+        Error::new(ErrorKind::InvalidUtf8).at(Position::locate(valid, valid.len()))
+    })
+}
+
+/// The sign, digits and radix of a `0x`, `0o` or `0b` integer token, or
+/// `None` for a decimal one.
+fn radix_digits(token: &str) -> Option<(bool, &str, u32)> {
+    let negative = token.starts_with('-');
+    let unsigned = token.trim_start_matches(['-', '+']);
+    let radix = match unsigned.get(..2)? {
+        "0x" | "0X" => 16,
+        "0o" | "0O" => 8,
+        "0b" | "0B" => 2,
+        _ => return None,
+    };
+    Some((negative, &unsigned[2..], radix))
+}
+
+/// Parse the decimal integer `text` as a `T`.
+///
+/// Negative numbers are out of range of unsigned types rather than invalid,
+/// and negative zero is zero.
+fn parse_integer<T: FromStr<Err = ParseIntError>>(
+    text: &str,
+) -> std::result::Result<T, IntErrorKind> {
+    match text.parse::<T>().map_err(|cause| *cause.kind()) {
+        // Unsigned types take no sign, so read the magnitude instead.
+        Err(IntErrorKind::InvalidDigit) if text.starts_with('-') => {
+            match parse_integer::<T>(&text[1..]) {
+                Ok(_) if text[1..].bytes().all(|digit| digit == b'0') => parse_integer("0"),
+                Ok(_) | Err(IntErrorKind::PosOverflow) => Err(IntErrorKind::NegOverflow),
+                Err(cause) => Err(cause),
+            }
+        }
+        result => result,
+    }
+}
+
+/// The sign and magnitude of a `0x`, `0o` or `0b` integer token, or `None`
+/// for a decimal one.
+fn radix_integer(token: &str) -> Option<std::result::Result<(bool, u128), IntErrorKind>> {
+    let (negative, digits, radix) = radix_digits(token)?;
+    Some(match u128::from_str_radix(digits, radix) {
+        Ok(magnitude) => Ok((negative, magnitude)),
+        Err(_) if negative => Err(IntErrorKind::NegOverflow),
+        Err(_) => Err(IntErrorKind::PosOverflow),
+    })
+}
+
+/// A decimal number split into its significant digits and the power of ten
+/// of the last one, so that equal values compare equal.
+///
+/// The digits stay borrowed from the text, split around the decimal point.
+#[derive(Debug, Clone, Copy)]
+struct Decimal<'a> {
+    negative: bool,
+    integer: &'a str,
+    fraction: &'a str,
+    /// Leading zeros to skip across `integer` then `fraction`.
+    skip: usize,
+    /// Significant digits after `skip`.
+    len: usize,
+    exponent: isize,
+}
+
+impl<'a> Decimal<'a> {
+    fn parse(number: &'a str) -> Self {
+        let (mantissa, exponent) = match number.split_once(['e', 'E']) {
+            // Exponents too large for `isize` saturate rather than vanish.
+            Some((mantissa, exponent)) => match exponent.parse() {
+                Ok(exponent) => (mantissa, exponent),
+                Err(_) if exponent.starts_with('-') => (mantissa, isize::MIN),
+                Err(_) => (mantissa, isize::MAX),
+            },
+            None => (number, 0),
+        };
+        let negative = mantissa.starts_with('-');
+        let mantissa = mantissa.trim_start_matches(['-', '+']);
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let zeros = |digits: &str| digits.len() - digits.trim_start_matches('0').len();
+        let trailing = |digits: &str| digits.len() - digits.trim_end_matches('0').len();
+        let total = integer.len() + fraction.len();
+        let leading = match zeros(integer) {
+            all if all == integer.len() => all + zeros(fraction),
+            some => some,
+        };
+        let trailing = match trailing(fraction) {
+            all if all == fraction.len() => all + trailing(integer),
+            some => some,
+        };
+        let len = total.saturating_sub(leading + trailing);
+        Self {
+            negative: negative && len > 0,
+            integer,
+            fraction,
+            skip: leading,
+            len,
+            exponent: match len {
+                0 => 0,
+                _ => exponent
+                    .saturating_sub(fraction.len() as isize)
+                    .saturating_add(trailing as isize),
+            },
+        }
+    }
+
+    /// Write a whole number out in full, which fails if it doesn't fit.
+    fn write_integer(&self, output: &mut impl fmt::Write) -> fmt::Result {
+        if self.negative {
+            output.write_char('-')?;
+        }
+        if self.len == 0 {
+            return output.write_char('0');
+        }
+        self.digits()
+            .try_for_each(|digit| output.write_char(digit.into()))?;
+        (0..self.exponent).try_for_each(|_| output.write_char('0'))
+    }
+
+    fn digits(&self) -> impl Iterator<Item = u8> + 'a {
+        let digits = self.integer.bytes().chain(self.fraction.bytes());
+        digits.skip(self.skip).take(self.len)
+    }
+}
+
+impl PartialEq for Decimal<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.negative, self.len, self.exponent) == (other.negative, other.len, other.exponent)
+            && self.digits().eq(other.digits())
+    }
+}
+
+/// A fixed-capacity text buffer on the stack.
+struct StackBuffer<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> StackBuffer<N> {
+    fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        std::str::from_utf8(&self.bytes[..self.len]).expect("only whole strs are written")
+    }
+}
+
+impl<const N: usize> fmt::Write for StackBuffer<N> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        let end = self.len + text.len();
+        let slot = self.bytes.get_mut(self.len..end).ok_or(fmt::Error)?;
+        slot.copy_from_slice(text.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+macro_rules! visit_integer {
+    ($($method:ident($ty:ident))+) => {
+        $(
+            fn $method(&mut self) -> Result<$ty> {
+                self.integer(stringify!($ty), ($ty::MIN, $ty::MAX))
+            }
+        )+
+    };
+}
+
+impl Deserializer for Parser<'_> {
+    fn visit_bool(&mut self) -> Result<bool> {
+        self.quoted("bool", |parser| {
+            parser.skip_whitespace();
+            if parser.rest().starts_with("true") {
+                parser.offset += 4;
+                Ok(true)
+            } else {
+                parser.literal("false", "bool").map(|_| false)
+            }
+        })
+    }
+
+    visit_integer! {
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_isize(isize)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_usize(usize)
+    }
+
+    fn visit_i128(&mut self) -> Result<i128> {
+        self.wide_integer("i128", (i128::MIN, i128::MAX))
+    }
+
+    fn visit_u128(&mut self) -> Result<u128> {
+        self.wide_integer("u128", (u128::MIN, u128::MAX))
+    }
+
+    fn visit_f32(&mut self) -> Result<f32> {
+        self.float("f32")
+    }
+
+    fn visit_f64(&mut self) -> Result<f64> {
+        self.float("f64")
+    }
+
+    fn visit_char(&mut self) -> Result<char> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let string = self.borrowed_string("char")?;
+        let mut chars = string.chars();
+        match (chars.next(), chars.next()) {
+            (Some(char), None) => Ok(char),
+            _ => Err(Error::new(ErrorKind::Syntax { expected: "char" }).at(self.locate(start))),
+        }
+    }
+
+    fn visit_string(&mut self) -> Result<String> {
+        self.string("string")
+    }
+
+    fn visit_string_into(&mut self, buffer: &mut String) -> Result<()> {
+        buffer.clear();
+        match self.plain_string()? {
+            Some(string) => buffer.push_str(string),
+            None => self.scan_string("string", |char| buffer.push(char))?,
+        }
+        Ok(())
+    }
+
+    fn warn(&mut self, kind: WarningKind) {
+        self.warn_at(kind, self.offset);
+    }
+
+    fn visit_os_string(&mut self) -> Result<OsString> {
+        self.skip_whitespace();
+        if self.peek() == Some(b'[') {
+            return self.visit_array().map(os_str::from_code_units);
+        }
+        self.visit_string().map(OsString::from)
+    }
+
+    /// Escapes are resolved before the text is decoded, so `"\u0041"` reads
+    /// as the base64 digit `A`, and the resolved text is held to
+    /// [`Json::max_string_len`] like any other string.
+    fn visit_bytes(&mut self, encoding: Encoding) -> Result<Vec<u8>> {
+        self.skip_whitespace();
+        let expected = encoding.name();
+        let start = self.offset;
+        let quote = self.open_quote(expected)?;
+        let limit = self.json.max_string_len.unwrap_or(usize::MAX);
+        let mut len = 0;
+        let mut decoder = Decoder::new(encoding);
+        let mut chars = self.rest().char_indices();
+        while let Some((index, char)) = chars.next() {
+            let char = match char {
+                _ if char == quote => {
+                    let end = self.offset + index;
+                    self.offset = end + 1;
+                    return decoder.finish().ok_or_else(|| {
+                        Error::new(ErrorKind::Syntax { expected }).at(self.locate(end))
+                    });
+                }
+                '\\' => match lexer::escape(self.json, quote, &mut chars) {
+                    Ok(Some(unescaped)) => unescaped,
+                    Ok(None) => continue,
+                    Err(ErrorKind::Eof) => break,
+                    Err(kind) => {
+                        self.offset += index;
+                        return Err(self.error(kind));
+                    }
+                },
+                char => char,
+            };
+            len += char.len_utf8();
+            if len > limit {
+                self.check_string_len(start, len)?;
+            }
+            // Only ASCII characters can be part of an encoding's alphabet.
+            if !u8::try_from(char).is_ok_and(|byte| decoder.push(byte)) {
+                self.offset += index;
+                return Err(self.syntax(expected));
+            }
+        }
+        self.offset = self.input.len();
+        Err(self.error(ErrorKind::Eof))
+    }
+
+    /// Errors from `read` without a position are placed at the start of the
+    /// value.
+    fn visit_raw<T>(&mut self, read: impl FnOnce(&str) -> Result<T>) -> Result<T> {
+        self.skip_whitespace();
+        let start = self.offset;
+        self.skip_value()?;
+        read(&self.input[start..self.offset]).map_err(|error| match error.position() {
+            Some(_) => error,
+            None => error.at(self.locate(start)),
+        })
+    }
+
+    fn peek_kind(&mut self) -> Result<Kind> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let kind = match self.peek() {
+            _ if self.at_quote() => Kind::String,
+            Some(b'[') => Kind::Seq,
+            Some(b'{') => Kind::Map,
+            Some(b't' | b'f') => Kind::Bool,
+            Some(b'n') => Kind::Null,
+            _ if self.non_finite().is_some() => Kind::Float,
+            _ => {
+                let (token, integral) = self.number("value")?;
+                match (integral, token.starts_with('-'), radix_integer(token)) {
+                    (_, _, Some(Ok((false, magnitude)))) if magnitude <= u64::MAX.into() => {
+                        Kind::Unsigned
+                    }
+                    (_, _, Some(Ok((true, magnitude)))) if magnitude <= 1 << 63 => Kind::Signed,
+                    (_, _, Some(Ok((false, _)))) => Kind::WideUnsigned,
+                    (_, _, Some(Ok((true, magnitude)))) if magnitude <= 1 << 127 => {
+                        Kind::WideSigned
+                    }
+                    (_, _, Some(_)) => Kind::Float,
+                    (true, false, None) if token.parse::<u64>().is_ok() => Kind::Unsigned,
+                    (true, true, None) if token.parse::<i64>().is_ok() => Kind::Signed,
+                    (true, false, None) if token.parse::<u128>().is_ok() => Kind::WideUnsigned,
+                    (true, true, None) if token.parse::<i128>().is_ok() => Kind::WideSigned,
+                    _ => Kind::Float,
+                }
+            }
+        };
+        self.offset = start;
+        Ok(kind)
+    }
+
+    fn visit_unit(&mut self) -> Result<()> {
+        self.literal("null", "null")
+    }
+
+    fn visit_option<T: Deserialize>(&mut self) -> Result<Option<T>> {
+        self.skip_whitespace();
+        if self.rest().starts_with("null") {
+            self.offset += 4;
+            return Ok(None);
+        }
+        T::accept(self).map(Some).map_err(Error::in_type::<T>)
+    }
+
+    fn begin_seq(&mut self, len: Option<usize>) -> Result<Seq> {
+        self.begin_array("array")?;
+        let mut seq = Seq::new(len, self.offset - 1);
+        seq.depth = self.depth;
+        Ok(seq)
+    }
+
+    fn next_element(&mut self, seq: &mut Seq) -> Result<bool> {
+        let index = seq.index;
+        self.advance(seq)
+            .map_err(|error| self.seq_error(seq, index, error))
+    }
+
+    /// With [`Json::collect_errors`], the failed element is skipped unless
+    /// it is malformed.
+    fn element_failed(&mut self, seq: &mut Seq, error: Error) -> Result<()> {
+        if !self.json.collect_errors {
+            return Err(error);
+        }
+        let index = seq.index - 1;
+        self.offset = seq.element;
+        // Forget the arrays and objects the element left open.
+        self.depth = seq.depth;
+        // Nothing after a malformed element can be trusted, so its syntax
+        // error ends the sequence.
+        if let Err(fatal) = self.skip_value() {
+            return Err(self.seq_error(seq, index, fatal));
+        }
+        seq.errors.push((index, error));
+        Ok(())
+    }
+
+    fn end_seq(&mut self, mut seq: Seq) -> Result<()> {
+        if let Err(error) = self.close_array(&seq) {
+            let index = seq.index;
+            return Err(self.seq_error(&mut seq, index, error));
+        }
+        match seq.errors.is_empty() {
+            true => Ok(()),
+            false => Err(self.elements_error(&mut seq)),
+        }
+    }
+
+    fn begin_map(&mut self) -> Result<Map> {
+        self.begin_object("object")?;
+        Ok(Map::new(self.offset - 1))
+    }
+
+    fn next_key_seed<S: DeserializeSeed>(
+        &mut self,
+        map: &mut Map,
+        seed: S,
+    ) -> Result<Option<S::Value>> {
+        if !self.advance_entry(map)? {
+            return Ok(None);
+        }
+        self.key(seed).map(Some)
+    }
+
+    fn end_map(&mut self, map: Map) -> Result<()> {
+        let _ = map;
+        self.skip_whitespace();
+        self.close(b'}', "`}`")
+    }
+
+    fn begin_struct(&mut self, name: &'static str) -> Result<Map> {
+        self.begin_object(name)?;
+        Ok(Map::new(self.offset - 1))
+    }
+
+    fn next_field(
+        &mut self,
+        map: &mut Map,
+        fields: &'static [&'static str],
+    ) -> Result<Option<usize>> {
+        while self.advance_entry(map)? {
+            self.skip_whitespace();
+            let start = self.offset;
+            let name = self.as_key(|parser| parser.borrowed_string("string"))?;
+            self.skip_whitespace();
+            self.expect(b':', "`:`")?;
+            if let Some(index) = fields.iter().position(|field| *field == name) {
+                return Ok(Some(index));
+            }
+            if self.warnings.is_some() {
+                let field = name.into_owned();
+                self.warn_at(WarningKind::UnknownField { field }, start);
+            }
+            self.skip_value()?;
+        }
+        Ok(None)
+    }
+
+    /// Unit variants are written as their name, other variants as an
+    /// object with the name as its only key and the data as its value.
+    fn begin_variant(&mut self, variants: &'static [Variant]) -> Result<usize> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let braced = self.peek() == Some(b'{');
+        if braced {
+            self.begin_object("variant")?;
+            self.skip_whitespace();
+        }
+        let position = self.offset;
+        let name = match braced {
+            true => self.as_key(|parser| parser.borrowed_string("variant"))?,
+            false => self.borrowed_string("variant")?,
+        };
+        let Some(index) = variants.iter().position(|variant| variant.name == name) else {
+            let variant = name.into_owned();
+            return Err(Error::new(ErrorKind::UnknownVariant { variant }).at(self.locate(position)));
+        };
+        match (braced, variants[index].unit) {
+            (false, true) => Ok(index),
+            (true, false) => {
+                self.skip_whitespace();
+                self.expect(b':', "`:`")?;
+                Ok(index)
+            }
+            (false, false) => {
+                Err(Error::new(ErrorKind::Syntax { expected: "object" }).at(self.locate(start)))
+            }
+            (true, true) => {
+                Err(Error::new(ErrorKind::Syntax { expected: "string" }).at(self.locate(start)))
+            }
+        }
+    }
+
+    fn visit_discriminant(&mut self, discriminants: &'static [i128]) -> Result<usize> {
+        self.skip_whitespace();
+        let start = self.offset;
+        let value = self.visit_i128()?;
+        discriminants
+            .iter()
+            .position(|discriminant| *discriminant == value)
+            .ok_or_else(|| {
+                let variant = value.to_string();
+                Error::new(ErrorKind::UnknownVariant { variant }).at(self.locate(start))
+            })
+    }
+
+    fn end_variant(&mut self) -> Result<()> {
+        self.skip_whitespace();
+        self.close(b'}', "`}`")
+    }
+
+    /// With [`Json::references`], reads the objects
+    /// [`SharedMode::Reference`](crate::serialize::SharedMode::Reference)
+    /// writes.
+    fn visit_shared<T: Deserialize, P: Clone + 'static>(&mut self, new: fn(T) -> P) -> Result<P> {
+        if !self.json.references {
+            return T::accept(self).map(new);
+        }
+        self.skip_whitespace();
+        let start = self.offset;
+        let Some(key) = self.shared_key() else {
+            return T::accept(self).map(new);
+        };
+        self.skip_whitespace();
+        let id_start = self.offset;
+        let id = self.visit_u64()?;
+        let at_id =
+            |parser: &Self, message: String| Error::custom(message).at(parser.locate(id_start));
+        if key == "$ref" {
+            self.skip_whitespace();
+            self.close(b'}', "`}`")?;
+            return self.shared.get(id).ok_or_else(|| {
+                at_id(
+                    self,
+                    format!("no shared value of this type with id {id} was read before"),
+                )
+            });
+        }
+        let mut map = Map::new(start);
+        map.index = 1;
+        let more = self.advance_entry(&mut map)?;
+        let key_start = self.offset;
+        if !more || self.as_key(|parser| parser.borrowed_string("string"))? != "$value" {
+            let expected = "`\"$value\"`";
+            return Err(Error::new(ErrorKind::Syntax { expected }).at(self.locate(key_start)));
+        }
+        self.skip_whitespace();
+        self.expect(b':', "`:`")?;
+        let value = new(T::accept(self).map_err(Error::in_type::<T>)?);
+        self.skip_whitespace();
+        self.close(b'}', "`}`")?;
+        match self.shared.insert(id, value.clone()) {
+            true => Ok(value),
+            false => Err(at_id(
+                self,
+                format!("the shared value id {id} is used twice"),
+            )),
+        }
+    }
+}
+
+/// An iterator over the values of concatenated JSON documents, created by
+/// [`Json::stream`].
+#[derive(Debug)]
+pub struct StreamDeserializer<'a, T> {
+    parser: Parser<'a>,
+    /// An error to return before reading anything.
+    pending: Option<Error>,
+    /// Whether the input is used up or an error ended iteration.
+    done: bool,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> StreamDeserializer<'_, T> {
+    /// Where the next value, or the end of the input, begins.
+    pub fn position(&self) -> Position {
+        self.parser.position()
+    }
+
+    /// Remove and return the warnings collected so far.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        self.parser.take_warnings()
+    }
+}
+
+impl<T: Deserialize> Iterator for StreamDeserializer<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.done {
+            return None;
+        }
+        if let Some(error) = self.pending.take() {
+            self.done = true;
+            return Some(Err(error));
+        }
+        self.parser.skip_whitespace();
+        if self.parser.peek().is_none() {
+            self.done = true;
+            return None;
+        }
+        let value = T::accept(&mut self.parser).map_err(Error::in_type::<T>);
+        self.done = value.is_err();
+        self.parser.skip_whitespace();
+        Some(value)
+    }
+}
+
+impl<T: Deserialize> std::iter::FusedIterator for StreamDeserializer<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::collections::{BTreeMap, BinaryHeap, HashMap, LinkedList, VecDeque};
+    use std::convert::Infallible;
+    use std::marker::PhantomData;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use super::*;
+    use crate::allocations;
+    use crate::bytes::{Base64, Hex};
+    use crate::Value;
+
+    fn kind<T: Deserialize + std::fmt::Debug>(input: &str) -> ErrorKind {
+        Json::new()
+            .deserialize::<T>(input)
+            .unwrap_err()
+            .kind()
+            .clone()
+    }
+
+    #[test]
+    fn scalars() {
+        let json = Json::new();
+        assert!(json.deserialize::<bool>(" true ").unwrap());
+        assert_eq!(json.deserialize::<i16>("-300").unwrap(), -300);
+        assert_eq!(json.deserialize::<f64>("2.25").unwrap(), 2.25);
+        assert_eq!(json.deserialize::<char>("\"é\"").unwrap(), 'é');
+        assert_eq!(json.deserialize::<Option<u8>>("null").unwrap(), None);
+        assert_eq!(json.deserialize::<Option<u8>>("4").unwrap(), Some(4));
+        json.deserialize::<()>("null").unwrap();
+        json.deserialize::<PhantomData<str>>("null").unwrap();
+    }
+
+    #[test]
+    fn bounds_and_control_flow() {
+        use std::ops::{Bound, ControlFlow};
+
+        let json = Json::new();
+        let bounds: Vec<Bound<u8>> = json
+            .deserialize(r#"[{"Included": 1}, {"Excluded": 2}, "Unbounded"]"#)
+            .unwrap();
+        assert_eq!(
+            bounds,
+            [Bound::Included(1), Bound::Excluded(2), Bound::Unbounded]
+        );
+        let flows: Vec<ControlFlow<String, u8>> = json
+            .deserialize(r#"[{"Continue": 3}, {"Break": "x"}]"#)
+            .unwrap();
+        assert_eq!(
+            flows,
+            [ControlFlow::Continue(3), ControlFlow::Break("x".into())]
+        );
+        assert_eq!(
+            kind::<Bound<u8>>(r#"{"Unbounded": 1}"#),
+            ErrorKind::Syntax { expected: "string" }
+        );
+        assert_eq!(
+            kind::<ControlFlow<u8, u8>>(r#""Break""#),
+            ErrorKind::Syntax { expected: "object" }
+        );
+        assert_eq!(
+            kind::<Bound<u8>>(r#""Open""#),
+            ErrorKind::UnknownVariant {
+                variant: "Open".into()
+            }
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let json = Json::new();
+        let value: String = json.deserialize(r#""say \"hi\" \\o/""#).unwrap();
+        assert_eq!(value, r#"say "hi" \o/"#);
+        let value: String = json.deserialize(r#""a\nb\r\tc\b\f\/""#).unwrap();
+        assert_eq!(value, "a\nb\r\tc\u{8}\u{c}/");
+        let value: char = json.deserialize(r#""\t""#).unwrap();
+        assert_eq!(value, '\t');
+        let value: String = json
+            .deserialize(r#""\u0041\u00e9\u20AC\ud83d\ude00""#)
+            .unwrap();
+        assert_eq!(value, "Aé€😀");
+        let error = json.deserialize::<String>(r#""ab\ud83d x""#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            r"unpaired surrogate \uD83D at line 1, column 4"
+        );
+        assert_eq!(
+            kind::<String>(r#""\ud83dA""#),
+            ErrorKind::UnpairedSurrogate { unit: 0xd83d }
+        );
+        assert_eq!(
+            kind::<String>(r#""\ude00""#),
+            ErrorKind::UnpairedSurrogate { unit: 0xde00 }
+        );
+        assert_eq!(
+            kind::<String>(r#""\u12g4""#),
+            ErrorKind::Syntax {
+                expected: "4 hex digits"
+            }
+        );
+        assert_eq!(kind::<String>(r#""\u12"#), ErrorKind::Eof);
+        assert_eq!(
+            kind::<String>(r#""\q""#),
+            ErrorKind::Syntax {
+                expected: "escape sequence"
+            }
+        );
+        assert_eq!(kind::<String>("\"open"), ErrorKind::Eof);
+    }
+
+    #[test]
+    fn control_characters() {
+        let json = Json::new();
+        for byte in 1..0x20u8 {
+            let input = format!("[\"ab{}\"]", char::from(byte));
+            let error = json.deserialize::<Vec<String>>(&input).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::ControlCharacter, "{byte:#04x}");
+            assert_eq!(error.position().unwrap().offset, 4);
+            let error = json.deserialize::<Value>(&input).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::ControlCharacter);
+        }
+        let error = json.deserialize::<Pair>("{\"name\": \"a\", \"x\": \"\t\"}");
+        assert_eq!(error.unwrap_err().kind(), &ErrorKind::ControlCharacter);
+        let error = json.deserialize::<BTreeMap<String, u8>>("{\"a\rb\": 1}");
+        assert_eq!(
+            error.unwrap_err().to_string(),
+            "unescaped control character in string at line 1, column 4"
+        );
+        // Escaped, they are fine.
+        assert_eq!(
+            json.deserialize::<String>(r#""\t\u001f""#).unwrap(),
+            "\t\u{1f}"
+        );
+
+        let json = Json::new().control_characters(true);
+        let value: Vec<String> = json.deserialize("[\"a\tb\", \"c\nd\"]").unwrap();
+        assert_eq!(value, ["a\tb", "c\nd"]);
+        let json = Json::new().json5(true);
+        assert_eq!(json.deserialize::<String>("'a\tb'").unwrap(), "a\tb");
+        let error = json.deserialize::<String>("'a\nb'").unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ControlCharacter);
+    }
+
+    #[test]
+    fn nul_policies() {
+        let input = "[\"a\0\", \"\0\"]";
+        let json = Json::new();
+        let value: Vec<String> = json.deserialize(input).unwrap();
+        assert_eq!(value, ["a\0", "\0"]);
+        let json = Json::new().nul_policy(NulPolicy::Replace);
+        let value: (String, char) = json.deserialize(input).unwrap();
+        assert_eq!(value, (String::from("a\u{fffd}"), '\u{fffd}'));
+        let json = Json::new().nul_policy(NulPolicy::Reject);
+        assert_eq!(
+            json.deserialize::<String>(r#""\u0000""#)
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::Nul
+        );
+        let error = json.deserialize::<Vec<String>>(input).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "NUL character in string at line 1, column 4"
+        );
+    }
+
+    #[test]
+    fn trailing_policies() {
+        let cases = [
+            ("7", [true, true, true]),
+            ("7\n", [true, true, false]),
+            ("7\r\n", [true, true, false]),
+            ("7 \n", [true, false, false]),
+            ("7\n\n", [true, false, false]),
+            ("7\n,", [false, false, false]),
+        ];
+        let policies = [
+            TrailingPolicy::Whitespace,
+            TrailingPolicy::Newline,
+            TrailingPolicy::Exact,
+        ];
+        for (input, accepted) in cases {
+            for (policy, accepted) in policies.into_iter().zip(accepted) {
+                let json = Json::new().trailing_policy(policy);
+                for result in [
+                    json.deserialize::<u8>(input).map(drop),
+                    json.deserialize::<String>(&input.replace('7', "\"7\""))
+                        .map(drop),
+                    json.deserialize::<[u8; 1]>(&input.replace('7', "[7]"))
+                        .map(drop),
+                ] {
+                    assert_eq!(result.is_ok(), accepted, "{input:?} with {policy:?}");
+                }
+            }
+        }
+        let error = Json::new()
+            .trailing_policy(TrailingPolicy::Newline)
+            .deserialize::<bool>("true\n ")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "trailing characters at line 2, column 1");
+    }
+
+    #[test]
+    fn comments() {
+        let json = Json::new().comments(true);
+        let input = "/* list */ [1, // one\r\n 2 /* two */, /**/3//\n]// done";
+        assert_eq!(json.deserialize::<Vec<u8>>(input).unwrap(), [1, 2, 3]);
+        let pair: Pair = json
+            .deserialize("{\"name\" /* : */ : \"a\", // \"x\": 1\n\"score\": 2}")
+            .unwrap();
+        assert_eq!(pair.name, "a");
+        let strings: Vec<String> = json.deserialize(r#"["/* no */", "// no"]"#).unwrap();
+        assert_eq!(strings, ["/* no */", "// no"]);
+        let error = json
+            .deserialize::<Vec<u8>>("[1, /* a\n * b\n */ x]")
+            .unwrap_err();
+        let position = error.position().unwrap();
+        assert_eq!((position.row, position.col), (3, 5));
+        assert_eq!(
+            json.deserialize::<u8>("1 /* open").unwrap_err().kind(),
+            &ErrorKind::TrailingCharacters
+        );
+        let error = json.deserialize::<Vec<u8>>("[1 /* open").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Syntax {
+                expected: "`,` or `]`"
+            }
+        );
+        assert_eq!(error.position().unwrap().col, 4);
+        assert_eq!(kind::<u8>("1 // one"), ErrorKind::TrailingCharacters);
+        assert_eq!(kind::<u8>("/ 1"), ErrorKind::Syntax { expected: "u8" });
+    }
+
+    #[test]
+    fn single_quotes() {
+        let json = Json::new().single_quotes(true);
+        let map: BTreeMap<String, String> = json
+            .deserialize(r#"{'a': 'it\'s "x"', "b": 'don\u0027t', 'c': "'"}"#)
+            .unwrap();
+        assert_eq!(map["a"], r#"it's "x""#);
+        assert_eq!(map["b"], "don't");
+        assert_eq!(map["c"], "'");
+        let numbers: BTreeMap<u8, char> = json.deserialize(r#"{'1': 'x', "2": "y"}"#).unwrap();
+        assert_eq!(numbers, BTreeMap::from([(1, 'x'), (2, 'y')]));
+        let pair: Pair = json.deserialize("{'name': 'a', 'score': 1}").unwrap();
+        assert_eq!(pair.name, "a");
+        let value: Value = json.deserialize("['a', {'b': null}]").unwrap();
+        assert_eq!(value.pointer("/0").and_then(Value::as_str), Some("a"));
+        assert_eq!(
+            json.deserialize::<String>(r#""it\'s""#).unwrap_err().kind(),
+            &ErrorKind::Syntax {
+                expected: "escape sequence"
+            }
+        );
+        assert_eq!(
+            json.deserialize::<String>(r#"'a""#).unwrap_err().kind(),
+            &ErrorKind::Eof
+        );
+        assert_eq!(
+            kind::<String>("'a'"),
+            ErrorKind::Syntax { expected: "string" }
+        );
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let json = Json::new().trailing_commas(true);
+        assert_eq!(json.deserialize::<Vec<u8>>("[1, 2 , ]").unwrap(), [1, 2]);
+        assert_eq!(json.deserialize::<(u8, u8)>("[1, 2,]").unwrap(), (1, 2));
+        assert_eq!(json.deserialize::<Vec<u8>>("[]").unwrap(), [0u8; 0]);
+        let map: BTreeMap<String, u8> = json.deserialize(r#"{"a": 1,}"#).unwrap();
+        assert_eq!(map["a"], 1);
+        let pair: Pair = json.deserialize(r#"{"name": "a", "x": [1,],}"#).unwrap();
+        assert_eq!(pair.name, "a");
+        assert_eq!(
+            json.deserialize::<Vec<u8>>("[,]").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            json.deserialize::<(u8, u8)>("[1,]").unwrap_err().kind(),
+            &ErrorKind::Length {
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(
+            kind::<Vec<u8>>("[1,]"),
+            ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            kind::<(u8,)>("[1,]"),
+            ErrorKind::Syntax { expected: "value" }
+        );
+        assert_eq!(
+            kind::<BTreeMap<String, u8>>(r#"{"a": 1,}"#),
+            ErrorKind::Syntax { expected: "string" }
+        );
+    }
+
+    #[test]
+    fn radix_integers() {
+        let json = Json::new().radix_integers(true);
+        let values: (u8, i32, u64, i8) =
+            json.deserialize("[0b11111111, -0O17, 0XfF, 0b0]").unwrap();
+        assert_eq!(values, (255, -15, 255, 0));
+        let value: Value = json.deserialize("[0o777, -0b1, 0.5]").unwrap();
+        assert_eq!(value.pointer("/0").and_then(Value::as_u64), Some(511));
+        assert_eq!(value.pointer("/1").and_then(Value::as_i64), Some(-1));
+        assert_eq!(json.deserialize::<f32>("0x10").unwrap(), 16.0);
+        assert_eq!(
+            json.deserialize::<i8>("-0x81").unwrap_err().kind(),
+            &ErrorKind::Overflow { target: "i8" }
+        );
+        let clamp = json.clone().overflow_policy(OverflowPolicy::Clamp);
+        let (values, warnings) = clamp
+            .deserialize_with_warnings::<Vec<u8>>("[0x100, -0b1]")
+            .unwrap();
+        assert_eq!((values, warnings.len()), (vec![255, 0], 2));
+        assert_eq!(
+            json.deserialize::<u8>("0b2").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            json.deserialize::<u8>("+0b1").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            json.deserialize::<u8>("0o18").unwrap_err().kind(),
+            &ErrorKind::TrailingCharacters
+        );
+        assert_eq!(kind::<u8>("0b1"), ErrorKind::TrailingCharacters);
+        let json5 = Json::new().json5(true);
+        assert_eq!(json5.deserialize::<u8>("+0x1").unwrap(), 1);
+        assert_eq!(
+            json5.deserialize::<u8>("0b1").unwrap_err().kind(),
+            &ErrorKind::TrailingCharacters
+        );
+    }
+
+    #[test]
+    fn unquoted_keys() {
+        let json = Json::new().unquoted_keys(true);
+        let pair: Pair = json
+            .deserialize(r#"{name: "a", unknown_1: {$x: [1]}, inner: {"name": "b"}}"#)
+            .unwrap();
+        assert_eq!(pair.inner.unwrap().name, "b");
+        let map: BTreeMap<String, bool> = json.deserialize("{ \u{e9}t\u{e9}: true }").unwrap();
+        assert!(map["\u{e9}t\u{e9}"]);
+        let value: Value = json.deserialize("{a: {b: null}}").unwrap();
+        assert_eq!(value.pointer("/a/b"), Some(&Value::Null));
+        assert_eq!(
+            json.deserialize::<BTreeMap<u8, u8>>("{x: 1}")
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            json.deserialize::<BTreeMap<String, u8>>("{1a: 1}")
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::Syntax { expected: "string" }
+        );
+        assert_eq!(
+            json.deserialize::<String>("abc").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "string" }
+        );
+        assert_eq!(
+            json.deserialize::<Vec<u8>>("[1, 'a']").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "u8" }
+        );
+    }
+
+    #[test]
+    fn json5() {
+        let json = Json::new().json5(true);
+        let input = "\u{feff}{name: 'a', $x_1: {é: +.5e1,}, inner: {name: \"b\"},}\u{a0}";
+        let pair: Pair = json.deserialize(input).unwrap();
+        assert_eq!(pair.inner.unwrap().name, "b");
+        let map: BTreeMap<String, f64> = json
+            .deserialize("{a: 0x1F, b: -0Xff, c: 5., d: -.25, e: +Infinity, f: 1e2}")
+            .unwrap();
+        assert_eq!(
+            map.values().copied().take(4).collect::<Vec<_>>(),
+            [31.0, -255.0, 5.0, -0.25]
+        );
+        assert_eq!((map["e"], map["f"]), (f64::INFINITY, 100.0));
+        assert!(json.deserialize::<f64>("-NaN").unwrap().is_nan());
+        let keys: BTreeMap<bool, u8> = json.deserialize("{true: 1, 'false': 0}").unwrap();
+        assert_eq!(keys, BTreeMap::from([(false, 0), (true, 1)]));
+        let integers: Vec<i64> = json.deserialize("[0x7fffffffffffffff, -0x10, +3]").unwrap();
+        assert_eq!(integers, [i64::MAX, -16, 3]);
+        assert_eq!(
+            json.deserialize::<u8>("0x100").unwrap_err().kind(),
+            &ErrorKind::Overflow { target: "u8" }
+        );
+        assert_eq!(
+            json.deserialize::<u128>("0x1_0000_0000_0000_0000_0000_0000_0000_0000")
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::TrailingCharacters
+        );
+        assert_eq!(
+            json.deserialize::<u128>("0x100000000000000000000000000000000")
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::Overflow { target: "u128" }
+        );
+        assert_eq!(
+            json.deserialize::<u8>("0x").unwrap_err().kind(),
+            &ErrorKind::Eof
+        );
+        let value: Value = json
+            .deserialize("[0xff, -0x1, 0x10000000000000000]")
+            .unwrap();
+        assert_eq!(value.pointer("/0").and_then(Value::as_u64), Some(255));
+        assert_eq!(value.pointer("/1").and_then(Value::as_i64), Some(-1));
+        assert_eq!(
+            value.pointer("/2").and_then(Value::as_f64),
+            Some(18446744073709551616.0)
+        );
+        let strings: Vec<String> = json
+            .deserialize("['a\\\r\nb\\\nc', \"\\x41\\v\\0\\q\\'\", '\\u00e9']")
+            .unwrap();
+        assert_eq!(strings, ["abc", "A\u{b}\0q'", "é"]);
+        assert_eq!(
+            json.deserialize::<String>(r#""\1""#).unwrap_err().kind(),
+            &ErrorKind::Syntax {
+                expected: "escape sequence"
+            }
+        );
+        assert_eq!(
+            json.deserialize::<String>(r#""\x4g""#).unwrap_err().kind(),
+            &ErrorKind::Syntax {
+                expected: "2 hex digits"
+            }
+        );
+        assert_eq!(
+            json.deserialize::<Vec<u8>>("[name]").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            json.deserialize::<Vec<f64>>("[.]").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "f64" }
+        );
+        assert_eq!(kind::<f64>("+1"), ErrorKind::Syntax { expected: "f64" });
+        assert_eq!(kind::<u8>("0x1"), ErrorKind::TrailingCharacters);
+        assert_eq!(
+            kind::<BTreeMap<String, u8>>("{a: 1}"),
+            ErrorKind::Syntax { expected: "string" }
+        );
+    }
+
+    #[test]
+    fn depth_limit() {
+        let deep = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        let json = Json::new();
+        json.deserialize::<Value>(&deep(128)).unwrap();
+        let error = json.deserialize::<Value>(&deep(129)).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::DepthLimit { limit: 128 });
+        assert_eq!(error.position().unwrap().offset, 128);
+        assert_eq!(
+            error.to_string(),
+            "nesting deeper than the limit of 128 for Value at line 1, column 129"
+        );
+        let error = json.deserialize::<Value>(&"[".repeat(100_000)).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::DepthLimit { limit: 128 });
+
+        let json = Json::new().max_depth(2);
+        let pair: Pair = json.deserialize(r#"{"name": "a", "x": [1]}"#).unwrap();
+        assert_eq!(pair.name, "a");
+        assert_eq!(
+            json.deserialize::<Pair>(r#"{"name": "a", "x": [[1]]}"#)
+                .unwrap_err()
+                .kind(),
+            &ErrorKind::DepthLimit { limit: 2 }
+        );
+        let map: BTreeMap<String, Vec<u8>> = json.deserialize(r#"{"a": [1], "b": []}"#).unwrap();
+        assert_eq!(map.len(), 2);
+        assert_eq!(
+            kind::<(u8, u8)>("[1, 2, [3]]"),
+            ErrorKind::Length {
+                expected: 2,
+                found: 3
+            }
+        );
+        let flat = json.clone().max_depth(0);
+        assert_eq!(flat.deserialize::<u8>("7").unwrap(), 7);
+        assert_eq!(
+            flat.deserialize::<Vec<u8>>("[]").unwrap_err().kind(),
+            &ErrorKind::DepthLimit { limit: 0 }
+        );
+
+        // Elements that fail midway leave no depth behind.
+        let collect = json.collect_errors(true);
+        let input = format!("[{}]", [r#"{"name": 1}"#; 5].join(", "));
+        let error = collect.deserialize::<Vec<Pair>>(&input).unwrap_err();
+        let ErrorKind::Elements { errors } = error.kind() else {
+            panic!("{error}");
+        };
+        assert_eq!(errors.len(), 5);
+    }
+
+    #[test]
+    fn size_limits() {
+        let limit = |what, limit| ErrorKind::SizeLimit { what, limit };
+        let json = Json::new().max_input_len(4);
+        assert_eq!(json.deserialize::<u32>("1234").unwrap(), 1234);
+        let error = json.deserialize_with_warnings::<u32>("12345").unwrap_err();
+        assert_eq!(error.kind(), &limit("input", 4));
+        assert_eq!(error.position(), None);
+        assert_eq!(error.to_string(), "input larger than the limit of 4");
+
+        // Strings are measured once their escapes are resolved.
+        let json = Json::new().max_string_len(2);
+        assert_eq!(json.deserialize::<String>(r#""A\n""#).unwrap(), "A\n");
+        let error = json.deserialize::<String>(r#""éé""#).unwrap_err();
+        assert_eq!(error.kind(), &limit("string", 2));
+        assert_eq!(error.position().unwrap().offset, 0);
+        let nul = json.clone().nul_policy(NulPolicy::Replace);
+        assert!(nul.deserialize::<String>("\"\0\"").is_err());
+        let key = json.deserialize::<BTreeMap<String, u8>>(r#"{"abc": 1}"#);
+        assert_eq!(key.unwrap_err().kind(), &limit("string", 2));
+        let unquoted = json.clone().unquoted_keys(true);
+        let key = unquoted.deserialize::<BTreeMap<String, u8>>("{abc: 1}");
+        assert_eq!(key.unwrap_err().kind(), &limit("string", 2));
+        let skipped = json.deserialize::<Pair>(r#"{"name": "a", "x": "abc"}"#);
+        assert_eq!(skipped.unwrap_err().kind(), &limit("string", 2));
+        let error = json.deserialize::<Base64>(r#" "Zm9vYmFy""#).unwrap_err();
+        assert_eq!(error.kind(), &limit("string", 2));
+        assert_eq!(error.position().unwrap().offset, 1);
+        let error = json.deserialize::<Hex>(r#""00ff""#).unwrap_err();
+        assert_eq!(error.kind(), &limit("string", 2));
+        assert_eq!(error.position().unwrap().offset, 0);
+        assert_eq!(
+            json.deserialize::<Hex>(r#""0\u0030""#).unwrap(),
+            Hex(vec![0])
+        );
+
+        let json = Json::new().max_elements(2);
+        let map: BTreeMap<String, u8> = json.deserialize(r#"{"a": 1, "b": 2}"#).unwrap();
+        assert_eq!(map.len(), 2);
+        let error = json
+            .deserialize::<BTreeMap<String, u8>>(r#"{"a": 1, "b": 2, "c": 3}"#)
+            .unwrap_err();
+        assert_eq!(error.kind(), &limit("object", 2));
+        assert_eq!(error.position().unwrap().offset, 17);
+        let skipped = json.deserialize::<Pair>(r#"{"name": "a", "x": [1, 2, 3]}"#);
+        assert_eq!(skipped.unwrap_err().kind(), &limit("array", 2));
+        let trailing = json.clone().trailing_commas(true);
+        assert_eq!(trailing.deserialize::<Vec<u8>>("[1, 2,]").unwrap(), [1, 2]);
+        assert_eq!(
+            trailing
+                .deserialize::<Vec<u8>>("[1, 2, 3,]")
+                .unwrap_err()
+                .kind(),
+            &limit("array", 2)
+        );
+        assert_eq!(
+            kind::<(u8, u8)>("[1, 2, 3]"),
+            ErrorKind::Length {
+                expected: 2,
+                found: 3
+            }
+        );
+    }
+
+    #[test]
+    fn partial() {
+        let json = Json::new().trailing_policy(TrailingPolicy::Exact);
+        let mut rest = "1 2\n[3]{}";
+        let mut values = Vec::new();
+        while !rest.is_empty() {
+            let (value, tail) = json.deserialize_partial::<Value>(rest).unwrap();
+            values.push(value);
+            rest = tail;
+        }
+        assert_eq!(values.len(), 4);
+        assert_eq!(values[2].as_array().unwrap()[0].as_u64(), Some(3));
+
+        let (pair, rest) = json
+            .deserialize_partial::<Pair>(r#"{"name": "a"}, more"#)
+            .unwrap();
+        assert_eq!((pair.name.as_str(), rest), ("a", ", more"));
+        let error = json.deserialize_partial::<u8>("  x").unwrap_err();
+        assert_eq!(error.position().unwrap().offset, 2);
+        assert_eq!(
+            json.deserialize_partial::<u8>("  ").unwrap_err().kind(),
+            &ErrorKind::Eof
+        );
+    }
+
+    #[test]
+    fn stream() {
+        let json = Json::new();
+        assert!(json.stream::<Value>(" \n ").next().is_none());
+        let values: Vec<String> = json.stream(r#""a""b" "c""#).collect::<Result<_>>().unwrap();
+        assert_eq!(values, ["a", "b", "c"]);
+
+        let commented = Json::new().comments(true).max_depth(1);
+        let mut stream = commented.stream::<Vec<f32>>("[0.123456789] // first\n[2] /* second */");
+        assert_eq!(stream.next().unwrap().unwrap(), [0.12345679]);
+        assert_eq!(stream.take_warnings().len(), 1);
+        assert_eq!(stream.next().unwrap().unwrap(), [2.0]);
+        assert_eq!(stream.position().offset, 39);
+        assert!(stream.next().is_none());
+
+        let mut stream = json.stream::<u8>("1 300 3");
+        assert_eq!(stream.next().unwrap().unwrap(), 1);
+        let error = stream.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Overflow { target: "u8" });
+        assert!(stream.next().is_none());
+
+        let limited = Json::new().max_input_len(2);
+        let mut stream = limited.stream::<u8>("1 2");
+        let error = stream.next().unwrap().unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::SizeLimit {
+                what: "input",
+                limit: 2
+            }
+        );
+        assert!(stream.next().is_none());
+    }
+
+    #[test]
+    fn bytes() {
+        let json = Json::new();
+        let pair: Pair = json
+            .deserialize_bytes(b"{\"name\": \"\xe2\x9c\x93\"}")
+            .unwrap();
+        assert_eq!(pair.name, "\u{2713}");
+        let error = json
+            .deserialize_bytes::<Value>(b"[1,\n \"\xe2\x9c\"]")
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidUtf8);
+        assert_eq!(
+            error.position(),
+            Some(Position {
+                offset: 6,
+                row: 2,
+                col: 3
+            })
+        );
+        let limited = Json::new().max_input_len(1);
+        assert!(limited.deserialize_bytes::<u8>(b"10").is_err());
+    }
+
+    #[test]
+    fn non_finite_floats() {
+        let json = Json::new().non_finite(true);
+        let values: (f32, f64, f64) = json.deserialize("[Infinity, -Infinity, NaN]").unwrap();
+        assert_eq!((values.0, values.1), (f32::INFINITY, f64::NEG_INFINITY));
+        assert!(values.2.is_nan());
+        let map: BTreeMap<String, f64> = json.deserialize(r#"{"a": -Infinity}"#).unwrap();
+        assert_eq!(map["a"], f64::NEG_INFINITY);
+        let pair: Pair = json.deserialize(r#"{"score": NaN, "name": "a"}"#).unwrap();
+        assert_eq!(pair.name, "a");
+        assert_eq!(
+            kind::<f64>("Infinity"),
+            ErrorKind::Syntax { expected: "f64" }
+        );
+        assert_eq!(
+            json.deserialize::<i32>("Infinity").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "i32" }
+        );
+        assert_eq!(
+            json.deserialize::<f64>("NaNa").unwrap_err().kind(),
+            &ErrorKind::TrailingCharacters
+        );
+    }
+
+    #[test]
+    fn lossy_floats_warn() {
+        let json = Json::new();
+        let input = "[0.5, 1.50, 0.1, 100, 9007199254740993, 0.000]";
+        let (_, warnings) = json.deserialize_with_warnings::<Vec<f64>>(input).unwrap();
+        assert_eq!(
+            warnings,
+            [Warning {
+                kind: WarningKind::LossyFloat { target: "f64" },
+                position: Some(Position::locate(input, 22)),
+            }]
+        );
+        let (_, warnings) = json.deserialize_with_warnings::<f32>("16777217").unwrap();
+        assert_eq!(warnings.len(), 1);
+    }
+
+    #[test]
+    fn decimals_compare_by_value() {
+        let same = |a, b| Decimal::parse(a) == Decimal::parse(b);
+        assert!(same("1.50", "1.5"));
+        assert!(same("100", "1e2"));
+        assert!(same("0.000", "-0"));
+        assert!(same("0.05", "5e-2"));
+        assert!(!same("0.05", "0.5"));
+        assert!(!same("-1", "1"));
+        assert!(!same("12", "21"));
+    }
+
+    #[test]
+    fn scratch_stays_on_the_stack() {
+        struct Point(f64, f32);
+
+        impl Deserialize for Point {
+            fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+                let mut point = Point(0.0, 0.0);
+                let mut map = deserializer.begin_struct("Point")?;
+                while let Some(index) = deserializer.next_field(&mut map, &["x", "y"])? {
+                    match index {
+                        0 => point.0 = f64::accept(deserializer)?,
+                        _ => point.1 = f32::accept(deserializer)?,
+                    }
+                }
+                deserializer.end_struct(map)?;
+                Ok(point)
+            }
+        }
+
+        let json = Json::new();
+        let mut point = Point(0.0, 0.0);
+        let mut tuple = (false, [0u8; 2]);
+        let allocations = allocations::count(|| {
+            point = json
+                .deserialize(r#"{"y": 2.5, "x": 0.000000000000000000001}"#)
+                .unwrap();
+            tuple = json.deserialize("[true, [7, 8]]").unwrap();
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!((point.0, point.1), (1e-21, 2.5));
+        assert_eq!(tuple, (true, [7, 8]));
+    }
+
+    #[test]
+    fn integer_errors() {
+        assert_eq!(kind::<u8>("256"), ErrorKind::Overflow { target: "u8" });
+        assert_eq!(kind::<u8>("-1"), ErrorKind::Overflow { target: "u8" });
+        assert_eq!(kind::<u8>("1.5"), ErrorKind::Syntax { expected: "u8" });
+        assert_eq!(kind::<u64>("-1e2"), ErrorKind::Overflow { target: "u64" });
+        assert_eq!(
+            kind::<u16>("-99999999999999999999999"),
+            ErrorKind::Overflow { target: "u16" }
+        );
+        assert_eq!(kind::<u8>("01"), ErrorKind::Syntax { expected: "u8" });
+        assert_eq!(kind::<i8>("-00"), ErrorKind::Syntax { expected: "i8" });
+        assert_eq!(kind::<u8>(""), ErrorKind::Eof);
+        assert_eq!(kind::<u8>("1 2"), ErrorKind::TrailingCharacters);
+        assert_eq!(
+            kind::<[u8; 1]>("[1e]"),
+            ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(kind::<u8>("1e-1"), ErrorKind::Syntax { expected: "u8" });
+        assert_eq!(kind::<u8>("3e2"), ErrorKind::Overflow { target: "u8" });
+        assert_eq!(
+            kind::<i64>("-1e999999999999999999999"),
+            ErrorKind::Overflow { target: "i64" }
+        );
+    }
+
+    #[test]
+    fn quoted_wide_integers() {
+        let json = Json::new().quote_wide_integers(true);
+        let value: (i128, u128, u128) = json
+            .deserialize(r#"["-170141183460469231731687303715884105728", 5, "1e3"]"#)
+            .unwrap();
+        assert_eq!(value, (i128::MIN, 5, 1000));
+        let map: BTreeMap<u128, i128> = json.deserialize(r#"{"7": "8"}"#).unwrap();
+        assert_eq!(map, BTreeMap::from([(7, 8)]));
+        assert_eq!(
+            json.deserialize::<u128>(r#""-1""#).unwrap_err().kind(),
+            &ErrorKind::Overflow { target: "u128" }
+        );
+        assert_eq!(
+            json.deserialize::<u64>(r#""1""#).unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "u64" }
+        );
+        assert_eq!(
+            kind::<u128>(r#""1""#),
+            ErrorKind::Syntax { expected: "u128" }
+        );
+    }
+
+    #[test]
+    fn exponents() {
+        let json = Json::new();
+        assert_eq!(json.deserialize::<f64>("1.5e3").unwrap(), 1500.0);
+        assert_eq!(json.deserialize::<f64>("-2E-2").unwrap(), -0.02);
+        assert_eq!(json.deserialize::<f32>("1e+1").unwrap(), 10.0);
+        assert_eq!(json.deserialize::<u32>("1e3").unwrap(), 1000);
+        assert_eq!(json.deserialize::<i8>("-2.5E1").unwrap(), -25);
+        assert_eq!(json.deserialize::<u8>("0e99").unwrap(), 0);
+        assert_eq!(json.deserialize::<u16>("1200e-2").unwrap(), 12);
+        let (values, warnings) = json
+            .deserialize_with_warnings::<Vec<f64>>("[1e2, 25E-1]")
+            .unwrap();
+        assert_eq!((values, warnings), (vec![100.0, 2.5], vec![]));
+        assert_eq!(
+            json.deserialize::<Vec<bool>>("[1e5]").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "bool" }
+        );
+        let clamp = Json::new().overflow_policy(OverflowPolicy::Clamp);
+        assert_eq!(clamp.deserialize::<i8>("-1e40").unwrap(), -128);
+        assert_eq!(clamp.deserialize::<u8>("-1").unwrap(), 0);
+    }
+
+    #[test]
+    fn whole_numbers_in_any_notation() {
+        let json = Json::new();
+        assert_eq!(json.deserialize::<u8>("1.0").unwrap(), 1);
+        assert_eq!(json.deserialize::<i32>("-12.500e1").unwrap(), -125);
+        assert_eq!(json.deserialize::<u8>("1.5e1").unwrap(), 15);
+        assert_eq!(kind::<u8>("1.5"), ErrorKind::Syntax { expected: "u8" });
+        assert_eq!(kind::<u8>("15e-1"), ErrorKind::Syntax { expected: "u8" });
+        // Negative zero is zero, whatever the type.
+        let zeros: (u8, u16, u64, i8) = json.deserialize("[-0, -0.0, -0e5, -0]").unwrap();
+        assert_eq!(zeros, (0, 0, 0, 0));
+        assert_eq!(kind::<u16>("-1"), ErrorKind::Overflow { target: "u16" });
+    }
+
+    #[test]
+    fn numbers_are_strict() {
+        let json = Json::new();
+        for input in ["00", "-01", "012.5", "00e1"] {
+            assert!(json.deserialize::<f64>(input).is_err(), "{input}");
+            assert!(json.deserialize::<Value>(input).is_err(), "{input}");
+        }
+        assert!(Json::new().json5(true).deserialize::<f64>("00.5").is_err());
+        assert_eq!(json.deserialize::<f64>("0.5").unwrap(), 0.5);
+        assert_eq!(json.deserialize::<f64>("-0").unwrap(), 0.0);
+
+        // Too large for the type is out of range, not infinite.
+        for input in ["1e400", "-1e400", "1e309"] {
+            let error = json.deserialize::<f64>(input).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::Overflow { target: "f64" });
+        }
+        assert_eq!(kind::<f32>("3.5e38"), ErrorKind::Overflow { target: "f32" });
+        assert_eq!(json.deserialize::<f32>("3.4e38").unwrap(), 3.4e38);
+        let radix = Json::new().radix_integers(true);
+        let huge = format!("0x{}", "F".repeat(300));
+        assert_eq!(
+            radix.deserialize::<f64>(&huge).unwrap_err().kind(),
+            &ErrorKind::Overflow { target: "f64" }
+        );
+        // Too small rounds to zero, with a warning.
+        let (value, warnings) = json.deserialize_with_warnings::<f64>("1e-400").unwrap();
+        assert_eq!((value, warnings.len()), (0.0, 1));
+    }
+
+    #[cfg(feature = "charset")]
+    #[test]
+    fn charset_positions_are_byte_offsets() {
+        let input = b"[\"\xe9\xe9\", 0.123456789]";
+        let (_, warnings) = Json::new()
+            .deserialize_charset::<(String, f32)>(input, Charset::Latin1)
+            .unwrap();
+        assert_eq!(warnings[0].position.unwrap().offset, 7);
+        assert_eq!(warnings[0].position.unwrap().col, 8);
+    }
+
+    #[test]
+    fn clamped_integers_warn() {
+        let json = Json::new().overflow_policy(OverflowPolicy::Clamp);
+        let input = "[-129, 127, 128, -99999999999999999999999999999999999999999]";
+        let (value, warnings) = json.deserialize_with_warnings::<Vec<i8>>(input).unwrap();
+        assert_eq!(value, [-128, 127, 127, -128]);
+        let columns: Vec<usize> = warnings
+            .iter()
+            .map(|warning| warning.position.unwrap().col)
+            .collect();
+        assert_eq!(columns, [2, 13, 18]);
+        assert_eq!(
+            warnings[0].to_string(),
+            "number clamped to the range of i8 at line 1, column 2"
+        );
+        assert_eq!(
+            json.deserialize::<u8>("1.5").unwrap_err().kind(),
+            &ErrorKind::Syntax { expected: "u8" }
+        );
+    }
+
+    #[test]
+    fn sequences() {
+        let json = Json::new();
+        let value: Vec<Vec<u8>> = json.deserialize("[ [1, 2] , [], [3]]").unwrap();
+        assert_eq!(value, vec![vec![1, 2], vec![], vec![3]]);
+        let value: (u8, (bool, String)) = json.deserialize(r#"[1, [false, "x"]]"#).unwrap();
+        assert_eq!(value, (1, (false, String::from("x"))));
+        type Sixteen = (
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            u8,
+            char,
+        );
+        let value: Sixteen = json
+            .deserialize(r#"[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, "p"]"#)
+            .unwrap();
+        assert_eq!((value.0, value.14, value.15), (1, 15, 'p'));
+    }
+
+    #[test]
+    fn nested_containers() {
+        let json = Json::new();
+        let value: ((u8, u8), u8) = json.deserialize("[[1, 2], 3]").unwrap();
+        assert_eq!(value, ((1, 2), 3));
+        let value: (Vec<(String, [u8; 1])>, Vec<u8>) = json
+            .deserialize(r#"[[["],", [1]], ["[", [2]]], [3]]"#)
+            .unwrap();
+        assert_eq!(value.0[0], (String::from("],"), [1]));
+        assert_eq!(value.0[1], (String::from("["), [2]));
+        assert_eq!(value.1, [3]);
+        let value: (BTreeMap<String, Vec<(u8, bool)>>, u8) = json
+            .deserialize(r#"[{"a,]": [[1, true], [2, false]]}, 4]"#)
+            .unwrap();
+        assert_eq!(value.0["a,]"], [(1, true), (2, false)]);
+        assert_eq!(value.1, 4);
+        let value: Vec<Vec<Vec<Vec<u8>>>> = json.deserialize("[[[[1], []]], [[[2, 3]]]]").unwrap();
+        assert_eq!(value, [vec![vec![vec![1], vec![]]], vec![vec![vec![2, 3]]]]);
+    }
+
+    #[test]
+    fn os_strings() {
+        let json = Json::new();
+        let value: OsString = json.deserialize(r#""dir/é""#).unwrap();
+        assert_eq!(value, "dir/é");
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let value: OsString = json.deserialize("[97, 255]").unwrap();
+            assert_eq!(value.as_bytes(), b"a\xff");
+        }
+    }
+
+    #[test]
+    fn smart_pointers() {
+        let json = Json::new();
+        assert_eq!(*json.deserialize::<Box<u8>>("1").unwrap(), 1);
+        let value: Rc<String> = json.deserialize(r#""rc""#).unwrap();
+        assert_eq!(*value, "rc");
+        let value: Arc<Vec<Box<bool>>> = json.deserialize("[true]").unwrap();
+        assert_eq!(*value, [Box::new(true)]);
+    }
+
+    #[test]
+    fn shared_values() {
+        use crate::serialize::{self, SharedMode};
+
+        let json = Json::new().references(true);
+        let leaf = Arc::new(String::from("leaf"));
+        let tree = vec![(leaf.clone(), Rc::new(1u8)), (leaf, Rc::new(2))];
+        let written = serialize::Json::new()
+            .shared_mode(SharedMode::Reference)
+            .serialize(&tree)
+            .unwrap();
+        let read: Vec<(Arc<String>, Rc<u8>)> = json.deserialize(&written).unwrap();
+        assert_eq!(read, tree);
+        assert!(Arc::ptr_eq(&read[0].0, &read[1].0));
+
+        let input = r#"[ { "$id" : 7 , "$value" : {"$idx": 1} }, {"$ref": 7}, {}]"#;
+        let read: Vec<Rc<BTreeMap<String, u8>>> = json.deserialize(input).unwrap();
+        assert!(Rc::ptr_eq(&read[0], &read[1]));
+        assert_eq!(read[0]["$idx"], 1);
+        assert!(read[2].is_empty());
+        let read: Vec<BTreeMap<String, u8>> = json.deserialize(r#"[{"$id": 1}]"#).unwrap();
+        assert_eq!(read[0]["$id"], 1);
+        let read: Vec<Rc<BTreeMap<String, u8>>> = Json::new()
+            .deserialize(r#"[{"$id": 1}, {"$ref": 1}]"#)
+            .unwrap();
+        assert!(!Rc::ptr_eq(&read[0], &read[1]));
+
+        let error = |input| {
+            json.deserialize::<Vec<Rc<Vec<u8>>>>(input)
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error(r#"[{"$ref": 1}]"#),
+            "no shared value of this type with id 1 was read before at line 1, column 11"
+        );
+        assert_eq!(
+            error(r#"[{"$id": 1, "$value": []}, {"$id": 1, "$value": []}]"#),
+            "the shared value id 1 is used twice at line 1, column 36"
+        );
+        assert_eq!(
+            error(r#"[{"$id": 1, "value": []}]"#),
+            "expected `\"$value\"` at line 1, column 13"
+        );
+        assert_eq!(
+            error(r#"[{"$id": 1}]"#),
+            "expected `\"$value\"` at line 1, column 11"
+        );
+        assert_eq!(
+            error(r#"[{"$id": -1, "$value": []}]"#),
+            "number out of range for u64 at line 1, column 10"
+        );
+        let error = json
+            .deserialize::<(Rc<u8>, Rc<i8>)>(r#"[{"$id": 1, "$value": 1}, {"$ref": 1}]"#)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "no shared value of this type with id 1 was read before for (Rc<u8>, Rc<i8>) at line \
+             1, column 36"
+        );
+    }
+
+    #[test]
+    fn interior_mutability() {
+        let json = Json::new();
+        let value: (Cell<u8>, RefCell<String>) = json.deserialize(r#"[1, "a"]"#).unwrap();
+        assert_eq!((value.0.get(), value.1.borrow().as_str()), (1, "a"));
+        let value: Mutex<RwLock<bool>> = json.deserialize("true").unwrap();
+        assert!(*value.lock().unwrap().read().unwrap());
+    }
+
+    #[test]
+    fn infallible() {
+        let json = Json::new();
+        let error = json.deserialize::<Vec<Infallible>>("[1]").unwrap_err();
+        assert_eq!(error.to_string(), "Infallible has no values to deserialize");
+        assert_eq!(json.deserialize::<Vec<Infallible>>("[]").unwrap(), []);
+    }
+
+    #[test]
+    fn collections() {
+        let json = Json::new();
+        let deque: VecDeque<u8> = json.deserialize("[1, 2]").unwrap();
+        assert_eq!(deque, [1, 2]);
+        let list: LinkedList<String> = json.deserialize(r#"["a"]"#).unwrap();
+        assert_eq!(list.front().map(String::as_str), Some("a"));
+        let heap: BinaryHeap<u8> = json.deserialize("[2, 9, 4]").unwrap();
+        assert_eq!(heap.into_sorted_vec(), [2, 4, 9]);
+    }
+
+    #[test]
+    fn maps() {
+        let json = Json::new();
+        let map: HashMap<String, Vec<u8>> = json
+            .deserialize(" {\n  \"a\\\"b\" : [1],\"c\":[] } ")
+            .unwrap();
+        assert_eq!(map["a\"b"], [1]);
+        assert_eq!(map["c"], [0u8; 0]);
+        let nested: BTreeMap<u8, BTreeMap<bool, f32>> = json
+            .deserialize(r#"{"2": {"false": 0.5}, "1": {}}"#)
+            .unwrap();
+        assert_eq!(nested[&2][&false], 0.5);
+        assert!(nested[&1].is_empty());
+        let last: BTreeMap<char, u8> = json.deserialize(r#"{"x": 1, "x": 2}"#).unwrap();
+        assert_eq!(last[&'x'], 2);
+        assert_eq!(
+            kind::<[u8; 0]>(r#"[{"a": [1, {}]}]"#),
+            ErrorKind::Length {
+                expected: 0,
+                found: 1
+            }
+        );
+    }
+
+    #[test]
+    fn map_api() {
+        let json = Json::new();
+        let mut parser = json.parser(r#" { "a" : [1] , "b": [] } "#);
+        let mut map = parser.begin_map().unwrap();
+        let mut entries = Vec::new();
+        while let Some(key) = parser.next_key::<char>(&mut map).unwrap() {
+            let value: Vec<u8> = parser.next_value(&mut map).unwrap();
+            entries.push((key, value));
+        }
+        parser.end_map(map).unwrap();
+        parser.finish().unwrap();
+        assert_eq!(entries, [('a', vec![1]), ('b', vec![])]);
+        let mut parser = json.parser(r#"{"a": 1}"#);
+        let mut map = parser.begin_map().unwrap();
+        let key: Option<String> = parser.next_key(&mut map).unwrap();
+        assert_eq!(key.as_deref(), Some("a"));
+        assert_eq!(
+            parser.next_value::<bool>(&mut map).unwrap_err().to_string(),
+            "expected bool at line 1, column 7"
+        );
+    }
+
+    #[test]
+    fn seeds() {
+        use std::collections::HashSet;
+
+        /// Shares one allocation between equal strings.
+        #[derive(Default)]
+        struct Interner(HashSet<Rc<str>>);
+
+        impl DeserializeSeed for &mut Interner {
+            type Value = Rc<str>;
+
+            fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<Rc<str>> {
+                let string = deserializer.visit_string()?;
+                if let Some(interned) = self.0.get(string.as_str()) {
+                    return Ok(interned.clone());
+                }
+                let interned: Rc<str> = string.into();
+                self.0.insert(interned.clone());
+                Ok(interned)
+            }
+        }
+
+        struct Names<'a>(&'a mut Interner);
+
+        impl DeserializeSeed for Names<'_> {
+            type Value = BTreeMap<u8, Vec<Rc<str>>>;
+
+            fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<Self::Value> {
+                let mut groups = BTreeMap::new();
+                let mut map = deserializer.begin_map()?;
+                while let Some(key) = deserializer.next_key(&mut map)? {
+                    let mut names = Vec::new();
+                    let mut seq = deserializer.begin_seq(None)?;
+                    while deserializer.next_element(&mut seq)? {
+                        names.extend(deserializer.element_seed(&mut seq, &mut *self.0)?);
+                    }
+                    deserializer.end_seq(seq)?;
+                    groups.insert(key, names);
+                }
+                deserializer.end_map(map)?;
+                Ok(groups)
+            }
+        }
+
+        let mut interner = Interner::default();
+        let json = Json::new();
+        let groups = json
+            .deserialize_seed(r#"{"1": ["ada", "bo"], "2": ["bo"]}"#, Names(&mut interner))
+            .unwrap();
+        assert_eq!(interner.0.len(), 2);
+        assert!(Rc::ptr_eq(&groups[&1][1], &groups[&2][0]));
+        let value = json.deserialize_seed(r#""ada""#, &mut interner).unwrap();
+        assert!(Rc::ptr_eq(&value, &groups[&1][0]));
+        let json = Json::new().collect_errors(true);
+        let error = json
+            .deserialize_seed(r#"{"1": ["ada", 2, 3]}"#, Names(&mut interner))
+            .unwrap_err();
+        let ErrorKind::Elements { errors } = error.kind() else {
+            panic!("{error}");
+        };
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn in_place() {
+        let json = Json::new().collect_errors(true);
+        let mut values: Vec<String> = vec!["a".into(), "b".into(), "c".into()];
+        json.deserialize_in_place(r#"["x", "y"]"#, &mut values)
+            .unwrap();
+        assert_eq!(values, ["x", "y"]);
+        json.deserialize_in_place(r#"["x", "y", "z", "w"]"#, &mut values)
+            .unwrap();
+        assert_eq!(values, ["x", "y", "z", "w"]);
+        let error = json
+            .deserialize_in_place(r#"["p", 1, "q", 2]"#, &mut values)
+            .unwrap_err();
+        let ErrorKind::Elements { errors } = error.kind() else {
+            panic!("{error}");
+        };
+        assert_eq!(
+            errors.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+            [1, 3]
+        );
+        assert_eq!(values, ["p", "q"]);
+
+        let mut text = String::with_capacity(16);
+        for (input, expected) in [(r#""plain""#, "plain"), (r#""tab\t\u00e9""#, "tab\té")] {
+            json.deserialize_in_place(input, &mut text).unwrap();
+            assert_eq!(text, expected);
+            assert_eq!(text.capacity(), 16);
+        }
+    }
+
+    #[test]
+    fn map_errors() {
+        let error = Json::new()
+            .deserialize::<HashMap<u8, u8>>(r#"{"1": 2, 3: 4}"#)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "expected u8 at line 1, column 10");
+        assert_eq!(
+            kind::<HashMap<String, u8>>(r#"{"a" 1}"#),
+            ErrorKind::Syntax { expected: "`:`" }
+        );
+        assert_eq!(
+            kind::<HashMap<String, u8>>(r#"{"a": 1,}"#),
+            ErrorKind::Syntax { expected: "string" }
+        );
+        assert_eq!(
+            kind::<HashMap<String, u8>>(r#"{"a": 1 "b": 2}"#),
+            ErrorKind::Syntax {
+                expected: "`,` or `}`"
+            }
+        );
+        assert_eq!(
+            kind::<HashMap<u8, u8>>(r#"{"1x": 2}"#),
+            ErrorKind::Syntax { expected: "`\"`" }
+        );
+        assert_eq!(kind::<HashMap<String, u8>>(r#"{"a": 1"#), ErrorKind::Eof);
+        assert_eq!(
+            kind::<HashMap<String, u8>>("[]"),
+            ErrorKind::Syntax { expected: "object" }
+        );
+    }
+
+    #[test]
+    fn fixed_arrays() {
+        let json = Json::new();
+        let value: [u8; 4] = json.deserialize("[1,2,3,4]").unwrap();
+        assert_eq!(value, [1, 2, 3, 4]);
+        let value: [[bool; 1]; 0] = json.deserialize("[]").unwrap();
+        assert!(value.is_empty());
+        let error = json.deserialize::<[u8; 3]>("[1, 2]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected 3 elements, found 2 at line 1, column 6"
+        );
+        let error = json.deserialize::<[u8; 1]>("[1,\n 2, 3]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected 1 elements, found 3 at line 1, column 3"
+        );
+    }
+
+    #[test]
+    fn sequence_api() {
+        let json = Json::new();
+        let mut parser = json.parser(" [ 1 , 2 ] ");
+        let mut seq = parser.begin_seq(None).unwrap();
+        let mut elements = Vec::new();
+        while parser.next_element(&mut seq).unwrap() {
+            elements.extend(parser.element::<u8>(&mut seq).unwrap());
+        }
+        parser.end_seq(seq).unwrap();
+        assert_eq!(elements, [1, 2]);
+        parser.finish().unwrap();
+        assert_eq!(json.deserialize::<Vec<u8>>("[ ]").unwrap(), [0u8; 0]);
+        assert_eq!(
+            kind::<Vec<u8>>("[1,]"),
+            ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            kind::<Vec<u8>>("[1 2]"),
+            ErrorKind::Syntax {
+                expected: "`,` or `]`"
+            }
+        );
+        assert_eq!(kind::<(u8, u8)>("[1, 2"), ErrorKind::Eof);
+        assert_eq!(kind::<Vec<u8>>("[1, 2"), ErrorKind::Eof);
+    }
+
+    #[test]
+    fn collected_element_errors() {
+        let json = Json::new().collect_errors(true);
+        let value: Vec<u8> = json.deserialize("[1, 2]").unwrap();
+        assert_eq!(value, [1, 2]);
+        let error = json.deserialize::<Vec<u8>>("[1, -2, [3], 4]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "2 elements failed: element 1: number out of range for u8 at line 1, column 5; \
+             element 2: expected u8 at line 1, column 9 at line 1, column 1"
+        );
+        let error = json
+            .deserialize::<(u8, String, bool)>(r#"[-1, "a", 2]"#)
+            .unwrap_err();
+        let ErrorKind::Elements { errors } = error.kind() else {
+            panic!("{error}");
+        };
+        let indices: Vec<usize> = errors.iter().map(|(index, _)| *index).collect();
+        assert_eq!(indices, [0, 2]);
+        let error = json.deserialize::<[u8; 3]>("[1, true, 3]").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Elements { errors } if errors.len() == 1));
+        // A short array is reported along with the elements before it.
+        let error = json.deserialize::<[u8; 3]>("[1, true]").unwrap_err();
+        let ErrorKind::Elements { errors } = error.kind() else {
+            panic!("{error}");
+        };
+        assert_eq!(errors[1].0, 2);
+        assert_eq!(
+            errors[1].1.kind(),
+            &ErrorKind::Length {
+                expected: 3,
+                found: 2
+            }
+        );
+        // Malformed input cannot be skipped, so it ends the sequence.
+        let error = json.deserialize::<Vec<u8>>(r#"[-1, "open]"#).unwrap_err();
+        let ErrorKind::Elements { errors } = error.kind() else {
+            panic!("{error}");
+        };
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[1].1.kind(), &ErrorKind::Eof);
+        // Without the option, the first error is reported on its own.
+        assert_eq!(
+            kind::<Vec<u8>>("[1, -2, -3]").to_string(),
+            "number out of range for u8"
+        );
+    }
+
+    #[test]
+    fn tuple_length_is_checked() {
+        let error = Json::new().deserialize::<(u8, u8)>("[1]").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Length {
+                expected: 2,
+                found: 1
+            }
+        );
+        assert_eq!(error.position().unwrap().col, 3);
+        let error = Json::new()
+            .deserialize::<(u8,)>("[1, [2], \"3\"]")
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Length {
+                expected: 1,
+                found: 3
+            }
+        );
+        assert_eq!(error.position().unwrap().col, 3);
+    }
+
+    #[test]
+    fn bytes_decode_in_place() {
+        let json = Json::new();
+        let Base64(bytes) = json.deserialize(r#" "aGk=" "#).unwrap();
+        assert_eq!(bytes, b"hi");
+        let Base64(bytes) = json.deserialize(r#""\/+8=""#).unwrap();
+        assert_eq!(bytes, [255, 239]);
+        let value: Vec<Hex> = json.deserialize(r#"["00ff", ""]"#).unwrap();
+        assert_eq!(value, [Hex(vec![0, 255]), Hex(vec![])]);
+        let Hex(bytes) = json.deserialize(r#""\u0030\u00301\u0046""#).unwrap();
+        assert_eq!(bytes, [0, 31]);
+        let Base64(bytes) = Json::new().json5(true).deserialize(r"'aG\x6b='").unwrap();
+        assert_eq!(bytes, b"hi");
+        let error = json.deserialize::<Hex>(r#""00\u00e9""#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected hex for Hex at line 1, column 4"
+        );
+        let error = json.deserialize::<Hex>(r#""00\n""#).unwrap_err();
+        assert_eq!(error.position().unwrap().col, 4);
+        let error = json.deserialize::<Hex>(r#""0\u00""#).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Syntax {
+                expected: "4 hex digits"
+            }
+        );
+        let error = json.deserialize::<Base64>(r#""aG*k""#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected base64 for Base64 at line 1, column 4"
+        );
+        let error = json.deserialize::<Hex>(r#""abc""#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected hex for Hex at line 1, column 5"
+        );
+    }
+
+    #[test]
+    fn errors_name_user_types() {
+        #[derive(Debug)]
+        struct Port(#[allow(dead_code)] u16);
+
+        impl Deserialize for Port {
+            fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+                deserializer.visit_u16().map(Port)
+            }
+        }
+
+        #[derive(Debug)]
+        struct Config {
+            _port: Port,
+        }
+
+        impl Deserialize for Config {
+            fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+                let (port,) = <(Port,)>::accept(deserializer)
+                    .map_err(|error| error.in_field("Config", Some("port")))?;
+                Ok(Config { _port: port })
+            }
+        }
+
+        let json = Json::new();
+        let error = json.deserialize::<Vec<Port>>("[1, -2]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "number out of range for u16 for Port at line 1, column 5"
+        );
+        let error = json.deserialize::<Config>("[true]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected u16 for Port at line 1, column 2"
+        );
+        let error = json.deserialize::<Option<Config>>("[]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected 1 elements, found 0 for field `port` of Config at line 1, column 2"
+        );
+    }
+
+    #[derive(Debug, Default, PartialEq)]
+    struct Pair {
+        name: String,
+        inner: Option<Box<Pair>>,
+    }
+
+    impl Deserialize for Pair {
+        fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+            let mut pair = Pair::default();
+            let mut name = None;
+            let mut map = deserializer.begin_struct("Pair")?;
+            while let Some(index) = deserializer.next_field(&mut map, &["name", "inner"])? {
+                match index {
+                    0 => name = Some(String::accept(deserializer)?),
+                    _ => pair.inner = Option::accept(deserializer)?,
+                }
+            }
+            deserializer.end_struct(map)?;
+            pair.name = name.ok_or(Error::new(ErrorKind::MissingField { field: "name" }))?;
+            Ok(pair)
+        }
+    }
+
+    #[test]
+    fn structs() {
+        let json = Json::new();
+        let input = r#"{"inner": {"name": "b", "inner": null}, "name": "a"}"#;
+        let pair: Pair = json.deserialize(input).unwrap();
+        assert_eq!(pair.name, "a");
+        assert_eq!(pair.inner.unwrap().name, "b");
+        let nested: Vec<Pair> = json
+            .deserialize(r#"[{"inner": {"name": "b", "inner": {"name": "c"}}, "name": "a"}]"#)
+            .unwrap();
+        assert_eq!(
+            nested[0]
+                .inner
+                .as_ref()
+                .unwrap()
+                .inner
+                .as_ref()
+                .unwrap()
+                .name,
+            "c"
+        );
+    }
+
+    #[test]
+    fn warnings_are_located_in_linear_time() {
+        let json = Json::new();
+        let n = 2_000;
+        let pairs = vec![r#"{"name": "a", "x": 1, "inner": null}"#; n].join(",\n");
+        let floats = vec!["16777217"; n].join(", ");
+        let input = format!("[[{pairs}], [{floats}]]");
+        let mut warnings = Vec::new();
+        let scanned = crate::scanned::count(|| {
+            (_, warnings) = json
+                .deserialize_with_warnings::<(Vec<Pair>, Vec<f32>)>(&input)
+                .unwrap();
+        });
+        let last = input.rfind("16777217").unwrap();
+        assert_eq!(warnings.len(), 2 * n);
+        assert_eq!(warnings[n - 1].position.unwrap().row, n);
+        assert_eq!(
+            warnings[2 * n - 1].position,
+            Some(Position::locate(&input, last))
+        );
+        // Locating each warning from the start of the input would look at
+        // about `n` times the input.
+        assert!(scanned <= input.len(), "{scanned} of {}", input.len());
+    }
+
+    #[test]
+    fn struct_fields_are_checked() {
+        let json = Json::new();
+        let (pair, warnings) = json
+            .deserialize_with_warnings::<Pair>(
+                r#"{"name": "a", "extra": [{"x": 1}], "inner": null}"#,
+            )
+            .unwrap();
+        assert_eq!(pair.name, "a");
+        assert_eq!(
+            warnings[0].to_string(),
+            "unknown field `extra` ignored at line 1, column 15"
+        );
+        let error = json
+            .deserialize::<Pair>(r#"{"inner": {}, "name": "a"}"#)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "missing field `name` for Pair");
+        assert_eq!(
+            kind::<Pair>(r#"{"name": "a" "inner": null}"#),
+            ErrorKind::Syntax {
+                expected: "`,` or `}`"
+            }
+        );
+        assert_eq!(
+            kind::<Pair>(r#"["a"]"#),
+            ErrorKind::Syntax { expected: "Pair" }
+        );
+    }
+
+    #[test]
+    fn raw_values() {
+        #[derive(Debug)]
+        struct Celsius(f64);
+
+        impl Deserialize for Celsius {
+            fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+                deserializer.visit_raw(|text| {
+                    let degrees = text
+                        .strip_suffix("C\"")
+                        .and_then(|text| text.strip_prefix('"'));
+                    match degrees.and_then(|degrees| degrees.parse().ok()) {
+                        Some(degrees) => Ok(Celsius(degrees)),
+                        None => Err(Error::custom("expected a temperature")),
+                    }
+                })
+            }
+        }
+
+        let json = Json::new();
+        let values: Vec<Celsius> = json.deserialize(r#"["21.5C", "-3C"]"#).unwrap();
+        assert_eq!(values[1].0, -3.0);
+        let error = json
+            .deserialize::<Vec<Celsius>>(r#"["1C",  "hot"]"#)
+            .unwrap_err();
+        assert_eq!(error.position().unwrap().col, 9);
+        assert_eq!(kind::<Vec<Celsius>>(r#"["1C", "#), ErrorKind::Eof);
+    }
+
+    #[test]
+    fn errors_are_positioned() {
+        let error = Json::new()
+            .deserialize::<Vec<u8>>("[1,\n 2,\n x]")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "expected u8 at line 3, column 2");
+    }
+}
@@ -0,0 +1,584 @@
+//! Splitting JSON text into tokens.
+//!
+//! [`Json::lexer`] returns a [`Lexer`] yielding the tokens of its input
+//! with their text and position, for tools such as formatters, linters and
+//! syntax highlighters that work on the text rather than the values it
+//! holds. The lexer scans strings, numbers and comments with the same code
+//! as the parser and accepts the same lenient syntax, but does not check
+//! that the tokens form a valid document.
+//!
+//! ```
+//! use shallot::deserialize::lexer::TokenKind;
+//! use shallot::deserialize::Json;
+//!
+//! let json = Json::new();
+//! let kinds: Vec<TokenKind> = json
+//!     .lexer(r#"{"a": [1, null]}"#)
+//!     .map(|token| token.map(|token| token.kind))
+//!     .collect::<shallot::Result<_>>()?;
+//! assert_eq!(
+//!     kinds,
+//!     [
+//!         TokenKind::BeginObject,
+//!         TokenKind::String,
+//!         TokenKind::Colon,
+//!         TokenKind::BeginArray,
+//!         TokenKind::Number,
+//!         TokenKind::Comma,
+//!         TokenKind::Null,
+//!         TokenKind::EndArray,
+//!         TokenKind::EndObject,
+//!     ]
+//! );
+//! # Ok::<(), shallot::Error>(())
+//! ```
+
+use std::str::CharIndices;
+
+use super::{scan, Json};
+use crate::error::{Error, ErrorKind, Position, Result};
+
+/// What a [`Token`] is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenKind {
+    /// `[`.
+    BeginArray,
+    /// `]`.
+    EndArray,
+    /// `{`.
+    BeginObject,
+    /// `}`.
+    EndObject,
+    /// `:`.
+    Colon,
+    /// `,`.
+    Comma,
+    /// A quoted string, whose text includes its quotes and escapes.
+    String,
+    /// A number, or `NaN` or `Infinity` if they are accepted.
+    Number,
+    /// `true` or `false`.
+    Bool,
+    /// `null`.
+    Null,
+    /// A bare word, which only makes sense as an object key if unquoted
+    /// keys are accepted.
+    Identifier,
+    /// A `//` or `/* */` comment, if they are accepted.
+    Comment,
+}
+
+/// A token of JSON text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    /// The token as it appears in the input.
+    pub text: &'a str,
+    /// Where the token starts.
+    pub position: Position,
+}
+
+/// An iterator over the tokens of JSON text, created by [`Json::lexer`].
+///
+/// Whitespace between tokens is skipped. Input that starts no token is an
+/// error, after which the iterator ends.
+#[derive(Debug, Clone)]
+pub struct Lexer<'a> {
+    json: &'a Json,
+    input: &'a str,
+    position: Position,
+    failed: bool,
+}
+
+impl Json {
+    /// Split `input` into tokens, as accepted by this configuration.
+    pub fn lexer<'a>(&'a self, input: &'a str) -> Lexer<'a> {
+        Lexer {
+            json: self,
+            input,
+            position: Position {
+                offset: 0,
+                row: 1,
+                col: 1,
+            },
+            failed: false,
+        }
+    }
+}
+
+impl<'a> Lexer<'a> {
+    /// The position of the next token, or of the whitespace before it.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position.offset..]
+    }
+
+    /// Move past the next `len` bytes, returning them.
+    fn advance(&mut self, len: usize) -> &'a str {
+        let text = &self.rest()[..len];
+        self.position = self.position.after(text);
+        text
+    }
+
+    /// The kind and length of the token at the start of the remaining
+    /// input, or the offset into it and kind of the error found instead.
+    fn token(&self) -> std::result::Result<(TokenKind, usize), (usize, ErrorKind)> {
+        let rest = self.rest();
+        let json = self.json;
+        let punctuation = match rest.as_bytes()[0] {
+            b'[' => Some(TokenKind::BeginArray),
+            b']' => Some(TokenKind::EndArray),
+            b'{' => Some(TokenKind::BeginObject),
+            b'}' => Some(TokenKind::EndObject),
+            b':' => Some(TokenKind::Colon),
+            b',' => Some(TokenKind::Comma),
+            _ => None,
+        };
+        if let Some(kind) = punctuation {
+            return Ok((kind, 1));
+        }
+        if json.comments || json.json5 {
+            if let Some(len) = comment(rest) {
+                return Ok((TokenKind::Comment, len));
+            }
+        }
+        if rest.starts_with('"') || (rest.starts_with('\'') && (json.single_quotes || json.json5)) {
+            return string(json, rest).map(|len| (TokenKind::String, len));
+        }
+        if let Some(token) = non_finite(json, rest) {
+            return Ok((TokenKind::Number, token.len()));
+        }
+        if let Some(len) = identifier(rest) {
+            let kind = match &rest[..len] {
+                "true" | "false" => TokenKind::Bool,
+                "null" => TokenKind::Null,
+                _ if json.unquoted_keys || json.json5 => TokenKind::Identifier,
+                _ => return Err((0, ErrorKind::Syntax { expected: "value" })),
+            };
+            return Ok((kind, len));
+        }
+        match number(json, rest) {
+            Ok((len, _)) => Ok((TokenKind::Number, len)),
+            Err(0) => Err((0, ErrorKind::Syntax { expected: "token" })),
+            Err(at) if at == rest.len() => Err((at, ErrorKind::Eof)),
+            Err(at) => Err((at, ErrorKind::Syntax { expected: "number" })),
+        }
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        self.advance(whitespace(self.json, self.rest()));
+        if self.rest().is_empty() {
+            return None;
+        }
+        let position = self.position;
+        match self.token() {
+            Ok((kind, len)) => Some(Ok(Token {
+                kind,
+                text: self.advance(len),
+                position,
+            })),
+            Err((at, kind)) => {
+                self.failed = true;
+                self.advance(at);
+                Some(Err(Error::new(kind).at(self.position)))
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for Lexer<'_> {}
+
+/// The length of the whitespace `rest` starts with.
+pub(super) fn whitespace(json: &Json, rest: &str) -> usize {
+    match json.json5 {
+        true => {
+            let trimmed =
+                rest.trim_start_matches(|char: char| char.is_whitespace() || char == '\u{feff}');
+            rest.len() - trimmed.len()
+        }
+        false => scan::whitespace(rest.as_bytes()),
+    }
+}
+
+/// The length of the comment `rest` starts with, if it starts with a
+/// complete one.
+pub(super) fn comment(rest: &str) -> Option<usize> {
+    if let Some(comment) = rest.strip_prefix("//") {
+        Some(2 + comment.find('\n').unwrap_or(comment.len()))
+    } else if let Some(comment) = rest.strip_prefix("/*") {
+        comment.find("*/").map(|end| end + 4)
+    } else {
+        None
+    }
+}
+
+/// The length of the identifier `rest` starts with, if any.
+pub(super) fn identifier(rest: &str) -> Option<usize> {
+    let first = rest.chars().next()?;
+    if !(first.is_alphabetic() || first == '$' || first == '_') {
+        return None;
+    }
+    let end = rest
+        .find(|char: char| {
+            !(char.is_alphanumeric() || matches!(char, '$' | '_' | '\u{200c}' | '\u{200d}'))
+        })
+        .unwrap_or(rest.len());
+    Some(end)
+}
+
+/// The `NaN` or `Infinity` token `rest` starts with, if they are accepted.
+pub(super) fn non_finite(json: &Json, rest: &str) -> Option<&'static str> {
+    if !json.non_finite && !json.json5 {
+        return None;
+    }
+    let signed: &[&'static str] = if json.json5 {
+        &["+Infinity", "+NaN", "-NaN"]
+    } else {
+        &[]
+    };
+    ["NaN", "Infinity", "-Infinity"]
+        .iter()
+        .chain(signed)
+        .find(|token| rest.starts_with(**token))
+        .copied()
+}
+
+/// The length of the number token `rest` starts with and whether it is a
+/// plain integer, without a fraction or an exponent, or the offset at which
+/// it stops being one.
+pub(super) fn number(json: &Json, rest: &str) -> std::result::Result<(usize, bool), usize> {
+    let bytes = rest.as_bytes();
+    let digits = |at: usize| scan::digits(&bytes[at..]);
+    let mut at = match bytes.first() {
+        Some(b'-') => 1,
+        Some(b'+') if json.json5 => 1,
+        _ => 0,
+    };
+    let radix = match &bytes[at..] {
+        [b'0', b'x' | b'X', ..] if json.json5 || json.radix_integers => Some(16),
+        [b'0', b'o' | b'O', ..] if json.radix_integers => Some(8),
+        [b'0', b'b' | b'B', ..] if json.radix_integers => Some(2),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        at += 2;
+        let digits = rest[at..]
+            .find(|char: char| !char.is_digit(radix))
+            .unwrap_or(rest.len() - at);
+        return match digits {
+            0 => Err(at),
+            _ => Ok((at + digits, true)),
+        };
+    }
+    let integer = digits(at);
+    // Neither JSON nor JSON5 allow leading zeros, which could be read as
+    // octal.
+    if integer > 1 && bytes[at] == b'0' {
+        return Err(at + 1);
+    }
+    at += integer;
+    if integer == 0 && !(json.json5 && bytes.get(at) == Some(&b'.')) {
+        return Err(0);
+    }
+    let mut integral = true;
+    if bytes.get(at) == Some(&b'.') {
+        at += 1;
+        let fraction = digits(at);
+        at += fraction;
+        if fraction == 0 && !(json.json5 && integer > 0) {
+            return Err(at);
+        }
+        integral = false;
+    }
+    if let Some(b'e' | b'E') = bytes.get(at) {
+        at += 1;
+        if let Some(b'+' | b'-') = bytes.get(at) {
+            at += 1;
+        }
+        let exponent = digits(at);
+        if exponent == 0 {
+            return Err(at);
+        }
+        at += exponent;
+        integral = false;
+    }
+    Ok((at, integral))
+}
+
+/// The length of the quoted string `rest` starts with, or the offset and
+/// kind of the error in it.
+fn string(json: &Json, rest: &str) -> std::result::Result<usize, (usize, ErrorKind)> {
+    match string_tail(json, rest.as_bytes()[0], &rest[1..]) {
+        Ok(len) => Ok(1 + len),
+        Err((_, ErrorKind::Eof)) => Err((rest.len(), ErrorKind::Eof)),
+        Err((at, kind)) => Err((1 + at, kind)),
+    }
+}
+
+/// The length of the rest of a string opened with `quote`, up to and
+/// including its closing quote, from `rest` that starts within it but not
+/// within an escape, or the offset and kind of the error in it.
+///
+/// A string that `rest` ends within is an [`ErrorKind::Eof`] at the offset
+/// scanning can start again from once more of it is known.
+pub(super) fn string_tail(
+    json: &Json,
+    quote: u8,
+    rest: &str,
+) -> std::result::Result<usize, (usize, ErrorKind)> {
+    let bytes = rest.as_bytes();
+    let mut at = 0;
+    while let Some(found) = scan::string_special(&bytes[at..], quote) {
+        at += found;
+        match bytes[at] {
+            byte if byte == quote => return Ok(at + 1),
+            b'\\' => {
+                let mut chars = rest[at + 1..].char_indices();
+                match escape(json, char::from(quote), &mut chars) {
+                    Ok(_) => at += 1 + chars.offset(),
+                    Err(ErrorKind::Eof) => return Err((at, ErrorKind::Eof)),
+                    Err(kind) => return Err((at, kind)),
+                }
+            }
+            byte if !json.accepts_control(char::from(byte)) => {
+                return Err((at, ErrorKind::ControlCharacter))
+            }
+            _ => at += 1,
+        }
+    }
+    Err((rest.len(), ErrorKind::Eof))
+}
+
+/// Resolve the escape following a backslash in a string opened with
+/// `quote`, reading the characters it takes from `chars`, or `None` for a
+/// JSON5 line continuation.
+pub(super) fn escape(
+    json: &Json,
+    quote: char,
+    chars: &mut CharIndices<'_>,
+) -> std::result::Result<Option<char>, ErrorKind> {
+    match chars.next() {
+        Some((_, '\'')) if quote == '\'' => Ok(Some('\'')),
+        Some((_, escaped)) if json.json5 => json5_escape(escaped, chars),
+        Some((_, 'u')) => unicode_escape(chars).map(Some),
+        Some((_, escaped)) => unescape(escaped).map(Some).ok_or(ErrorKind::Syntax {
+            expected: "escape sequence",
+        }),
+        None => Err(ErrorKind::Eof),
+    }
+}
+
+/// The character a backslash followed by `char` stands for in a string,
+/// if that is a valid escape.
+fn unescape(char: char) -> Option<char> {
+    match char {
+        '"' | '\\' | '/' => Some(char),
+        'b' => Some('\u{8}'),
+        'f' => Some('\u{c}'),
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        _ => None,
+    }
+}
+
+/// Resolve a JSON5 escape of `char`, reading any further characters it
+/// takes from `chars`, or `None` for a line continuation.
+fn json5_escape(
+    char: char,
+    chars: &mut CharIndices<'_>,
+) -> std::result::Result<Option<char>, ErrorKind> {
+    let mut after = chars.clone();
+    let next = after.next().map(|(_, char)| char);
+    match char {
+        'u' => unicode_escape(chars).map(Some),
+        'x' => {
+            let digits = [chars.next(), chars.next()];
+            let digits = digits.map(|digit| digit.map(|(_, digit)| digit.to_digit(16)));
+            match digits {
+                [Some(Some(high)), Some(Some(low))] => Ok(char::from_u32(high << 4 | low)),
+                [None, _] | [_, None] => Err(ErrorKind::Eof),
+                _ => Err(ErrorKind::Syntax {
+                    expected: "2 hex digits",
+                }),
+            }
+        }
+        '\r' if next == Some('\n') => {
+            *chars = after;
+            Ok(None)
+        }
+        '\r' | '\n' | '\u{2028}' | '\u{2029}' => Ok(None),
+        'v' => Ok(Some('\u{b}')),
+        '0' if !next.is_some_and(|next| next.is_ascii_digit()) => Ok(Some('\0')),
+        '0'..='9' => Err(ErrorKind::Syntax {
+            expected: "escape sequence",
+        }),
+        _ => Ok(Some(unescape(char).unwrap_or(char))),
+    }
+}
+
+/// Read the rest of a `\u` escape: four hex digits giving a UTF-16 code
+/// unit, and for a high surrogate the `\u` escape of the low surrogate
+/// completing it.
+fn unicode_escape(chars: &mut CharIndices<'_>) -> std::result::Result<char, ErrorKind> {
+    let unit = hex_code_unit(chars)?;
+    if !(0xD800..0xDC00).contains(&unit) {
+        return char::from_u32(unit.into()).ok_or(ErrorKind::UnpairedSurrogate { unit });
+    }
+    let mut after = chars.clone();
+    if after.next().map(|(_, char)| char) == Some('\\')
+        && after.next().map(|(_, char)| char) == Some('u')
+    {
+        let low = hex_code_unit(&mut after)?;
+        if (0xDC00..0xE000).contains(&low) {
+            *chars = after;
+            let code = 0x10000 + ((u32::from(unit) - 0xD800) << 10) + (u32::from(low) - 0xDC00);
+            return Ok(char::from_u32(code).expect("surrogate pairs encode valid characters"));
+        }
+    }
+    Err(ErrorKind::UnpairedSurrogate { unit })
+}
+
+/// Read the four hex digits of a `\u` escape.
+fn hex_code_unit(chars: &mut CharIndices<'_>) -> std::result::Result<u16, ErrorKind> {
+    let mut unit = 0;
+    for _ in 0..4 {
+        let (_, char) = chars.next().ok_or(ErrorKind::Eof)?;
+        let digit = char.to_digit(16).ok_or(ErrorKind::Syntax {
+            expected: "4 hex digits",
+        })?;
+        unit = unit << 4 | digit as u16;
+    }
+    Ok(unit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens<'a>(json: &'a Json, input: &'a str) -> Vec<(TokenKind, &'a str)> {
+        json.lexer(input)
+            .map(|token| token.map(|token| (token.kind, token.text)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn positions() {
+        let json = Json::new();
+        let input = "{\n  \"é\": [1.5e3, -2, true]\n}";
+        let tokens: Vec<_> = json.lexer(input).map(Result::unwrap).collect();
+        let spans: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.text, token.position.row, token.position.col))
+            .collect();
+        assert_eq!(
+            spans,
+            [
+                ("{", 1, 1),
+                ("\"é\"", 2, 3),
+                (":", 2, 6),
+                ("[", 2, 8),
+                ("1.5e3", 2, 9),
+                (",", 2, 14),
+                ("-2", 2, 16),
+                (",", 2, 18),
+                ("true", 2, 20),
+                ("]", 2, 24),
+                ("}", 3, 1),
+            ]
+        );
+        assert_eq!(tokens[4].position.offset, 11);
+    }
+
+    #[test]
+    fn lenient_syntax() {
+        let json = Json::new().json5(true);
+        assert_eq!(
+            tokens(
+                &json,
+                "{a: 'b\\'c', // d\n e: [+Infinity, 0x1F, .5] /* f */}"
+            ),
+            [
+                (TokenKind::BeginObject, "{"),
+                (TokenKind::Identifier, "a"),
+                (TokenKind::Colon, ":"),
+                (TokenKind::String, "'b\\'c'"),
+                (TokenKind::Comma, ","),
+                (TokenKind::Comment, "// d"),
+                (TokenKind::Identifier, "e"),
+                (TokenKind::Colon, ":"),
+                (TokenKind::BeginArray, "["),
+                (TokenKind::Number, "+Infinity"),
+                (TokenKind::Comma, ","),
+                (TokenKind::Number, "0x1F"),
+                (TokenKind::Comma, ","),
+                (TokenKind::Number, ".5"),
+                (TokenKind::EndArray, "]"),
+                (TokenKind::Comment, "/* f */"),
+                (TokenKind::EndObject, "}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn strings() {
+        let json = Json::new();
+        let input = "[\"é\\\"\0\\u00e9 \", \"\", \"ü\\\\\"]";
+        assert_eq!(
+            tokens(&json, input),
+            [
+                (TokenKind::BeginArray, "["),
+                (TokenKind::String, "\"é\\\"\0\\u00e9 \""),
+                (TokenKind::Comma, ","),
+                (TokenKind::String, "\"\""),
+                (TokenKind::Comma, ","),
+                (TokenKind::String, "\"ü\\\\\""),
+                (TokenKind::EndArray, "]"),
+            ]
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let json = Json::new();
+        let error = |input| {
+            let mut lexer = json.lexer(input);
+            let error = lexer.find_map(Result::err).unwrap();
+            assert!(lexer.next().is_none());
+            (error.kind().clone(), error.position().unwrap().col)
+        };
+        assert_eq!(
+            error("[1, a]"),
+            (ErrorKind::Syntax { expected: "value" }, 5)
+        );
+        assert_eq!(error("// no"), (ErrorKind::Syntax { expected: "token" }, 1));
+        assert_eq!(error("[1.]"), (ErrorKind::Syntax { expected: "number" }, 4));
+        assert_eq!(
+            error(r#""a\q""#),
+            (
+                ErrorKind::Syntax {
+                    expected: "escape sequence"
+                },
+                3
+            )
+        );
+        assert_eq!(error(r#"["abc"#), (ErrorKind::Eof, 6));
+        assert_eq!(error("[\"a\x01\"]"), (ErrorKind::ControlCharacter, 4));
+        assert_eq!(error("\"line\nbreak\""), (ErrorKind::ControlCharacter, 6));
+
+        let tokens = |json: Json, input| json.lexer(input).collect::<Result<Vec<_>>>().map(drop);
+        assert!(tokens(Json::new().control_characters(true), "\"a\tb\nc\"").is_ok());
+        assert!(tokens(Json::new().json5(true), "'a\tb'").is_ok());
+        assert!(tokens(Json::new().json5(true), "'a\nb'").is_err());
+    }
+}
@@ -0,0 +1,475 @@
+//! JSON input pushed in chunks as it arrives.
+
+use std::marker::PhantomData;
+
+use super::lexer::{self, TokenKind};
+use super::{from_utf8, Json};
+use crate::deserialize::Deserialize;
+use crate::error::{ErrorKind, Position, Result, Warning};
+
+/// The outcome of [`PushParser::feed`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Feed<T> {
+    /// A complete value was read.
+    Value(T),
+    /// The input so far holds no complete value.
+    NeedsMore,
+}
+
+impl Json {
+    /// Create a parser that is given its input in chunks as they arrive,
+    /// such as reads from a socket, and reads a `T` from each complete
+    /// value in it.
+    ///
+    /// ```
+    /// use shallot::deserialize::{Feed, Json};
+    ///
+    /// let json = Json::new();
+    /// let mut parser = json.push_parser::<Vec<u16>>();
+    /// assert_eq!(parser.feed(b"[80, 4")?, Feed::NeedsMore);
+    /// assert_eq!(parser.feed(b"43] [8")?, Feed::Value(vec![80, 443]));
+    /// assert_eq!(parser.feed(b"080]")?, Feed::Value(vec![8080]));
+    /// assert_eq!(parser.finish()?, None);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn push_parser<T: Deserialize>(&self) -> PushParser<'_, T> {
+        PushParser {
+            json: self,
+            buffer: Vec::new(),
+            read: 0,
+            scanned: 0,
+            unfinished: None,
+            depth: 0,
+            start: Position {
+                offset: 0,
+                row: 1,
+                col: 1,
+            },
+            warnings: Vec::new(),
+            marker: PhantomData,
+        }
+    }
+}
+
+/// A parser of values from input that arrives in chunks, created by
+/// [`Json::push_parser`].
+///
+/// Values follow one another like in [`Json::stream`]. Each is only read
+/// once it is complete, so a chunk can end anywhere, even within a string
+/// or a UTF-8 character. A number or literal at the top level is only
+/// known to be complete once something follows it, or at
+/// [`PushParser::finish`].
+///
+/// [`Json::max_input_len`] limits how much input a single value may take,
+/// including the whitespace before it. Error and warning positions count
+/// from the start of the first chunk.
+#[derive(Debug)]
+pub struct PushParser<'a, T> {
+    json: &'a Json,
+    /// Input, of which what follows `read` is not yet read as a value.
+    buffer: Vec<u8>,
+    /// Where the next value starts in `buffer`.
+    read: usize,
+    /// Where scanning for the end of the next value carries on from.
+    scanned: usize,
+    /// The token `scanned` is within, if it stopped partway through one.
+    unfinished: Option<Unfinished>,
+    /// How many arrays and objects are open after the scanned tokens.
+    depth: usize,
+    /// Where the next value starts in the whole input.
+    start: Position,
+    warnings: Vec<Warning>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Deserialize> PushParser<'_, T> {
+    /// Add `chunk` to the input and read the next value if it is complete.
+    ///
+    /// A chunk can complete more than one value; feed an empty chunk to
+    /// read each of the rest. After an error, the input buffered so far is
+    /// discarded and the next chunk starts afresh.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<Feed<T>> {
+        self.buffer.extend_from_slice(chunk);
+        match self.value_end() {
+            Some(end) => self.parse(end).map(Feed::Value),
+            None => match self.json.check_input_len(self.buffer.len() - self.read) {
+                Ok(()) => Ok(Feed::NeedsMore),
+                Err(error) => {
+                    let start = self.start;
+                    self.consume(self.buffer.len());
+                    Err(error.at(start))
+                }
+            },
+        }
+    }
+
+    /// End the input, reading the value still buffered, if there is one.
+    ///
+    /// Fails if the input ends partway through a value, or holds more than
+    /// one; read all the complete values with [`PushParser::feed`] first.
+    pub fn finish(mut self) -> Result<Option<T>> {
+        let blank = from_utf8(&self.buffer[self.read..]).is_ok_and(|text| {
+            self.json
+                .lexer(text)
+                .all(|token| token.is_ok_and(|token| token.kind == TokenKind::Comment))
+        });
+        match blank {
+            true => Ok(None),
+            false => self.parse(self.buffer.len()).map(Some),
+        }
+    }
+
+    /// Remove and return the warnings collected so far.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Scan the tokens added since the last call, returning where the next
+    /// value ends if it is complete.
+    ///
+    /// Input the lexer rejects ends the value there, for the parser to
+    /// report. The input is validated and scanned a window at a time, so
+    /// reading each value looks at little more than the value itself.
+    fn value_end(&mut self) -> Option<usize> {
+        // Taken for the duration, so that scanning can update the rest.
+        let buffer = std::mem::take(&mut self.buffer);
+        let end = self.scan_windows(&buffer);
+        self.buffer = buffer;
+        end
+    }
+
+    /// The end of the next value in `buffer`, taken out of `self` while
+    /// `self` is updated, if it is complete.
+    fn scan_windows(&mut self, buffer: &[u8]) -> Option<usize> {
+        let mut window = WINDOW;
+        loop {
+            let tail = &buffer[self.scanned..];
+            let last = window >= tail.len();
+            let tail = &tail[..tail.len().min(window)];
+            #[cfg(test)]
+            crate::scanned::add(tail.len());
+            let text = match std::str::from_utf8(tail) {
+                Ok(text) => text,
+                // A character split between chunks, or windows.
+                Err(error) if error.error_len().is_none() => {
+                    std::str::from_utf8(&tail[..error.valid_up_to()]).expect("the prefix is valid")
+                }
+                Err(_) => return Some(buffer.len()),
+            };
+            if let Some(end) = self.scan(text, buffer.len()) {
+                return Some(end);
+            }
+            if last {
+                return None;
+            }
+            window *= 2;
+        }
+    }
+
+    /// Scan `text`, which follows `scanned`, returning where the next value
+    /// ends if it does within it.
+    fn scan(&mut self, text: &str, len: usize) -> Option<usize> {
+        let json = self.json;
+        let mut scanned = 0;
+        if let Some(unfinished) = self.unfinished.take() {
+            match unfinished.resume(json, text) {
+                Ok(len) => scanned = len,
+                Err(Some(len)) => {
+                    self.scanned += len;
+                    self.unfinished = Some(unfinished);
+                    return None;
+                }
+                Err(None) => return Some(len),
+            }
+            if let (Unfinished::String(_), 0) = (unfinished, self.depth) {
+                self.scanned += scanned;
+                return Some(self.scanned);
+            }
+        }
+        let from = scanned;
+        let mut end = None;
+        for token in json.lexer(&text[from..]) {
+            let token = match token {
+                Ok(token) => token,
+                Err(error) => {
+                    let at = from + error.position().map_or(0, |position| position.offset);
+                    if !json.incomplete(&text[at..]) {
+                        end = Some(len);
+                    } else {
+                        let start = scanned + lexer::whitespace(json, &text[scanned..]);
+                        if let Some((unfinished, len)) = Unfinished::at(json, &text[start..]) {
+                            self.unfinished = Some(unfinished);
+                            scanned = start + len;
+                        }
+                    }
+                    break;
+                }
+            };
+            let token_end = from + token.position.offset + token.text.len();
+            let open_ended = match token.kind {
+                TokenKind::Number | TokenKind::Bool | TokenKind::Null | TokenKind::Identifier => {
+                    true
+                }
+                TokenKind::Comment => token.text.starts_with("//"),
+                _ => false,
+            };
+            if open_ended && token_end == text.len() {
+                if token.kind == TokenKind::Comment {
+                    self.unfinished = Some(Unfinished::LineComment);
+                    scanned = token_end;
+                }
+                break;
+            }
+            scanned = token_end;
+            match token.kind {
+                TokenKind::BeginArray | TokenKind::BeginObject => self.depth += 1,
+                TokenKind::EndArray | TokenKind::EndObject => {
+                    self.depth = self.depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+            if self.depth == 0 && token.kind != TokenKind::Comment {
+                end = Some(self.scanned + token_end);
+                break;
+            }
+        }
+        self.scanned += scanned;
+        end
+    }
+
+    /// Read the buffer up to `end` as a `T`, starting the next value there.
+    fn parse(&mut self, end: usize) -> Result<T> {
+        let result = from_utf8(&self.buffer[self.read..end])
+            .and_then(|text| self.json.deserialize_with_warnings(text));
+        let start = self.start;
+        self.consume(end);
+        match result {
+            Ok((value, warnings)) => {
+                for mut warning in warnings {
+                    warning.position = warning.position.map(|at| locate(start, at));
+                    self.warnings.push(warning);
+                }
+                Ok(value)
+            }
+            Err(error) => Err(match error.position() {
+                Some(position) => error.at(locate(start, position)),
+                None => error,
+            }),
+        }
+    }
+
+    /// Start the next value at `end` in the buffer, removing the input read
+    /// so far once it is more than half the buffer.
+    fn consume(&mut self, end: usize) {
+        let consumed = String::from_utf8_lossy(&self.buffer[self.read..end]);
+        self.start = Position {
+            offset: self.start.offset + consumed.len(),
+            ..self.start.after(&consumed)
+        };
+        self.read = end;
+        if self.read * 2 > self.buffer.len() {
+            self.buffer.drain(..self.read);
+            self.read = 0;
+        }
+        self.scanned = self.read;
+        self.unfinished = None;
+        self.depth = 0;
+    }
+}
+
+/// How many bytes [`PushParser`] first looks at for the end of a value.
+const WINDOW: usize = 1024;
+
+/// A token that the input scanned so far ends within.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Unfinished {
+    /// A string opened with the given quote, outside any escape.
+    String(u8),
+    /// A `/* */` comment.
+    BlockComment,
+    /// A `//` comment.
+    LineComment,
+}
+
+impl Unfinished {
+    /// The token `rest` starts with, if it is one that scanning can resume
+    /// within, and how far into it scanning can resume from.
+    fn at(json: &Json, rest: &str) -> Option<(Unfinished, usize)> {
+        let unfinished = match rest.as_bytes().first()? {
+            b'"' => Unfinished::String(b'"'),
+            b'\'' if json.json5 => Unfinished::String(b'\''),
+            b'/' if rest.starts_with("/*") => Unfinished::BlockComment,
+            _ => return None,
+        };
+        let opening = match unfinished {
+            Unfinished::BlockComment => 2,
+            _ => 1,
+        };
+        match unfinished.resume(json, &rest[opening..]) {
+            Err(Some(len)) => Some((unfinished, opening + len)),
+            _ => None,
+        }
+    }
+
+    /// The length of the rest of the token in `rest`, or if `rest` ends
+    /// within it, how far scanning can resume from, or `None` if it is
+    /// invalid.
+    fn resume(self, json: &Json, rest: &str) -> std::result::Result<usize, Option<usize>> {
+        match self {
+            Unfinished::String(quote) => match lexer::string_tail(json, quote, rest) {
+                Ok(len) => Ok(len),
+                Err((at, ErrorKind::Eof)) => Err(Some(at)),
+                Err(_) => Err(None),
+            },
+            Unfinished::BlockComment => match rest.find("*/") {
+                Some(end) => Ok(end + 2),
+                None => Err(Some(rest.len() - usize::from(rest.ends_with('*')))),
+            },
+            Unfinished::LineComment => rest.find('\n').ok_or(Some(rest.len())),
+        }
+    }
+}
+
+impl Json {
+    /// Whether the token the lexer rejected at the start of `rest` could
+    /// still be completed by more input.
+    fn incomplete(&self, rest: &str) -> bool {
+        if (self.comments || self.json5) && rest.starts_with("/*") {
+            return !rest.contains("*/");
+        }
+        !rest.contains(|char: char| char.is_whitespace() || "[]{},:\"'".contains(char))
+    }
+}
+
+/// Move `position`, counted from the start of a value at `start`, to the
+/// same place in the whole input.
+fn locate(start: Position, position: Position) -> Position {
+    Position {
+        offset: start.offset + position.offset,
+        row: start.row + position.row - 1,
+        col: match position.row {
+            1 => start.col + position.col - 1,
+            _ => position.col,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Value;
+
+    #[test]
+    fn chunks() {
+        let json = Json::new().comments(true);
+        let input = "{\"a\": \"é\", /* x */ \"b\": [true, -1.5e2]}\n12 // end\n\"z\"";
+        // Every split point, including within `é` and each token.
+        for split in 0..=input.len() {
+            let mut parser = json.push_parser::<Value>();
+            let mut values = Vec::new();
+            let (first, second) = input.as_bytes().split_at(split);
+            for chunk in [first, second, b""] {
+                if let Feed::Value(value) = parser.feed(chunk).unwrap() {
+                    values.push(value);
+                }
+            }
+            while let Feed::Value(value) = parser.feed(b"").unwrap() {
+                values.push(value);
+            }
+            values.extend(parser.finish().unwrap());
+            let expected: Vec<Value> = json.stream(input).collect::<Result<_>>().unwrap();
+            assert_eq!(values, expected, "split at {split}");
+        }
+    }
+
+    #[test]
+    fn long_tokens_in_small_chunks() {
+        let json = Json::new().comments(true);
+        let long = "é\\\"".repeat(2_000);
+        let input =
+            format!("[\"{long}\", /* {long} */ 1] // {long}\n\"{long}\" {{\"{long}\": null}}");
+        let mut parser = json.push_parser::<Value>();
+        let mut values = Vec::new();
+        for chunk in input.as_bytes().chunks(7) {
+            let mut feed = parser.feed(chunk).unwrap();
+            while let Feed::Value(value) = feed {
+                values.push(value);
+                feed = parser.feed(b"").unwrap();
+            }
+        }
+        values.extend(parser.finish().unwrap());
+        let expected: Vec<Value> = json.stream(&input).collect::<Result<_>>().unwrap();
+        assert_eq!(values, expected);
+    }
+
+    #[test]
+    fn input_is_read_in_linear_time() {
+        let json = Json::new();
+        // Scanning from the start of the buffer or the token each time
+        // would look at the input about once per value or chunk.
+
+        // Many values in one chunk.
+        let n = 1_000;
+        let input = r#"{"name": "a", "values": [1, 2, 3]}"#.repeat(n);
+        let mut count = 0;
+        let scanned = crate::scanned::count(|| {
+            let mut parser = json.push_parser::<Value>();
+            let mut feed = parser.feed(input.as_bytes()).unwrap();
+            while let Feed::Value(_) = feed {
+                count += 1;
+                feed = parser.feed(b"").unwrap();
+            }
+        });
+        assert_eq!(count, n);
+        // Each value also validates up to a window of the input after it.
+        let bound = 3 * input.len() + n * WINDOW;
+        assert!(scanned <= bound, "{scanned} of {}", input.len());
+
+        // One long string in small chunks.
+        let n = 50_000;
+        let input = format!("\"{}\"", "a\\n".repeat(n));
+        let mut value = String::new();
+        let scanned = crate::scanned::count(|| {
+            let mut parser = json.push_parser::<String>();
+            let mut chunks = input.as_bytes().chunks(256);
+            value = loop {
+                match parser.feed(chunks.next().unwrap()).unwrap() {
+                    Feed::Value(value) => break value,
+                    Feed::NeedsMore => {}
+                }
+            };
+        });
+        assert_eq!(value.len(), 2 * n);
+        assert!(scanned <= 3 * input.len(), "{scanned} of {}", input.len());
+    }
+
+    #[test]
+    fn errors() {
+        let json = Json::new();
+        let mut parser = json.push_parser::<Vec<u8>>();
+        assert_eq!(parser.feed(b"[1]\n[2,\n").unwrap(), Feed::Value(vec![1]));
+        assert_eq!(parser.feed(b"").unwrap(), Feed::NeedsMore);
+        let error = parser.feed(b" x]").unwrap_err();
+        assert_eq!(error.to_string(), "expected u8 at line 3, column 2");
+        assert_eq!(parser.feed(b"[3]").unwrap(), Feed::Value(vec![3]));
+        assert_eq!(parser.feed(b"[30").unwrap(), Feed::NeedsMore);
+        let error = parser.finish().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Eof);
+
+        let json = Json::new().max_input_len(8);
+        let mut parser = json.push_parser::<String>();
+        assert_eq!(parser.feed(b"  \"abcd").unwrap(), Feed::NeedsMore);
+        let error = parser.feed(b"efgh").unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::SizeLimit {
+                what: "input",
+                limit: 8
+            }
+        );
+        assert_eq!(
+            parser.feed(b"\"ok\"").unwrap(),
+            Feed::Value(String::from("ok"))
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! The innermost loops of JSON scanning, searching input bytes.
+//!
+//! With the `simd` feature on x86_64, each search checks 16 bytes at a time
+//! with SSE2, which every x86_64 processor has; elsewhere, and for the last
+//! few bytes, it checks one byte at a time. Both find the same index, so
+//! errors and their positions do not depend on the feature.
+
+/// The index of the first `quote`, backslash or control character below
+/// U+0020 in `bytes`: the bytes that end the plain text of a string.
+///
+/// All of them are ASCII, so the index is always at a character boundary.
+pub(super) fn string_special(bytes: &[u8], quote: u8) -> Option<usize> {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    return simd::string_special(bytes, quote);
+    #[cfg(all(feature = "memchr", not(all(feature = "simd", target_arch = "x86_64"))))]
+    return {
+        // `memchr` finds bytes but not ranges, so the text before the quote
+        // or backslash is checked for control characters separately.
+        let end = memchr::memchr2(quote, b'\\', bytes);
+        let plain = &bytes[..end.unwrap_or(bytes.len())];
+        plain.iter().position(|&byte| is_control(byte)).or(end)
+    };
+    #[allow(unreachable_code)]
+    scalar::string_special(bytes, quote)
+}
+
+/// The length of the run of spaces, tabs, line feeds and carriage returns
+/// `bytes` starts with.
+pub(super) fn whitespace(bytes: &[u8]) -> usize {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    return simd::whitespace(bytes);
+    #[allow(unreachable_code)]
+    scalar::whitespace(bytes)
+}
+
+/// The length of the run of ASCII digits `bytes` starts with.
+pub(super) fn digits(bytes: &[u8]) -> usize {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    return simd::digits(bytes);
+    #[allow(unreachable_code)]
+    scalar::digits(bytes)
+}
+
+fn is_control(byte: u8) -> bool {
+    byte < 0x20
+}
+
+fn is_whitespace(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r')
+}
+
+mod scalar {
+    pub(super) fn string_special(bytes: &[u8], quote: u8) -> Option<usize> {
+        bytes
+            .iter()
+            .position(|&byte| byte == quote || byte == b'\\' || super::is_control(byte))
+    }
+
+    pub(super) fn whitespace(bytes: &[u8]) -> usize {
+        bytes
+            .iter()
+            .position(|&byte| !super::is_whitespace(byte))
+            .unwrap_or(bytes.len())
+    }
+
+    pub(super) fn digits(bytes: &[u8]) -> usize {
+        bytes
+            .iter()
+            .position(|byte| !byte.is_ascii_digit())
+            .unwrap_or(bytes.len())
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod simd {
+    use std::arch::x86_64::{
+        __m128i, _mm_cmpeq_epi8, _mm_loadu_si128, _mm_max_epu8, _mm_min_epu8, _mm_movemask_epi8,
+        _mm_or_si128, _mm_set1_epi8,
+    };
+
+    const LANES: usize = 16;
+
+    // SAFETY, for the intrinsics below: SSE2 is part of every x86_64
+    // target, so it is always available.
+
+    /// Load a block of 16 bytes.
+    fn load(block: &[u8]) -> __m128i {
+        assert_eq!(block.len(), LANES);
+        // SAFETY: `block` is 16 readable bytes, and `loadu` does not need
+        // them aligned.
+        unsafe { _mm_loadu_si128(block.as_ptr().cast()) }
+    }
+
+    /// Mark the bytes of `block` equal to `byte`.
+    fn equal(block: __m128i, byte: u8) -> __m128i {
+        unsafe { _mm_cmpeq_epi8(block, _mm_set1_epi8(byte as i8)) }
+    }
+
+    /// Mark the bytes of `block` below U+0020, which are those unchanged by
+    /// clamping to `0..=0x1F`.
+    fn control(block: __m128i) -> __m128i {
+        unsafe { _mm_cmpeq_epi8(_mm_min_epu8(block, _mm_set1_epi8(0x1F)), block) }
+    }
+
+    /// Mark the bytes of `block` that are ASCII digits, which are those
+    /// unchanged by clamping to `0..=9`.
+    fn digit(block: __m128i) -> __m128i {
+        unsafe {
+            let low = _mm_max_epu8(block, _mm_set1_epi8(b'0' as i8));
+            _mm_cmpeq_epi8(_mm_min_epu8(low, _mm_set1_epi8(b'9' as i8)), block)
+        }
+    }
+
+    fn or(left: __m128i, right: __m128i) -> __m128i {
+        unsafe { _mm_or_si128(left, right) }
+    }
+
+    /// One bit per byte of `marks`, set for the marked bytes.
+    fn mask(marks: __m128i) -> u32 {
+        unsafe { _mm_movemask_epi8(marks) as u32 }
+    }
+
+    /// The index of the first byte of `bytes` that `hits` sets a bit for in
+    /// the mask of a block, or that `scalar` finds in the bytes left over
+    /// after the last whole block.
+    fn find(
+        bytes: &[u8],
+        hits: impl Fn(__m128i) -> u32,
+        scalar: impl Fn(&[u8]) -> Option<usize>,
+    ) -> Option<usize> {
+        let mut blocks = bytes.chunks_exact(LANES);
+        for (index, block) in blocks.by_ref().enumerate() {
+            let mask = hits(load(block));
+            if mask != 0 {
+                return Some(index * LANES + mask.trailing_zeros() as usize);
+            }
+        }
+        let start = bytes.len() - blocks.remainder().len();
+        scalar(blocks.remainder()).map(|index| start + index)
+    }
+
+    pub(super) fn string_special(bytes: &[u8], quote: u8) -> Option<usize> {
+        find(
+            bytes,
+            |block| {
+                let special = or(equal(block, quote), equal(block, b'\\'));
+                mask(or(special, control(block)))
+            },
+            |rest| super::scalar::string_special(rest, quote),
+        )
+    }
+
+    pub(super) fn whitespace(bytes: &[u8]) -> usize {
+        find(
+            bytes,
+            |block| {
+                let space = or(equal(block, b' '), equal(block, b'\t'));
+                let newline = or(equal(block, b'\n'), equal(block, b'\r'));
+                !mask(or(space, newline)) & 0xFFFF
+            },
+            |rest| rest.iter().position(|&byte| !super::is_whitespace(byte)),
+        )
+        .unwrap_or(bytes.len())
+    }
+
+    pub(super) fn digits(bytes: &[u8]) -> usize {
+        find(
+            bytes,
+            |block| !mask(digit(block)) & 0xFFFF,
+            |rest| rest.iter().position(|byte| !byte.is_ascii_digit()),
+        )
+        .unwrap_or(bytes.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_scalar() {
+        // Every position of a match within and across 16-byte blocks.
+        for len in 0..40 {
+            for at in 0..=len {
+                let mut text = vec![b'a'; len];
+                if at < len {
+                    text[at] = b'\\';
+                }
+                assert_eq!(
+                    string_special(&text, b'"'),
+                    scalar::string_special(&text, b'"')
+                );
+                if at < len {
+                    text[at] = b'\t';
+                }
+                assert_eq!(string_special(&text, b'"'), (at < len).then_some(at));
+                let mut text = vec![b' '; len];
+                let mut numbers = vec![b'7'; len];
+                if at < len {
+                    text[at] = b'x';
+                    numbers[at] = b'/';
+                }
+                assert_eq!(whitespace(&text), scalar::whitespace(&text));
+                assert_eq!(digits(&numbers), scalar::digits(&numbers));
+            }
+        }
+        let bytes: Vec<u8> = (0..=255).collect();
+        for start in 0..bytes.len() {
+            let bytes = &bytes[start..];
+            assert_eq!(
+                string_special(bytes, b'\''),
+                scalar::string_special(bytes, b'\'')
+            );
+            assert_eq!(whitespace(bytes), scalar::whitespace(bytes));
+            assert_eq!(digits(bytes), scalar::digits(bytes));
+        }
+    }
+}
@@ -0,0 +1,33 @@
+//! The values a parser read for the `$id`s of shared values.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The values a [`Parser`](super::Parser) read for `$id`s, each the `Rc` or
+/// `Arc` it was read as.
+///
+/// The values are held by the parser itself and dropped with it. Since they
+/// may be `Rc`s, this keeps the parser on the thread that made them.
+#[derive(Debug, Default, Clone)]
+pub(super) struct SharedValues {
+    values: HashMap<u64, Rc<dyn Any>>,
+}
+
+impl SharedValues {
+    /// A clone of the pointer read for `id`, or `None` if there is none or
+    /// it is not a `P`.
+    pub(super) fn get<P: Clone + 'static>(&self, id: u64) -> Option<P> {
+        self.values.get(&id)?.downcast_ref::<P>().cloned()
+    }
+
+    /// Add `value` as the pointer read for `id`, returning `false` if
+    /// there already is one.
+    pub(super) fn insert<P: 'static>(&mut self, id: u64, value: P) -> bool {
+        if self.values.contains_key(&id) {
+            return false;
+        }
+        self.values.insert(id, Rc::new(value));
+        true
+    }
+}
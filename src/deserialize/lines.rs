@@ -0,0 +1,205 @@
+//! Line-delimited JSON input, also known as NDJSON or JSON Lines.
+
+use std::io::BufRead;
+use std::marker::PhantomData;
+
+use super::json::from_utf8;
+use super::{Deserialize, Json};
+use crate::error::{Error, Position, Result, Warning};
+
+impl Json {
+    /// Iterate over the lines of `reader`, each read as one `T`.
+    ///
+    /// Lines end with `\n` or `\r\n`, and blank lines are skipped. A line
+    /// that fails does not end iteration, since the next one starts afresh,
+    /// but an I/O error does. Error positions count lines and bytes from the
+    /// start of `reader`.
+    ///
+    /// ```
+    /// use shallot::deserialize::Json;
+    ///
+    /// let input = "[1, 2]\n\n[3]\r\n[\"x\"]\n[]";
+    /// let json = Json::new();
+    /// let mut lines = json.lines::<_, Vec<u8>>(input.as_bytes());
+    /// assert_eq!(lines.next().transpose()?, Some(vec![1, 2]));
+    /// assert_eq!(lines.next().transpose()?, Some(vec![3]));
+    /// let error = lines.next().unwrap().unwrap_err();
+    /// assert_eq!(error.to_string(), "expected u8 at line 4, column 2");
+    /// assert_eq!(lines.next().transpose()?, Some(vec![]));
+    /// assert!(lines.next().is_none());
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn lines<R: BufRead, T: Deserialize>(&self, reader: R) -> JsonLines<'_, R, T> {
+        JsonLines {
+            json: self,
+            reader,
+            line: Vec::new(),
+            row: 0,
+            offset: 0,
+            warnings: Vec::new(),
+            done: false,
+            marker: PhantomData,
+        }
+    }
+}
+
+/// An iterator over the values of line-delimited JSON, created by
+/// [`Json::lines`].
+#[derive(Debug)]
+pub struct JsonLines<'a, R, T> {
+    json: &'a Json,
+    reader: R,
+    /// The current line, kept to reuse its allocation.
+    line: Vec<u8>,
+    /// The 1-based number of the current line.
+    row: usize,
+    /// The byte offset of the next line.
+    offset: usize,
+    warnings: Vec<Warning>,
+    /// Whether the input is used up or an I/O error ended iteration.
+    done: bool,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<R, T> JsonLines<'_, R, T> {
+    /// Remove and return the warnings collected so far.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Move `position` within the current line, which starts at byte
+    /// `start`, to the same place in the whole input.
+    fn locate(&self, start: usize, position: Position) -> Position {
+        Position {
+            offset: start + position.offset,
+            row: self.row,
+            col: position.col,
+        }
+    }
+
+    /// Read the current line as a `T`.
+    fn parse(&mut self, start: usize) -> Result<T>
+    where
+        T: Deserialize,
+    {
+        let line = from_utf8(&self.line);
+        match line.and_then(|line| self.json.deserialize_with_warnings(line)) {
+            Ok((value, warnings)) => {
+                for mut warning in warnings {
+                    warning.position = warning.position.map(|at| self.locate(start, at));
+                    self.warnings.push(warning);
+                }
+                Ok(value)
+            }
+            Err(error) => Err(match error.position() {
+                Some(position) => {
+                    let position = self.locate(start, position);
+                    error.at(position)
+                }
+                None => error,
+            }),
+        }
+    }
+}
+
+impl<R: BufRead, T: Deserialize> Iterator for JsonLines<'_, R, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        while !self.done {
+            self.line.clear();
+            let read = match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(read) => read,
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(Error::from(error)));
+                }
+            };
+            if read == 0 {
+                self.done = true;
+                break;
+            }
+            let start = self.offset;
+            self.offset += read;
+            self.row += 1;
+            for terminator in [b'\n', b'\r'] {
+                if self.line.last() == Some(&terminator) {
+                    self.line.pop();
+                }
+            }
+            if self.line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            return Some(self.parse(start));
+        }
+        None
+    }
+}
+
+impl<R: BufRead, T: Deserialize> std::iter::FusedIterator for JsonLines<'_, R, T> {}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, BufReader, Read};
+
+    use super::*;
+    use crate::{ErrorKind, Value};
+
+    #[test]
+    fn positions() {
+        let json = Json::new();
+        let bytes = b"1\n\"\xc3\xa9\",\n\"\xc3\xa9\xff\"\n0.5\n";
+        let mut lines = json.lines::<_, Value>(&bytes[..]);
+        lines.next().unwrap().unwrap();
+        let error = lines.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::TrailingCharacters);
+        assert_eq!(
+            error.position(),
+            Some(Position {
+                offset: 6,
+                row: 2,
+                col: 4
+            })
+        );
+        let error = lines.next().unwrap().unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidUtf8);
+        assert_eq!(
+            error.position(),
+            Some(Position {
+                offset: 11,
+                row: 3,
+                col: 3
+            })
+        );
+        let value: Value = lines.next().unwrap().unwrap();
+        assert!(value.as_f64().is_some());
+        assert!(lines.next().is_none());
+    }
+
+    #[test]
+    fn warnings_and_io_errors() {
+        let json = Json::new();
+        let mut lines = json.lines::<_, f32>(&b"\r\n  \n0.123456789\r\n"[..]);
+        assert_eq!(lines.next().unwrap().unwrap(), 0.12345679);
+        let warnings = lines.take_warnings();
+        assert_eq!(
+            warnings[0].position,
+            Some(Position {
+                offset: 5,
+                row: 3,
+                col: 1
+            })
+        );
+
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, "gone"))
+            }
+        }
+        let mut lines = json.lines::<_, u8>(BufReader::new(Failing));
+        let error = lines.next().unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "I/O error: gone");
+        assert!(lines.next().is_none());
+    }
+}
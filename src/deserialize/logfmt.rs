@@ -0,0 +1,628 @@
+//! logfmt input: a line of `key=value` pairs.
+
+use std::borrow::Cow;
+use std::num::{IntErrorKind, ParseIntError};
+use std::str::FromStr;
+
+use super::{Deserialize, DeserializeSeed, Deserializer, Kind, Map, Seq, Variant};
+use crate::error::{Error, ErrorKind, Position, Result, WarningKind};
+
+/// Deserializes a struct or map from logfmt, a single line of `key=value`
+/// pairs as written by many loggers.
+///
+/// Values are quoted with `"` when they contain spaces, with `\` escapes as
+/// in JSON strings, and a key without `=` is a flag standing for `true`.
+/// Values carry no type of their own, so each is parsed as whatever the
+/// field asks for; read into a [`Value`](crate::Value), unquoted numbers and
+/// `true` and `false` become numbers and bools, an empty value `null`, and
+/// anything else a string. Values cannot be arrays or nested objects.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use shallot::deserialize::Logfmt;
+/// use shallot::Value;
+///
+/// let line = r#"level=info msg="request done" status=200 cached"#;
+/// let pairs: BTreeMap<String, Value> = Logfmt::new().deserialize(line)?;
+/// assert_eq!(pairs["msg"], Value::from("request done"));
+/// assert_eq!(pairs["status"], Value::from(200));
+/// assert_eq!(pairs["cached"], Value::from(true));
+/// let status: BTreeMap<String, u16> = Logfmt::new().deserialize("status=\"404\"")?;
+/// assert_eq!(status["status"], 404);
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Logfmt {}
+
+impl Logfmt {
+    /// Create a logfmt deserializer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a `T` from `line`, which may end with a line ending.
+    pub fn deserialize<T: Deserialize>(&self, line: &str) -> Result<T> {
+        let line = line
+            .strip_suffix('\n')
+            .map_or(line, |line| line.strip_suffix('\r').unwrap_or(line));
+        let mut reader = Reader {
+            input: line,
+            pairs: pairs(line)?,
+            next: 0,
+            at: At::Start,
+        };
+        T::accept(&mut reader).map_err(Error::in_type::<T>)
+    }
+}
+
+/// The kind an unquoted logfmt value reads as.
+pub(crate) fn kind(text: &str) -> Kind {
+    let numeric = |c: char| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | 'e' | 'E');
+    match text {
+        "" => Kind::Null,
+        "true" | "false" => Kind::Bool,
+        _ if text.parse::<u64>().is_ok() => Kind::Unsigned,
+        _ if text.parse::<i64>().is_ok() => Kind::Signed,
+        _ if text.parse::<u128>().is_ok() => Kind::WideUnsigned,
+        _ if text.parse::<i128>().is_ok() => Kind::WideSigned,
+        _ if text.chars().all(numeric) && text.parse::<f64>().is_ok() => Kind::Float,
+        _ => Kind::String,
+    }
+}
+
+/// One `key=value` pair, or a bare key.
+struct Pair<'a> {
+    key: &'a str,
+    key_at: usize,
+    /// The value with its escapes resolved, or `None` for a bare key.
+    value: Option<Cow<'a, str>>,
+    quoted: bool,
+    value_at: usize,
+}
+
+/// Split `line` into its pairs.
+fn pairs(line: &str) -> Result<Vec<Pair<'_>>> {
+    let error = |kind, at| Err(Error::new(kind).at(Position::locate(line, at)));
+    let syntax = |expected, at| error(ErrorKind::Syntax { expected }, at);
+    let bytes = line.as_bytes();
+    let blank = |at: usize| {
+        bytes
+            .get(at)
+            .is_none_or(|byte| matches!(byte, b' ' | b'\t'))
+    };
+    let mut pairs = Vec::new();
+    let mut at = 0;
+    loop {
+        while at < bytes.len() && blank(at) {
+            at += 1;
+        }
+        if at == bytes.len() {
+            return Ok(pairs);
+        }
+        let key_at = at;
+        while !blank(at) && !matches!(bytes[at], b'=' | b'"') && !bytes[at].is_ascii_control() {
+            at += 1;
+        }
+        if at == key_at {
+            return syntax("key", at);
+        }
+        let key = &line[key_at..at];
+        let (value, quoted, value_at) = match bytes.get(at) {
+            Some(b'=') if bytes.get(at + 1) == Some(&b'"') => {
+                let (value, len) = quoted_value(line, at + 2)?;
+                let value_at = at + 1;
+                at = value_at + len;
+                (Some(Cow::Owned(value)), true, value_at)
+            }
+            Some(b'=') => {
+                let value_at = at + 1;
+                at = value_at;
+                while !blank(at) {
+                    match bytes[at] {
+                        b'"' => return syntax("space", at),
+                        byte if byte.is_ascii_control() => {
+                            return error(ErrorKind::ControlCharacter, at)
+                        }
+                        _ => at += 1,
+                    }
+                }
+                (Some(Cow::Borrowed(&line[value_at..at])), false, value_at)
+            }
+            _ => (None, false, at),
+        };
+        if !blank(at) {
+            return match bytes[at] {
+                byte if byte.is_ascii_control() => error(ErrorKind::ControlCharacter, at),
+                _ => syntax("space", at),
+            };
+        }
+        pairs.push(Pair {
+            key,
+            key_at,
+            value,
+            quoted,
+            value_at,
+        });
+    }
+}
+
+/// The value of the quoted string starting at `start` in `line`, just after
+/// its opening quote, and its length with both quotes.
+fn quoted_value(line: &str, start: usize) -> Result<(String, usize)> {
+    let error = |kind, at| Error::new(kind).at(Position::locate(line, at));
+    let mut value = String::new();
+    let mut chars = line[start..].char_indices();
+    while let Some((offset, c)) = chars.next() {
+        let at = start + offset;
+        match c {
+            '"' => return Ok((value, offset + 2)),
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some(c @ ('"' | '\\' | '/')) => c,
+                    Some('n') => '\n',
+                    Some('r') => '\r',
+                    Some('t') => '\t',
+                    Some('b') => '\u{8}',
+                    Some('f') => '\u{c}',
+                    Some('u') => {
+                        let digits = line
+                            .get(at + 2..at + 6)
+                            .filter(|digits| digits.bytes().all(|byte| byte.is_ascii_hexdigit()));
+                        let unit = digits
+                            .map(|digits| u16::from_str_radix(digits, 16).expect("hex digits"))
+                            .ok_or_else(|| error(ErrorKind::Syntax { expected: "escape" }, at))?;
+                        chars.nth(3);
+                        char::from_u32(unit.into())
+                            .ok_or_else(|| error(ErrorKind::UnpairedSurrogate { unit }, at))?
+                    }
+                    None => break,
+                    Some(_) => return Err(error(ErrorKind::Syntax { expected: "escape" }, at)),
+                };
+                value.push(escaped);
+            }
+            c if c.is_control() => return Err(error(ErrorKind::ControlCharacter, at)),
+            c => value.push(c),
+        }
+    }
+    Err(error(ErrorKind::Eof, line.len()))
+}
+
+/// What a [`Reader`] reads next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum At {
+    /// The pairs as a whole.
+    Start,
+    /// The key of the next pair.
+    Key,
+    /// The value of the next pair.
+    Value,
+    /// Between pairs.
+    Pairs,
+}
+
+/// A deserializer reading the pairs of one line in order.
+struct Reader<'a> {
+    input: &'a str,
+    pairs: Vec<Pair<'a>>,
+    /// The pair being read, or the next one between pairs.
+    next: usize,
+    at: At,
+}
+
+/// A scalar read by a [`Reader`]: its text and where it starts.
+struct Scalar<'a> {
+    text: Option<Cow<'a, str>>,
+    at: usize,
+}
+
+fn not_scalar() -> Error {
+    Error::custom("logfmt values must be scalars")
+}
+
+impl<'a> Reader<'a> {
+    /// Read the key or value of the pair being read.
+    fn scalar(&mut self) -> Result<Scalar<'a>> {
+        let pair = self.pairs.get_mut(self.next);
+        match (self.at, pair) {
+            (At::Key, Some(pair)) => {
+                self.at = At::Value;
+                Ok(Scalar {
+                    text: Some(Cow::Borrowed(pair.key)),
+                    at: pair.key_at,
+                })
+            }
+            (At::Value, Some(pair)) => {
+                self.at = At::Pairs;
+                self.next += 1;
+                Ok(Scalar {
+                    text: pair.value.take(),
+                    at: pair.value_at,
+                })
+            }
+            _ => Err(self.error(ErrorKind::Syntax {
+                expected: "a struct or map",
+            })),
+        }
+    }
+
+    /// The value about to be read, if it is the value of a pair.
+    fn peek_value(&self) -> Option<&Pair<'a>> {
+        match self.at {
+            At::Value => self.pairs.get(self.next),
+            _ => None,
+        }
+    }
+
+    fn error(&self, kind: ErrorKind) -> Error {
+        let at = match (self.at, self.pairs.get(self.next)) {
+            (At::Key, Some(pair)) => pair.key_at,
+            (At::Value, Some(pair)) => pair.value_at,
+            _ => 0,
+        };
+        self.error_at(kind, at)
+    }
+
+    fn error_at(&self, kind: ErrorKind, at: usize) -> Error {
+        Error::new(kind).at(Position::locate(self.input, at))
+    }
+
+    /// Read a scalar and parse it with `parse`, failing with a syntax error
+    /// naming `expected` if there is no text or `parse` returns `None`.
+    fn parse<T>(
+        &mut self,
+        expected: &'static str,
+        parse: impl FnOnce(&str) -> std::result::Result<T, ErrorKind>,
+    ) -> Result<T> {
+        let scalar = self.scalar()?;
+        let syntax = ErrorKind::Syntax { expected };
+        let parsed = scalar.text.as_deref().ok_or(syntax).and_then(parse);
+        parsed.map_err(|kind| self.error_at(kind, scalar.at))
+    }
+
+    fn integer<T: FromStr<Err = ParseIntError>>(&mut self, expected: &'static str) -> Result<T> {
+        self.parse(expected, |text| {
+            text.parse()
+                .map_err(|error: ParseIntError| match error.kind() {
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                        ErrorKind::Overflow { target: expected }
+                    }
+                    _ => ErrorKind::Syntax { expected },
+                })
+        })
+    }
+}
+
+macro_rules! visit_integer {
+    ($($method:ident($ty:ident))+) => {
+        $(
+            fn $method(&mut self) -> Result<$ty> {
+                self.integer(stringify!($ty))
+            }
+        )+
+    };
+}
+
+impl Deserializer for Reader<'_> {
+    /// A bare key reads as `true`.
+    fn visit_bool(&mut self) -> Result<bool> {
+        if self.peek_value().is_some_and(|pair| pair.value.is_none()) {
+            self.scalar()?;
+            return Ok(true);
+        }
+        self.parse("bool", |text| {
+            text.parse()
+                .map_err(|_| ErrorKind::Syntax { expected: "bool" })
+        })
+    }
+
+    visit_integer! {
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_i128(i128)
+        visit_isize(isize)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_u128(u128)
+        visit_usize(usize)
+    }
+
+    fn visit_f32(&mut self) -> Result<f32> {
+        self.parse("f32", |text| {
+            text.parse()
+                .map_err(|_| ErrorKind::Syntax { expected: "f32" })
+        })
+    }
+
+    fn visit_f64(&mut self) -> Result<f64> {
+        self.parse("f64", |text| {
+            text.parse()
+                .map_err(|_| ErrorKind::Syntax { expected: "f64" })
+        })
+    }
+
+    fn visit_char(&mut self) -> Result<char> {
+        self.parse("char", |text| {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(char), None) => Ok(char),
+                _ => Err(ErrorKind::Syntax { expected: "char" }),
+            }
+        })
+    }
+
+    fn visit_string(&mut self) -> Result<String> {
+        let scalar = self.scalar()?;
+        match scalar.text {
+            Some(text) => Ok(text.into_owned()),
+            None => Err(self.error_at(ErrorKind::Syntax { expected: "string" }, scalar.at)),
+        }
+    }
+
+    fn peek_kind(&mut self) -> Result<Kind> {
+        Ok(match self.at {
+            At::Start => Kind::Map,
+            At::Key => Kind::String,
+            _ => match self.peek_value() {
+                Some(Pair { value: None, .. }) => Kind::Bool,
+                Some(Pair { quoted: true, .. }) => Kind::String,
+                Some(Pair {
+                    value: Some(text), ..
+                }) => kind(text),
+                None => return Err(self.error(ErrorKind::Eof)),
+            },
+        })
+    }
+
+    /// An empty value, as in `key=`, is the unit value.
+    fn visit_unit(&mut self) -> Result<()> {
+        self.parse("an empty value", |text| match text {
+            "" => Ok(()),
+            _ => Err(ErrorKind::Syntax {
+                expected: "an empty value",
+            }),
+        })
+    }
+
+    fn visit_option<T: Deserialize>(&mut self) -> Result<Option<T>> {
+        if self.peek_kind()? == Kind::Null {
+            self.scalar()?;
+            return Ok(None);
+        }
+        T::accept(self).map(Some).map_err(Error::in_type::<T>)
+    }
+
+    fn begin_seq(&mut self, len: Option<usize>) -> Result<Seq> {
+        let _ = len;
+        Err(match self.at {
+            At::Start => self.error(ErrorKind::Syntax {
+                expected: "a struct or map",
+            }),
+            _ => not_scalar(),
+        })
+    }
+
+    /// No sequence can be started, but a [`Seq`] made by hand can still be
+    /// passed in.
+    fn next_element(&mut self, seq: &mut Seq) -> Result<bool> {
+        let _ = seq;
+        Err(not_scalar())
+    }
+
+    fn end_seq(&mut self, seq: Seq) -> Result<()> {
+        let _ = seq;
+        Err(not_scalar())
+    }
+
+    fn begin_map(&mut self) -> Result<Map> {
+        match self.at {
+            At::Start => {
+                self.at = At::Pairs;
+                Ok(Map::new(0))
+            }
+            _ => Err(self.error(ErrorKind::Custom(not_scalar().to_string()))),
+        }
+    }
+
+    fn next_key_seed<S: DeserializeSeed>(
+        &mut self,
+        map: &mut Map,
+        seed: S,
+    ) -> Result<Option<S::Value>> {
+        if self.next == self.pairs.len() {
+            return Ok(None);
+        }
+        map.index += 1;
+        self.at = At::Key;
+        seed.accept(self)
+            .map(Some)
+            .map_err(Error::in_type::<S::Value>)
+    }
+
+    fn end_map(&mut self, map: Map) -> Result<()> {
+        let _ = map;
+        match self.next == self.pairs.len() {
+            true => Ok(()),
+            false => Err(Error::custom("not every pair was read")),
+        }
+    }
+
+    fn next_field(
+        &mut self,
+        map: &mut Map,
+        fields: &'static [&'static str],
+    ) -> Result<Option<usize>> {
+        while let Some(key) = self.next_key::<String>(map)? {
+            if let Some(index) = fields.iter().position(|field| *field == key) {
+                return Ok(Some(index));
+            }
+            self.warn(WarningKind::UnknownField { field: key });
+            self.scalar()?;
+        }
+        Ok(None)
+    }
+
+    /// Only unit variants, written as their names, can be read.
+    fn begin_variant(&mut self, variants: &'static [Variant]) -> Result<usize> {
+        let scalar = self.scalar()?;
+        let name = scalar.text.unwrap_or_default();
+        let Some(index) = variants.iter().position(|variant| variant.name == name) else {
+            let variant = name.into_owned();
+            return Err(self.error_at(ErrorKind::UnknownVariant { variant }, scalar.at));
+        };
+        match variants[index].unit {
+            true => Ok(index),
+            false => Err(not_scalar()),
+        }
+    }
+
+    fn end_variant(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::Value;
+
+    fn kind_of<T: Deserialize + std::fmt::Debug>(line: &str) -> ErrorKind {
+        Logfmt::new()
+            .deserialize::<T>(line)
+            .unwrap_err()
+            .kind()
+            .clone()
+    }
+
+    #[test]
+    fn pairs_and_values() {
+        let logfmt = Logfmt::new();
+        let line = "a=1 b=-2 c=1.5 d=x e=\"x y\" f g= h=\"\" i=true j=\"7\" k=a=b\r\n";
+        let value: Value = logfmt.deserialize(line).unwrap();
+        let expected = BTreeMap::from([
+            ("a", Value::from(1)),
+            ("b", Value::from(-2)),
+            ("c", crate::value::to_value(&1.5).unwrap()),
+            ("d", Value::from("x")),
+            ("e", Value::from("x y")),
+            ("f", Value::from(true)),
+            ("g", Value::Null),
+            ("h", Value::from("")),
+            ("i", Value::from(true)),
+            ("j", Value::from("7")),
+            ("k", Value::from("a=b")),
+        ]);
+        let expected = expected.into_iter().map(|(key, value)| (key.into(), value));
+        assert_eq!(value, Value::Object(expected.collect()));
+
+        let map: BTreeMap<String, u8> = logfmt.deserialize("  x=\"1\"\ty=2  ").unwrap();
+        assert_eq!(map, BTreeMap::from([("x".into(), 1), ("y".into(), 2)]));
+        let map: BTreeMap<String, Option<String>> = logfmt.deserialize("a= b=\"\"").unwrap();
+        assert_eq!(map["a"], None);
+        assert_eq!(map["b"].as_deref(), Some(""));
+        let map: BTreeMap<u8, bool> = logfmt.deserialize("1 2=false").unwrap();
+        assert_eq!(map, BTreeMap::from([(1, true), (2, false)]));
+        let map: BTreeMap<String, String> = logfmt.deserialize("").unwrap();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn escapes() {
+        let map: BTreeMap<String, String> = Logfmt::new()
+            .deserialize(r#"a="q\"b\\s\/n\nt\tué""#)
+            .unwrap();
+        assert_eq!(map["a"], "q\"b\\s/n\nt\tu\u{e9}");
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>(r#"a="\ud800""#),
+            ErrorKind::UnpairedSurrogate { unit: 0xd800 }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>(r#"a="\x""#),
+            ErrorKind::Syntax { expected: "escape" }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>("a=\"x\ty\""),
+            ErrorKind::ControlCharacter
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>(r#"a="open"#),
+            ErrorKind::Eof
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let logfmt = Logfmt::new();
+        let error = logfmt
+            .deserialize::<BTreeMap<String, u8>>("a=1 b=300")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "number out of range for u8 at line 1, column 7"
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, u8>>("a=x"),
+            ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, u8>>("a"),
+            ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>("a=\"b\"c"),
+            ErrorKind::Syntax { expected: "space" }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>("a=b\"c\""),
+            ErrorKind::Syntax { expected: "space" }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>("=b"),
+            ErrorKind::Syntax { expected: "key" }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>("a=b\nc=d"),
+            ErrorKind::ControlCharacter
+        );
+        assert_eq!(
+            kind_of::<u8>("a=1"),
+            ErrorKind::Syntax {
+                expected: "a struct or map"
+            }
+        );
+        assert_eq!(
+            kind_of::<Vec<u8>>("a=1"),
+            ErrorKind::Syntax {
+                expected: "a struct or map"
+            }
+        );
+        let error = logfmt
+            .deserialize::<BTreeMap<String, Vec<u8>>>("a=1")
+            .unwrap_err();
+        assert_eq!(error.to_string(), "logfmt values must be scalars");
+
+        /// Reads a sequence it starts itself.
+        #[derive(Debug)]
+        struct Forged;
+
+        impl Deserialize for Forged {
+            fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+                let mut seq = Seq::new(None, 0);
+                deserializer.next_element(&mut seq)?;
+                deserializer.end_seq(seq).map(|()| Forged)
+            }
+        }
+
+        let error = logfmt.deserialize::<Forged>("a=1").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "logfmt values must be scalars for Forged"
+        );
+    }
+}
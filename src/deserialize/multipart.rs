@@ -0,0 +1,920 @@
+//! `multipart/form-data` input: HTML form bodies with file uploads.
+
+use std::collections::HashMap;
+use std::num::{IntErrorKind, ParseIntError};
+use std::str::FromStr;
+
+use super::{Deserialize, DeserializeSeed, Deserializer, Kind, Map, RepeatPolicy, Seq, Variant};
+use crate::error::{Error, ErrorKind, Position, Result, WarningKind};
+
+/// Deserializes a struct or map from a `multipart/form-data` body, as sent
+/// by HTML forms that upload files.
+///
+/// Each part of the body is an entry keyed by the name in its
+/// `Content-Disposition` header. Text parts are read like urlencoded
+/// values: they carry no type of their own, so each is parsed as whatever
+/// the field asks for, and an empty part reads as `None` for an `Option`.
+/// A file part, one with a `filename`, is read as its bytes by a `Vec<u8>`,
+/// which can also read a text part as its number.
+///
+/// A name may be given more than once. A sequence takes all of its parts in
+/// order, so several files read into a `Vec<Vec<u8>>`, while a single value
+/// takes one of them as set by [`Multipart::repeat_policy`], the last by
+/// default.
+///
+/// A part read as a struct or map has the entries `filename` and
+/// `content_type`, where its headers give them, and `data`, its content.
+///
+/// ```
+/// # #[cfg(feature = "derive")]
+/// # {
+/// use shallot::deserialize::Multipart;
+/// use shallot::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Upload {
+///     title: String,
+///     photo: Vec<u8>,
+/// }
+///
+/// let content_type = "multipart/form-data; boundary=XyZ";
+/// let body = b"--XyZ\r\n\
+///     Content-Disposition: form-data; name=\"title\"\r\n\
+///     \r\n\
+///     Holiday\r\n\
+///     --XyZ\r\n\
+///     Content-Disposition: form-data; name=\"photo\"; filename=\"a.jpg\"\r\n\
+///     Content-Type: image/jpeg\r\n\
+///     \r\n\
+///     \xff\xd8\xff\r\n\
+///     --XyZ--\r\n";
+/// let upload: Upload = Multipart::new().deserialize(content_type, body)?;
+/// assert_eq!(upload.title, "Holiday");
+/// assert_eq!(upload.photo, b"\xff\xd8\xff");
+/// # }
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Multipart {
+    repeats: RepeatPolicy,
+}
+
+impl Multipart {
+    /// Create a multipart deserializer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set which part a single value takes from a name given more than
+    /// once.
+    pub fn repeat_policy(mut self, policy: RepeatPolicy) -> Self {
+        self.repeats = policy;
+        self
+    }
+
+    /// Read a `T` from `body`, whose `Content-Type` header is
+    /// `content_type`, which gives the boundary between its parts.
+    ///
+    /// A content type other than `multipart/form-data` with a boundary
+    /// fails with a syntax error without a position.
+    pub fn deserialize<T: Deserialize>(&self, content_type: &str, body: &[u8]) -> Result<T> {
+        let boundary = boundary(content_type).ok_or_else(|| {
+            Error::new(ErrorKind::Syntax {
+                expected: "a multipart/form-data content type with a boundary",
+            })
+        })?;
+        let mut reader = Reader {
+            multipart: self,
+            body,
+            next: Some(Node::Map(entries(body, boundary)?)),
+            frames: Vec::new(),
+        };
+        T::accept(&mut reader).map_err(Error::in_type::<T>)
+    }
+}
+
+/// The boundary given by a `multipart/form-data` content type.
+fn boundary(content_type: &str) -> Option<&str> {
+    let (kind, parameters) = parameters(content_type)?;
+    if !kind.eq_ignore_ascii_case("multipart/form-data") {
+        return None;
+    }
+    let (_, boundary) = parameters
+        .into_iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case("boundary"))?;
+    (1..=70).contains(&boundary.len()).then_some(boundary)
+}
+
+/// Split a header value such as `form-data; name="a"` into what comes
+/// before the first `;` and the parameters after it, with the quotes of
+/// quoted values removed.
+fn parameters(value: &str) -> Option<(&str, Vec<(&str, &str)>)> {
+    let (kind, mut rest) = value.split_once(';').unwrap_or((value, ""));
+    let mut parameters = Vec::new();
+    loop {
+        rest = rest.trim_start_matches([' ', '\t', ';']);
+        if rest.is_empty() {
+            return Some((kind.trim(), parameters));
+        }
+        let (name, value) = rest.split_once('=')?;
+        let value = value.trim_start_matches([' ', '\t']);
+        let end = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                parameters.push((name.trim(), &quoted[..end]));
+                end + 2
+            }
+            None => {
+                let end = value.find(';').unwrap_or(value.len());
+                parameters.push((name.trim(), value[..end].trim_end()));
+                end
+            }
+        };
+        rest = &value[end..];
+    }
+}
+
+/// The position of the byte `offset` within `body`, which need not be
+/// UTF-8.
+fn locate(body: &[u8], offset: usize) -> Position {
+    let before = &body[..offset];
+    let line = before.iter().rposition(|&byte| byte == b'\n');
+    let line = &before[line.map_or(0, |newline| newline + 1)..];
+    Position {
+        offset,
+        row: before.iter().filter(|&&byte| byte == b'\n').count() + 1,
+        col: String::from_utf8_lossy(line).chars().count() + 1,
+    }
+}
+
+/// The offset of the first `needle` in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    #[cfg(feature = "memchr")]
+    return memchr::memmem::find(haystack, needle);
+    #[cfg(not(feature = "memchr"))]
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// A header value or part name and where it starts in the body.
+#[derive(Debug, Clone, Copy)]
+struct Text<'a> {
+    text: &'a str,
+    at: usize,
+}
+
+/// One part of the body.
+#[derive(Debug, Clone, Copy)]
+struct Part<'a> {
+    filename: Option<Text<'a>>,
+    content_type: Option<Text<'a>>,
+    data: &'a [u8],
+    /// Where `data` starts in the body.
+    at: usize,
+}
+
+/// What the body gives for a name.
+#[derive(Debug)]
+enum Node<'a> {
+    /// The parts given for the name, in body order.
+    Parts(Vec<Part<'a>>),
+    /// Entries by name, in the order each name first appears.
+    Map(Vec<(Text<'a>, Node<'a>)>),
+    /// A name, or a header value of a part.
+    Text(Text<'a>),
+    /// One byte of a file part and where it is in the body.
+    Byte(u8, usize),
+}
+
+impl Node<'_> {
+    /// Where the node starts in the body.
+    fn at(&self) -> usize {
+        match self {
+            Node::Parts(parts) => parts.first().map_or(0, |part| part.at),
+            Node::Map(entries) => entries.first().map_or(0, |(key, _)| key.at),
+            Node::Text(text) => text.at,
+            Node::Byte(_, at) => *at,
+        }
+    }
+}
+
+/// Split `body` into its parts, grouping the parts given for each name.
+fn entries<'a>(body: &'a [u8], boundary: &str) -> Result<Vec<(Text<'a>, Node<'a>)>> {
+    let error = |kind, at| Error::new(kind).at(locate(body, at));
+    let syntax = |expected, at| error(ErrorKind::Syntax { expected }, at);
+    let delimiter = format!("\r\n--{boundary}");
+    let delimiter = delimiter.as_bytes();
+    // The first boundary may start the body, without a line break before it.
+    let mut at = match body.starts_with(&delimiter[2..]) {
+        true => delimiter.len() - 2,
+        false => match find(body, delimiter) {
+            Some(start) => start + delimiter.len(),
+            None => return Err(error(ErrorKind::Eof, body.len())),
+        },
+    };
+    let mut entries: Vec<(Text, Vec<Part>)> = Vec::new();
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    loop {
+        let rest = &body[at..];
+        if rest.starts_with(b"--") {
+            let entries = entries.into_iter();
+            return Ok(entries
+                .map(|(name, parts)| (name, Node::Parts(parts)))
+                .collect());
+        }
+        let padding = rest.iter().take_while(|byte| matches!(byte, b' ' | b'\t'));
+        at += padding.count();
+        if !body[at..].starts_with(b"\r\n") {
+            return Err(syntax("a line break after the boundary", at));
+        }
+        at += 2;
+        let start = at;
+        let mut name = None;
+        let mut part = Part {
+            filename: None,
+            content_type: None,
+            data: &[],
+            at: 0,
+        };
+        loop {
+            let Some(end) = find(&body[at..], b"\r\n") else {
+                return Err(error(ErrorKind::Eof, body.len()));
+            };
+            let line = &body[at..at + end];
+            let line_at = at;
+            at += end + 2;
+            if line.is_empty() {
+                break;
+            }
+            let line = std::str::from_utf8(line)
+                .map_err(|utf8| error(ErrorKind::InvalidUtf8, line_at + utf8.valid_up_to()))?;
+            let Some((header, value)) = line.split_once(':') else {
+                return Err(syntax("`:` in a part header", line_at));
+            };
+            let value_at = line_at + header.len() + 1;
+            let value_at = value_at + value.len() - value.trim_start().len();
+            let value = value.trim();
+            if header.eq_ignore_ascii_case("Content-Type") {
+                part.content_type = Some(Text {
+                    text: value,
+                    at: value_at,
+                });
+                continue;
+            }
+            if !header.eq_ignore_ascii_case("Content-Disposition") {
+                continue;
+            }
+            let parameters =
+                parameters(value).filter(|(kind, _)| kind.eq_ignore_ascii_case("form-data"));
+            let Some((_, parameters)) = parameters else {
+                return Err(syntax("`form-data` with parameters", value_at));
+            };
+            let text = |text: &'a str| Text {
+                text,
+                at: value_at + (text.as_ptr() as usize - value.as_ptr() as usize),
+            };
+            for (parameter, value) in parameters {
+                match parameter {
+                    "name" => name = Some(text(value)),
+                    "filename" => part.filename = Some(text(value)),
+                    _ => {}
+                }
+            }
+        }
+        let Some(name) = name else {
+            return Err(syntax("a Content-Disposition header with a name", start));
+        };
+        let Some(end) = find(&body[at..], delimiter) else {
+            return Err(error(ErrorKind::Eof, body.len()));
+        };
+        part.data = &body[at..at + end];
+        part.at = at;
+        at += end + delimiter.len();
+        match indices.get(name.text) {
+            Some(&index) => entries[index].1.push(part),
+            None => {
+                indices.insert(name.text, entries.len());
+                entries.push((name, vec![part]));
+            }
+        }
+    }
+}
+
+/// The elements of a sequence a [`Reader`] is reading.
+enum Elements<'a> {
+    Nodes(std::vec::IntoIter<Node<'a>>),
+    /// The bytes of a file part, from `next` on.
+    Bytes {
+        part: Part<'a>,
+        next: usize,
+    },
+}
+
+/// A map or sequence a [`Reader`] is reading.
+enum Frame<'a> {
+    Map {
+        entries: std::vec::IntoIter<(Text<'a>, Node<'a>)>,
+        /// The value of the entry whose key was just read.
+        value: Option<Node<'a>>,
+    },
+    Seq {
+        elements: Elements<'a>,
+        len: usize,
+    },
+}
+
+/// A deserializer reading the parts of a body in order.
+struct Reader<'a> {
+    multipart: &'a Multipart,
+    body: &'a [u8],
+    /// The node to be read next.
+    next: Option<Node<'a>>,
+    /// The maps and sequences being read, innermost last.
+    frames: Vec<Frame<'a>>,
+}
+
+impl<'a> Reader<'a> {
+    fn error_at(&self, kind: ErrorKind, at: usize) -> Error {
+        Error::new(kind).at(locate(self.body, at))
+    }
+
+    /// Take the node to be read next.
+    fn take(&mut self) -> Result<Node<'a>> {
+        self.next.take().ok_or_else(|| Error::new(ErrorKind::Eof))
+    }
+
+    /// The part a single value takes from `parts`, by the repeat policy.
+    fn choose(&self, parts: &[Part<'a>]) -> Result<Part<'a>> {
+        match (self.multipart.repeats, parts) {
+            (RepeatPolicy::Reject, [_, second, ..]) => {
+                Err(self.error_at(ErrorKind::RepeatedKey, second.at))
+            }
+            (RepeatPolicy::First, [first, ..]) => Ok(*first),
+            _ => Ok(parts[parts.len() - 1]),
+        }
+    }
+
+    /// Read the next node as text, failing with a syntax error naming
+    /// `expected` if it is not.
+    fn scalar(&mut self, expected: &'static str) -> Result<Text<'a>> {
+        match self.take()? {
+            Node::Parts(parts) => {
+                let part = self.choose(&parts)?;
+                match std::str::from_utf8(part.data) {
+                    Ok(text) => Ok(Text { text, at: part.at }),
+                    Err(utf8) => {
+                        let at = part.at + utf8.valid_up_to();
+                        Err(self.error_at(ErrorKind::InvalidUtf8, at))
+                    }
+                }
+            }
+            Node::Text(text) => Ok(text),
+            node => Err(self.error_at(ErrorKind::Syntax { expected }, node.at())),
+        }
+    }
+
+    /// Read text and parse it with `parse`.
+    fn parse<T>(
+        &mut self,
+        expected: &'static str,
+        parse: impl FnOnce(&str) -> std::result::Result<T, ErrorKind>,
+    ) -> Result<T> {
+        let value = self.scalar(expected)?;
+        parse(value.text).map_err(|kind| self.error_at(kind, value.at))
+    }
+
+    /// Read a byte of a file part, or parse text.
+    fn integer<T>(&mut self, expected: &'static str) -> Result<T>
+    where
+        T: FromStr<Err = ParseIntError> + TryFrom<u8>,
+    {
+        if let Some(Node::Byte(byte, at)) = self.next {
+            self.next = None;
+            let overflow = ErrorKind::Overflow { target: expected };
+            return T::try_from(byte).map_err(|_| self.error_at(overflow, at));
+        }
+        self.parse(expected, |text| {
+            text.parse()
+                .map_err(|error: ParseIntError| match error.kind() {
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                        ErrorKind::Overflow { target: expected }
+                    }
+                    _ => ErrorKind::Syntax { expected },
+                })
+        })
+    }
+
+    /// Make the value of the entry whose key was just read the next node.
+    fn enter_value(&mut self) -> Result<()> {
+        let Some(Frame::Map { value, .. }) = self.frames.last_mut() else {
+            return Err(Error::custom("no map is being read"));
+        };
+        self.next = value.take();
+        Ok(())
+    }
+
+    /// If a sequence of parts was taken for the bytes of its only part,
+    /// which is a file, and its first element is read as a sequence, read
+    /// it as a sequence of that one part instead.
+    fn unread_bytes(&mut self) {
+        let (Some(Node::Byte(..)), Some(Frame::Seq { elements, len })) =
+            (&self.next, self.frames.last_mut())
+        else {
+            return;
+        };
+        if let Elements::Bytes { part, next: 1 } = elements {
+            self.next = Some(Node::Parts(vec![*part]));
+            *elements = Elements::Nodes(Vec::new().into_iter());
+            *len = 1;
+        }
+    }
+}
+
+macro_rules! visit_integer {
+    ($($method:ident($ty:ident))+) => {
+        $(
+            fn $method(&mut self) -> Result<$ty> {
+                self.integer(stringify!($ty))
+            }
+        )+
+    };
+}
+
+macro_rules! visit_float {
+    ($($method:ident($ty:ident))+) => {
+        $(
+            fn $method(&mut self) -> Result<$ty> {
+                self.parse(stringify!($ty), |text| {
+                    text.parse().map_err(|_| ErrorKind::Syntax {
+                        expected: stringify!($ty),
+                    })
+                })
+            }
+        )+
+    };
+}
+
+impl Deserializer for Reader<'_> {
+    fn visit_bool(&mut self) -> Result<bool> {
+        self.parse("bool", |text| {
+            text.parse()
+                .map_err(|_| ErrorKind::Syntax { expected: "bool" })
+        })
+    }
+
+    visit_integer! {
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_i128(i128)
+        visit_isize(isize)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_u128(u128)
+        visit_usize(usize)
+    }
+
+    visit_float! {
+        visit_f32(f32)
+        visit_f64(f64)
+    }
+
+    fn visit_char(&mut self) -> Result<char> {
+        self.parse("char", |text| {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(char), None) => Ok(char),
+                _ => Err(ErrorKind::Syntax { expected: "char" }),
+            }
+        })
+    }
+
+    fn visit_string(&mut self) -> Result<String> {
+        self.scalar("string").map(|value| value.text.to_owned())
+    }
+
+    /// A name given more than once is a sequence, as is a file part, a
+    /// name with one text part is a string, and a byte of a file is a
+    /// number.
+    fn peek_kind(&mut self) -> Result<Kind> {
+        match &self.next {
+            Some(Node::Map(_)) => Ok(Kind::Map),
+            Some(Node::Parts(parts)) if parts.len() > 1 => Ok(Kind::Seq),
+            Some(Node::Parts(parts)) if parts[0].filename.is_some() => Ok(Kind::Seq),
+            Some(Node::Parts(_) | Node::Text(_)) => Ok(Kind::String),
+            Some(Node::Byte(..)) => Ok(Kind::Unsigned),
+            None => Err(Error::new(ErrorKind::Eof)),
+        }
+    }
+
+    /// An empty part is the unit value.
+    fn visit_unit(&mut self) -> Result<()> {
+        self.parse("an empty value", |text| match text {
+            "" => Ok(()),
+            _ => Err(ErrorKind::Syntax {
+                expected: "an empty value",
+            }),
+        })
+    }
+
+    /// An empty part, such as a file input with no file chosen, is `None`.
+    fn visit_option<T: Deserialize>(&mut self) -> Result<Option<T>> {
+        let empty = match &self.next {
+            Some(Node::Parts(parts)) => self.choose(parts)?.data.is_empty(),
+            Some(_) => false,
+            None => return Err(Error::new(ErrorKind::Eof)),
+        };
+        if empty {
+            self.take()?;
+            return Ok(None);
+        }
+        T::accept(self).map(Some).map_err(Error::in_type::<T>)
+    }
+
+    /// Reads the bytes of a single file part, or else the parts given for
+    /// a name.
+    fn begin_seq(&mut self, len: Option<usize>) -> Result<Seq> {
+        self.unread_bytes();
+        let node = self.take()?;
+        let at = node.at();
+        let (elements, count) = match node {
+            Node::Parts(parts) if parts.len() == 1 && parts[0].filename.is_some() => {
+                let part = parts[0];
+                (Elements::Bytes { part, next: 0 }, part.data.len())
+            }
+            Node::Parts(parts) => {
+                let count = parts.len();
+                let elements = parts.into_iter().map(|part| Node::Parts(vec![part]));
+                let elements: Vec<Node> = elements.collect();
+                (Elements::Nodes(elements.into_iter()), count)
+            }
+            node => {
+                let expected = "a sequence";
+                return Err(self.error_at(ErrorKind::Syntax { expected }, node.at()));
+            }
+        };
+        self.frames.push(Frame::Seq {
+            elements,
+            len: count,
+        });
+        Ok(Seq::new(len, at))
+    }
+
+    fn next_element(&mut self, seq: &mut Seq) -> Result<bool> {
+        let Some(Frame::Seq { elements, len }) = self.frames.last_mut() else {
+            return Err(Error::custom("no sequence is being read"));
+        };
+        if seq.len == Some(seq.index) {
+            return Ok(false);
+        }
+        let element = match elements {
+            Elements::Nodes(nodes) => nodes.next(),
+            Elements::Bytes { part, next } => part.data.get(*next).map(|&byte| {
+                *next += 1;
+                Node::Byte(byte, part.at + *next - 1)
+            }),
+        };
+        let Some(element) = element else {
+            return match seq.len {
+                Some(expected) => Err(Error::new(ErrorKind::Length {
+                    expected,
+                    found: *len,
+                })),
+                None => Ok(false),
+            };
+        };
+        seq.index += 1;
+        seq.element = element.at();
+        self.next = Some(element);
+        Ok(true)
+    }
+
+    fn end_seq(&mut self, seq: Seq) -> Result<()> {
+        let Some(Frame::Seq { elements, len }) = self.frames.pop() else {
+            return Err(Error::custom("no sequence is being read"));
+        };
+        let remaining = match elements {
+            Elements::Nodes(nodes) => nodes.len(),
+            Elements::Bytes { part, next } => part.data.len() - next,
+        };
+        match seq.len {
+            Some(expected) if remaining > 0 => Err(Error::new(ErrorKind::Length {
+                expected,
+                found: len,
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the body as a whole, or the headers and data of one part.
+    fn begin_map(&mut self) -> Result<Map> {
+        let node = self.take()?;
+        let at = node.at();
+        let entries = match node {
+            Node::Map(entries) => entries,
+            Node::Parts(parts) => {
+                let part = self.choose(&parts)?;
+                let headers = [
+                    ("filename", part.filename),
+                    ("content_type", part.content_type),
+                ];
+                let mut entries: Vec<_> = headers
+                    .into_iter()
+                    .filter_map(|(key, value)| Some((Text { text: key, at }, Node::Text(value?))))
+                    .collect();
+                entries.push((Text { text: "data", at }, Node::Parts(vec![part])));
+                entries
+            }
+            node => {
+                let expected = "a struct or map";
+                return Err(self.error_at(ErrorKind::Syntax { expected }, node.at()));
+            }
+        };
+        self.frames.push(Frame::Map {
+            entries: entries.into_iter(),
+            value: None,
+        });
+        Ok(Map::new(at))
+    }
+
+    fn next_key_seed<S: DeserializeSeed>(
+        &mut self,
+        map: &mut Map,
+        seed: S,
+    ) -> Result<Option<S::Value>> {
+        let Some(Frame::Map { entries, value }) = self.frames.last_mut() else {
+            return Err(Error::custom("no map is being read"));
+        };
+        let Some((key, node)) = entries.next() else {
+            return Ok(None);
+        };
+        *value = Some(node);
+        map.index += 1;
+        self.next = Some(Node::Text(key));
+        seed.accept(self)
+            .map(Some)
+            .map_err(Error::in_type::<S::Value>)
+    }
+
+    fn next_value_seed<S: DeserializeSeed>(&mut self, map: &mut Map, seed: S) -> Result<S::Value> {
+        let _ = map;
+        self.enter_value()?;
+        seed.accept(self).map_err(Error::in_type::<S::Value>)
+    }
+
+    fn end_map(&mut self, map: Map) -> Result<()> {
+        let _ = map;
+        match self.frames.pop() {
+            Some(Frame::Map { .. }) => Ok(()),
+            _ => Err(Error::custom("no map is being read")),
+        }
+    }
+
+    fn next_field(
+        &mut self,
+        map: &mut Map,
+        fields: &'static [&'static str],
+    ) -> Result<Option<usize>> {
+        while let Some(key) = self.next_key::<String>(map)? {
+            if let Some(index) = fields.iter().position(|field| *field == key) {
+                self.enter_value()?;
+                return Ok(Some(index));
+            }
+            self.warn(WarningKind::UnknownField { field: key });
+        }
+        Ok(None)
+    }
+
+    /// Only unit variants, written as their names, can be read.
+    fn begin_variant(&mut self, variants: &'static [Variant]) -> Result<usize> {
+        let value = self.scalar("string")?;
+        let Some(index) = variants
+            .iter()
+            .position(|variant| variant.name == value.text)
+        else {
+            let variant = value.text.to_owned();
+            return Err(self.error_at(ErrorKind::UnknownVariant { variant }, value.at));
+        };
+        match variants[index].unit {
+            true => Ok(index),
+            false => Err(self.error_at(
+                ErrorKind::Syntax {
+                    expected: "a unit variant",
+                },
+                value.at,
+            )),
+        }
+    }
+
+    fn end_variant(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::Value;
+
+    const CONTENT_TYPE: &str = "multipart/form-data; boundary=B0und";
+
+    /// A body of parts given as name, file name and content.
+    fn body(parts: &[(&str, Option<&str>, &[u8])]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (name, filename, data) in parts {
+            let filename = filename.map_or(String::new(), |file| format!("; filename=\"{file}\""));
+            let disposition = format!("Content-Disposition: form-data; name=\"{name}\"{filename}");
+            body.extend_from_slice(format!("--B0und\r\n{disposition}\r\n\r\n").as_bytes());
+            body.extend_from_slice(data);
+            body.extend_from_slice(b"\r\n");
+        }
+        body.extend_from_slice(b"--B0und--\r\n");
+        body
+    }
+
+    fn kind_of<T: Deserialize>(content_type: &str, body: &[u8]) -> ErrorKind {
+        Multipart::new()
+            .deserialize::<T>(content_type, body)
+            .err()
+            .expect("an error")
+            .kind()
+            .clone()
+    }
+
+    #[test]
+    fn parts_and_values() {
+        let multipart = Multipart::new();
+        let input = body(&[
+            ("a", None, b"1"),
+            ("b", None, b"x y\r\nz"),
+            ("a", None, b"2"),
+        ]);
+        let map: BTreeMap<String, String> = multipart.deserialize(CONTENT_TYPE, &input).unwrap();
+        let expected = [("a", "2"), ("b", "x y\r\nz")];
+        assert_eq!(
+            map,
+            BTreeMap::from(expected.map(|(k, v)| (k.into(), v.into())))
+        );
+        let repeated = body(&[("a", None, b"1"), ("a", None, b"2")]);
+        let map: BTreeMap<String, Vec<u8>> =
+            multipart.deserialize(CONTENT_TYPE, &repeated).unwrap();
+        assert_eq!(map["a"], [1, 2]);
+        let first = multipart.clone().repeat_policy(RepeatPolicy::First);
+        let map: BTreeMap<String, u8> = first.deserialize(CONTENT_TYPE, &repeated).unwrap();
+        assert_eq!(map["a"], 1);
+        let reject = multipart.clone().repeat_policy(RepeatPolicy::Reject);
+        let error = reject
+            .deserialize::<BTreeMap<String, u8>>(CONTENT_TYPE, &repeated)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::RepeatedKey);
+        assert_eq!(error.position().unwrap().row, 8);
+
+        let value: Value = multipart
+            .deserialize(
+                CONTENT_TYPE,
+                &body(&[("a", None, b"1"), ("f", Some("f.bin"), b"\x01\x02")]),
+            )
+            .unwrap();
+        let expected = BTreeMap::from([
+            ("a".into(), Value::from("1")),
+            (
+                "f".into(),
+                Value::Array(vec![Value::from(1), Value::from(2)]),
+            ),
+        ]);
+        assert_eq!(value, Value::Object(expected));
+
+        // A preamble, an epilogue, padding after boundaries, a quoted
+        // boundary, and header names in any case.
+        let input = b"preamble\r\n--a'b c  \r\n\
+            content-disposition: form-data; name=x; filename=\"n.txt\"\r\n\
+            CONTENT-TYPE: text/plain\r\n\
+            X-Other: ignored\r\n\
+            \r\n\
+            --a'b \r\n\
+            --a'b c--epilogue";
+        let content_type = "Multipart/Form-Data; charset=utf-8; boundary=\"a'b c\"";
+        let map: BTreeMap<String, BTreeMap<String, String>> =
+            multipart.deserialize(content_type, input).unwrap();
+        let expected = [
+            ("filename", "n.txt"),
+            ("content_type", "text/plain"),
+            ("data", "--a'b "),
+        ];
+        assert_eq!(
+            map["x"],
+            BTreeMap::from(expected.map(|(k, v)| (k.into(), v.into())))
+        );
+        let map: BTreeMap<String, String> =
+            multipart.deserialize(CONTENT_TYPE, b"--B0und--").unwrap();
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn files() {
+        let multipart = Multipart::new();
+        let one = body(&[("f", Some("a.bin"), b"\x00\xff")]);
+        let two = body(&[
+            ("f", Some("a.bin"), b"\x00\xff"),
+            ("f", Some("b.bin"), b"\x07"),
+        ]);
+        let files: BTreeMap<String, Vec<u8>> = multipart.deserialize(CONTENT_TYPE, &one).unwrap();
+        assert_eq!(files["f"], [0, 255]);
+        let files: BTreeMap<String, Vec<Vec<u8>>> =
+            multipart.deserialize(CONTENT_TYPE, &one).unwrap();
+        assert_eq!(files["f"], [vec![0, 255]]);
+        let files: BTreeMap<String, Vec<Vec<u8>>> =
+            multipart.deserialize(CONTENT_TYPE, &two).unwrap();
+        assert_eq!(files["f"], [vec![0, 255], vec![7]]);
+        let files: BTreeMap<String, [u8; 2]> = multipart.deserialize(CONTENT_TYPE, &one).unwrap();
+        assert_eq!(files["f"], [0, 255]);
+
+        let empty = body(&[("f", Some(""), b"")]);
+        let files: BTreeMap<String, Option<Vec<u8>>> =
+            multipart.deserialize(CONTENT_TYPE, &empty).unwrap();
+        assert_eq!(files["f"], None);
+        let files: BTreeMap<String, Option<Vec<u8>>> =
+            multipart.deserialize(CONTENT_TYPE, &one).unwrap();
+        assert_eq!(files["f"], Some(vec![0, 255]));
+    }
+
+    #[test]
+    fn errors() {
+        let input = body(&[("a", None, b"1")]);
+        let expected = ErrorKind::Syntax {
+            expected: "a multipart/form-data content type with a boundary",
+        };
+        for content_type in [
+            "multipart/form-data",
+            "multipart/mixed; boundary=B0und",
+            "text/plain",
+            "multipart/form-data; boundary=\"B0und",
+            "multipart/form-data; boundary=",
+        ] {
+            let kind = kind_of::<BTreeMap<String, String>>(content_type, &input);
+            assert_eq!(kind, expected, "{content_type}");
+        }
+
+        let error = Multipart::new()
+            .deserialize::<BTreeMap<String, u8>>(
+                CONTENT_TYPE,
+                &body(&[("a", None, b"1"), ("b", None, b"x")]),
+            )
+            .unwrap_err();
+        assert_eq!(error.to_string(), "expected u8 at line 8, column 1");
+        let error = Multipart::new()
+            .deserialize::<BTreeMap<String, String>>(CONTENT_TYPE, &body(&[("a", None, b"x\xff")]))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::InvalidUtf8);
+        assert_eq!(error.position().unwrap().col, 2);
+
+        for (input, kind) in [
+            (&b"no boundary"[..], ErrorKind::Eof),
+            (
+                b"--B0und\r\nContent-Disposition: form-data; name=a\r\n\r\n1",
+                ErrorKind::Eof,
+            ),
+            (
+                b"--B0und\r\nContent-Disposition: form-data; name=a\r\n",
+                ErrorKind::Eof,
+            ),
+            (
+                b"--B0und x\r\n",
+                ErrorKind::Syntax {
+                    expected: "a line break after the boundary",
+                },
+            ),
+            (
+                b"--B0und\r\nContent-Disposition form-data\r\n\r\n\r\n--B0und--",
+                ErrorKind::Syntax {
+                    expected: "`:` in a part header",
+                },
+            ),
+            (
+                b"--B0und\r\nContent-Disposition: inline; name=a\r\n\r\n\r\n--B0und--",
+                ErrorKind::Syntax {
+                    expected: "`form-data` with parameters",
+                },
+            ),
+            (
+                b"--B0und\r\nContent-Type: text/plain\r\n\r\n\r\n--B0und--",
+                ErrorKind::Syntax {
+                    expected: "a Content-Disposition header with a name",
+                },
+            ),
+        ] {
+            assert_eq!(kind_of::<Value>(CONTENT_TYPE, input), kind);
+        }
+        let two = body(&[("f", Some("a"), b"x"), ("f", Some("b"), b"y")]);
+        assert_eq!(
+            kind_of::<BTreeMap<String, Vec<u8>>>(CONTENT_TYPE, &two),
+            ErrorKind::Syntax { expected: "u8" }
+        );
+    }
+}
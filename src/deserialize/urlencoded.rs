@@ -0,0 +1,863 @@
+//! `application/x-www-form-urlencoded` input: query strings and form bodies.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::num::{IntErrorKind, ParseIntError};
+use std::ops::Range;
+use std::str::FromStr;
+
+use super::{Deserialize, DeserializeSeed, Deserializer, Kind, Map, Seq, Variant};
+use crate::error::{Error, ErrorKind, Position, Result, WarningKind};
+
+/// Deserializes a struct or map from a URL query string or a urlencoded
+/// form body, `key=value` pairs joined by `&`.
+///
+/// Keys and values are percent-decoded, with `+` standing for a space, and
+/// a key without `=` has an empty value. Values carry no type of their own,
+/// so each is parsed as whatever the field asks for; read into a
+/// [`Value`](crate::Value), they are strings. An empty value reads as
+/// `None` for an `Option`.
+///
+/// A key may be given more than once. A sequence, such as a `Vec` field,
+/// takes all of its values in order, while a single value takes one of them
+/// as set by [`Urlencoded::repeat_policy`], the last by default. A key
+/// ending in `[]`, as in `tag[]=a`, is the same as the key without it.
+///
+/// Keys in brackets after a key nest a struct or map under it, so
+/// `user[name]=ada&user[tags][]=admin` gives `user` the fields `name` and
+/// `tags`. A sequence can also be read from nested keys that are indices,
+/// as in `ids[0]=4&ids[1]=2`, taking their values in order of index.
+/// Brackets are found after decoding, so `%5B` and `%5D` work as well.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use shallot::deserialize::Urlencoded;
+///
+/// let query = "q=red+shoes&size=42&tag=sale&tag=new";
+/// let tags: BTreeMap<String, Vec<String>> = Urlencoded::new().deserialize(query)?;
+/// assert_eq!(tags["tag"], ["sale", "new"]);
+/// let last: BTreeMap<String, String> = Urlencoded::new().deserialize(query)?;
+/// assert_eq!(last["q"], "red shoes");
+/// assert_eq!(last["tag"], "new");
+///
+/// let query = "user[name]=ada&user[tags][]=admin&user[tags][]=ops";
+/// let users: BTreeMap<String, BTreeMap<String, Vec<String>>> =
+///     Urlencoded::new().deserialize(query)?;
+/// assert_eq!(users["user"]["tags"], ["admin", "ops"]);
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Urlencoded {
+    repeats: RepeatPolicy,
+    max_depth: Option<usize>,
+}
+
+/// The nesting depth [`Urlencoded::max_depth`] allows unless set.
+const DEFAULT_MAX_DEPTH: usize = 32;
+
+/// Which value a single value takes from a key given more than once, as
+/// set with [`Urlencoded::repeat_policy`] or
+/// [`Multipart::repeat_policy`](super::Multipart::repeat_policy).
+///
+/// Sequences take every value whatever the policy.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RepeatPolicy {
+    /// The last value given, as most web frameworks do.
+    #[default]
+    Last,
+    /// The first value given.
+    First,
+    /// None: fail with [`ErrorKind::RepeatedKey`] at the second value.
+    Reject,
+}
+
+impl Urlencoded {
+    /// Create a urlencoded deserializer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set which value a single value takes from a key given more than
+    /// once.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// use shallot::deserialize::{RepeatPolicy, Urlencoded};
+    /// use shallot::ErrorKind;
+    ///
+    /// let first = Urlencoded::new().repeat_policy(RepeatPolicy::First);
+    /// let page: BTreeMap<String, u32> = first.deserialize("page=2&page=9")?;
+    /// assert_eq!(page["page"], 2);
+    /// let strict = Urlencoded::new().repeat_policy(RepeatPolicy::Reject);
+    /// let error = strict.deserialize::<BTreeMap<String, u32>>("page=2&page=9").unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::RepeatedKey);
+    /// let pages: BTreeMap<String, Vec<u32>> = strict.deserialize("page=2&page=9")?;
+    /// assert_eq!(pages["page"], [2, 9]);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn repeat_policy(mut self, policy: RepeatPolicy) -> Self {
+        self.repeats = policy;
+        self
+    }
+
+    /// Set how deeply keys may be nested in brackets, 32 unless set.
+    ///
+    /// Reading nested values recurses, so without a limit a key of many
+    /// brackets can overflow the stack. A key nested deeper fails with
+    /// [`ErrorKind::DepthLimit`]; a trailing `[]` does not count.
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// use shallot::deserialize::Urlencoded;
+    /// use shallot::{ErrorKind, Value};
+    ///
+    /// let urlencoded = Urlencoded::new().max_depth(1);
+    /// assert!(urlencoded.deserialize::<Value>("a[b][]=1").is_ok());
+    /// let error = urlencoded.deserialize::<Value>("a[b][c]=1").unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::DepthLimit { limit: 1 });
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    /// Read a `T` from `input`, which does not include the leading `?` of a
+    /// query string.
+    pub fn deserialize<T: Deserialize>(&self, input: &str) -> Result<T> {
+        let max_depth = self.max_depth.unwrap_or(DEFAULT_MAX_DEPTH);
+        let mut reader = Reader {
+            urlencoded: self,
+            input,
+            next: Some(Node::Map(entries(input, max_depth)?)),
+            frames: Vec::new(),
+        };
+        T::accept(&mut reader).map_err(Error::in_type::<T>)
+    }
+}
+
+/// A decoded key or value and where it starts in the input.
+#[derive(Debug)]
+struct Text<'a> {
+    text: Cow<'a, str>,
+    at: usize,
+}
+
+/// What the input gives for a key.
+#[derive(Debug)]
+enum Node<'a> {
+    /// The values given for the key, in input order.
+    Values(Vec<Text<'a>>),
+    /// The keys nested in brackets under the key.
+    Map(Entries<'a>),
+}
+
+/// Entries by key, in the order each key first appears.
+#[derive(Debug, Default)]
+struct Entries<'a> {
+    list: Vec<(Text<'a>, Node<'a>)>,
+    /// The index in `list` of each key.
+    indices: HashMap<Cow<'a, str>, usize>,
+}
+
+impl<'a> Entries<'a> {
+    /// The node for `key`, added with `new` if the key is new.
+    fn entry(&mut self, key: Text<'a>, new: fn() -> Node<'a>) -> &mut Node<'a> {
+        let index = match self.indices.get(&key.text) {
+            Some(&index) => index,
+            None => {
+                self.indices.insert(key.text.clone(), self.list.len());
+                self.list.push((key, new()));
+                self.list.len() - 1
+            }
+        };
+        &mut self.list[index].1
+    }
+}
+
+impl Node<'_> {
+    /// Where the node starts in the input.
+    fn at(&self) -> usize {
+        match self {
+            Node::Values(values) => values.first().map_or(0, |value| value.at),
+            Node::Map(entries) => entries.list.first().map_or(0, |(key, _)| key.at),
+        }
+    }
+}
+
+/// Split `input` into its pairs, grouping the values given for each key.
+fn entries(input: &str, max_depth: usize) -> Result<Entries<'_>> {
+    let mut entries = Entries::default();
+    let mut start = 0;
+    for pair in input.split('&') {
+        let at = start;
+        start += pair.len() + 1;
+        if pair.is_empty() {
+            continue;
+        }
+        let (key, value_at) = match pair.find('=') {
+            Some(equals) => (&pair[..equals], at + equals + 1),
+            None => (pair, at + pair.len()),
+        };
+        let key = decode(input, at, key)?;
+        let value = decode(input, value_at, &input[value_at..at + pair.len()])?;
+        let error = |kind| Err(Error::new(kind).at(Position::locate(input, at)));
+        let path = match path(&key.text) {
+            Ok(path) if path.len() > max_depth + 1 => {
+                return error(ErrorKind::DepthLimit { limit: max_depth })
+            }
+            Ok(path) => path,
+            Err(expected) => return error(ErrorKind::Syntax { expected }),
+        };
+        let (last, parents) = path.split_last().expect("a key has a name");
+        let mut map = &mut entries;
+        for segment in parents {
+            match map.entry(key.slice(segment.clone()), || Node::Map(Entries::default())) {
+                Node::Map(entries) => map = entries,
+                Node::Values(_) => return Err(mixed().at(Position::locate(input, at))),
+            }
+        }
+        match map.entry(key.slice(last.clone()), || Node::Values(Vec::new())) {
+            Node::Values(values) => values.push(value),
+            Node::Map(_) => return Err(mixed().at(Position::locate(input, at))),
+        }
+    }
+    Ok(entries)
+}
+
+/// The ranges of the name and each bracketed key in `key`, leaving out a
+/// trailing `[]`, or what was expected where it is malformed.
+fn path(key: &str) -> std::result::Result<Vec<Range<usize>>, &'static str> {
+    let name = key.find('[').unwrap_or(key.len());
+    if name == 0 {
+        return Err("key");
+    }
+    let mut path = Vec::new();
+    path.push(0..name);
+    let mut at = name;
+    while at < key.len() {
+        if key.as_bytes()[at] != b'[' {
+            return Err("`[` or `=`");
+        }
+        let end = at + 1 + key[at + 1..].find(']').ok_or("`]`")?;
+        if end == at + 1 {
+            return match end + 1 == key.len() {
+                true => Ok(path),
+                false => Err("`=` after `[]`"),
+            };
+        }
+        path.push(at + 1..end);
+        at = end + 1;
+    }
+    Ok(path)
+}
+
+fn mixed() -> Error {
+    Error::custom("a key cannot have both a value and nested keys")
+}
+
+impl<'a> Text<'a> {
+    /// The text in `range` of this text, starting where it does.
+    fn slice(&self, range: Range<usize>) -> Text<'a> {
+        let text = match &self.text {
+            Cow::Borrowed(text) => Cow::Borrowed(&text[range]),
+            Cow::Owned(text) => Cow::Owned(text[range].to_owned()),
+        };
+        Text { text, at: self.at }
+    }
+}
+
+/// Percent-decode `text`, which starts at `at` in `input`.
+fn decode<'a>(input: &'a str, at: usize, text: &'a str) -> Result<Text<'a>> {
+    if !text.contains(['%', '+']) {
+        return Ok(Text {
+            text: Cow::Borrowed(text),
+            at,
+        });
+    }
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.bytes().enumerate();
+    while let Some((index, byte)) = rest.next() {
+        bytes.push(match byte {
+            b'+' => b' ',
+            b'%' => {
+                let hex = text.get(index + 1..index + 3);
+                let byte = hex.and_then(|hex| match hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                    true => u8::from_str_radix(hex, 16).ok(),
+                    false => None,
+                });
+                let Some(byte) = byte else {
+                    let kind = ErrorKind::Syntax {
+                        expected: "two hex digits after `%`",
+                    };
+                    return Err(Error::new(kind).at(Position::locate(input, at + index)));
+                };
+                rest.nth(1);
+                byte
+            }
+            byte => byte,
+        });
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok(Text {
+            text: Cow::Owned(text),
+            at,
+        }),
+        Err(_) => Err(Error::new(ErrorKind::InvalidUtf8).at(Position::locate(input, at))),
+    }
+}
+
+/// A map or sequence a [`Reader`] is reading.
+enum Frame<'a> {
+    Map {
+        entries: std::vec::IntoIter<(Text<'a>, Node<'a>)>,
+        /// The value of the entry whose key was just read.
+        value: Option<Node<'a>>,
+    },
+    Seq {
+        elements: std::vec::IntoIter<Node<'a>>,
+        len: usize,
+    },
+}
+
+/// A deserializer reading the entries of the input in order.
+struct Reader<'a> {
+    urlencoded: &'a Urlencoded,
+    input: &'a str,
+    /// The node to be read next.
+    next: Option<Node<'a>>,
+    /// The maps and sequences being read, innermost last.
+    frames: Vec<Frame<'a>>,
+}
+
+impl<'a> Reader<'a> {
+    fn error_at(&self, kind: ErrorKind, at: usize) -> Error {
+        Error::new(kind).at(Position::locate(self.input, at))
+    }
+
+    /// Take the node to be read next.
+    fn take(&mut self) -> Result<Node<'a>> {
+        self.next.take().ok_or_else(|| Error::new(ErrorKind::Eof))
+    }
+
+    /// The index of the value a single value takes from `values`, by the
+    /// repeat policy.
+    fn choose(&self, values: &[Text<'a>]) -> Result<usize> {
+        match (self.urlencoded.repeats, values) {
+            (RepeatPolicy::Reject, [_, second, ..]) => {
+                Err(self.error_at(ErrorKind::RepeatedKey, second.at))
+            }
+            (RepeatPolicy::First, _) => Ok(0),
+            _ => Ok(values.len() - 1),
+        }
+    }
+
+    /// Read the next node as a single value, failing with a syntax error
+    /// naming `expected` if it is a map.
+    fn scalar(&mut self, expected: &'static str) -> Result<Text<'a>> {
+        match self.take()? {
+            Node::Values(mut values) => {
+                let index = self.choose(&values)?;
+                Ok(values.swap_remove(index))
+            }
+            node => Err(self.error_at(ErrorKind::Syntax { expected }, node.at())),
+        }
+    }
+
+    /// Make the value of the entry whose key was just read the next node.
+    fn enter_value(&mut self) -> Result<()> {
+        let Some(Frame::Map { value, .. }) = self.frames.last_mut() else {
+            return Err(Error::custom("no map is being read"));
+        };
+        self.next = value.take();
+        Ok(())
+    }
+
+    /// Read a single value and parse it with `parse`.
+    fn parse<T>(
+        &mut self,
+        expected: &'static str,
+        parse: impl FnOnce(&str) -> std::result::Result<T, ErrorKind>,
+    ) -> Result<T> {
+        let value = self.scalar(expected)?;
+        parse(&value.text).map_err(|kind| self.error_at(kind, value.at))
+    }
+
+    fn integer<T: FromStr<Err = ParseIntError>>(&mut self, expected: &'static str) -> Result<T> {
+        self.parse(expected, |text| {
+            text.parse()
+                .map_err(|error: ParseIntError| match error.kind() {
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow => {
+                        ErrorKind::Overflow { target: expected }
+                    }
+                    _ => ErrorKind::Syntax { expected },
+                })
+        })
+    }
+
+    /// Whether the next node is a single value that is empty.
+    fn next_is_empty(&self) -> Result<bool> {
+        match &self.next {
+            Some(Node::Values(values)) => Ok(values[self.choose(values)?].text.is_empty()),
+            Some(Node::Map(_)) => Ok(false),
+            None => Err(Error::new(ErrorKind::Eof)),
+        }
+    }
+}
+
+macro_rules! visit_integer {
+    ($($method:ident($ty:ident))+) => {
+        $(
+            fn $method(&mut self) -> Result<$ty> {
+                self.integer(stringify!($ty))
+            }
+        )+
+    };
+}
+
+macro_rules! visit_float {
+    ($($method:ident($ty:ident))+) => {
+        $(
+            fn $method(&mut self) -> Result<$ty> {
+                self.parse(stringify!($ty), |text| {
+                    text.parse().map_err(|_| ErrorKind::Syntax {
+                        expected: stringify!($ty),
+                    })
+                })
+            }
+        )+
+    };
+}
+
+impl Deserializer for Reader<'_> {
+    fn visit_bool(&mut self) -> Result<bool> {
+        self.parse("bool", |text| {
+            text.parse()
+                .map_err(|_| ErrorKind::Syntax { expected: "bool" })
+        })
+    }
+
+    visit_integer! {
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_i128(i128)
+        visit_isize(isize)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_u128(u128)
+        visit_usize(usize)
+    }
+
+    visit_float! {
+        visit_f32(f32)
+        visit_f64(f64)
+    }
+
+    fn visit_char(&mut self) -> Result<char> {
+        self.parse("char", |text| {
+            let mut chars = text.chars();
+            match (chars.next(), chars.next()) {
+                (Some(char), None) => Ok(char),
+                _ => Err(ErrorKind::Syntax { expected: "char" }),
+            }
+        })
+    }
+
+    fn visit_string(&mut self) -> Result<String> {
+        self.scalar("string").map(|value| value.text.into_owned())
+    }
+
+    /// Nested keys are a map, a key given more than once a sequence, and
+    /// any other key a string.
+    fn peek_kind(&mut self) -> Result<Kind> {
+        match &self.next {
+            Some(Node::Map(_)) => Ok(Kind::Map),
+            Some(Node::Values(values)) if values.len() > 1 => Ok(Kind::Seq),
+            Some(Node::Values(_)) => Ok(Kind::String),
+            None => Err(Error::new(ErrorKind::Eof)),
+        }
+    }
+
+    /// An empty value, as in `key=` or a bare `key`, is the unit value.
+    fn visit_unit(&mut self) -> Result<()> {
+        self.parse("an empty value", |text| match text {
+            "" => Ok(()),
+            _ => Err(ErrorKind::Syntax {
+                expected: "an empty value",
+            }),
+        })
+    }
+
+    fn visit_option<T: Deserialize>(&mut self) -> Result<Option<T>> {
+        if self.next_is_empty()? {
+            self.take()?;
+            return Ok(None);
+        }
+        T::accept(self).map(Some).map_err(Error::in_type::<T>)
+    }
+
+    /// Reads the values given for a key, or the keys nested under it if
+    /// each is an index, as in `a[0]=x&a[1]=y`, in order of index.
+    fn begin_seq(&mut self, len: Option<usize>) -> Result<Seq> {
+        let node = self.take()?;
+        let at = node.at();
+        let elements: Vec<Node> = match node {
+            Node::Values(values) => {
+                let elements = values.into_iter();
+                elements.map(|value| Node::Values(vec![value])).collect()
+            }
+            Node::Map(entries) => {
+                let mut indexed = Vec::with_capacity(entries.list.len());
+                for (key, node) in entries.list {
+                    let Ok(index) = key.text.parse::<usize>() else {
+                        let expected = "a sequence";
+                        return Err(self.error_at(ErrorKind::Syntax { expected }, key.at));
+                    };
+                    indexed.push((index, node));
+                }
+                indexed.sort_by_key(|(index, _)| *index);
+                indexed.into_iter().map(|(_, node)| node).collect()
+            }
+        };
+        self.frames.push(Frame::Seq {
+            len: elements.len(),
+            elements: elements.into_iter(),
+        });
+        Ok(Seq::new(len, at))
+    }
+
+    fn next_element(&mut self, seq: &mut Seq) -> Result<bool> {
+        let Some(Frame::Seq { elements, len }) = self.frames.last_mut() else {
+            return Err(Error::custom("no sequence is being read"));
+        };
+        if seq.len == Some(seq.index) {
+            return Ok(false);
+        }
+        let Some(element) = elements.next() else {
+            return match seq.len {
+                Some(expected) => Err(Error::new(ErrorKind::Length {
+                    expected,
+                    found: *len,
+                })),
+                None => Ok(false),
+            };
+        };
+        seq.index += 1;
+        seq.element = element.at();
+        self.next = Some(element);
+        Ok(true)
+    }
+
+    fn end_seq(&mut self, seq: Seq) -> Result<()> {
+        let Some(Frame::Seq { elements, len }) = self.frames.pop() else {
+            return Err(Error::custom("no sequence is being read"));
+        };
+        match (seq.len, elements.len()) {
+            (Some(expected), remaining) if remaining > 0 => Err(Error::new(ErrorKind::Length {
+                expected,
+                found: len,
+            })),
+            _ => Ok(()),
+        }
+    }
+
+    fn begin_map(&mut self) -> Result<Map> {
+        match self.take()? {
+            Node::Map(entries) => {
+                let at = entries.list.first().map_or(0, |(key, _)| key.at);
+                self.frames.push(Frame::Map {
+                    entries: entries.list.into_iter(),
+                    value: None,
+                });
+                Ok(Map::new(at))
+            }
+            node => Err(self.error_at(
+                ErrorKind::Syntax {
+                    expected: "a struct or map",
+                },
+                node.at(),
+            )),
+        }
+    }
+
+    fn next_key_seed<S: DeserializeSeed>(
+        &mut self,
+        map: &mut Map,
+        seed: S,
+    ) -> Result<Option<S::Value>> {
+        let Some(Frame::Map { entries, value }) = self.frames.last_mut() else {
+            return Err(Error::custom("no map is being read"));
+        };
+        let Some((key, node)) = entries.next() else {
+            return Ok(None);
+        };
+        *value = Some(node);
+        map.index += 1;
+        self.next = Some(Node::Values(vec![key]));
+        seed.accept(self)
+            .map(Some)
+            .map_err(Error::in_type::<S::Value>)
+    }
+
+    fn next_value_seed<S: DeserializeSeed>(&mut self, map: &mut Map, seed: S) -> Result<S::Value> {
+        let _ = map;
+        self.enter_value()?;
+        seed.accept(self).map_err(Error::in_type::<S::Value>)
+    }
+
+    fn end_map(&mut self, map: Map) -> Result<()> {
+        let _ = map;
+        match self.frames.pop() {
+            Some(Frame::Map { .. }) => Ok(()),
+            _ => Err(Error::custom("no map is being read")),
+        }
+    }
+
+    fn next_field(
+        &mut self,
+        map: &mut Map,
+        fields: &'static [&'static str],
+    ) -> Result<Option<usize>> {
+        while let Some(key) = self.next_key::<String>(map)? {
+            if let Some(index) = fields.iter().position(|field| *field == key) {
+                self.enter_value()?;
+                return Ok(Some(index));
+            }
+            self.warn(WarningKind::UnknownField { field: key });
+        }
+        Ok(None)
+    }
+
+    /// Only unit variants, written as their names, can be read.
+    fn begin_variant(&mut self, variants: &'static [Variant]) -> Result<usize> {
+        let value = self.scalar("string")?;
+        let Some(index) = variants
+            .iter()
+            .position(|variant| variant.name == value.text)
+        else {
+            let variant = value.text.into_owned();
+            return Err(self.error_at(ErrorKind::UnknownVariant { variant }, value.at));
+        };
+        match variants[index].unit {
+            true => Ok(index),
+            false => Err(self.error_at(
+                ErrorKind::Syntax {
+                    expected: "a unit variant",
+                },
+                value.at,
+            )),
+        }
+    }
+
+    fn end_variant(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::Value;
+
+    fn kind_of<T: Deserialize>(input: &str) -> ErrorKind {
+        Urlencoded::new()
+            .deserialize::<T>(input)
+            .err()
+            .expect("an error")
+            .kind()
+            .clone()
+    }
+
+    #[test]
+    fn pairs_and_values() {
+        let urlencoded = Urlencoded::new();
+        let map: BTreeMap<String, String> = urlencoded
+            .deserialize("a=1&b=x+y&c=%C3%A9%2B&d&e=&&f=a=b&%41%62=c")
+            .unwrap();
+        let expected = [
+            ("Ab", "c"),
+            ("a", "1"),
+            ("b", "x y"),
+            ("c", "é+"),
+            ("d", ""),
+            ("e", ""),
+            ("f", "a=b"),
+        ];
+        let expected = expected.map(|(key, value)| (key.into(), value.into()));
+        assert_eq!(map, BTreeMap::from(expected));
+
+        let map: BTreeMap<String, Option<u8>> = urlencoded.deserialize("a=&b=2").unwrap();
+        assert_eq!(
+            map,
+            BTreeMap::from([("a".into(), None), ("b".into(), Some(2))])
+        );
+        let map: BTreeMap<u8, bool> = urlencoded.deserialize("1=true&2=false").unwrap();
+        assert_eq!(map, BTreeMap::from([(1, true), (2, false)]));
+        let map: BTreeMap<String, String> = urlencoded.deserialize("").unwrap();
+        assert!(map.is_empty());
+
+        let value: Value = urlencoded.deserialize("a=1&b=x&a=2").unwrap();
+        let expected = BTreeMap::from([
+            ("a".into(), Value::Array(vec!["1".into(), "2".into()])),
+            ("b".into(), Value::from("x")),
+        ]);
+        assert_eq!(value, Value::Object(expected));
+    }
+
+    #[test]
+    fn repeated_keys() {
+        let input = "a=1&b=5&a=2&a=3";
+        let urlencoded = Urlencoded::new();
+        let last: BTreeMap<String, u8> = urlencoded.deserialize(input).unwrap();
+        assert_eq!(last["a"], 3);
+        let all: BTreeMap<String, Vec<u8>> = urlencoded.deserialize(input).unwrap();
+        assert_eq!(all["a"], [1, 2, 3]);
+        assert_eq!(all["b"], [5]);
+        let (first, _): (u8, u8) = urlencoded
+            .deserialize::<BTreeMap<String, (u8, u8)>>("a=1&a=2")
+            .unwrap()["a"];
+        assert_eq!(first, 1);
+
+        let first = urlencoded.clone().repeat_policy(RepeatPolicy::First);
+        let map: BTreeMap<String, u8> = first.deserialize(input).unwrap();
+        assert_eq!(map["a"], 1);
+
+        let reject = urlencoded.clone().repeat_policy(RepeatPolicy::Reject);
+        let error = reject
+            .deserialize::<BTreeMap<String, u8>>(input)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::RepeatedKey);
+        assert_eq!(error.position().unwrap().offset, 10);
+        let all: BTreeMap<String, Vec<u8>> = reject.deserialize(input).unwrap();
+        assert_eq!(all["a"], [1, 2, 3]);
+    }
+
+    #[test]
+    fn nested_keys() {
+        let urlencoded = Urlencoded::new();
+        let input = "user[name]=ada&user[tags][]=a&id=7&user[tags][]=b&user%5Bage%5D=36&tags[]=x";
+        let value: Value = urlencoded.deserialize(input).unwrap();
+        let user = BTreeMap::from([
+            ("name".into(), Value::from("ada")),
+            ("tags".into(), Value::Array(vec!["a".into(), "b".into()])),
+            ("age".into(), Value::from("36")),
+        ]);
+        let expected = BTreeMap::from([
+            ("user".into(), Value::Object(user)),
+            ("id".into(), Value::from("7")),
+            ("tags".into(), Value::from("x")),
+        ]);
+        assert_eq!(value, Value::Object(expected));
+
+        type Filters = BTreeMap<String, BTreeMap<String, BTreeMap<String, u32>>>;
+        let filters: Filters = urlencoded
+            .deserialize("f[price][min]=5&f[price][max]=9&f[size][max]=2")
+            .unwrap();
+        assert_eq!(filters["f"]["price"]["max"], 9);
+        assert_eq!(filters["f"]["size"].len(), 1);
+
+        let ids: BTreeMap<String, Vec<u8>> = urlencoded
+            .deserialize("ids[2]=30&ids[0]=10&ids[10]=40&ids[1]=20")
+            .unwrap();
+        assert_eq!(ids["ids"], [10, 20, 30, 40]);
+        type Rows = BTreeMap<String, Vec<BTreeMap<String, String>>>;
+        let rows: Rows = urlencoded
+            .deserialize("rows[0][a]=x&rows[1][a]=y&rows[0][b]=z")
+            .unwrap();
+        assert_eq!(rows["rows"][0]["b"], "z");
+        assert_eq!(rows["rows"][1]["a"], "y");
+    }
+
+    #[test]
+    fn errors() {
+        let urlencoded = Urlencoded::new();
+        let error = urlencoded
+            .deserialize::<BTreeMap<String, u8>>("a=1&b=300")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "number out of range for u8 at line 1, column 7"
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, u8>>("a"),
+            ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>("=b"),
+            ErrorKind::Syntax { expected: "key" }
+        );
+        let error = urlencoded
+            .deserialize::<BTreeMap<String, String>>("a=%4")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected two hex digits after `%` at line 1, column 3"
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>("a=%zz"),
+            ErrorKind::Syntax {
+                expected: "two hex digits after `%`"
+            }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, String>>("a=%FF"),
+            ErrorKind::InvalidUtf8
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, (u8, u8)>>("a=1&a=2&a=3"),
+            ErrorKind::Length {
+                expected: 2,
+                found: 3
+            }
+        );
+        assert_eq!(
+            kind_of::<BTreeMap<String, (u8, u8)>>("a=1"),
+            ErrorKind::Length {
+                expected: 2,
+                found: 1
+            }
+        );
+        for (input, expected) in [
+            ("[a]=1", "key"),
+            ("a[b=1", "`]`"),
+            ("a[b]c=1", "`[` or `=`"),
+            ("a[][b]=1", "`=` after `[]`"),
+        ] {
+            let error = urlencoded
+                .deserialize::<Value>(&format!("x=0&{input}"))
+                .unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::Syntax { expected }, "{input}");
+            assert_eq!(error.position().unwrap().offset, 4);
+        }
+        let error = urlencoded.deserialize::<Value>("a=1&a[b]=2").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "a key cannot have both a value and nested keys at line 1, column 5"
+        );
+        assert!(urlencoded.deserialize::<Value>("a[b]=1&a=2").is_err());
+        let deep = format!("a{}=1", "[b]".repeat(33));
+        assert_eq!(kind_of::<Value>(&deep), ErrorKind::DepthLimit { limit: 32 });
+        assert_eq!(
+            kind_of::<BTreeMap<String, Vec<u8>>>("a[0]=1&a[x]=2"),
+            ErrorKind::Syntax {
+                expected: "a sequence"
+            }
+        );
+        assert_eq!(kind_of::<u8>("a=1"), ErrorKind::Syntax { expected: "u8" });
+        assert_eq!(
+            kind_of::<BTreeMap<String, BTreeMap<String, u8>>>("a=1"),
+            ErrorKind::Syntax {
+                expected: "a struct or map"
+            }
+        );
+    }
+}
@@ -0,0 +1,84 @@
+//! Guessing the format of serialized input.
+
+/// A format recognized by [`detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum FormatKind {
+    /// JSON, read with [`deserialize::Json`](crate::deserialize::Json).
+    Json,
+    /// Input that matches no supported format, including empty input.
+    Unknown,
+}
+
+/// Guess the format of `input` from its first bytes, so loaders that accept
+/// several formats can pick a deserializer without trying each in turn.
+///
+/// Input is taken as JSON if, after an optional byte order mark and
+/// whitespace, it starts like a JSON value or a comment; binary formats added
+/// later are to be told apart by their magic bytes. Only the start of the
+/// input is looked at, so a match does not mean the rest parses.
+///
+/// ```
+/// use shallot::{detect, FormatKind};
+///
+/// assert_eq!(detect(b"  {\"id\": 7}"), FormatKind::Json);
+/// assert_eq!(detect(b"\x89PNG\r\n"), FormatKind::Unknown);
+/// ```
+pub fn detect(input: &[u8]) -> FormatKind {
+    let input = input.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(input);
+    let start = input
+        .iter()
+        .position(|byte| !matches!(byte, b' ' | b'\t' | b'\n' | b'\r'));
+    let input = &input[start.unwrap_or(input.len())..];
+    match input.first() {
+        Some(b'{' | b'[' | b'"' | b'-' | b'0'..=b'9' | b'/') => FormatKind::Json,
+        Some(b't' | b'f' | b'n') if starts_with_literal(input) => FormatKind::Json,
+        _ => FormatKind::Unknown,
+    }
+}
+
+/// Whether `input` starts with a whole `true`, `false` or `null`, so that
+/// text such as logfmt's `name=x` is not taken for JSON.
+fn starts_with_literal(input: &[u8]) -> bool {
+    [&b"true"[..], b"false", b"null"].iter().any(|literal| {
+        input.strip_prefix(*literal).is_some_and(|rest| {
+            matches!(
+                rest.first(),
+                None | Some(b' ' | b'\t' | b'\n' | b'\r' | b',' | b']' | b'}' | b'/')
+            )
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        for input in [
+            &b"{}"[..],
+            b"\xEF\xBB\xBF[1]",
+            b"\r\n\t \"text\"",
+            b"-1.5",
+            b"null",
+            b"true\n",
+            b"false // comment",
+            b"// comment\n{}",
+        ] {
+            assert_eq!(detect(input), FormatKind::Json, "{input:?}");
+        }
+        for input in [&b""[..], b"   ", b"\x00\x01", b"<xml/>", b"\xEF\xBB"] {
+            assert_eq!(detect(input), FormatKind::Unknown, "{input:?}");
+        }
+        // logfmt keys can start like a JSON literal.
+        for input in [
+            &b"name=x level=info"[..],
+            b"trace=1",
+            b"fail",
+            b"nullable=true",
+        ] {
+            assert_eq!(detect(input), FormatKind::Unknown, "{input:?}");
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Impls for types of other crates, each behind a feature named after the
+//! crate; the `ecosystem` feature enables all of them.
+//!
+//! URLs, versions, and UTF-8 paths are written as strings and parsed back
+//! with the crate's own parser. `Either` is written like an enum with
+//! `Left` and `Right` variants.
+
+#[cfg(any(feature = "either", feature = "semver", feature = "url"))]
+use crate::error::Error;
+use crate::error::Result;
+use crate::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Read a string and parse it with `T`'s `FromStr` impl.
+#[cfg(any(feature = "semver", feature = "url"))]
+fn parse<T, D>(deserializer: &mut D) -> Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+    D: Deserializer,
+{
+    let text = deserializer.visit_string()?;
+    text.parse().map_err(Error::custom)
+}
+
+#[cfg(feature = "url")]
+impl Serialize for url::Url {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "url")]
+impl Deserialize for url::Url {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        parse(deserializer)
+    }
+}
+
+#[cfg(feature = "semver")]
+impl Serialize for semver::Version {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "semver")]
+impl Deserialize for semver::Version {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        parse(deserializer)
+    }
+}
+
+#[cfg(feature = "semver")]
+impl Serialize for semver::VersionReq {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(&self.to_string())
+    }
+}
+
+#[cfg(feature = "semver")]
+impl Deserialize for semver::VersionReq {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        parse(deserializer)
+    }
+}
+
+#[cfg(feature = "camino")]
+impl Serialize for camino::Utf8Path {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "camino")]
+impl Serialize for camino::Utf8PathBuf {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "camino")]
+impl Deserialize for camino::Utf8PathBuf {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_string().map(Self::from)
+    }
+}
+
+#[cfg(feature = "either")]
+impl<L: Serialize, R: Serialize> Serialize for either::Either<L, R> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        match self {
+            Self::Left(value) => serializer.visit_variant("Left", serializer.serialize(value)?),
+            Self::Right(value) => serializer.visit_variant("Right", serializer.serialize(value)?),
+        }
+    }
+}
+
+#[cfg(feature = "either")]
+impl<L: Deserialize, R: Deserialize> Deserialize for either::Either<L, R> {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        use crate::deserialize::Variant;
+
+        const VARIANTS: &[Variant] = &[
+            Variant {
+                name: "Left",
+                unit: false,
+            },
+            Variant {
+                name: "Right",
+                unit: false,
+            },
+        ];
+        let value = match deserializer.begin_variant(VARIANTS)? {
+            0 => Self::Left(L::accept(deserializer).map_err(Error::in_type::<L>)?),
+            _ => Self::Right(R::accept(deserializer).map_err(Error::in_type::<R>)?),
+        };
+        deserializer.end_variant()?;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{deserialize, serialize};
+
+    #[cfg(feature = "url")]
+    #[test]
+    fn urls() {
+        let url = url::Url::parse("https://example.com/a?b=c").unwrap();
+        let text = serialize::Json::new().serialize(&url).unwrap();
+        assert_eq!(text, r#""https://example.com/a?b=c""#);
+        let read: url::Url = deserialize::Json::new().deserialize(&text).unwrap();
+        assert_eq!(read, url);
+        let error = deserialize::Json::new()
+            .deserialize::<url::Url>(r#""no scheme""#)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "relative URL without a base for Url");
+    }
+
+    #[cfg(feature = "semver")]
+    #[test]
+    fn versions() {
+        let version = semver::Version::parse("1.2.3-rc.1").unwrap();
+        let text = serialize::Json::new().serialize(&version).unwrap();
+        assert_eq!(text, r#""1.2.3-rc.1""#);
+        let read: semver::Version = deserialize::Json::new().deserialize(&text).unwrap();
+        assert_eq!(read, version);
+        let requirement: semver::VersionReq =
+            deserialize::Json::new().deserialize(r#""^1.2""#).unwrap();
+        assert!(requirement.matches(&semver::Version::new(1, 4, 0)));
+        assert!(deserialize::Json::new()
+            .deserialize::<semver::Version>(r#""1.2""#)
+            .is_err());
+    }
+
+    #[cfg(feature = "camino")]
+    #[test]
+    fn utf8_paths() {
+        let path = camino::Utf8PathBuf::from("src/lib.rs");
+        let text = serialize::Json::new().serialize(path.as_path()).unwrap();
+        assert_eq!(text, r#""src/lib.rs""#);
+        let read: camino::Utf8PathBuf = deserialize::Json::new().deserialize(&text).unwrap();
+        assert_eq!(read, path);
+    }
+
+    #[cfg(feature = "either")]
+    #[test]
+    fn eithers() {
+        use either::Either;
+
+        let values: Vec<Either<u8, String>> = vec![Either::Left(1), Either::Right("a".into())];
+        let text = serialize::Json::new().serialize(&values).unwrap();
+        assert_eq!(text, r#"[{"Left": 1}, {"Right": "a"}]"#);
+        let read: Vec<Either<u8, String>> = deserialize::Json::new().deserialize(&text).unwrap();
+        assert_eq!(read, values);
+        let error = deserialize::Json::new()
+            .deserialize::<Either<u8, String>>(r#"{"Up": 1}"#)
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &crate::ErrorKind::UnknownVariant {
+                variant: "Up".into()
+            }
+        );
+    }
+}
@@ -0,0 +1,393 @@
+//! Errors produced while deserializing.
+
+use std::fmt;
+
+/// Shorthand for results carrying a shallot [`Error`].
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// A location within textual input.
+///
+/// `row` and `col` are 1-based and count characters; `offset` is the 0-based
+/// byte offset into the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub offset: usize,
+    pub row: usize,
+    pub col: usize,
+}
+
+impl Position {
+    /// Compute the position of the byte `offset` within `input`.
+    pub fn locate(input: &str, offset: usize) -> Self {
+        #[cfg(test)]
+        crate::scanned::add(offset);
+        let before = &input[..offset];
+        let row = before.matches('\n').count() + 1;
+        let line = before
+            .rfind('\n')
+            .map_or(before, |index| &before[index + 1..]);
+        Self {
+            offset,
+            row,
+            col: line.chars().count() + 1,
+        }
+    }
+
+    /// The position just past `text`, which starts at this position.
+    pub(crate) fn after(self, text: &str) -> Self {
+        #[cfg(test)]
+        crate::scanned::add(text.len());
+        match text.rfind('\n') {
+            Some(newline) => Self {
+                offset: self.offset + text.len(),
+                row: self.row + text.matches('\n').count(),
+                col: text[newline + 1..].chars().count() + 1,
+            },
+            None => Self {
+                offset: self.offset + text.len(),
+                row: self.row,
+                col: self.col + text.chars().count(),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.row, self.col)
+    }
+}
+
+/// The reason an operation failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// Input ended before a complete value was read.
+    Eof,
+    /// The input did not contain the expected kind of value.
+    Syntax { expected: &'static str },
+    /// A number was well formed but does not fit in the target type.
+    Overflow { target: &'static str },
+    /// A complete value was read but input remains.
+    TrailingCharacters,
+    /// A string contained U+0000 where it is not allowed.
+    Nul,
+    /// A string contained a control character between U+0001 and U+001F
+    /// that was not escaped.
+    ControlCharacter,
+    /// Byte input that is not valid UTF-8, positioned at the first byte
+    /// that is not.
+    InvalidUtf8,
+    /// A `\u` escape of a UTF-16 surrogate that is not part of a pair.
+    UnpairedSurrogate { unit: u16 },
+    /// A NaN or infinite float that the format cannot represent.
+    NonFinite { value: &'static str },
+    /// A map key that the format cannot write as an object key; `found`
+    /// names what it serialized to.
+    UnsupportedKey { found: &'static str },
+    /// A key given more than once where only one value is allowed,
+    /// positioned at its second value.
+    RepeatedKey,
+    /// Arrays and objects were nested deeper than the configured limit.
+    DepthLimit { limit: usize },
+    /// The input, a string, or an array or object was larger than the
+    /// configured limit; `what` names which, and strings and the input are
+    /// measured in bytes.
+    SizeLimit { what: &'static str, limit: usize },
+    /// Serializing wrote more bytes than the configured limit.
+    OutputLimit { limit: usize },
+    /// A value shared through `Rc` or `Arc` was reached again while it was
+    /// being written, so it contains itself.
+    Cycle,
+    /// A fixed-size sequence had the wrong number of elements.
+    Length { expected: usize, found: usize },
+    /// Elements of a sequence failed, each with its index.
+    Elements { errors: Vec<(usize, Error)> },
+    /// A struct was missing a field that has no default.
+    MissingField { field: &'static str },
+    /// An enum variant name that the type does not have.
+    UnknownVariant { variant: String },
+    /// A JSON pointer that does not lead to a value.
+    NoSuchPath { path: String },
+    /// A JSON Patch `test` operation found a different value.
+    TestFailed { path: String },
+    /// Reading or writing the underlying input or output failed.
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+    /// A free-form message, typically from a hand-written implementation.
+    Custom(String),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Eof => f.write_str("unexpected end of input"),
+            Self::Syntax { expected } => write!(f, "expected {expected}"),
+            Self::Overflow { target } => write!(f, "number out of range for {target}"),
+            Self::TrailingCharacters => f.write_str("trailing characters"),
+            Self::Nul => f.write_str("NUL character in string"),
+            Self::ControlCharacter => f.write_str("unescaped control character in string"),
+            Self::InvalidUtf8 => f.write_str("invalid UTF-8"),
+            Self::UnpairedSurrogate { unit } => write!(f, "unpaired surrogate \\u{unit:04X}"),
+            Self::NonFinite { value } => write!(f, "{value} cannot be written as a number"),
+            Self::UnsupportedKey { found } => {
+                write!(f, "map keys must be strings, numbers or bools, not {found}")
+            }
+            Self::RepeatedKey => f.write_str("key given more than once"),
+            Self::DepthLimit { limit } => {
+                write!(f, "nesting deeper than the limit of {limit}")
+            }
+            Self::SizeLimit { what, limit } => write!(f, "{what} larger than the limit of {limit}"),
+            Self::OutputLimit { limit } => {
+                write!(f, "output larger than the limit of {limit} bytes")
+            }
+            Self::Cycle => f.write_str("a shared value contains itself"),
+            Self::Length { expected, found } => {
+                write!(f, "expected {expected} elements, found {found}")
+            }
+            Self::Elements { errors } => {
+                write!(f, "{} elements failed", errors.len())?;
+                for (index, (element, error)) in errors.iter().enumerate() {
+                    let separator = if index == 0 { ": " } else { "; " };
+                    write!(f, "{separator}element {element}: {error}")?;
+                }
+                Ok(())
+            }
+            Self::MissingField { field } => write!(f, "missing field `{field}`"),
+            Self::UnknownVariant { variant } => write!(f, "unknown variant `{variant}`"),
+            Self::NoSuchPath { path } => write!(f, "no value at `{path}`"),
+            Self::TestFailed { path } => write!(f, "test failed at `{path}`"),
+            Self::Io { message, .. } => write!(f, "I/O error: {message}"),
+            Self::Custom(message) => f.write_str(message),
+        }
+    }
+}
+
+/// An error with an optional location in the input.
+///
+/// Besides the position, an error can name the Rust type (and field of that
+/// type) that was being read when it occurred. Only the innermost type is
+/// kept, since that is where the input needs fixing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    inner: Box<Inner>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Inner {
+    kind: ErrorKind,
+    position: Option<Position>,
+    type_name: Option<&'static str>,
+    field: Option<&'static str>,
+}
+
+impl Error {
+    /// Create an error without a position.
+    pub fn new(kind: ErrorKind) -> Self {
+        let inner = Inner {
+            kind,
+            position: None,
+            type_name: None,
+            field: None,
+        };
+        Self {
+            inner: Box::new(inner),
+        }
+    }
+
+    /// Create an error carrying a free-form message.
+    pub fn custom(message: impl fmt::Display) -> Self {
+        Self::new(ErrorKind::Custom(message.to_string()))
+    }
+
+    /// Attach a position, replacing any existing one.
+    pub fn at(mut self, position: Position) -> Self {
+        self.inner.position = Some(position);
+        self
+    }
+
+    /// Name `T` as the type being read, unless a type is already named.
+    ///
+    /// Standard library types are skipped because the error kind already
+    /// describes them, leaving the hint for user-defined types.
+    pub fn in_type<T: ?Sized>(self) -> Self {
+        let type_name = std::any::type_name::<T>();
+        if is_std(type_name) {
+            return self;
+        }
+        self.in_field(type_name, None)
+    }
+
+    /// Name `field` of `type_name` as the location being read, unless a type
+    /// is already named.
+    pub fn in_field(mut self, type_name: &'static str, field: Option<&'static str>) -> Self {
+        if self.inner.type_name.is_none() {
+            self.inner.type_name = Some(type_name);
+            self.inner.field = field;
+        }
+        self
+    }
+
+    /// The reason for the error.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.inner.kind
+    }
+
+    /// Where in the input the error occurred, if known.
+    pub fn position(&self) -> Option<Position> {
+        self.inner.position
+    }
+
+    /// The Rust type being read when the error occurred, if known.
+    pub fn type_name(&self) -> Option<&'static str> {
+        self.inner.type_name
+    }
+
+    /// The field of [`Error::type_name`] being read, if known.
+    pub fn field(&self) -> Option<&'static str> {
+        self.inner.field
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let Inner {
+            kind,
+            position,
+            type_name,
+            field,
+        } = &*self.inner;
+        kind.fmt(f)?;
+        match (field, type_name) {
+            (Some(field), Some(type_name)) => {
+                write!(f, " for field `{field}` of {}", short_type_name(type_name))?
+            }
+            (None, Some(type_name)) => write!(f, " for {}", short_type_name(type_name))?,
+            _ => {}
+        }
+        match position {
+            Some(position) => write!(f, " at {position}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The reason for a [`Warning`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A number was rounded to the nearest value of the target float type.
+    LossyFloat { target: &'static str },
+    /// An integer was saturated to the range of the target type.
+    Clamped { target: &'static str },
+    /// A struct field that the type does not have was skipped.
+    UnknownField { field: String },
+    /// A free-form message, typically from a hand-written implementation.
+    Custom(String),
+}
+
+impl fmt::Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LossyFloat { target } => {
+                write!(f, "number cannot be represented exactly as {target}")
+            }
+            Self::Clamped { target } => write!(f, "number clamped to the range of {target}"),
+            Self::UnknownField { field } => write!(f, "unknown field `{field}` ignored"),
+            Self::Custom(message) => f.write_str(message),
+        }
+    }
+}
+
+/// A non-fatal finding made while deserializing.
+///
+/// Warnings never stop deserialization; they are collected so callers can
+/// surface them, for example with
+/// [`deserialize::Json::deserialize_with_warnings`](crate::deserialize::Json::deserialize_with_warnings).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub position: Option<Position>,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.position {
+            Some(position) => write!(f, "{} at {position}", self.kind),
+            None => self.kind.fmt(f),
+        }
+    }
+}
+
+/// Whether `type_name` is a primitive or from the standard library.
+fn is_std(type_name: &str) -> bool {
+    let path = type_name.split('<').next().unwrap_or_default();
+    !path.contains("::")
+        || ["alloc::", "core::", "std::"]
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+}
+
+/// Strip module paths from every path in `type_name`.
+fn short_type_name(type_name: &str) -> String {
+    let mut output = String::with_capacity(type_name.len());
+    let mut segment = String::new();
+    for char in type_name.chars() {
+        if char.is_alphanumeric() || char == '_' || char == ':' {
+            segment.push(char);
+        } else {
+            output.push_str(segment.rsplit("::").next().unwrap_or_default());
+            segment.clear();
+            output.push(char);
+        }
+    }
+    output.push_str(segment.rsplit("::").next().unwrap_or_default());
+    output
+}
+
+impl std::error::Error for Error {}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::new(ErrorKind::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_counts_rows_and_chars() {
+        let position = Position::locate("[1,\n  \"é\", x]", 12);
+        assert_eq!(position.row, 2);
+        assert_eq!(position.col, 8);
+        assert_eq!(position.offset, 12);
+    }
+
+    #[test]
+    fn type_hints() {
+        struct Port;
+        let error = Error::new(ErrorKind::Syntax { expected: "u16" });
+        assert_eq!(error.clone().in_type::<Vec<u16>>().type_name(), None);
+        let error = error.in_type::<Port>().in_type::<Option<Port>>();
+        assert_eq!(error.to_string(), "expected u16 for Port");
+        let error = Error::new(ErrorKind::Eof).in_field("config::Config", Some("port"));
+        assert_eq!(
+            error.to_string(),
+            "unexpected end of input for field `port` of Config"
+        );
+        assert_eq!(short_type_name("a::B<c::D, [e::F; 2]>"), "B<D, [F; 2]>");
+    }
+
+    #[test]
+    fn display_includes_position() {
+        let error = Error::new(ErrorKind::Syntax { expected: "u8" }).at(Position::locate("x", 0));
+        assert_eq!(error.to_string(), "expected u8 at line 1, column 1");
+    }
+}
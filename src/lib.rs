@@ -1,14 +1,953 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
-}
+//! Shallot is a small, format-agnostic serialization framework.
+//!
+//! Types implement [`Serialize`] and [`Deserialize`] once and can then be
+//! written and read by any [`Serializer`] or [`Deserializer`], such as the
+//! JSON implementations in [`serialize::Json`] and [`deserialize::Json`].
+//!
+//! ```
+//! use shallot::{deserialize, serialize, Serializer};
+//!
+//! let text = serialize::Json::new().serialize(&(1u8, vec![true, false]))?;
+//! assert_eq!(text, "[1, [true, false]]");
+//!
+//! let value: (u8, Vec<bool>) = deserialize::Json::new().deserialize(&text)?;
+//! assert_eq!(value, (1, vec![true, false]));
+//! # Ok::<(), shallot::Error>(())
+//! ```
+//!
+//! With the `derive` feature, `#[derive(Serialize, Deserialize)]` implements
+//! both traits for structs with named fields, unit structs, tuple structs,
+//! and enums. Structs become objects keyed by field name and tuple structs
+//! arrays of their fields, while a newtype struct such as
+//! `struct Meters(f64)` is written as the value it wraps, as is any
+//! single-field struct marked `#[shallot(transparent)]`. Unit variants become
+//! their names as strings and other variants an object with the name as its
+//! only key, as in `{"Circle": {"radius": 1.0}}`; an enum of unit variants
+//! marked `#[shallot(discriminant)]` is written as its integer discriminants
+//! instead, in the type given by its `#[repr]`.
+//!
+//! A field or variant marked `#[shallot(rename = "...")]` is written under
+//! that name instead of its own, and a field marked
+//! `#[shallot(alias = "...")]` is also read under that name.
+//! `#[shallot(rename_all = "camelCase")]` on a struct or enum respells the
+//! names of all its fields or variants, and also accepts `"snake_case"`,
+//! `"kebab-case"`, and `"SCREAMING_SNAKE_CASE"`. A field marked
+//! `#[shallot(view = "...")]` belongs to that view only; see
+//! [`serialize::Json::field_filter`]. Fields are written in the order they
+//! are declared, or sorted by name with
+//! `#[shallot(field_order = "alphabetical")]` on the struct or enum;
+//! [`serialize::Json::field_order`] overrides either for every type.
+//!
+//! `#[derive(Metadata)]` describes the fields of a struct for tooling; see
+//! [`metadata`].
+//!
+//! No two fields, or variants, may end up with the same name once renamed,
+//! nor may an alias repeat the name or alias of another field, since input
+//! could not tell them apart. Either fails to compile:
+//!
+//! ```compile_fail
+//! #[derive(shallot::Deserialize)]
+//! #[shallot(rename_all = "camelCase")]
+//! struct Request {
+//!     user_id: u64,
+//!     #[shallot(rename = "userId")]
+//!     legacy_id: u64,
+//! }
+//! ```
+//!
+//! ```compile_fail
+//! #[derive(shallot::Deserialize)]
+//! struct Contact {
+//!     email: String,
+//!     #[shallot(alias = "email")]
+//!     backup_email: String,
+//! }
+//! ```
+//!
+//! Fields of types without impls of their own can name the functions used
+//! in their place. `#[shallot(serialize_with = "path")]` names a function
+//! with the signature of [`Serialize::accept`] taking the field as its first
+//! argument, `#[shallot(deserialize_with = "path")]` one with the signature
+//! of [`Deserialize::accept`], and `#[shallot(with = "module")]` both of
+//! `module::serialize` and `module::deserialize`.
+//!
+//! ```
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use shallot::{deserialize, serialize, Deserialize, Serialize, Serializer};
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! enum Role {
+//!     Reader,
+//!     Admin,
+//! }
+//!
+//! #[derive(Debug, PartialEq, Serialize, Deserialize)]
+//! struct User {
+//!     name: String,
+//!     role: Role,
+//!     #[shallot(view = "admin")]
+//!     email: Option<String>,
+//! }
+//!
+//! let user = User { name: "ada".into(), role: Role::Admin, email: None };
+//! let text = serialize::Json::new().serialize(&user)?;
+//! assert_eq!(text, r#"{"name": "ada", "role": "Admin", "email": null}"#);
+//!
+//! let read: User = deserialize::Json::new().deserialize(r#"{"role": "Admin", "name": "ada"}"#)?;
+//! assert_eq!(read, user);
+//! # }
+//! # Ok::<(), shallot::Error>(())
+//! ```
+
+// Lets the derive macros' `::shallot` paths resolve inside this crate.
+extern crate self as shallot;
 
 #[cfg(test)]
+mod allocations;
+pub mod bytes;
+#[cfg(feature = "charset")]
+pub mod charset;
+pub mod deserialize;
+mod detect;
+#[cfg(any(
+    feature = "camino",
+    feature = "either",
+    feature = "semver",
+    feature = "url"
+))]
+mod ecosystem;
+mod error;
+pub mod metadata;
+mod os_str;
+pub mod pool;
+pub mod round_trip;
+#[cfg(test)]
+mod scanned;
+#[cfg(feature = "serde")]
+pub mod serde;
+pub mod serialize;
+mod transcode;
+pub mod value;
+
+pub use deserialize::{Deserialize, Deserializer};
+pub use detect::{detect, FormatKind};
+pub use error::{Error, ErrorKind, Position, Result, Warning, WarningKind};
+pub use serialize::{Serialize, Serializer};
+#[cfg(feature = "derive")]
+pub use shallot_derive::{Deserialize, Metadata, Serialize};
+pub use transcode::{transcode, Transcode};
+pub use value::Value;
+
+#[cfg(all(test, feature = "derive"))]
 mod tests {
-    use super::*;
+    use crate::{
+        deserialize, serialize, value, Deserialize, ErrorKind, Serialize, Serializer, Value,
+    };
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Level {
+        Low,
+        r#High,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Marker;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Reading<T> {
+        r#type: Level,
+        values: Vec<T>,
+        #[shallot(view = "debug", view = "full")]
+        note: Option<String>,
+        marker: Marker,
+    }
+
+    fn reading() -> Reading<u8> {
+        Reading {
+            r#type: Level::High,
+            values: vec![1, 2],
+            note: None,
+            marker: Marker,
+        }
+    }
+
+    #[test]
+    fn derived_round_trip() {
+        let text = serialize::Json::new().serialize(&reading()).unwrap();
+        assert_eq!(
+            text,
+            r#"{"type": "High", "values": [1, 2], "note": null, "marker": null}"#
+        );
+        let read: Reading<u8> = deserialize::Json::new().deserialize(&text).unwrap();
+        assert_eq!(read, reading());
+        let read: Reading<u8> = deserialize::Json::new()
+            .deserialize(r#"{"marker": null, "values": [1, 2], "type": "High"}"#)
+            .unwrap();
+        assert_eq!(read, reading());
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Shape<T> {
+        Empty,
+        Circle(T),
+        Segment(T, T),
+        Rect {
+            width: T,
+            #[shallot(view = "full")]
+            height: Option<T>,
+        },
+    }
+
+    #[test]
+    fn derived_enums() {
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Circle(1),
+            Shape::Segment(2, 3),
+            Shape::Rect {
+                width: 4,
+                height: Some(5),
+            },
+        ];
+        let text = serialize::Json::new().serialize(&shapes).unwrap();
+        assert_eq!(
+            text,
+            r#"["Empty", {"Circle": 1}, {"Segment": [2, 3]}, {"Rect": {"width": 4, "height": 5}}]"#
+        );
+        let read: Vec<Shape<i8>> = deserialize::Json::new().deserialize(&text).unwrap();
+        assert_eq!(read, shapes);
+        let json = serialize::Json::new().field_filter(|field| field.in_view("summary"));
+        assert_eq!(
+            json.serialize(&shapes[3]).unwrap(),
+            r#"{"Rect": {"width": 4}}"#
+        );
+        let read: Shape<i8> = deserialize::Json::new()
+            .deserialize(r#" { "Rect" : { "width": 4 } } "#)
+            .unwrap();
+        assert_eq!(
+            read,
+            Shape::Rect {
+                width: 4,
+                height: None
+            }
+        );
+    }
+
+    #[test]
+    fn derived_enum_errors() {
+        let json = deserialize::Json::new();
+        let error = json.deserialize::<Shape<u8>>(r#""Circle""#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected object for Shape<u8> at line 1, column 1"
+        );
+        let error = json
+            .deserialize::<Shape<u8>>(r#"{"Empty": null}"#)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Syntax { expected: "string" });
+        let error = json
+            .deserialize::<Shape<u8>>(r#"{"Circle": 1, "Empty": null}"#)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected `}` for Shape<u8> at line 1, column 13"
+        );
+        let error = json
+            .deserialize::<Shape<u8>>(r#"{"Rect": {"width": -1}}"#)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "number out of range for u8 for field `width` of Shape<u8> at line 1, column 20"
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(discriminant)]
+    #[repr(u8)]
+    enum Status {
+        Active = 1,
+        Suspended = 4,
+        Closed,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(discriminant)]
+    enum Sign {
+        Minus = -1,
+        Zero,
+        Plus,
+    }
+
+    #[test]
+    fn derived_discriminants() {
+        let statuses = (Status::Active, Status::Suspended, Status::Closed);
+        let text = serialize::Json::new().serialize(&statuses).unwrap();
+        assert_eq!(text, "[1, 4, 5]");
+        let json = deserialize::Json::new();
+        assert_eq!(
+            json.deserialize::<(Status, Status, Status)>(&text).unwrap(),
+            statuses
+        );
+        let signs: Vec<Sign> = json.deserialize("[-1, 0, 1]").unwrap();
+        assert_eq!(signs, [Sign::Minus, Sign::Zero, Sign::Plus]);
+        assert_eq!(
+            serialize::Json::new().serialize(&signs).unwrap(),
+            "[-1, 0, 1]"
+        );
+        let error = json.deserialize::<Vec<Status>>("[1, 2]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown variant `2` for Status at line 1, column 5"
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Meters(f64);
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(transparent)]
+    struct Tag {
+        label: String,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(transparent)]
+    struct Celsius(Meters);
+
+    #[test]
+    fn derived_newtypes() {
+        let value = (
+            Meters(1.5),
+            Tag { label: "a".into() },
+            Celsius(Meters(-2.5)),
+        );
+        let text = serialize::Json::new().serialize(&value).unwrap();
+        assert_eq!(text, r#"[1.5, "a", -2.5]"#);
+        let json = deserialize::Json::new();
+        let read: (Meters, Tag, Celsius) = json.deserialize(&text).unwrap();
+        assert_eq!(read, value);
+        let error = json.deserialize::<Meters>("true").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected f64 for Meters at line 1, column 1"
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Span<T>(T, T, Level);
+
+    #[test]
+    fn derived_tuple_structs() {
+        let spans = vec![Span(1, 2, Level::Low), Span(3, 4, Level::High)];
+        let text = serialize::Json::new().serialize(&spans).unwrap();
+        assert_eq!(text, r#"[[1, 2, "Low"], [3, 4, "High"]]"#);
+        let json = deserialize::Json::new();
+        let read: Vec<Span<u8>> = json.deserialize(&text).unwrap();
+        assert_eq!(read, spans);
+        let error = json.deserialize::<Span<u8>>("[1, 2]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected 3 elements, found 2 for Span<u8> at line 1, column 6"
+        );
+        let error = json
+            .deserialize::<Span<u8>>(r#"[1, 2, "Low", 3]"#)
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Length {
+                expected: 3,
+                found: 4
+            }
+        );
+        let error = json.deserialize::<Span<u8>>("[1, -2]").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "number out of range for u8 for Span<u8> at line 1, column 5"
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Event {
+        #[shallot(rename = "sign-in")]
+        SignIn {
+            #[shallot(rename = "user-id")]
+            user_id: u32,
+        },
+        #[shallot(rename = "sign-out")]
+        SignOut,
+    }
+
+    #[test]
+    fn derived_renames() {
+        let events = vec![Event::SignIn { user_id: 7 }, Event::SignOut];
+        let text = serialize::Json::new().serialize(&events).unwrap();
+        assert_eq!(text, r#"[{"sign-in": {"user-id": 7}}, "sign-out"]"#);
+        let json = deserialize::Json::new();
+        let read: Vec<Event> = json.deserialize(&text).unwrap();
+        assert_eq!(read, events);
+        let error = json.deserialize::<Event>(r#""SignOut""#).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::UnknownVariant {
+                variant: "SignOut".into()
+            }
+        );
+        let error = json
+            .deserialize::<Event>(r#"{"sign-in": {"user_id": 7}}"#)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::MissingField { field: "user-id" });
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(rename_all = "camelCase")]
+    struct Account {
+        user_id: u32,
+        display_name_2: String,
+        #[shallot(rename = "MODE")]
+        access_mode: AccessMode,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(rename_all = "SCREAMING_SNAKE_CASE")]
+    enum AccessMode {
+        ReadOnly,
+        ReadWrite { audit_log: bool },
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(rename_all = "kebab-case")]
+    enum Theme {
+        DarkHighContrast,
+        r#Light,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(rename_all = "snake_case")]
+    enum Visibility {
+        PublicToAll,
+    }
+
+    #[test]
+    fn derived_rename_all() {
+        let account = Account {
+            user_id: 1,
+            display_name_2: "ada".into(),
+            access_mode: AccessMode::ReadWrite { audit_log: true },
+        };
+        let text = serialize::Json::new().serialize(&account).unwrap();
+        assert_eq!(
+            text,
+            r#"{"userId": 1, "displayName2": "ada", "MODE": {"READ_WRITE": {"audit_log": true}}}"#
+        );
+        let json = deserialize::Json::new();
+        assert_eq!(json.deserialize::<Account>(&text).unwrap(), account);
+        let value = (AccessMode::ReadOnly, Theme::DarkHighContrast, Theme::Light);
+        let text = serialize::Json::new().serialize(&value).unwrap();
+        assert_eq!(text, r#"["READ_ONLY", "dark-high-contrast", "light"]"#);
+        assert_eq!(
+            json.deserialize::<(AccessMode, Theme, Theme)>(&text)
+                .unwrap(),
+            value
+        );
+        let text = serialize::Json::new()
+            .serialize(&Visibility::PublicToAll)
+            .unwrap();
+        assert_eq!(text, r#""public_to_all""#);
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(field_order = "alphabetical")]
+    struct Sorted {
+        zone: u8,
+        #[shallot(rename = "Id")]
+        id: u8,
+        area: Unsorted,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[shallot(field_order = "declaration")]
+    enum Unsorted {
+        Point { y: u8, x: u8 },
+    }
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn derived_field_order() {
+        use serialize::FieldOrder;
+
+        let sorted = Sorted {
+            zone: 1,
+            id: 2,
+            area: Unsorted::Point { y: 3, x: 4 },
+        };
+        let text = serialize::Json::new().serialize(&sorted).unwrap();
+        assert_eq!(
+            text,
+            r#"{"Id": 2, "area": {"Point": {"y": 3, "x": 4}}, "zone": 1}"#
+        );
+        let read: Sorted = deserialize::Json::new().deserialize(&text).unwrap();
+        assert_eq!(read, sorted);
+        let json = serialize::Json::new().field_order(FieldOrder::Alphabetical);
+        assert_eq!(
+            json.serialize(&sorted).unwrap(),
+            r#"{"Id": 2, "area": {"Point": {"x": 4, "y": 3}}, "zone": 1}"#
+        );
+        let json = serialize::Json::new().field_order(FieldOrder::Declaration);
+        assert_eq!(
+            json.serialize(&sorted).unwrap(),
+            r#"{"zone": 1, "Id": 2, "area": {"Point": {"y": 3, "x": 4}}}"#
+        );
+    }
+
+    #[derive(crate::Metadata)]
+    #[allow(dead_code)]
+    struct Described<'a, T> {
+        #[shallot(rename = "r#type")]
+        r#type: &'a str,
+        pairs: Vec<(T, [u8; 4])>,
+        callback: Box<dyn Fn(&mut T) -> u8 + Send>,
+        unit: (),
+        single: (u8,),
+    }
+
+    #[test]
+    fn derived_metadata() {
+        use crate::metadata::{FieldInfo, Metadata};
+
+        assert_eq!(<Described<u8> as Metadata>::NAME, "Described");
+        let fields = <Described<u8> as Metadata>::FIELDS;
+        let names: Vec<_> = fields
+            .iter()
+            .map(|field| (field.name, field.wire_name))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("type", "r#type"),
+                ("pairs", "pairs"),
+                ("callback", "callback"),
+                ("unit", "unit"),
+                ("single", "single"),
+            ]
+        );
+        let types: Vec<_> = fields.iter().map(|field| field.ty).collect();
+        assert_eq!(
+            types,
+            [
+                "&'a str",
+                "Vec<(T, [u8; 4])>",
+                "Box<dyn Fn(&mut T) -> u8 + Send>",
+                "()",
+                "(u8,)",
+            ]
+        );
+        assert_eq!(
+            fields[0],
+            FieldInfo {
+                name: "type",
+                wire_name: "r#type",
+                ty: "&'a str",
+                aliases: &[],
+            }
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Contact {
+        #[shallot(alias = "mail", alias = "e_mail")]
+        email: String,
+        #[shallot(rename = "phone", alias = "tel")]
+        telephone: Option<String>,
+    }
+
+    #[test]
+    fn derived_aliases() {
+        let json = deserialize::Json::new();
+        let expected = Contact {
+            email: "a@b.c".into(),
+            telephone: Some("1".into()),
+        };
+        for text in [
+            r#"{"email": "a@b.c", "phone": "1"}"#,
+            r#"{"mail": "a@b.c", "tel": "1"}"#,
+            r#"{"tel": "1", "e_mail": "a@b.c"}"#,
+        ] {
+            assert_eq!(json.deserialize::<Contact>(text).unwrap(), expected);
+        }
+        assert_eq!(
+            serialize::Json::new().serialize(&expected).unwrap(),
+            r#"{"email": "a@b.c", "phone": "1"}"#
+        );
+        let error = json.deserialize::<Contact>(r#"{"tel": 1}"#).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected string for field `phone` of Contact at line 1, column 9"
+        );
+    }
+
+    mod millis {
+        use std::time::Duration;
+
+        use crate::{Deserializer, Result, Serializer};
+
+        pub fn serialize<S: Serializer>(input: &Duration, serializer: &S) -> Result<S::Output> {
+            serializer.visit_u128(&input.as_millis())
+        }
+
+        pub fn deserialize<D: Deserializer>(deserializer: &mut D) -> Result<Duration> {
+            deserializer.visit_u64().map(Duration::from_millis)
+        }
+    }
+
+    fn upper<S: Serializer>(input: &str, serializer: &S) -> crate::Result<S::Output> {
+        serializer.visit_str(&input.to_uppercase())
+    }
+
+    fn lower<D: crate::Deserializer>(deserializer: &mut D) -> crate::Result<String> {
+        deserializer.visit_string().map(|text| text.to_lowercase())
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    enum Job {
+        Timer {
+            #[shallot(with = "millis")]
+            timeout: std::time::Duration,
+            #[shallot(serialize_with = "upper", deserialize_with = "lower")]
+            label: String,
+            #[shallot(deserialize_with = "lower")]
+            owner: String,
+        },
+    }
+
+    #[test]
+    fn derived_with() {
+        let job = Job::Timer {
+            timeout: std::time::Duration::from_millis(1500),
+            label: "tick".into(),
+            owner: "ada".into(),
+        };
+        let text = serialize::Json::new().serialize(&job).unwrap();
+        assert_eq!(
+            text,
+            r#"{"Timer": {"timeout": 1500, "label": "TICK", "owner": "ada"}}"#
+        );
+        let json = deserialize::Json::new();
+        assert_eq!(json.deserialize::<Job>(&text).unwrap(), job);
+        let error = json
+            .deserialize::<Job>(r#"{"Timer": {"timeout": -1}}"#)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "number out of range for u64 for field `timeout` of Job at line 1, column 23"
+        );
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Blob {
+        #[shallot(with = "crate::bytes::base64")]
+        data: Vec<u8>,
+        #[shallot(with = "crate::bytes::hex")]
+        digest: Vec<u8>,
+    }
+
+    #[test]
+    fn derived_bytes() {
+        let blob = Blob {
+            data: b"hi".to_vec(),
+            digest: vec![0, 255],
+        };
+        let text = serialize::Json::new().serialize(&blob).unwrap();
+        assert_eq!(text, r#"{"data": "aGk=", "digest": "00ff"}"#);
+        let json = deserialize::Json::new();
+        assert_eq!(json.deserialize::<Blob>(&text).unwrap(), blob);
+        let escaped = r#"{"data": "\u0061Gk=", "digest": "00\u0066f"}"#;
+        assert_eq!(json.deserialize::<Blob>(escaped).unwrap(), blob);
+        let error = json
+            .deserialize::<Blob>(r#"{"data": "aG", "digest": ""}"#)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected base64 for field `data` of Blob at line 1, column 13"
+        );
+    }
+
+    #[test]
+    fn derived_in_place() {
+        let json = deserialize::Json::new();
+        let mut contact = Contact {
+            email: String::with_capacity(64),
+            telephone: Some("555".into()),
+        };
+        json.deserialize_in_place(r#"{"mail": "ada@example.com"}"#, &mut contact)
+            .unwrap();
+        assert_eq!(contact.email, "ada@example.com");
+        assert_eq!(contact.email.capacity(), 64);
+        assert_eq!(contact.telephone, None);
+
+        let mut reading = reading();
+        let values = reading.values.as_ptr();
+        let text = r#"{"type": "Low", "values": [3], "marker": null}"#;
+        json.deserialize_in_place(text, &mut reading).unwrap();
+        assert_eq!(reading.values, [3]);
+        assert_eq!(reading.values.as_ptr(), values);
+        assert_eq!(reading.r#type, Level::Low);
+        let error = json
+            .deserialize_in_place(r#"{"type": "Low"}"#, &mut reading)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::MissingField { field: "values" });
+    }
+
+    #[test]
+    fn derived_values() {
+        let value = value::to_value(&reading()).unwrap();
+        assert_eq!(value.get("type"), Some(&Value::from("High")));
+        assert_eq!(
+            value.get("values"),
+            Some(&Value::Array(vec![1u8.into(), 2u8.into()]))
+        );
+        assert_eq!(value::from_value::<Reading<u8>>(value).unwrap(), reading());
+
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Segment(2, 3),
+            Shape::Rect {
+                width: 4,
+                height: None,
+            },
+        ];
+        let value = value::to_value(&shapes).unwrap();
+        let text = serialize::Json::new().serialize(&value).unwrap();
+        assert_eq!(
+            text,
+            r#"["Empty", {"Segment": [2, 3]}, {"Rect": {"height": null, "width": 4}}]"#
+        );
+        assert_eq!(value::from_value::<Vec<Shape<i8>>>(value).unwrap(), shapes);
+
+        let error =
+            value::from_value::<Reading<u8>>(Value::Object(Default::default())).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::MissingField { field: "type" });
+    }
+
+    #[test]
+    fn derived_views() {
+        let json = serialize::Json::new().field_filter(|field| field.in_view("summary"));
+        assert_eq!(
+            json.serialize(&reading()).unwrap(),
+            r#"{"type": "High", "values": [1, 2], "marker": null}"#
+        );
+        let json = serialize::Json::new().field_filter(|field| field.in_view("full"));
+        assert!(json.serialize(&reading()).unwrap().contains("note"));
+    }
+
+    #[test]
+    fn derived_errors() {
+        let json = deserialize::Json::new();
+        let error = json
+            .deserialize::<Reading<u8>>(r#"{"type": "Low", "values": [300]}"#)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "number out of range for u8 for field `values` of Reading<u8> at line 1, column 28"
+        );
+        let error = json
+            .deserialize::<Reading<u8>>(r#"{"type": "Medium"}"#)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "unknown variant `Medium` for Level at line 1, column 10"
+        );
+        let error = json
+            .deserialize::<Reading<u8>>(r#"{"type": "Low", "marker": null}"#)
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::MissingField { field: "values" });
+    }
+
+    #[test]
+    fn derived_logfmt() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Entry {
+            level: Level,
+            msg: String,
+            status: u16,
+            cached: bool,
+            user: Option<String>,
+        }
+
+        let entry = Entry {
+            level: Level::High,
+            msg: "request done".into(),
+            status: 200,
+            cached: true,
+            user: None,
+        };
+        let line = serialize::Logfmt::new().serialize(&entry).unwrap();
+        assert_eq!(
+            line,
+            r#"level=High msg="request done" status=200 cached=true"#
+        );
+        let logfmt = deserialize::Logfmt::new();
+        assert_eq!(logfmt.deserialize::<Entry>(&line).unwrap(), entry);
+        let entry: Entry = logfmt
+            .deserialize("status=404 msg=missing cached level=Low user=\"a b\" extra=1")
+            .unwrap();
+        assert_eq!(entry.level, Level::Low);
+        assert_eq!(entry.user.as_deref(), Some("a b"));
+        assert!(entry.cached);
+        let error = logfmt
+            .deserialize::<Entry>("level=Low msg=x status=x cached")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected u16 for field `status` of Entry at line 1, column 24"
+        );
+    }
+
+    #[test]
+    fn derived_urlencoded() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Search {
+            q: String,
+            level: Level,
+            page: u32,
+            tag: Vec<String>,
+            lang: Option<String>,
+        }
+
+        let urlencoded = deserialize::Urlencoded::new();
+        let search: Search = urlencoded
+            .deserialize("q=red+shoes&page=1&tag=sale&level=Low&tag=new&page=2&utm=x")
+            .unwrap();
+        let expected = Search {
+            q: "red shoes".into(),
+            level: Level::Low,
+            page: 2,
+            tag: vec!["sale".into(), "new".into()],
+            lang: None,
+        };
+        assert_eq!(search, expected);
+        let search: Search = urlencoded
+            .deserialize("q=&level=High&page=3&tag=one&lang=")
+            .unwrap();
+        assert_eq!(search.tag, ["one"]);
+        assert_eq!(search.lang, None);
+
+        let strict = urlencoded.repeat_policy(deserialize::RepeatPolicy::Reject);
+        let error = strict
+            .deserialize::<Search>("q=a&level=Low&page=1&page=2&tag=x")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "key given more than once for field `page` of Search at line 1, column 27"
+        );
+        assert!(strict
+            .deserialize::<Search>("q=a&level=Low&page=1&tag=x&tag=y")
+            .is_ok());
+    }
+
+    #[test]
+    fn derived_urlencoded_nested() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Range {
+            min: Option<u32>,
+            max: Option<u32>,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct User {
+            name: String,
+            tags: Vec<String>,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Filter {
+            user: User,
+            price: Range,
+            levels: Vec<Level>,
+        }
+
+        let filter: Filter = deserialize::Urlencoded::new()
+            .deserialize(
+                "user[name]=x&user[tags][]=a&price[min]=5&user[tags][]=b\
+                 &levels[1]=High&levels[0]=Low&price[max]=",
+            )
+            .unwrap();
+        let expected = Filter {
+            user: User {
+                name: "x".into(),
+                tags: vec!["a".into(), "b".into()],
+            },
+            price: Range {
+                min: Some(5),
+                max: None,
+            },
+            levels: vec![Level::Low, Level::High],
+        };
+        assert_eq!(filter, expected);
+        let error = deserialize::Urlencoded::new()
+            .deserialize::<Filter>("user[name]=x&user[tags]=a&price[min]=y&levels=Low")
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "expected u32 for field `min` of Range at line 1, column 38"
+        );
+    }
+
+    #[test]
+    fn derived_multipart() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct File {
+            filename: String,
+            content_type: Option<String>,
+            data: Vec<u8>,
+        }
+
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Upload {
+            title: String,
+            level: Level,
+            tags: Vec<String>,
+            avatar: Option<Vec<u8>>,
+            cover: File,
+            attachments: Vec<Vec<u8>>,
+        }
+
+        let body = b"--x\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\nTrip\r\n\
+            --x\r\n\
+            Content-Disposition: form-data; name=\"tags\"\r\n\r\nsea\r\n\
+            --x\r\n\
+            Content-Disposition: form-data; name=\"avatar\"; filename=\"\"\r\n\r\n\r\n\
+            --x\r\n\
+            Content-Disposition: form-data; name=\"cover\"; filename=\"c.png\"\r\n\
+            Content-Type: image/png\r\n\r\n\x89PNG\r\n\
+            --x\r\n\
+            Content-Disposition: form-data; name=\"level\"\r\n\r\nHigh\r\n\
+            --x\r\n\
+            Content-Disposition: form-data; name=\"tags\"\r\n\r\nsun\r\n\
+            --x\r\n\
+            Content-Disposition: form-data; name=\"attachments\"; filename=\"a.txt\"\r\n\
+            \r\nnotes\r\n\
+            --x--\r\n";
+        let multipart = deserialize::Multipart::new();
+        let upload: Upload = multipart
+            .deserialize("multipart/form-data; boundary=x", body)
+            .unwrap();
+        let expected = Upload {
+            title: "Trip".into(),
+            level: Level::High,
+            tags: vec!["sea".into(), "sun".into()],
+            avatar: None,
+            cover: File {
+                filename: "c.png".into(),
+                content_type: Some("image/png".into()),
+                data: b"\x89PNG".to_vec(),
+            },
+            attachments: vec![b"notes".to_vec()],
+        };
+        assert_eq!(upload, expected);
+        let error = multipart
+            .deserialize::<Upload>("multipart/form-data; boundary=x", &body[..200])
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Eof);
     }
 }
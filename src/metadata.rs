@@ -0,0 +1,50 @@
+//! Descriptions of derived types that tooling can read at runtime, such as
+//! schema generators or admin interfaces, without parsing source.
+//!
+//! With the `derive` feature, `#[derive(Metadata)]` describes a struct with
+//! named fields, taking the names fields are written under from the same
+//! `#[shallot(...)]` attributes as the other derives.
+//!
+//! ```
+//! # #[cfg(feature = "derive")]
+//! # {
+//! use shallot::metadata::Metadata;
+//!
+//! #[derive(shallot::Metadata)]
+//! #[shallot(rename_all = "camelCase")]
+//! struct User {
+//!     user_id: u64,
+//!     #[shallot(alias = "mail")]
+//!     email: Option<String>,
+//! }
+//!
+//! assert_eq!(User::NAME, "User");
+//! let field = &User::FIELDS[1];
+//! assert_eq!((field.name, field.wire_name, field.ty), ("email", "email", "Option<String>"));
+//! assert_eq!(field.aliases, ["mail"]);
+//! assert_eq!(User::FIELDS[0].wire_name, "userId");
+//! # }
+//! ```
+
+/// A field of a type described by [`Metadata`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FieldInfo {
+    /// The name of the field in Rust.
+    pub name: &'static str,
+    /// The name the field is written under.
+    pub wire_name: &'static str,
+    /// The type of the field, as written in the source.
+    pub ty: &'static str,
+    /// Other names the field is read under.
+    pub aliases: &'static [&'static str],
+}
+
+/// A type whose shape is known at compile time, usually through
+/// `#[derive(Metadata)]`.
+pub trait Metadata {
+    /// The name of the type.
+    const NAME: &'static str;
+
+    /// The fields in declaration order.
+    const FIELDS: &'static [FieldInfo];
+}
@@ -0,0 +1,48 @@
+//! Platform code units for strings that are not valid Unicode.
+//!
+//! An `OsStr` that fails UTF-8 validation is written as the array of its
+//! native code units: bytes on Unix and UTF-16 units on Windows.
+
+use std::ffi::{OsStr, OsString};
+
+#[cfg(unix)]
+pub(crate) type Unit = u8;
+#[cfg(windows)]
+pub(crate) type Unit = u16;
+
+#[cfg(unix)]
+pub(crate) fn code_units(input: &OsStr) -> Vec<Unit> {
+    use std::os::unix::ffi::OsStrExt;
+    input.as_bytes().to_vec()
+}
+
+#[cfg(windows)]
+pub(crate) fn code_units(input: &OsStr) -> Vec<Unit> {
+    use std::os::windows::ffi::OsStrExt;
+    input.encode_wide().collect()
+}
+
+#[cfg(unix)]
+pub(crate) fn from_code_units(units: Vec<Unit>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(units)
+}
+
+#[cfg(windows)]
+pub(crate) fn from_code_units(units: Vec<Unit>) -> OsString {
+    use std::os::windows::ffi::OsStringExt;
+    OsString::from_wide(&units)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) type Unit = u8;
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn code_units(input: &OsStr) -> Vec<Unit> {
+    input.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn from_code_units(units: Vec<Unit>) -> OsString {
+    OsString::from(String::from_utf8_lossy(&units).into_owned())
+}
@@ -0,0 +1,97 @@
+//! Per-thread reuse of serializers and output buffers.
+//!
+//! Services that serialize many small responses can keep one configured
+//! [`serialize::Json`] per thread and reuse the `String` buffers responses
+//! are assembled in, instead of setting both up for every request.
+//!
+//! ```
+//! use shallot::pool;
+//! use shallot::serialize::Json;
+//!
+//! pool::configure(Json::new().sort_maps(true));
+//! let length = pool::with_json(|json, buffer| {
+//!     json.serialize_into(&[1, 2, 3], buffer)?;
+//!     buffer.push('\n');
+//!     Ok::<_, shallot::Error>(buffer.len())
+//! })?;
+//! assert_eq!(length, 10);
+//! # Ok::<(), shallot::Error>(())
+//! ```
+
+use std::cell::RefCell;
+
+use crate::serialize;
+
+/// Buffers kept per thread once returned; more are dropped.
+const MAX_BUFFERS: usize = 8;
+
+/// Buffers that grew beyond this many bytes are dropped rather than kept.
+const MAX_CAPACITY: usize = 1 << 20;
+
+thread_local! {
+    static JSON: RefCell<serialize::Json> = RefCell::default();
+    static BUFFERS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Set the serializer handed out by [`with_json`] on the current thread.
+///
+/// # Panics
+///
+/// Panics if called from within [`with_json`] on the same thread, while
+/// the serializer is lent out.
+pub fn configure(json: serialize::Json) {
+    JSON.with_borrow_mut(|current| *current = json);
+}
+
+/// Run `f` with the current thread's serializer and an empty buffer.
+///
+/// The serializer is lent, not cloned. The buffer keeps its capacity
+/// between calls. Calls may nest; each gets its own buffer.
+pub fn with_json<R>(f: impl FnOnce(&serialize::Json, &mut String) -> R) -> R {
+    let mut buffer = BUFFERS.with_borrow_mut(Vec::pop).unwrap_or_default();
+    let result = JSON.with_borrow(|json| f(json, &mut buffer));
+    if buffer.capacity() <= MAX_CAPACITY {
+        buffer.clear();
+        BUFFERS.with_borrow_mut(|buffers| {
+            if buffers.len() < MAX_BUFFERS {
+                buffers.push(buffer);
+            }
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn buffers_are_reused() {
+        let capacity = with_json(|_, buffer| {
+            buffer.push_str(&"x".repeat(100));
+            buffer.capacity()
+        });
+        with_json(|_, buffer| {
+            assert!(buffer.is_empty());
+            assert_eq!(buffer.capacity(), capacity);
+            with_json(|_, inner| assert_eq!(inner.capacity(), 0));
+        });
+        with_json(|_, buffer| buffer.reserve(MAX_CAPACITY + 1));
+        with_json(|_, buffer| assert!(buffer.capacity() <= capacity));
+    }
+
+    #[test]
+    fn configuration_is_per_thread() {
+        let map = HashMap::from([(2, ()), (1, ()), (3, ())]);
+        configure(serialize::Json::new().sort_maps(true));
+        let sorted = with_json(|json, _| json.serialize(&map).unwrap());
+        assert_eq!(sorted, r#"{"1": null, "2": null, "3": null}"#);
+        let nested = with_json(|outer, _| with_json(|inner, _| std::ptr::eq(outer, inner)));
+        assert!(nested);
+        std::thread::spawn(|| with_json(|json, _| assert_eq!(json.serialize(&1).unwrap(), "1")))
+            .join()
+            .unwrap();
+    }
+}
@@ -0,0 +1,179 @@
+//! Checking that values survive a trip through two formats.
+//!
+//! [`check`] writes a value with one format, reads it back, then writes the
+//! result with the other format and reads that back too, in both orders.
+//! Any step that fails to parse or produces a different value is reported
+//! as a [`Mismatch`], which makes differences in how two formats (or two
+//! configurations of one format) represent the same type visible early.
+
+use std::fmt;
+
+use crate::deserialize::{self, Deserialize};
+use crate::error::{Error, Result};
+use crate::serialize::{self, Serialize};
+
+/// A serializer paired with the deserializer that reads its output.
+pub trait Format {
+    /// The serialized representation.
+    type Output: fmt::Debug;
+
+    /// Serialize `value`.
+    fn write<T: Serialize + ?Sized>(&self, value: &T) -> Result<Self::Output>;
+
+    /// Deserialize a value from `output`.
+    fn read<T: Deserialize>(&self, output: &Self::Output) -> Result<T>;
+}
+
+impl Format for (serialize::Json, deserialize::Json) {
+    type Output = String;
+
+    fn write<T: Serialize + ?Sized>(&self, value: &T) -> Result<String> {
+        self.0.serialize(value)
+    }
+
+    fn read<T: Deserialize>(&self, output: &String) -> Result<T> {
+        self.1.deserialize(output)
+    }
+}
+
+/// Why a step of a round trip failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reason {
+    /// The value could not be written.
+    Unwritable(Error),
+    /// The output could not be read back.
+    Unreadable(Error),
+    /// The output was read back as a different value.
+    Changed { expected: String, found: String },
+}
+
+/// A step of a round trip that did not reproduce the original value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mismatch {
+    /// The formats the value passed through, e.g. `"first then second"`.
+    pub route: &'static str,
+    /// The output of the last format, formatted with `Debug`, or empty if
+    /// the value could not be written.
+    pub output: String,
+    pub reason: Reason,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.reason {
+            Reason::Unwritable(error) => write!(f, "{}: could not write: {error}", self.route),
+            Reason::Unreadable(error) => {
+                write!(f, "{}: could not read {}: {error}", self.route, self.output)
+            }
+            Reason::Changed { expected, found } => write!(
+                f,
+                "{}: {} was read as {found}, expected {expected}",
+                self.route, self.output
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// Round trip `value` through `first` and `second` in both orders.
+///
+/// ```
+/// use shallot::{deserialize, round_trip, serialize};
+///
+/// let json = (serialize::Json::new(), deserialize::Json::new());
+/// round_trip::check(&(1u8, vec![String::from("a")]), &json, &json)?;
+/// # Ok::<(), round_trip::Mismatch>(())
+/// ```
+pub fn check<T, A, B>(value: &T, first: &A, second: &B) -> std::result::Result<(), Mismatch>
+where
+    T: Serialize + Deserialize + PartialEq + fmt::Debug,
+    A: Format,
+    B: Format,
+{
+    let via_first = step(value, first, "first")?;
+    step(&via_first, second, "first then second")?;
+    let via_second = step(value, second, "second")?;
+    step(&via_second, first, "second then first")?;
+    Ok(())
+}
+
+/// Write and read back `value` with `format`, requiring it to be unchanged.
+fn step<T, F>(value: &T, format: &F, route: &'static str) -> std::result::Result<T, Mismatch>
+where
+    T: Serialize + Deserialize + PartialEq + fmt::Debug,
+    F: Format,
+{
+    let output = format.write(value).map_err(|error| Mismatch {
+        route,
+        output: String::new(),
+        reason: Reason::Unwritable(error),
+    })?;
+    let mismatch = |reason| Mismatch {
+        route,
+        output: format!("{output:?}"),
+        reason,
+    };
+    let read: T = format
+        .read(&output)
+        .map_err(|error| mismatch(Reason::Unreadable(error)))?;
+    if read != *value {
+        return Err(mismatch(Reason::Changed {
+            expected: format!("{value:?}"),
+            found: format!("{read:?}"),
+        }));
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// JSON that mangles every `.5` it reads back to `.`.
+    struct Lossy;
+
+    impl Format for Lossy {
+        type Output = String;
+
+        fn write<T: Serialize + ?Sized>(&self, value: &T) -> Result<String> {
+            serialize::Json::new().serialize(value)
+        }
+
+        fn read<T: Deserialize>(&self, output: &String) -> Result<T> {
+            deserialize::Json::new().deserialize(&output.replace(".5", "."))
+        }
+    }
+
+    fn json() -> (serialize::Json, deserialize::Json) {
+        (serialize::Json::new(), deserialize::Json::new())
+    }
+
+    #[test]
+    fn equivalent_formats_pass() {
+        let value = (u128::MAX, -1i8, Some(String::from("x")), vec![0.5f64]);
+        assert_eq!(check(&value, &json(), &json()), Ok(()));
+    }
+
+    #[test]
+    fn changed_values_are_reported() {
+        let mismatch = check(&String::from("v1.5"), &Lossy, &json()).unwrap_err();
+        assert_eq!(mismatch.route, "first");
+        assert_eq!(mismatch.output, r#""\"v1.5\"""#);
+        assert_eq!(
+            mismatch.reason,
+            Reason::Changed {
+                expected: String::from(r#""v1.5""#),
+                found: String::from(r#""v1.""#),
+            }
+        );
+    }
+
+    #[test]
+    fn unreadable_output_is_reported() {
+        let mismatch = check(&vec![1.5f64], &json(), &Lossy).unwrap_err();
+        assert_eq!(mismatch.route, "first then second");
+        assert_eq!(mismatch.output, "\"[1.5]\"");
+        assert!(matches!(mismatch.reason, Reason::Unreadable(_)));
+    }
+}
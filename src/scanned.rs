@@ -0,0 +1,19 @@
+//! Counting bytes scanned, for tests of code meant to run in linear time.
+
+use std::cell::Cell;
+
+thread_local! {
+    static SCANNED: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Record that the current thread looked at `bytes` bytes of input.
+pub(crate) fn add(bytes: usize) {
+    SCANNED.with(|count| count.set(count.get() + bytes));
+}
+
+/// How many bytes of input `f` looks at on the current thread.
+pub(crate) fn count(f: impl FnOnce()) -> usize {
+    let before = SCANNED.with(Cell::get);
+    f();
+    SCANNED.with(Cell::get) - before
+}
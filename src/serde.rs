@@ -0,0 +1,676 @@
+//! Adapters between shallot and [serde](https://serde.rs), so a codebase
+//! can move from one to the other a type at a time.
+//!
+//! [`Serde`] lets any shallot [`Serializer`] write a `serde::Serialize`
+//! type, and [`from_deserializer`] reads any shallot [`Deserialize`] type
+//! from a serde `Deserializer`, such as a field of a struct that still
+//! derives serde's traits.
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//!
+//! use shallot::serde::Serde;
+//! use shallot::serialize::Json;
+//! use shallot::Serializer;
+//!
+//! // A type only serde knows how to write.
+//! let scores: BTreeMap<&str, (u8, Option<f32>)> = BTreeMap::from([("ada", (3, Some(0.5)))]);
+//! assert_eq!(Json::new().serialize(&Serde(&scores))?, r#"{"ada": [3, 0.5]}"#);
+//! # Ok::<(), shallot::Error>(())
+//! ```
+
+use std::any::Any;
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::fmt;
+
+use ::serde::{de, ser};
+
+use crate::deserialize::Deserialize;
+use crate::error::{Error, Result};
+use crate::serialize::{Serialize, Serializer, Tuple};
+use crate::value::{self, Value};
+
+/// A `serde::Serialize` value, which any shallot [`Serializer`] can write.
+///
+/// Serde's data model maps onto shallot's: structs and maps are written as
+/// maps, sequences as sequences, tuples as tuples and enum variants as
+/// variants. Bytes are written as an array of numbers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Serde<T>(pub T);
+
+impl<T: ser::Serialize> Serialize for Serde<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        self.0.serialize(Adapter(serializer))
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::custom(message)
+    }
+}
+
+/// Read a `T` from a serde deserializer.
+///
+/// The input is first read into a [`Value`], so only what a `Value` can
+/// hold gets through: numbers must fit in 64 bits, and map keys must be
+/// strings, numbers or booleans. Its signature suits serde's
+/// `deserialize_with` attribute:
+///
+/// ```
+/// use shallot::Deserialize;
+///
+/// // A type only shallot knows how to read.
+/// struct Celsius(f64);
+///
+/// impl Deserialize for Celsius {
+///     fn accept<D: shallot::Deserializer>(deserializer: &mut D) -> shallot::Result<Self> {
+///         deserializer.visit_f64().map(Celsius)
+///     }
+/// }
+///
+/// #[derive(serde::Deserialize)]
+/// struct Reading {
+///     #[serde(deserialize_with = "shallot::serde::from_deserializer")]
+///     temperature: Celsius,
+/// }
+///
+/// let value = serde::de::value::MapDeserializer::<_, serde::de::value::Error>::new(
+///     [("temperature", 21.5)].into_iter(),
+/// );
+/// let reading: Reading = serde::Deserialize::deserialize(value).unwrap();
+/// assert_eq!(reading.temperature.0, 21.5);
+/// ```
+pub fn from_deserializer<'de, T, D>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    T: Deserialize,
+    D: de::Deserializer<'de>,
+{
+    let value = deserializer.deserialize_any(ValueVisitor)?;
+    value::from_value(value).map_err(de::Error::custom)
+}
+
+/// A serde serializer that hands each value to a shallot serializer.
+struct Adapter<'a, S>(&'a S);
+
+/// A value already serialized, which serializes to its output once.
+///
+/// Serde passes the parts of a compound value one at a time, while shallot
+/// takes them all at once, so each part is serialized as it comes and
+/// kept until the whole is.
+struct Ready<O>(Cell<Option<O>>);
+
+impl<O> Ready<O> {
+    fn new(output: O) -> Self {
+        Self(Cell::new(Some(output)))
+    }
+}
+
+impl<O: 'static> Serialize for Ready<O> {
+    fn accept<S: Serializer>(&self, _serializer: &S) -> Result<S::Output> {
+        let output: Box<dyn Any> = Box::new(self.0.take());
+        match output.downcast::<Option<S::Output>>() {
+            Ok(output) => output.ok_or_else(|| Error::custom("a serde value was written twice")),
+            Err(_) => Err(Error::custom(
+                "a serde value was written by another serializer than the one it was read for",
+            )),
+        }
+    }
+}
+
+/// The elements of a serde tuple, already serialized.
+struct Elements<O>(Vec<Ready<O>>);
+
+impl<O: 'static> Tuple for Elements<O> {
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn serialize_elements<S: Serializer>(
+        &self,
+        serializer: &S,
+        mut element: impl FnMut(S::Output),
+    ) -> Result<()> {
+        for ready in &self.0 {
+            element(serializer.serialize(ready)?);
+        }
+        Ok(())
+    }
+}
+
+/// Which serde value a [`SeqAdapter`] collects the elements of.
+enum SeqShape {
+    Seq,
+    Tuple,
+    TupleStruct(&'static str),
+    TupleVariant(&'static str),
+}
+
+/// Collects the elements of a serde sequence, tuple or tuple variant.
+struct SeqAdapter<'a, S: Serializer> {
+    serializer: &'a S,
+    shape: SeqShape,
+    elements: Vec<Ready<S::Output>>,
+}
+
+impl<'a, S: Serializer> SeqAdapter<'a, S> {
+    fn new(serializer: &'a S, shape: SeqShape, len: Option<usize>) -> Self {
+        Self {
+            serializer,
+            shape,
+            elements: Vec::with_capacity(len.unwrap_or_default()),
+        }
+    }
+
+    fn push<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let output = self.serializer.serialize(&Serde(value))?;
+        self.elements.push(Ready::new(output));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<S::Output> {
+        let serializer = self.serializer;
+        match self.shape {
+            SeqShape::Seq => serializer.visit_seq(&self.elements),
+            SeqShape::Tuple => serializer.visit_tuple(&Elements(self.elements)),
+            SeqShape::TupleStruct(name) => {
+                serializer.visit_tuple_struct(name, &Elements(self.elements))
+            }
+            SeqShape::TupleVariant(variant) => {
+                let content = serializer.visit_tuple(&Elements(self.elements))?;
+                serializer.visit_variant(variant, content)
+            }
+        }
+    }
+}
+
+/// A key and its value, already serialized.
+type Entry<O> = (Ready<O>, Ready<O>);
+
+/// Collects the entries of a serde map, struct or struct variant.
+struct MapAdapter<'a, S: Serializer> {
+    serializer: &'a S,
+    variant: Option<&'static str>,
+    entries: Vec<Entry<S::Output>>,
+    /// A key passed without its value yet.
+    key: Option<Ready<S::Output>>,
+}
+
+impl<'a, S: Serializer> MapAdapter<'a, S> {
+    fn new(serializer: &'a S, variant: Option<&'static str>, len: Option<usize>) -> Self {
+        Self {
+            serializer,
+            variant,
+            entries: Vec::with_capacity(len.unwrap_or_default()),
+            key: None,
+        }
+    }
+
+    fn push<T: ser::Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<()> {
+        let key = Ready::new(self.serializer.visit_str(key)?);
+        let value = Ready::new(self.serializer.serialize(&Serde(value))?);
+        self.entries.push((key, value));
+        Ok(())
+    }
+
+    fn finish(self) -> Result<S::Output> {
+        let serializer = self.serializer;
+        let entries = self.entries.iter().map(|(key, value)| (key, value));
+        let map = serializer.visit_map(entries)?;
+        match self.variant {
+            Some(variant) => serializer.visit_variant(variant, map),
+            None => Ok(map),
+        }
+    }
+}
+
+macro_rules! adapt_primitive {
+    ($($method:ident($ty:ty) => $visit:ident,)+) => {
+        $(
+            fn $method(self, input: $ty) -> Result<S::Output> {
+                self.0.$visit(&input)
+            }
+        )+
+    };
+}
+
+impl<'a, S: Serializer> ser::Serializer for Adapter<'a, S> {
+    type Ok = S::Output;
+    type Error = Error;
+    type SerializeSeq = SeqAdapter<'a, S>;
+    type SerializeTuple = SeqAdapter<'a, S>;
+    type SerializeTupleStruct = SeqAdapter<'a, S>;
+    type SerializeTupleVariant = SeqAdapter<'a, S>;
+    type SerializeMap = MapAdapter<'a, S>;
+    type SerializeStruct = MapAdapter<'a, S>;
+    type SerializeStructVariant = MapAdapter<'a, S>;
+
+    adapt_primitive! {
+        serialize_bool(bool) => visit_bool,
+        serialize_i8(i8) => visit_i8,
+        serialize_i16(i16) => visit_i16,
+        serialize_i32(i32) => visit_i32,
+        serialize_i64(i64) => visit_i64,
+        serialize_i128(i128) => visit_i128,
+        serialize_u8(u8) => visit_u8,
+        serialize_u16(u16) => visit_u16,
+        serialize_u32(u32) => visit_u32,
+        serialize_u64(u64) => visit_u64,
+        serialize_u128(u128) => visit_u128,
+        serialize_f32(f32) => visit_f32,
+        serialize_f64(f64) => visit_f64,
+        serialize_char(char) => visit_char,
+    }
+
+    fn serialize_str(self, input: &str) -> Result<S::Output> {
+        self.0.visit_str(input)
+    }
+
+    fn serialize_bytes(self, input: &[u8]) -> Result<S::Output> {
+        self.0.visit_array(input)
+    }
+
+    fn serialize_none(self) -> Result<S::Output> {
+        self.0.visit_option(&None::<()>)
+    }
+
+    fn serialize_some<T: ser::Serialize + ?Sized>(self, input: &T) -> Result<S::Output> {
+        self.0.visit_option(&Some(Serde(input)))
+    }
+
+    fn serialize_unit(self) -> Result<S::Output> {
+        self.0.visit_unit()
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<S::Output> {
+        self.0.visit_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<S::Output> {
+        self.0.visit_unit_variant(variant)
+    }
+
+    fn serialize_newtype_struct<T: ser::Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        input: &T,
+    ) -> Result<S::Output> {
+        self.0.visit_newtype_struct(name, &Serde(input))
+    }
+
+    fn serialize_newtype_variant<T: ser::Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        input: &T,
+    ) -> Result<S::Output> {
+        let content = self.0.serialize(&Serde(input))?;
+        self.0.visit_variant(variant, content)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq> {
+        Ok(SeqAdapter::new(self.0, SeqShape::Seq, len))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple> {
+        Ok(SeqAdapter::new(self.0, SeqShape::Tuple, Some(len)))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct> {
+        Ok(SeqAdapter::new(
+            self.0,
+            SeqShape::TupleStruct(name),
+            Some(len),
+        ))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant> {
+        let shape = SeqShape::TupleVariant(variant);
+        Ok(SeqAdapter::new(self.0, shape, Some(len)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap> {
+        Ok(MapAdapter::new(self.0, None, len))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Self::SerializeStruct> {
+        Ok(MapAdapter::new(self.0, None, Some(len)))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant> {
+        Ok(MapAdapter::new(self.0, Some(variant), Some(len)))
+    }
+}
+
+impl<S: Serializer> ser::SerializeSeq for SeqAdapter<'_, S> {
+    type Ok = S::Output;
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<S::Output> {
+        self.finish()
+    }
+}
+
+impl<S: Serializer> ser::SerializeTuple for SeqAdapter<'_, S> {
+    type Ok = S::Output;
+    type Error = Error;
+
+    fn serialize_element<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<S::Output> {
+        self.finish()
+    }
+}
+
+impl<S: Serializer> ser::SerializeTupleStruct for SeqAdapter<'_, S> {
+    type Ok = S::Output;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<S::Output> {
+        self.finish()
+    }
+}
+
+impl<S: Serializer> ser::SerializeTupleVariant for SeqAdapter<'_, S> {
+    type Ok = S::Output;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<S::Output> {
+        self.finish()
+    }
+}
+
+impl<S: Serializer> ser::SerializeMap for MapAdapter<'_, S> {
+    type Ok = S::Output;
+    type Error = Error;
+
+    fn serialize_key<T: ser::Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(Ready::new(self.serializer.serialize(&Serde(key))?));
+        Ok(())
+    }
+
+    fn serialize_value<T: ser::Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::custom("serde passed a map value without a key"))?;
+        let value = Ready::new(self.serializer.serialize(&Serde(value))?);
+        self.entries.push((key, value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<S::Output> {
+        self.finish()
+    }
+}
+
+impl<S: Serializer> ser::SerializeStruct for MapAdapter<'_, S> {
+    type Ok = S::Output;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<S::Output> {
+        self.finish()
+    }
+}
+
+impl<S: Serializer> ser::SerializeStructVariant for MapAdapter<'_, S> {
+    type Ok = S::Output;
+    type Error = Error;
+
+    fn serialize_field<T: ser::Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.push(key, value)
+    }
+
+    fn end(self) -> Result<S::Output> {
+        self.finish()
+    }
+}
+
+/// Reads whatever a serde deserializer holds into a [`Value`].
+#[derive(Clone, Copy)]
+struct ValueVisitor;
+
+/// Convert a scalar with [`value::to_value`], which knows which numbers a
+/// [`Value`] can hold.
+fn scalar<T: Serialize, E: de::Error>(input: T) -> std::result::Result<Value, E> {
+    value::to_value(&input).map_err(E::custom)
+}
+
+impl<'de> de::DeserializeSeed<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> de::Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E: de::Error>(self, input: bool) -> std::result::Result<Value, E> {
+        Ok(Value::Bool(input))
+    }
+
+    fn visit_i64<E: de::Error>(self, input: i64) -> std::result::Result<Value, E> {
+        scalar(input)
+    }
+
+    fn visit_i128<E: de::Error>(self, input: i128) -> std::result::Result<Value, E> {
+        scalar(input)
+    }
+
+    fn visit_u64<E: de::Error>(self, input: u64) -> std::result::Result<Value, E> {
+        scalar(input)
+    }
+
+    fn visit_u128<E: de::Error>(self, input: u128) -> std::result::Result<Value, E> {
+        scalar(input)
+    }
+
+    fn visit_f64<E: de::Error>(self, input: f64) -> std::result::Result<Value, E> {
+        scalar(input)
+    }
+
+    fn visit_str<E: de::Error>(self, input: &str) -> std::result::Result<Value, E> {
+        Ok(Value::String(input.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, input: String) -> std::result::Result<Value, E> {
+        Ok(Value::String(input))
+    }
+
+    fn visit_bytes<E: de::Error>(self, input: &[u8]) -> std::result::Result<Value, E> {
+        scalar(input)
+    }
+
+    fn visit_none<E: de::Error>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> std::result::Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_newtype_struct<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> std::result::Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> std::result::Result<Value, A::Error> {
+        let mut elements = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(element) = seq.next_element_seed(self)? {
+            elements.push(element);
+        }
+        Ok(Value::Array(elements))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<Value, A::Error> {
+        let mut entries = BTreeMap::new();
+        while let Some(key) = map.next_key_seed(self)? {
+            let key = match key {
+                Value::String(key) => key,
+                Value::Number(key) => key.to_string(),
+                Value::Bool(key) => key.to_string(),
+                _ => {
+                    return Err(de::Error::custom(
+                        "map keys must be strings, numbers or booleans",
+                    ))
+                }
+            };
+            entries.insert(key, map.next_value_seed(self)?);
+        }
+        Ok(Value::Object(entries))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use ::serde::de::value::{Error as SerdeError, MapDeserializer, SeqDeserializer};
+    use ::serde::de::IntoDeserializer;
+
+    use super::*;
+    use crate::serialize::{ErasedSerializer, Json};
+    use crate::ErrorKind;
+
+    #[derive(::serde::Serialize)]
+    enum Shape {
+        Dot,
+        Circle(f32),
+        Line(i8, i8),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[derive(::serde::Serialize)]
+    struct Drawing<'a> {
+        name: &'a str,
+        shapes: Vec<Shape>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        pair: (u8, char),
+        layers: BTreeMap<u8, bool>,
+        id: Id,
+    }
+
+    #[derive(::serde::Serialize)]
+    struct Id(u64);
+
+    #[test]
+    fn serialize() {
+        let drawing = Drawing {
+            name: "d",
+            shapes: vec![
+                Shape::Dot,
+                Shape::Circle(1.5),
+                Shape::Line(-1, 2),
+                Shape::Rect { w: 3, h: 4 },
+            ],
+            note: None,
+            pair: (7, 'x'),
+            layers: BTreeMap::from([(2, true)]),
+            id: Id(9),
+        };
+        let expected = concat!(
+            r#"{"name":"d","shapes":["Dot",{"Circle":1.5},{"Line":[-1,2]},{"Rect":{"w":3,"h":4}}],"#,
+            r#""pair":[7,"x"],"layers":{"2":true},"id":9}"#,
+        );
+        let json = Json::new().compact(true);
+        assert_eq!(json.serialize(&Serde(&drawing)).unwrap(), expected);
+        let erased: &dyn ErasedSerializer = &json;
+        let out = erased.serialize(&Serde(&drawing)).unwrap();
+        assert_eq!(out.downcast::<String>().unwrap(), expected);
+
+        let value = value::to_value(&Serde(&drawing)).unwrap();
+        assert_eq!(value.get("id").and_then(Value::as_u64), Some(9));
+        let error = json.serialize(&Serde(f64::NAN)).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::NonFinite { value: "NaN" });
+        assert_eq!(json.serialize(&Serde(&b"hi"[..])).unwrap(), "[104,105]");
+    }
+
+    #[test]
+    fn deserialize() {
+        let input =
+            MapDeserializer::<_, SerdeError>::new([("b", vec![1u8, 2]), ("a", vec![])].into_iter());
+        let map: BTreeMap<String, Vec<u8>> = from_deserializer(input).unwrap();
+        assert_eq!(
+            map,
+            BTreeMap::from([("a".into(), vec![]), ("b".into(), vec![1, 2])])
+        );
+
+        let input = SeqDeserializer::<_, SerdeError>::new([-1i64, 2].into_iter());
+        let pair: (i8, u8) = from_deserializer(input).unwrap();
+        assert_eq!(pair, (-1, 2));
+
+        let input: ::serde::de::value::U128Deserializer<SerdeError> = u128::MAX.into_deserializer();
+        assert!(from_deserializer::<u128, _>(input).is_err());
+        let input: ::serde::de::value::I64Deserializer<SerdeError> = (-1i64).into_deserializer();
+        let error = from_deserializer::<u8, _>(input).unwrap_err();
+        assert_eq!(error.to_string(), "number out of range for u8");
+    }
+}
@@ -0,0 +1,632 @@
+//! Converting Rust values into a data format.
+//!
+//! A type opts in by implementing [`Serialize`], which hands the value to the
+//! matching `visit_*` method of a [`Serializer`]. The serializer decides how
+//! each kind of value is represented.
+
+mod erased;
+mod json;
+mod lines;
+mod logfmt;
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BinaryHeap, HashMap, LinkedList, VecDeque};
+use std::convert::Infallible;
+use std::ffi::{OsStr, OsString};
+use std::hash::BuildHasher;
+use std::marker::PhantomData;
+use std::ops::{Bound, ControlFlow};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate::error::{Error, Result};
+use crate::os_str;
+
+pub use erased::{ErasedSerialize, ErasedSerializer, ErasedStruct, ErasedTuple, Out};
+pub use json::{Json, NewlineMode, NonFiniteMode, OsStrMode, SharedMode};
+pub use logfmt::Logfmt;
+
+/// A value that can be written by any [`Serializer`].
+pub trait Serialize {
+    /// Pass `self` to the `visit_*` method of `serializer` matching its shape.
+    ///
+    /// Fails if the serializer cannot represent the value, or if the value
+    /// cannot be read, such as the data behind a poisoned lock.
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output>;
+}
+
+/// A data format that values can be written into.
+///
+/// Every method returns an [`Error`] for values the format cannot
+/// represent; the error propagates out of [`Serializer::serialize`].
+pub trait Serializer: Sized {
+    /// The representation produced for a value.
+    ///
+    /// It is `'static` so that [`ErasedSerializer`] can pass it around
+    /// without knowing its type.
+    type Output: 'static;
+
+    /// Serialize `input` into this format.
+    fn serialize<T: Serialize + ?Sized>(&self, input: &T) -> Result<Self::Output> {
+        input.accept(self)
+    }
+
+    fn visit_bool(&self, input: &bool) -> Result<Self::Output>;
+    fn visit_i8(&self, input: &i8) -> Result<Self::Output>;
+    fn visit_i16(&self, input: &i16) -> Result<Self::Output>;
+    fn visit_i32(&self, input: &i32) -> Result<Self::Output>;
+    fn visit_i64(&self, input: &i64) -> Result<Self::Output>;
+    fn visit_i128(&self, input: &i128) -> Result<Self::Output>;
+    fn visit_isize(&self, input: &isize) -> Result<Self::Output>;
+    fn visit_u8(&self, input: &u8) -> Result<Self::Output>;
+    fn visit_u16(&self, input: &u16) -> Result<Self::Output>;
+    fn visit_u32(&self, input: &u32) -> Result<Self::Output>;
+    fn visit_u64(&self, input: &u64) -> Result<Self::Output>;
+    fn visit_u128(&self, input: &u128) -> Result<Self::Output>;
+    fn visit_usize(&self, input: &usize) -> Result<Self::Output>;
+    fn visit_f32(&self, input: &f32) -> Result<Self::Output>;
+    fn visit_f64(&self, input: &f64) -> Result<Self::Output>;
+    fn visit_char(&self, input: &char) -> Result<Self::Output>;
+    fn visit_str(&self, input: &str) -> Result<Self::Output>;
+
+    /// Visit a platform string.
+    ///
+    /// The default writes valid Unicode as a string and anything else as the
+    /// array of its platform code units: bytes on Unix, UTF-16 units on
+    /// Windows.
+    fn visit_os_str(&self, input: &OsStr) -> Result<Self::Output> {
+        match input.to_str() {
+            Some(input) => self.visit_str(input),
+            None => self.visit_array(&os_str::code_units(input)),
+        }
+    }
+
+    /// Visit text already in this format, to be written as it is.
+    ///
+    /// This is how [`RawValue`](crate::value::RawValue) splices input it
+    /// captured without parsing it. The default fails, since not every
+    /// format is textual.
+    fn visit_raw(&self, input: &str) -> Result<Self::Output> {
+        let _ = input;
+        Err(Error::custom("raw output is not supported by this format"))
+    }
+
+    /// Visit the unit value `()`.
+    fn visit_unit(&self) -> Result<Self::Output>;
+
+    /// Visit an optional value.
+    fn visit_option<T: Serialize>(&self, input: &Option<T>) -> Result<Self::Output>;
+
+    /// Visit a homogeneous sequence of values.
+    fn visit_seq<'a, T, I>(&self, input: I) -> Result<Self::Output>
+    where
+        T: Serialize + 'a,
+        I: IntoIterator<Item = &'a T>;
+
+    /// Visit a homogeneous sequence stored contiguously.
+    fn visit_array<T: Serialize>(&self, input: &[T]) -> Result<Self::Output> {
+        self.visit_seq(input)
+    }
+
+    /// Visit a tuple, whose elements may each have a different type.
+    fn visit_tuple<T: Tuple + ?Sized>(&self, input: &T) -> Result<Self::Output>;
+
+    /// Visit a struct with named fields.
+    fn visit_struct<T: Struct + ?Sized>(&self, input: &T) -> Result<Self::Output>;
+
+    /// Visit a struct named `name` with unnamed fields, such as
+    /// `struct Point(f32, f32)`.
+    ///
+    /// The default writes the fields as a tuple.
+    fn visit_tuple_struct<T: Tuple + ?Sized>(
+        &self,
+        name: &'static str,
+        input: &T,
+    ) -> Result<Self::Output> {
+        let _ = name;
+        self.visit_tuple(input)
+    }
+
+    /// Visit a struct named `name` wrapping a single value, such as
+    /// `struct Meters(f64)`.
+    ///
+    /// The default writes the wrapped value as is, without a layer for the
+    /// struct.
+    fn visit_newtype_struct<T: Serialize + ?Sized>(
+        &self,
+        name: &'static str,
+        input: &T,
+    ) -> Result<Self::Output> {
+        let _ = name;
+        input.accept(self)
+    }
+
+    /// Visit an enum variant that carries no data.
+    ///
+    /// The default writes the variant's name as a string.
+    fn visit_unit_variant(&self, variant: &'static str) -> Result<Self::Output> {
+        self.visit_str(variant)
+    }
+
+    /// Visit an enum variant, given the already serialized `content` of its
+    /// data.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    /// use shallot::{Result, Serialize, Serializer};
+    ///
+    /// enum Shape {
+    ///     Circle(f32),
+    /// }
+    ///
+    /// impl Serialize for Shape {
+    ///     fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+    ///         match self {
+    ///             Shape::Circle(radius) => {
+    ///                 serializer.visit_variant("Circle", serializer.serialize(radius)?)
+    ///             }
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(Json::new().serialize(&Shape::Circle(1.5))?, r#"{"Circle": 1.5}"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    fn visit_variant(&self, variant: &'static str, content: Self::Output) -> Result<Self::Output>;
+
+    /// Visit a collection of key-value pairs.
+    fn visit_map<'a, K, V, I>(&self, input: I) -> Result<Self::Output>
+    where
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        I: IntoIterator<Item = (&'a K, &'a V)>;
+
+    /// Visit a collection of key-value pairs whose iteration order is
+    /// arbitrary, such as a `HashMap`.
+    ///
+    /// The default visits entries as they come; formats can override it to
+    /// make the output deterministic.
+    fn visit_unordered_map<'a, K, V, I>(&self, input: I) -> Result<Self::Output>
+    where
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        I: IntoIterator<Item = (&'a K, &'a V)>,
+    {
+        self.visit_map(input)
+    }
+
+    /// Visit the value behind an `Rc` or `Arc` that other pointers also
+    /// hold; `id` is its address, the same for each of them.
+    ///
+    /// Only shared values can be reached again from within themselves,
+    /// through a `RefCell` or `Mutex`, so this is where formats can catch
+    /// cycles or write each value once. The default writes the value in
+    /// full every time it is reached.
+    fn visit_shared<T: Serialize + ?Sized>(&self, id: usize, input: &T) -> Result<Self::Output> {
+        let _ = id;
+        input.accept(self)
+    }
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty => $method:ident,)+) => {
+        $(
+            impl Serialize for $ty {
+                fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+                    serializer.$method(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_primitive! {
+    bool => visit_bool,
+    i8 => visit_i8,
+    i16 => visit_i16,
+    i32 => visit_i32,
+    i64 => visit_i64,
+    i128 => visit_i128,
+    isize => visit_isize,
+    u8 => visit_u8,
+    u16 => visit_u16,
+    u32 => visit_u32,
+    u64 => visit_u64,
+    u128 => visit_u128,
+    usize => visit_usize,
+    f32 => visit_f32,
+    f64 => visit_f64,
+    char => visit_char,
+    str => visit_str,
+}
+
+impl Serialize for String {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(self)
+    }
+}
+
+impl Serialize for OsStr {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_os_str(self)
+    }
+}
+
+impl Serialize for OsString {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_os_str(self)
+    }
+}
+
+impl Serialize for () {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_unit()
+    }
+}
+
+impl Serialize for Infallible {
+    fn accept<S: Serializer>(&self, _serializer: &S) -> Result<S::Output> {
+        match *self {}
+    }
+}
+
+impl<T: ?Sized> Serialize for PhantomData<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_unit()
+    }
+}
+
+impl<T: Serialize> Serialize for Option<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_option(self)
+    }
+}
+
+impl<T: Serialize> Serialize for Bound<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        match self {
+            Bound::Included(value) => {
+                serializer.visit_variant("Included", serializer.serialize(value)?)
+            }
+            Bound::Excluded(value) => {
+                serializer.visit_variant("Excluded", serializer.serialize(value)?)
+            }
+            Bound::Unbounded => serializer.visit_unit_variant("Unbounded"),
+        }
+    }
+}
+
+impl<B: Serialize, C: Serialize> Serialize for ControlFlow<B, C> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        match self {
+            ControlFlow::Continue(value) => {
+                serializer.visit_variant("Continue", serializer.serialize(value)?)
+            }
+            ControlFlow::Break(value) => {
+                serializer.visit_variant("Break", serializer.serialize(value)?)
+            }
+        }
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for &T {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        (**self).accept(serializer)
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for &mut T {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        (**self).accept(serializer)
+    }
+}
+
+impl<T: Serialize + ?Sized> Serialize for Box<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        (**self).accept(serializer)
+    }
+}
+
+/// A value other `Rc`s also point to is passed to
+/// [`Serializer::visit_shared`].
+impl<T: Serialize + ?Sized> Serialize for Rc<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        match Rc::strong_count(self) {
+            1 => (**self).accept(serializer),
+            _ => serializer.visit_shared(Rc::as_ptr(self).cast::<()>() as usize, &**self),
+        }
+    }
+}
+
+/// A value other `Arc`s also point to is passed to
+/// [`Serializer::visit_shared`].
+impl<T: Serialize + ?Sized> Serialize for Arc<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        match Arc::strong_count(self) {
+            1 => (**self).accept(serializer),
+            _ => serializer.visit_shared(Arc::as_ptr(self).cast::<()>() as usize, &**self),
+        }
+    }
+}
+
+impl<T: Serialize + Copy> Serialize for Cell<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        self.get().accept(serializer)
+    }
+}
+
+/// A value that is currently mutably borrowed is an error.
+impl<T: Serialize + ?Sized> Serialize for RefCell<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        let value = self
+            .try_borrow()
+            .map_err(|_| Error::custom("value mutably borrowed while serializing"))?;
+        value.accept(serializer)
+    }
+}
+
+/// Blocks until the lock is acquired. A poisoned lock is an error, since
+/// the data it guards may be half updated.
+impl<T: Serialize + ?Sized> Serialize for Mutex<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        let guard = self.lock().map_err(|_| Error::custom(POISONED))?;
+        guard.accept(serializer)
+    }
+}
+
+/// Blocks until a read lock is acquired. A poisoned lock is an error, since
+/// the data it guards may be half updated.
+impl<T: Serialize + ?Sized> Serialize for RwLock<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        let guard = self.read().map_err(|_| Error::custom(POISONED))?;
+        guard.accept(serializer)
+    }
+}
+
+/// The error for data behind a poisoned lock.
+const POISONED: &str = "lock poisoned while serializing";
+
+impl<T: Serialize> Serialize for [T] {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_array(self)
+    }
+}
+
+impl<T: Serialize, const N: usize> Serialize for [T; N] {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_array(self)
+    }
+}
+
+impl<T: Serialize> Serialize for Vec<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_array(self)
+    }
+}
+
+impl<T: Serialize> Serialize for VecDeque<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_seq(self)
+    }
+}
+
+impl<T: Serialize> Serialize for LinkedList<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_seq(self)
+    }
+}
+
+/// Elements are visited in the heap's internal order, not sorted.
+impl<T: Serialize> Serialize for BinaryHeap<T> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_seq(self)
+    }
+}
+
+impl<K: Serialize, V: Serialize, H: BuildHasher> Serialize for HashMap<K, V, H> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_unordered_map(self)
+    }
+}
+
+impl<K: Serialize, V: Serialize> Serialize for BTreeMap<K, V> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_map(self)
+    }
+}
+
+/// A fixed-size sequence of values of possibly different types.
+///
+/// Implemented for tuples up to arity 16, so a [`Serializer`] handles every
+/// tuple with the single [`Serializer::visit_tuple`] method, and for tuple
+/// structs by `#[derive(Serialize)]`.
+pub trait Tuple {
+    /// The number of elements.
+    fn len(&self) -> usize;
+
+    /// Whether there are no elements.
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Serialize each element in order, passing each output to `element`.
+    ///
+    /// Stops at the first element that fails to serialize.
+    fn serialize_elements<S: Serializer>(
+        &self,
+        serializer: &S,
+        element: impl FnMut(S::Output),
+    ) -> Result<()>;
+}
+
+impl<T: Tuple + ?Sized> Tuple for &T {
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+
+    fn serialize_elements<S: Serializer>(
+        &self,
+        serializer: &S,
+        element: impl FnMut(S::Output),
+    ) -> Result<()> {
+        (**self).serialize_elements(serializer, element)
+    }
+}
+
+/// A named field of a [`Struct`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Field {
+    /// The name the field is written under.
+    pub name: &'static str,
+    /// The views the field belongs to; a field without views is in all of
+    /// them.
+    pub views: &'static [&'static str],
+}
+
+impl Field {
+    /// A field that belongs to every view.
+    pub const fn new(name: &'static str) -> Self {
+        Self { name, views: &[] }
+    }
+
+    /// Whether the field should be written in `view`.
+    pub fn in_view(&self, view: &str) -> bool {
+        self.views.is_empty() || self.views.contains(&view)
+    }
+}
+
+/// The order the fields of a [`Struct`] are written in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FieldOrder {
+    /// The order of [`Struct::fields`], which derived impls declare the
+    /// fields in.
+    #[default]
+    Declaration,
+    /// Sorted by the names the fields are written under.
+    Alphabetical,
+}
+
+/// A value made of named fields, such as a struct.
+///
+/// Implementing this alongside [`Serialize`] lets a [`Serializer`] write the
+/// fields under their names, for example as a JSON object. The serializer
+/// chooses which fields to write, so fields it leaves out are never
+/// serialized.
+///
+/// ```
+/// use shallot::serialize::{Field, Json, Struct};
+/// use shallot::{Result, Serialize, Serializer};
+///
+/// struct Point {
+///     x: i32,
+///     y: i32,
+/// }
+///
+/// impl Struct for Point {
+///     fn name(&self) -> &'static str {
+///         "Point"
+///     }
+///
+///     fn fields(&self) -> &'static [Field] {
+///         const FIELDS: &[Field] = &[Field::new("x"), Field::new("y")];
+///         FIELDS
+///     }
+///
+///     fn serialize_field<S: Serializer>(&self, index: usize, serializer: &S) -> Result<S::Output> {
+///         match index {
+///             0 => serializer.serialize(&self.x),
+///             _ => serializer.serialize(&self.y),
+///         }
+///     }
+/// }
+///
+/// impl Serialize for Point {
+///     fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+///         serializer.visit_struct(self)
+///     }
+/// }
+///
+/// let json = Json::new().serialize(&Point { x: 1, y: -2 })?;
+/// assert_eq!(json, r#"{"x": 1, "y": -2}"#);
+/// # Ok::<(), shallot::Error>(())
+/// ```
+pub trait Struct {
+    /// The name of the type.
+    fn name(&self) -> &'static str;
+
+    /// The fields in the order they are written.
+    fn fields(&self) -> &'static [Field];
+
+    /// Serialize the field at `index` in [`Struct::fields`].
+    fn serialize_field<S: Serializer>(&self, index: usize, serializer: &S) -> Result<S::Output>;
+
+    /// The order the fields are written in, unless the serializer is set to
+    /// another; the derive takes it from `#[shallot(field_order = "...")]`.
+    fn field_order(&self) -> FieldOrder {
+        FieldOrder::Declaration
+    }
+}
+
+impl<T: Struct + ?Sized> Struct for &T {
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn fields(&self) -> &'static [Field] {
+        (**self).fields()
+    }
+
+    fn serialize_field<S: Serializer>(&self, index: usize, serializer: &S) -> Result<S::Output> {
+        (**self).serialize_field(index, serializer)
+    }
+
+    fn field_order(&self) -> FieldOrder {
+        (**self).field_order()
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($len:literal($($name:ident $index:tt),+))+) => {
+        $(
+            impl<$($name: Serialize),+> Tuple for ($($name,)+) {
+                fn len(&self) -> usize {
+                    $len
+                }
+
+                fn serialize_elements<S: Serializer>(
+                    &self,
+                    serializer: &S,
+                    mut element: impl FnMut(S::Output),
+                ) -> Result<()> {
+                    $(element(serializer.serialize(&self.$index)?);)+
+                    Ok(())
+                }
+            }
+
+            impl<$($name: Serialize),+> Serialize for ($($name,)+) {
+                fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+                    serializer.visit_tuple(self)
+                }
+            }
+        )+
+    };
+}
+
+impl_tuple! {
+    1(A 0)
+    2(A 0, B 1)
+    3(A 0, B 1, C 2)
+    4(A 0, B 1, C 2, D 3)
+    5(A 0, B 1, C 2, D 3, E 4)
+    6(A 0, B 1, C 2, D 3, E 4, F 5)
+    7(A 0, B 1, C 2, D 3, E 4, F 5, G 6)
+    8(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7)
+    9(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8)
+    10(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9)
+    11(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10)
+    12(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11)
+    13(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12)
+    14(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13)
+    15(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14)
+    16(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11, M 12, N 13, O 14, P 15)
+}
@@ -0,0 +1,588 @@
+//! Object-safe counterparts of [`Serialize`] and [`Serializer`].
+//!
+//! [`Serialize::accept`] is generic over the serializer, so there is no
+//! `dyn Serialize`. Instead every `Serialize` type implements
+//! [`ErasedSerialize`], which can be used as a trait object, and
+//! `dyn ErasedSerialize` implements `Serialize` again. Every `Serializer`
+//! likewise implements [`ErasedSerializer`], whose output is an opaque
+//! [`Out`] holding the output of the underlying serializer.
+
+use std::any::Any;
+use std::ffi::OsStr;
+use std::fmt;
+
+use super::{Field, FieldOrder, Serialize, Serializer, Struct, Tuple};
+use crate::error::Result;
+
+/// The output of an [`ErasedSerializer`], whose type is only known to the
+/// serializer behind it.
+pub struct Out(Box<dyn Any>);
+
+impl Out {
+    fn new<T: 'static>(output: T) -> Self {
+        Self(Box::new(output))
+    }
+
+    /// The output as a `T`, or `self` again if the serializer produced
+    /// another type.
+    ///
+    /// ```
+    /// use shallot::serialize::{ErasedSerialize, ErasedSerializer, Json};
+    ///
+    /// let json = Json::new();
+    /// let serializer: &dyn ErasedSerializer = &json;
+    /// let out = [1, 2].erased_accept(serializer)?;
+    /// assert_eq!(out.downcast::<String>().unwrap(), "[1, 2]");
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn downcast<T: 'static>(self) -> std::result::Result<T, Self> {
+        self.0.downcast().map(|output| *output).map_err(Self)
+    }
+
+    /// The output of a serializer known to produce `T`.
+    fn take<T: 'static>(self) -> T {
+        match self.downcast() {
+            Ok(output) => output,
+            Err(_) => panic!("output of another serializer passed to an erased serializer"),
+        }
+    }
+}
+
+impl fmt::Debug for Out {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Out").finish_non_exhaustive()
+    }
+}
+
+/// A [`Serialize`] that can be used as a trait object.
+///
+/// Implemented for every `Serialize` type, so any of them can be boxed as a
+/// `dyn ErasedSerialize`, which is `Serialize` in turn:
+///
+/// ```
+/// use shallot::serialize::{ErasedSerialize, Json};
+/// use shallot::Serializer;
+///
+/// let values: Vec<Box<dyn ErasedSerialize>> = vec![Box::new(1), Box::new("two"), Box::new([3.5])];
+/// assert_eq!(Json::new().serialize(&values)?, r#"[1, "two", [3.5]]"#);
+/// # Ok::<(), shallot::Error>(())
+/// ```
+pub trait ErasedSerialize {
+    /// Pass `self` to the `erased_visit_*` method of `serializer` matching
+    /// its shape.
+    fn erased_accept(&self, serializer: &dyn ErasedSerializer) -> Result<Out>;
+}
+
+impl<T: Serialize + ?Sized> ErasedSerialize for T {
+    fn erased_accept(&self, serializer: &dyn ErasedSerializer) -> Result<Out> {
+        self.accept(&serializer)
+    }
+}
+
+macro_rules! impl_serialize_for_dyn {
+    ($($ty:ty),+) => {
+        $(
+            impl Serialize for $ty {
+                fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+                    ErasedSerialize::erased_accept(self, serializer).map(Out::take)
+                }
+            }
+        )+
+    };
+}
+
+impl_serialize_for_dyn! {
+    dyn ErasedSerialize + '_,
+    dyn ErasedSerialize + Send + '_,
+    dyn ErasedSerialize + Sync + '_,
+    dyn ErasedSerialize + Send + Sync + '_
+}
+
+/// A [`Tuple`] that can be used as a trait object, passed to
+/// [`ErasedSerializer::erased_visit_tuple`].
+pub trait ErasedTuple {
+    /// See [`Tuple::len`].
+    fn erased_len(&self) -> usize;
+
+    /// See [`Tuple::serialize_elements`].
+    fn erased_serialize_elements(
+        &self,
+        serializer: &dyn ErasedSerializer,
+        element: &mut dyn FnMut(Out),
+    ) -> Result<()>;
+}
+
+impl<T: Tuple + ?Sized> ErasedTuple for T {
+    fn erased_len(&self) -> usize {
+        self.len()
+    }
+
+    fn erased_serialize_elements(
+        &self,
+        serializer: &dyn ErasedSerializer,
+        element: &mut dyn FnMut(Out),
+    ) -> Result<()> {
+        self.serialize_elements(&serializer, element)
+    }
+}
+
+impl Tuple for dyn ErasedTuple + '_ {
+    fn len(&self) -> usize {
+        self.erased_len()
+    }
+
+    fn serialize_elements<S: Serializer>(
+        &self,
+        serializer: &S,
+        mut element: impl FnMut(S::Output),
+    ) -> Result<()> {
+        ErasedTuple::erased_serialize_elements(self, serializer, &mut |out: Out| {
+            element(out.take())
+        })
+    }
+}
+
+/// A [`Struct`] that can be used as a trait object, passed to
+/// [`ErasedSerializer::erased_visit_struct`].
+pub trait ErasedStruct {
+    /// See [`Struct::name`].
+    fn erased_name(&self) -> &'static str;
+
+    /// See [`Struct::fields`].
+    fn erased_fields(&self) -> &'static [Field];
+
+    /// See [`Struct::serialize_field`].
+    fn erased_serialize_field(
+        &self,
+        index: usize,
+        serializer: &dyn ErasedSerializer,
+    ) -> Result<Out>;
+
+    /// See [`Struct::field_order`].
+    fn erased_field_order(&self) -> FieldOrder;
+}
+
+impl<T: Struct + ?Sized> ErasedStruct for T {
+    fn erased_name(&self) -> &'static str {
+        self.name()
+    }
+
+    fn erased_fields(&self) -> &'static [Field] {
+        self.fields()
+    }
+
+    fn erased_serialize_field(
+        &self,
+        index: usize,
+        serializer: &dyn ErasedSerializer,
+    ) -> Result<Out> {
+        self.serialize_field(index, &serializer)
+    }
+
+    fn erased_field_order(&self) -> FieldOrder {
+        self.field_order()
+    }
+}
+
+impl Struct for dyn ErasedStruct + '_ {
+    fn name(&self) -> &'static str {
+        self.erased_name()
+    }
+
+    fn fields(&self) -> &'static [Field] {
+        self.erased_fields()
+    }
+
+    fn serialize_field<S: Serializer>(&self, index: usize, serializer: &S) -> Result<S::Output> {
+        ErasedStruct::erased_serialize_field(self, index, serializer).map(Out::take)
+    }
+
+    fn field_order(&self) -> FieldOrder {
+        self.erased_field_order()
+    }
+}
+
+/// A [`Serializer`] that can be used as a trait object.
+///
+/// Implemented for every `Serializer`, each method wrapping the output of
+/// the matching [`Serializer`] method in an [`Out`]. `&dyn ErasedSerializer`
+/// is a `Serializer` in turn, so a serializer picked at runtime can write
+/// any value.
+pub trait ErasedSerializer {
+    fn erased_visit_bool(&self, input: &bool) -> Result<Out>;
+    fn erased_visit_i8(&self, input: &i8) -> Result<Out>;
+    fn erased_visit_i16(&self, input: &i16) -> Result<Out>;
+    fn erased_visit_i32(&self, input: &i32) -> Result<Out>;
+    fn erased_visit_i64(&self, input: &i64) -> Result<Out>;
+    fn erased_visit_i128(&self, input: &i128) -> Result<Out>;
+    fn erased_visit_isize(&self, input: &isize) -> Result<Out>;
+    fn erased_visit_u8(&self, input: &u8) -> Result<Out>;
+    fn erased_visit_u16(&self, input: &u16) -> Result<Out>;
+    fn erased_visit_u32(&self, input: &u32) -> Result<Out>;
+    fn erased_visit_u64(&self, input: &u64) -> Result<Out>;
+    fn erased_visit_u128(&self, input: &u128) -> Result<Out>;
+    fn erased_visit_usize(&self, input: &usize) -> Result<Out>;
+    fn erased_visit_f32(&self, input: &f32) -> Result<Out>;
+    fn erased_visit_f64(&self, input: &f64) -> Result<Out>;
+    fn erased_visit_char(&self, input: &char) -> Result<Out>;
+    fn erased_visit_str(&self, input: &str) -> Result<Out>;
+    fn erased_visit_os_str(&self, input: &OsStr) -> Result<Out>;
+    fn erased_visit_raw(&self, input: &str) -> Result<Out>;
+    fn erased_visit_unit(&self) -> Result<Out>;
+    fn erased_visit_option(&self, input: Option<&dyn ErasedSerialize>) -> Result<Out>;
+    fn erased_visit_seq(
+        &self,
+        input: &mut dyn Iterator<Item = &dyn ErasedSerialize>,
+    ) -> Result<Out>;
+    fn erased_visit_array(
+        &self,
+        input: &mut dyn Iterator<Item = &dyn ErasedSerialize>,
+    ) -> Result<Out>;
+    fn erased_visit_tuple(&self, input: &dyn ErasedTuple) -> Result<Out>;
+    fn erased_visit_struct(&self, input: &dyn ErasedStruct) -> Result<Out>;
+    fn erased_visit_tuple_struct(&self, name: &'static str, input: &dyn ErasedTuple)
+        -> Result<Out>;
+    fn erased_visit_newtype_struct(
+        &self,
+        name: &'static str,
+        input: &dyn ErasedSerialize,
+    ) -> Result<Out>;
+    fn erased_visit_unit_variant(&self, variant: &'static str) -> Result<Out>;
+    fn erased_visit_variant(&self, variant: &'static str, content: Out) -> Result<Out>;
+    fn erased_visit_map(
+        &self,
+        input: &mut dyn Iterator<Item = (&dyn ErasedSerialize, &dyn ErasedSerialize)>,
+    ) -> Result<Out>;
+    fn erased_visit_unordered_map(
+        &self,
+        input: &mut dyn Iterator<Item = (&dyn ErasedSerialize, &dyn ErasedSerialize)>,
+    ) -> Result<Out>;
+    fn erased_visit_shared(&self, id: usize, input: &dyn ErasedSerialize) -> Result<Out>;
+}
+
+macro_rules! erase_primitive {
+    ($($erased:ident => $method:ident($ty:ty),)+) => {
+        $(
+            fn $erased(&self, input: &$ty) -> Result<Out> {
+                self.$method(input).map(Out::new)
+            }
+        )+
+    };
+}
+
+impl<S: Serializer> ErasedSerializer for S {
+    erase_primitive! {
+        erased_visit_bool => visit_bool(bool),
+        erased_visit_i8 => visit_i8(i8),
+        erased_visit_i16 => visit_i16(i16),
+        erased_visit_i32 => visit_i32(i32),
+        erased_visit_i64 => visit_i64(i64),
+        erased_visit_i128 => visit_i128(i128),
+        erased_visit_isize => visit_isize(isize),
+        erased_visit_u8 => visit_u8(u8),
+        erased_visit_u16 => visit_u16(u16),
+        erased_visit_u32 => visit_u32(u32),
+        erased_visit_u64 => visit_u64(u64),
+        erased_visit_u128 => visit_u128(u128),
+        erased_visit_usize => visit_usize(usize),
+        erased_visit_f32 => visit_f32(f32),
+        erased_visit_f64 => visit_f64(f64),
+        erased_visit_char => visit_char(char),
+        erased_visit_str => visit_str(str),
+        erased_visit_os_str => visit_os_str(OsStr),
+        erased_visit_raw => visit_raw(str),
+    }
+
+    fn erased_visit_unit(&self) -> Result<Out> {
+        self.visit_unit().map(Out::new)
+    }
+
+    fn erased_visit_option(&self, input: Option<&dyn ErasedSerialize>) -> Result<Out> {
+        self.visit_option(&input).map(Out::new)
+    }
+
+    fn erased_visit_seq(
+        &self,
+        input: &mut dyn Iterator<Item = &dyn ErasedSerialize>,
+    ) -> Result<Out> {
+        let elements: Vec<_> = input.collect();
+        self.visit_seq(&elements).map(Out::new)
+    }
+
+    fn erased_visit_array(
+        &self,
+        input: &mut dyn Iterator<Item = &dyn ErasedSerialize>,
+    ) -> Result<Out> {
+        let elements: Vec<_> = input.collect();
+        self.visit_array(&elements).map(Out::new)
+    }
+
+    fn erased_visit_tuple(&self, input: &dyn ErasedTuple) -> Result<Out> {
+        self.visit_tuple(input).map(Out::new)
+    }
+
+    fn erased_visit_struct(&self, input: &dyn ErasedStruct) -> Result<Out> {
+        self.visit_struct(input).map(Out::new)
+    }
+
+    fn erased_visit_tuple_struct(
+        &self,
+        name: &'static str,
+        input: &dyn ErasedTuple,
+    ) -> Result<Out> {
+        self.visit_tuple_struct(name, input).map(Out::new)
+    }
+
+    fn erased_visit_newtype_struct(
+        &self,
+        name: &'static str,
+        input: &dyn ErasedSerialize,
+    ) -> Result<Out> {
+        self.visit_newtype_struct(name, input).map(Out::new)
+    }
+
+    fn erased_visit_unit_variant(&self, variant: &'static str) -> Result<Out> {
+        self.visit_unit_variant(variant).map(Out::new)
+    }
+
+    fn erased_visit_variant(&self, variant: &'static str, content: Out) -> Result<Out> {
+        self.visit_variant(variant, content.take()).map(Out::new)
+    }
+
+    fn erased_visit_map(
+        &self,
+        input: &mut dyn Iterator<Item = (&dyn ErasedSerialize, &dyn ErasedSerialize)>,
+    ) -> Result<Out> {
+        let entries: Vec<_> = input.collect();
+        self.visit_map(entries.iter().map(|(key, value)| (key, value)))
+            .map(Out::new)
+    }
+
+    fn erased_visit_unordered_map(
+        &self,
+        input: &mut dyn Iterator<Item = (&dyn ErasedSerialize, &dyn ErasedSerialize)>,
+    ) -> Result<Out> {
+        let entries: Vec<_> = input.collect();
+        self.visit_unordered_map(entries.iter().map(|(key, value)| (key, value)))
+            .map(Out::new)
+    }
+
+    fn erased_visit_shared(&self, id: usize, input: &dyn ErasedSerialize) -> Result<Out> {
+        self.visit_shared(id, input).map(Out::new)
+    }
+}
+
+macro_rules! forward_primitive {
+    ($($method:ident($ty:ty) => $erased:ident,)+) => {
+        $(
+            fn $method(&self, input: &$ty) -> Result<Out> {
+                (**self).$erased(input)
+            }
+        )+
+    };
+}
+
+// Every call goes through `**self`: `&dyn ErasedSerializer` is itself an
+// `ErasedSerializer` through the blanket impl, which would call back here.
+impl Serializer for &dyn ErasedSerializer {
+    type Output = Out;
+
+    forward_primitive! {
+        visit_bool(bool) => erased_visit_bool,
+        visit_i8(i8) => erased_visit_i8,
+        visit_i16(i16) => erased_visit_i16,
+        visit_i32(i32) => erased_visit_i32,
+        visit_i64(i64) => erased_visit_i64,
+        visit_i128(i128) => erased_visit_i128,
+        visit_isize(isize) => erased_visit_isize,
+        visit_u8(u8) => erased_visit_u8,
+        visit_u16(u16) => erased_visit_u16,
+        visit_u32(u32) => erased_visit_u32,
+        visit_u64(u64) => erased_visit_u64,
+        visit_u128(u128) => erased_visit_u128,
+        visit_usize(usize) => erased_visit_usize,
+        visit_f32(f32) => erased_visit_f32,
+        visit_f64(f64) => erased_visit_f64,
+        visit_char(char) => erased_visit_char,
+        visit_str(str) => erased_visit_str,
+        visit_os_str(OsStr) => erased_visit_os_str,
+        visit_raw(str) => erased_visit_raw,
+    }
+
+    fn visit_unit(&self) -> Result<Out> {
+        (**self).erased_visit_unit()
+    }
+
+    fn visit_option<T: Serialize>(&self, input: &Option<T>) -> Result<Out> {
+        (**self).erased_visit_option(input.as_ref().map(|value| value as &dyn ErasedSerialize))
+    }
+
+    fn visit_seq<'a, T, I>(&self, input: I) -> Result<Out>
+    where
+        T: Serialize + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let mut elements = input
+            .into_iter()
+            .map(|element| element as &dyn ErasedSerialize);
+        (**self).erased_visit_seq(&mut elements)
+    }
+
+    fn visit_array<T: Serialize>(&self, input: &[T]) -> Result<Out> {
+        let mut elements = input.iter().map(|element| element as &dyn ErasedSerialize);
+        (**self).erased_visit_array(&mut elements)
+    }
+
+    fn visit_tuple<T: Tuple + ?Sized>(&self, input: &T) -> Result<Out> {
+        (**self).erased_visit_tuple(&input)
+    }
+
+    fn visit_struct<T: Struct + ?Sized>(&self, input: &T) -> Result<Out> {
+        (**self).erased_visit_struct(&input)
+    }
+
+    fn visit_tuple_struct<T: Tuple + ?Sized>(&self, name: &'static str, input: &T) -> Result<Out> {
+        (**self).erased_visit_tuple_struct(name, &input)
+    }
+
+    fn visit_newtype_struct<T: Serialize + ?Sized>(
+        &self,
+        name: &'static str,
+        input: &T,
+    ) -> Result<Out> {
+        (**self).erased_visit_newtype_struct(name, &input)
+    }
+
+    fn visit_unit_variant(&self, variant: &'static str) -> Result<Out> {
+        (**self).erased_visit_unit_variant(variant)
+    }
+
+    fn visit_variant(&self, variant: &'static str, content: Out) -> Result<Out> {
+        (**self).erased_visit_variant(variant, content)
+    }
+
+    fn visit_map<'a, K, V, I>(&self, input: I) -> Result<Out>
+    where
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        I: IntoIterator<Item = (&'a K, &'a V)>,
+    {
+        let mut entries = input
+            .into_iter()
+            .map(|(key, value)| (key as &dyn ErasedSerialize, value as &dyn ErasedSerialize));
+        (**self).erased_visit_map(&mut entries)
+    }
+
+    fn visit_unordered_map<'a, K, V, I>(&self, input: I) -> Result<Out>
+    where
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        I: IntoIterator<Item = (&'a K, &'a V)>,
+    {
+        let mut entries = input
+            .into_iter()
+            .map(|(key, value)| (key as &dyn ErasedSerialize, value as &dyn ErasedSerialize));
+        (**self).erased_visit_unordered_map(&mut entries)
+    }
+
+    fn visit_shared<T: Serialize + ?Sized>(&self, id: usize, input: &T) -> Result<Out> {
+        (**self).erased_visit_shared(id, &input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
+    use super::*;
+    use crate::serialize::Json;
+
+    struct Point {
+        x: i32,
+        y: Box<dyn ErasedSerialize>,
+    }
+
+    impl Struct for Point {
+        fn name(&self) -> &'static str {
+            "Point"
+        }
+
+        fn fields(&self) -> &'static [Field] {
+            const FIELDS: &[Field] = &[
+                Field::new("x"),
+                Field {
+                    name: "y",
+                    views: &["full"],
+                },
+            ];
+            FIELDS
+        }
+
+        fn serialize_field<S: Serializer>(
+            &self,
+            index: usize,
+            serializer: &S,
+        ) -> Result<S::Output> {
+            match index {
+                0 => serializer.serialize(&self.x),
+                _ => serializer.serialize(&self.y),
+            }
+        }
+    }
+
+    impl Serialize for Point {
+        fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+            serializer.visit_variant("Point", serializer.visit_struct(self)?)
+        }
+    }
+
+    #[test]
+    fn trait_objects() {
+        let mut map: HashMap<&str, Box<dyn ErasedSerialize + Send + Sync>> = HashMap::new();
+        map.insert("b", Box::new(Some('c')));
+        map.insert("a", Box::new((1u8, "two", ())));
+        let point = Point {
+            x: 1,
+            y: Box::new(vec![2.5]),
+        };
+        let values: Vec<Box<dyn ErasedSerialize>> = vec![
+            Box::new(map),
+            Box::new(point),
+            Box::new(BTreeMap::from([(1, None::<u8>)])),
+        ];
+        let json = Json::new().sort_maps(true);
+        assert_eq!(
+            json.serialize(&values).unwrap(),
+            r#"[{"a": [1, "two", null], "b": "c"}, {"Point": {"x": 1, "y": [2.5]}}, {"1": null}]"#
+        );
+        let json = Json::new().field_filter(|field| field.in_view("summary"));
+        assert_eq!(
+            json.serialize(&values[1]).unwrap(),
+            r#"{"Point": {"x": 1}}"#
+        );
+    }
+
+    #[test]
+    fn erased_serializers() {
+        let compact = Json::new();
+        let filtered = Json::new().field_filter(|field| field.name != "y");
+        let serializers: [&dyn ErasedSerializer; 2] = [&compact, &filtered];
+        let point = Point {
+            x: -1,
+            y: Box::new("up"),
+        };
+        let outputs: Vec<String> = serializers
+            .into_iter()
+            .map(|serializer| serializer.serialize(&point).unwrap().downcast().unwrap())
+            .collect();
+        assert_eq!(
+            outputs,
+            [
+                r#"{"Point": {"x": -1, "y": "up"}}"#,
+                r#"{"Point": {"x": -1}}"#
+            ]
+        );
+        let out = serializers[0].serialize(&1).unwrap();
+        assert!(out.downcast::<u8>().is_err());
+    }
+}
@@ -0,0 +1,1734 @@
+//! JSON output.
+
+mod reserialize;
+mod writer;
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fmt::Write;
+
+use super::{Field, FieldOrder, Serialize, Serializer, Struct, Tuple};
+use crate::deserialize;
+use crate::error::{Error, ErrorKind, Result};
+use writer::{Span, Writer};
+
+/// The capacity output starts with, enough for most small values to take
+/// a single allocation.
+const INITIAL_CAPACITY: usize = 128;
+
+/// Buffers of [`Json::write_to`] that grew beyond this many bytes are
+/// dropped rather than kept.
+const MAX_KEPT_CAPACITY: usize = 1 << 20;
+
+thread_local! {
+    /// The buffer [`Json::write_to`] writes through, kept between calls.
+    static WRITE_BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+
+    /// The addresses of the shared values being written in full on this
+    /// thread, to catch one that contains itself.
+    static WRITING_SHARED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+}
+
+/// Serializes values into JSON text.
+///
+/// Sequences and tuples become arrays, maps become objects, and `None` and
+/// `()` become `null`. Map keys that do not serialize to a string, such as
+/// numbers, are quoted, and those that serialize to `null`, an array or an
+/// object fail with [`ErrorKind::UnsupportedKey`].
+///
+/// ```
+/// use shallot::serialize::Json;
+/// use shallot::Serializer;
+///
+/// let json = Json::new();
+/// assert_eq!(json.serialize(&(1, "two", [3.5]))?, r#"[1, "two", [3.5]]"#);
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Json {
+    os_str: OsStrMode,
+    non_finite: NonFiniteMode,
+    ascii: bool,
+    html_safe: bool,
+    quote_wide_integers: bool,
+    sort_maps: bool,
+    canonical: bool,
+    separators: Option<(String, String)>,
+    pretty: bool,
+    newline: NewlineMode,
+    trailing_newline: bool,
+    field_filter: Option<fn(&Field) -> bool>,
+    field_order: Option<FieldOrder>,
+    max_output_len: Option<usize>,
+    shared: SharedMode,
+}
+
+/// How platform strings that are not valid Unicode are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OsStrMode {
+    /// Write the array of platform code units, which reads back exactly.
+    #[default]
+    CodeUnits,
+    /// Replace invalid sequences with U+FFFD and write a string.
+    Lossy,
+}
+
+/// How NaN and infinite floats, which JSON numbers cannot express, are
+/// written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NonFiniteMode {
+    /// Fail with [`ErrorKind::NonFinite`].
+    #[default]
+    Error,
+    /// Write `null`, which reads back as `None` but not as a float.
+    Null,
+    /// Write the strings `"NaN"`, `"Infinity"` and `"-Infinity"`.
+    String,
+}
+
+/// The line ending written by pretty output.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NewlineMode {
+    /// `\n`, as on Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, as on Windows.
+    CrLf,
+}
+
+/// How a value that several `Rc` or `Arc` pointers share is written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SharedMode {
+    /// Write the value in full wherever it is reached, failing with
+    /// [`ErrorKind::Cycle`] if it contains itself.
+    #[default]
+    Inline,
+    /// Write the value as `{"$id": 1, "$value": ...}` where it is first
+    /// reached, and as `{"$ref": 1}` wherever it is reached again,
+    /// including from within itself. Ids count up from 1 in each document.
+    ///
+    /// [`deserialize::Json::references`] reads the sharing back.
+    Reference,
+}
+
+impl NewlineMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+impl Json {
+    /// Create a JSON serializer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how platform strings that are not valid Unicode are written.
+    pub fn os_str_mode(mut self, mode: OsStrMode) -> Self {
+        self.os_str = mode;
+        self
+    }
+
+    /// Set how NaN and infinite floats are written.
+    ///
+    /// ```
+    /// use shallot::serialize::{Json, NonFiniteMode};
+    /// use shallot::Serializer;
+    ///
+    /// assert!(Json::new().serialize(&f64::NAN).is_err());
+    /// let json = Json::new().non_finite_mode(NonFiniteMode::String);
+    /// assert_eq!(json.serialize(&[1.0, f64::NEG_INFINITY])?, r#"[1.0, "-Infinity"]"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn non_finite_mode(mut self, mode: NonFiniteMode) -> Self {
+        self.non_finite = mode;
+        self
+    }
+
+    /// Set whether characters outside ASCII are written as `\u` escapes,
+    /// those outside the Basic Multilingual Plane as surrogate pairs.
+    ///
+    /// Control characters are always escaped; this keeps the whole output
+    /// ASCII for transports that mangle anything else.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    /// use shallot::Serializer;
+    ///
+    /// let json = Json::new().ascii(true);
+    /// assert_eq!(json.serialize("é😀")?, r#""\u00e9\ud83d\ude00""#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn ascii(mut self, ascii: bool) -> Self {
+        self.ascii = ascii;
+        self
+    }
+
+    /// Set whether `<`, `>`, `&`, U+2028 and U+2029 are written as `\u`
+    /// escapes, so the output can be embedded in an HTML `<script>` element
+    /// or a JavaScript string without ending it early.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    /// use shallot::Serializer;
+    ///
+    /// let json = Json::new().html_safe(true);
+    /// assert_eq!(json.serialize("</script>&")?, r#""\u003c/script\u003e\u0026""#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn html_safe(mut self, html_safe: bool) -> Self {
+        self.html_safe = html_safe;
+        self
+    }
+
+    /// Set whether `i128` and `u128` are written as strings, for consumers
+    /// that read every number as a 64-bit float or integer.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    /// use shallot::Serializer;
+    ///
+    /// let json = Json::new().quote_wide_integers(true);
+    /// assert_eq!(json.serialize(&(1u64, 1u128))?, r#"[1, "1"]"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn quote_wide_integers(mut self, quote: bool) -> Self {
+        self.quote_wide_integers = quote;
+        self
+    }
+
+    /// Set whether entries of maps with arbitrary iteration order, such as
+    /// `HashMap`, are sorted so the output is the same on every run.
+    ///
+    /// Entries are sorted by their serialized key, so numeric keys compare
+    /// as text. Maps with a defined order, such as `BTreeMap`, keep it.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use shallot::serialize::Json;
+    /// use shallot::Serializer;
+    ///
+    /// let map = HashMap::from([("b", 2), ("a", 1), ("c", 3)]);
+    /// let json = Json::new().sort_maps(true);
+    /// assert_eq!(json.serialize(&map)?, r#"{"a": 1, "b": 2, "c": 3}"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn sort_maps(mut self, sort: bool) -> Self {
+        self.sort_maps = sort;
+        self
+    }
+
+    /// Set whether output follows the JSON Canonicalization Scheme (RFC
+    /// 8785), so equal values always produce the same bytes, as needed for
+    /// hashing and signing.
+    ///
+    /// There is no whitespace, the entries of every map and struct are
+    /// sorted by the UTF-16 code units of their keys, and strings escape
+    /// only what JSON requires, overriding [`Json::ascii`] and
+    /// [`Json::html_safe`]. Numbers are written the way JavaScript prints
+    /// the nearest `f64`, so integers beyond 2^53 lose precision and NaN and
+    /// infinities always fail.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    ///
+    /// use shallot::serialize::Json;
+    /// use shallot::Serializer;
+    ///
+    /// let map = HashMap::from([("b", vec![1e21, 0.000001]), ("a", vec![-0.0, 1.5])]);
+    /// let json = Json::new().canonical(true);
+    /// assert_eq!(json.serialize(&map)?, r#"{"a":[0,1.5],"b":[1e+21,0.000001]}"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn canonical(mut self, canonical: bool) -> Self {
+        self.canonical = canonical;
+        self
+    }
+
+    /// Set whether output has no whitespace at all, rather than a space
+    /// after each `,` and `:`.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    /// use shallot::Serializer;
+    ///
+    /// let json = Json::new().compact(true);
+    /// assert_eq!(json.serialize(&(1, [("a", 2)]))?, r#"[1,[["a",2]]]"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.separators = compact.then(|| (",".into(), ":".into()));
+        self
+    }
+
+    /// Set the text between array elements or object entries, and between
+    /// keys and their values.
+    ///
+    /// They are written as given, so to keep the output valid JSON they
+    /// should be `,` and `:` with only spaces, tabs and newlines around
+    /// them. This replaces [`Json::compact`], and is ignored by
+    /// [`Json::canonical`].
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// use shallot::serialize::Json;
+    /// use shallot::Serializer;
+    ///
+    /// let json = Json::new().separators(",", " : ");
+    /// let map = BTreeMap::from([("a", [1, 2]), ("b", [3, 4])]);
+    /// assert_eq!(json.serialize(&map)?, r#"{"a" : [1,2],"b" : [3,4]}"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn separators(mut self, item: &str, key: &str) -> Self {
+        self.separators = Some((item.to_owned(), key.to_owned()));
+        self
+    }
+
+    /// Only write struct fields for which `filter` returns `true`.
+    ///
+    /// Combined with [`Field::views`], this lets one type produce several
+    /// shapes of output:
+    ///
+    /// ```
+    /// use shallot::serialize::{Field, Json, Struct};
+    /// use shallot::{Result, Serialize, Serializer};
+    ///
+    /// struct User {
+    ///     name: String,
+    ///     email: String,
+    /// }
+    ///
+    /// impl Struct for User {
+    ///     fn name(&self) -> &'static str {
+    ///         "User"
+    ///     }
+    ///
+    ///     fn fields(&self) -> &'static [Field] {
+    ///         const FIELDS: &[Field] = &[
+    ///             Field::new("name"),
+    ///             Field { name: "email", views: &["admin"] },
+    ///         ];
+    ///         FIELDS
+    ///     }
+    ///
+    ///     fn serialize_field<S: Serializer>(&self, index: usize, serializer: &S) -> Result<S::Output> {
+    ///         match index {
+    ///             0 => serializer.serialize(&self.name),
+    ///             _ => serializer.serialize(&self.email),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// impl Serialize for User {
+    ///     fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+    ///         serializer.visit_struct(self)
+    ///     }
+    /// }
+    ///
+    /// let user = User { name: "ada".into(), email: "ada@example.com".into() };
+    /// let public = Json::new().field_filter(|field| field.in_view("public"));
+    /// assert_eq!(public.serialize(&user)?, r#"{"name": "ada"}"#);
+    /// let admin = Json::new().field_filter(|field| field.in_view("admin"));
+    /// assert_eq!(admin.serialize(&user)?, r#"{"name": "ada", "email": "ada@example.com"}"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn field_filter(mut self, filter: fn(&Field) -> bool) -> Self {
+        self.field_filter = Some(filter);
+        self
+    }
+
+    /// Write the fields of every struct in `order`, rather than the order
+    /// each type asks for with [`Struct::field_order`].
+    ///
+    /// ```
+    /// use shallot::serialize::{Field, FieldOrder, Json, Struct};
+    /// use shallot::{Result, Serialize, Serializer};
+    ///
+    /// struct Span {
+    ///     start: u32,
+    ///     end: u32,
+    /// }
+    ///
+    /// impl Struct for Span {
+    ///     fn name(&self) -> &'static str {
+    ///         "Span"
+    ///     }
+    ///
+    ///     fn fields(&self) -> &'static [Field] {
+    ///         const FIELDS: &[Field] = &[Field::new("start"), Field::new("end")];
+    ///         FIELDS
+    ///     }
+    ///
+    ///     fn serialize_field<S: Serializer>(&self, index: usize, serializer: &S) -> Result<S::Output> {
+    ///         match index {
+    ///             0 => serializer.serialize(&self.start),
+    ///             _ => serializer.serialize(&self.end),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// impl Serialize for Span {
+    ///     fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+    ///         serializer.visit_struct(self)
+    ///     }
+    /// }
+    ///
+    /// let span = Span { start: 2, end: 5 };
+    /// assert_eq!(Json::new().serialize(&span)?, r#"{"start": 2, "end": 5}"#);
+    /// let json = Json::new().field_order(FieldOrder::Alphabetical);
+    /// assert_eq!(json.serialize(&span)?, r#"{"end": 5, "start": 2}"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn field_order(mut self, order: FieldOrder) -> Self {
+        self.field_order = Some(order);
+        self
+    }
+
+    /// Set whether each array element and object entry is written on its
+    /// own line, indented by two spaces per level of nesting.
+    ///
+    /// Empty arrays and objects stay on one line. The key separator from
+    /// [`Json::separators`] is kept, and [`Json::canonical`] turns this off.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    /// use shallot::Serializer;
+    ///
+    /// let json = Json::new().pretty(true);
+    /// assert_eq!(json.serialize(&[vec![1], vec![]])?, "[\n  [\n    1\n  ],\n  []\n]");
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn pretty(mut self, pretty: bool) -> Self {
+        self.pretty = pretty;
+        self
+    }
+
+    /// Set the line ending written by pretty output and by
+    /// [`Json::document`].
+    pub fn newline_mode(mut self, mode: NewlineMode) -> Self {
+        self.newline = mode;
+        self
+    }
+
+    /// Set whether [`Json::document`] ends its output with a line ending,
+    /// as text files conventionally do.
+    pub fn trailing_newline(mut self, trailing: bool) -> Self {
+        self.trailing_newline = trailing;
+        self
+    }
+
+    /// Set how many bytes of text one call may write, unlimited unless
+    /// set, so an unexpectedly large value fails rather than filling memory.
+    ///
+    /// Writing fails with [`ErrorKind::OutputLimit`] as soon as a value
+    /// takes the text past the limit, and any text written is discarded.
+    /// Text already in the buffer given to [`Json::serialize_into`] does not
+    /// count.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    /// use shallot::{ErrorKind, Serializer};
+    ///
+    /// let json = Json::new().max_output_len(8);
+    /// assert_eq!(json.serialize(&[1, 2])?, "[1, 2]");
+    /// let error = json.serialize(&[1, 2, 3, 4]).unwrap_err();
+    /// assert_eq!(error.kind(), &ErrorKind::OutputLimit { limit: 8 });
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn max_output_len(mut self, len: usize) -> Self {
+        self.max_output_len = Some(len);
+        self
+    }
+
+    /// Set how a value that several `Rc` or `Arc` pointers share is
+    /// written.
+    ///
+    /// Ids are given out across a whole document, so
+    /// [`SharedMode::Reference`] needs [`Json::serialize`] or another
+    /// method that writes the document at once; the [`Serializer`] methods
+    /// of `Json` itself, which write each value separately, fail on shared
+    /// values in that mode.
+    ///
+    /// ```
+    /// use std::rc::Rc;
+    ///
+    /// use shallot::serialize::{Json, SharedMode};
+    ///
+    /// let point = Rc::new((1, 2));
+    /// let line = [point.clone(), point];
+    /// assert_eq!(Json::new().serialize(&line)?, "[[1, 2], [1, 2]]");
+    /// let json = Json::new().shared_mode(SharedMode::Reference);
+    /// assert_eq!(json.serialize(&line)?, r#"[{"$id": 1, "$value": [1, 2]}, {"$ref": 1}]"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn shared_mode(mut self, mode: SharedMode) -> Self {
+        self.shared = mode;
+        self
+    }
+
+    /// Serialize `input` into JSON text.
+    ///
+    /// The text of every value is written through into one buffer, so a
+    /// short value takes a single allocation. Generic code calling
+    /// [`Serializer::serialize`] gets the same text, built from a string
+    /// per value.
+    pub fn serialize<T: Serialize + ?Sized>(&self, input: &T) -> Result<String> {
+        self.written(|writer| input.accept(writer))
+    }
+
+    /// Serialize `input` as a whole document, such as the contents of a
+    /// file.
+    ///
+    /// This is [`Json::serialize`] followed by a line ending if
+    /// [`Json::trailing_newline`] is set; nested values never get one.
+    ///
+    /// ```
+    /// use shallot::serialize::{Json, NewlineMode};
+    ///
+    /// let json = Json::new()
+    ///     .pretty(true)
+    ///     .newline_mode(NewlineMode::CrLf)
+    ///     .trailing_newline(true);
+    /// assert_eq!(json.document(&[1, 2])?, "[\r\n  1,\r\n  2\r\n]\r\n");
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn document<T: Serialize + ?Sized>(&self, input: &T) -> Result<String> {
+        let mut output = String::with_capacity(INITIAL_CAPACITY);
+        self.document_into(input, &mut output)?;
+        Ok(output)
+    }
+
+    /// Serialize `input` as a whole document like [`Json::document`],
+    /// appending the text to `output`.
+    pub(super) fn document_into<T: Serialize + ?Sized>(
+        &self,
+        input: &T,
+        output: &mut String,
+    ) -> Result<()> {
+        self.serialize_into(input, output)?;
+        if self.trailing_newline {
+            output.push_str(self.newline.as_str());
+        }
+        Ok(())
+    }
+
+    /// Serialize `input`, appending the text to `output` rather than
+    /// returning a new `String`, so one buffer can be reused across calls.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    ///
+    /// let json = Json::new();
+    /// let mut output = String::new();
+    /// for id in [7, 8] {
+    ///     output.clear();
+    ///     json.serialize_into(&("id", id), &mut output)?;
+    ///     assert!(output.starts_with(r#"["id", "#));
+    /// }
+    /// assert_eq!(output, r#"["id", 8]"#);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn serialize_into<T: Serialize + ?Sized>(
+        &self,
+        input: &T,
+        output: &mut String,
+    ) -> Result<()> {
+        self.with_writer(output, |writer| input.accept(writer))
+    }
+
+    /// Serialize `input`, writing the text to `output`, such as a
+    /// [`fmt::Formatter`](std::fmt::Formatter) inside a `Display` impl.
+    ///
+    /// The text goes through a buffer kept by the current thread, so once
+    /// it has grown to fit, writing allocates no output of its own. Fails
+    /// with [`ErrorKind::Custom`] if `output` does.
+    ///
+    /// ```
+    /// use std::fmt;
+    ///
+    /// use shallot::serialize::Json;
+    ///
+    /// struct Tags(Vec<&'static str>);
+    ///
+    /// impl fmt::Display for Tags {
+    ///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    ///         Json::new().compact(true).write_to(&self.0, f).map_err(|_| fmt::Error)
+    ///     }
+    /// }
+    ///
+    /// assert_eq!(Tags(vec!["a", "b"]).to_string(), r#"["a","b"]"#);
+    /// ```
+    pub fn write_to<T, W>(&self, input: &T, output: &mut W) -> Result<()>
+    where
+        T: Serialize + ?Sized,
+        W: Write + ?Sized,
+    {
+        // Taken rather than borrowed, in case `input` writes with this
+        // method too.
+        let mut buffer = WRITE_BUFFER.take();
+        let written = self.serialize_into(input, &mut buffer).and_then(|()| {
+            output
+                .write_str(&buffer)
+                .map_err(|_| Error::custom("the output could not be written"))
+        });
+        if buffer.capacity() <= MAX_KEPT_CAPACITY {
+            buffer.clear();
+            WRITE_BUFFER.set(buffer);
+        }
+        written
+    }
+
+    /// The text between items, and between keys and their values.
+    fn separators_or_default(&self) -> (&str, &str) {
+        match &self.separators {
+            _ if self.canonical => (",", ":"),
+            Some((item, key)) => (item, key),
+            None => (", ", ": "),
+        }
+    }
+
+    /// Write with a [`Writer`] appending to `output`, leaving `output` as
+    /// it was if writing fails.
+    fn with_writer(
+        &self,
+        output: &mut String,
+        write: impl FnOnce(&Writer) -> Result<Span>,
+    ) -> Result<()> {
+        let start = output.len();
+        let writer = Writer::new(self, std::mem::take(output));
+        let written = write(&writer);
+        *output = writer.finish(start, &written);
+        written.map(drop)
+    }
+
+    /// Write the shared value at address `id` in full with `write`, failing
+    /// with [`ErrorKind::Cycle`] if it is already being written.
+    fn write_shared<R>(&self, id: usize, write: impl FnOnce() -> Result<R>) -> Result<R> {
+        /// Removes the address again however writing ends.
+        struct Writing(usize);
+
+        impl Drop for Writing {
+            fn drop(&mut self) {
+                WRITING_SHARED.with_borrow_mut(|writing| writing.remove(&self.0));
+            }
+        }
+
+        if !WRITING_SHARED.with_borrow_mut(|writing| writing.insert(id)) {
+            return Err(Error::new(ErrorKind::Cycle));
+        }
+        let _writing = Writing(id);
+        write()
+    }
+
+    /// Write with a [`Writer`] into a new string.
+    fn written(&self, write: impl FnOnce(&Writer) -> Result<Span>) -> Result<String> {
+        let mut output = String::with_capacity(INITIAL_CAPACITY);
+        self.with_writer(&mut output, write)?;
+        Ok(output)
+    }
+
+    /// Write the digits of an `i128` or `u128` with `digits`, quoted if
+    /// configured.
+    fn wide_integer(&self, output: &mut String, digits: impl FnOnce(&mut String)) {
+        if self.quote_wide_integers {
+            output.push('"');
+            digits(output);
+            output.push('"');
+        } else {
+            digits(output);
+        }
+    }
+
+    /// Write an integer, as the nearest `f64` if canonical and beyond the
+    /// integers an `f64` holds exactly.
+    fn integer(&self, negative: bool, magnitude: u128, output: &mut String) {
+        if self.canonical && magnitude > 1 << f64::MANTISSA_DIGITS {
+            let magnitude = magnitude as f64;
+            output.push_str(&canonical_number(&format!(
+                "{:e}",
+                if negative { -magnitude } else { magnitude }
+            )));
+            return;
+        }
+        output.push_str(IntegerBuffer::new().format(negative, magnitude));
+    }
+
+    /// Write a float, applying the non-finite mode to NaN and infinities.
+    ///
+    /// Finite floats are written with the fewest digits that read back as
+    /// the same value, like `ryu` writes them: with an exponent if very
+    /// large or small, as in `1e300` and `1.5e-7`, and otherwise in full
+    /// with at least one digit after the point, as in `100.0`, so that they
+    /// read back as floats rather than integers.
+    fn float<F>(&self, input: F, output: &mut String) -> Result<()>
+    where
+        F: Into<f64> + std::fmt::LowerExp + Copy,
+    {
+        let value = input.into();
+        let name = match value {
+            // The shortest digits of `input` itself, so an `f32` is written
+            // as the `f64` its digits parse to.
+            _ if value.is_finite() && self.canonical => {
+                output.push_str(&canonical_number(&format!("{input:e}")));
+                return Ok(());
+            }
+            _ if value.is_finite() => {
+                shortest_float(input, output);
+                return Ok(());
+            }
+            _ if value.is_nan() => "NaN",
+            _ if value > 0.0 => "Infinity",
+            _ => "-Infinity",
+        };
+        match self.non_finite {
+            _ if self.canonical => return Err(Error::new(ErrorKind::NonFinite { value: name })),
+            NonFiniteMode::Error => return Err(Error::new(ErrorKind::NonFinite { value: name })),
+            NonFiniteMode::Null => output.push_str("null"),
+            NonFiniteMode::String => {
+                let _ = write!(output, "\"{name}\"");
+            }
+        }
+        Ok(())
+    }
+
+    /// Escape `input` for use between double quotes, appending it to
+    /// `output`.
+    fn encode_string(&self, input: &str, output: &mut String) {
+        output.reserve(input.len());
+        for char in input.chars() {
+            match char {
+                '"' => output.push_str("\\\""),
+                '\\' => output.push_str("\\\\"),
+                '\n' => output.push_str("\\n"),
+                '\r' => output.push_str("\\r"),
+                '\t' => output.push_str("\\t"),
+                '\u{8}' => output.push_str("\\b"),
+                '\u{c}' => output.push_str("\\f"),
+                '\0'..='\u{1f}' => {
+                    let _ = write!(output, "\\u{:04x}", u32::from(char));
+                }
+                _ if self.canonical => output.push(char),
+                '\u{7f}' => output.push_str("\\u007f"),
+                '<' | '>' | '&' | '\u{2028}' | '\u{2029}' if self.html_safe => {
+                    let _ = write!(output, "\\u{:04x}", u32::from(char));
+                }
+                _ if self.ascii && !char.is_ascii() => {
+                    for unit in char.encode_utf16(&mut [0; 2]) {
+                        let _ = write!(output, "\\u{unit:04x}");
+                    }
+                }
+                _ => output.push(char),
+            }
+        }
+    }
+}
+
+/// The UTF-16 code units of a serialized key, which canonical output sorts
+/// entries by.
+fn utf16_key(key: &str) -> Vec<u16> {
+    let key = deserialize::Json::new()
+        .deserialize::<String>(key)
+        .unwrap_or_else(|_| key.to_owned());
+    key.encode_utf16().collect()
+}
+
+/// Lay out a finite number, given in Rust's shortest exponential form such
+/// as `-1.5e-7`, the way JavaScript's `Number.prototype.toString` does, as
+/// RFC 8785 requires.
+fn canonical_number(exponential: &str) -> String {
+    let (mantissa, exponent) = exponential.split_once('e').expect("exponential form");
+    let (sign, mantissa) = match mantissa.strip_prefix('-') {
+        Some(mantissa) => ("-", mantissa),
+        None => ("", mantissa),
+    };
+    let digits = mantissa.replace('.', "");
+    if digits.bytes().all(|digit| digit == b'0') {
+        return String::from("0");
+    }
+    // The position of the decimal point relative to the digits.
+    let point = exponent.parse::<i32>().expect("integer exponent") + 1;
+    let len = digits.len() as i32;
+    let layout = match point {
+        _ if len <= point && point <= 21 => digits + &"0".repeat((point - len) as usize),
+        1..=21 => format!(
+            "{}.{}",
+            &digits[..point as usize],
+            &digits[point as usize..]
+        ),
+        -5..=0 => format!("0.{}{digits}", "0".repeat(-point as usize)),
+        _ => {
+            let exponent = point - 1;
+            let exponent_sign = if exponent < 0 { '-' } else { '+' };
+            let (first, rest) = digits.split_at(1);
+            let fraction = if rest.is_empty() {
+                String::new()
+            } else {
+                format!(".{rest}")
+            };
+            format!("{first}{fraction}e{exponent_sign}{}", exponent.abs())
+        }
+    };
+    format!("{sign}{layout}")
+}
+
+/// Write the finite float `input` with its shortest digits, in full with a
+/// fraction if `1e-5 <= |input| < 1e16` and with an exponent otherwise.
+fn shortest_float(input: impl std::fmt::LowerExp, output: &mut String) {
+    // Rust's exponential form has the shortest digits, as in `-1.5e-7`, and
+    // takes at most 24 bytes, for subnormal `f64`s.
+    let start = output.len();
+    let _ = write!(output, "{input:e}");
+    let mut exponential = [0; 32];
+    let len = output.len() - start;
+    exponential[..len].copy_from_slice(&output.as_bytes()[start..]);
+    output.truncate(start);
+    let exponential = std::str::from_utf8(&exponential[..len]).expect("ASCII");
+    let (mantissa, exponent) = exponential.split_once('e').expect("exponential form");
+    let mantissa = match mantissa.strip_prefix('-') {
+        Some(mantissa) => {
+            output.push('-');
+            mantissa
+        }
+        None => mantissa,
+    };
+    let mut digits = [0; 24];
+    let mut len = 0;
+    for digit in mantissa.bytes().filter(|&byte| byte != b'.') {
+        digits[len] = digit;
+        len += 1;
+    }
+    let digits = std::str::from_utf8(&digits[..len]).expect("ASCII");
+    let exponent = exponent.parse::<i32>().expect("integer exponent");
+    // The position of the decimal point relative to the digits.
+    let point = exponent + 1;
+    let zeros = |output: &mut String, count: i32| (0..count).for_each(|_| output.push('0'));
+    match point {
+        -4..=0 => {
+            output.push_str("0.");
+            zeros(output, -point);
+            output.push_str(digits);
+        }
+        1..=16 if len as i32 <= point => {
+            output.push_str(digits);
+            zeros(output, point - len as i32);
+            output.push_str(".0");
+        }
+        1..=16 => {
+            let (integer, fraction) = digits.split_at(point as usize);
+            output.push_str(integer);
+            output.push('.');
+            output.push_str(fraction);
+        }
+        _ => {
+            let (first, rest) = digits.split_at(1);
+            output.push_str(first);
+            if !rest.is_empty() {
+                output.push('.');
+                output.push_str(rest);
+            }
+            let _ = write!(output, "e{exponent}");
+        }
+    }
+}
+
+/// The digit pairs `00` to `99`, for writing integers two digits at a time.
+const DIGIT_PAIRS: &[u8; 200] = b"00010203040506070809101112131415161718192021222324252627282930313233343536373839404142434445464748495051525354555657585960616263646566676869707172737475767778798081828384858687888990919293949596979899";
+
+/// Formats integers into a stack buffer, without the `fmt` machinery.
+struct IntegerBuffer {
+    /// Long enough for `i128::MIN`.
+    bytes: [u8; 40],
+}
+
+impl IntegerBuffer {
+    fn new() -> Self {
+        Self { bytes: [0; 40] }
+    }
+
+    /// Write `magnitude` from the end of the buffer, with a minus sign if
+    /// `negative`.
+    fn format(&mut self, negative: bool, mut magnitude: u128) -> &str {
+        let mut start = self.bytes.len();
+        let mut push_pair = |start: &mut usize, pair: usize| {
+            *start -= 2;
+            self.bytes[*start..*start + 2].copy_from_slice(&DIGIT_PAIRS[pair * 2..pair * 2 + 2]);
+        };
+        // 128-bit division is slow, so only the digits beyond u64 use it.
+        while magnitude > u128::from(u64::MAX) {
+            push_pair(&mut start, (magnitude % 100) as usize);
+            magnitude /= 100;
+        }
+        let mut magnitude = magnitude as u64;
+        while magnitude >= 100 {
+            push_pair(&mut start, (magnitude % 100) as usize);
+            magnitude /= 100;
+        }
+        if magnitude >= 10 {
+            push_pair(&mut start, magnitude as usize);
+        } else {
+            start -= 1;
+            self.bytes[start] = b'0' + magnitude as u8;
+        }
+        if negative {
+            start -= 1;
+            self.bytes[start] = b'-';
+        }
+        std::str::from_utf8(&self.bytes[start..]).expect("only ASCII is written")
+    }
+}
+
+macro_rules! write_through {
+    ($($method:ident($ty:ty))+) => {
+        $(
+            fn $method(&self, input: &$ty) -> Result<String> {
+                self.written(|writer| writer.$method(input))
+            }
+        )+
+    };
+}
+
+/// Each method returns a new string, with the values it is given, such as
+/// the elements of [`Serializer::visit_seq`], written with `self` as
+/// separate strings and copied in, since they may hold output `self`
+/// already produced, as serde values do. [`Json::serialize`] writes a whole
+/// value through into one buffer instead.
+impl Serializer for Json {
+    type Output = String;
+
+    write_through! {
+        visit_bool(bool)
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_i128(i128)
+        visit_isize(isize)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_u128(u128)
+        visit_usize(usize)
+        visit_f32(f32)
+        visit_f64(f64)
+        visit_char(char)
+        visit_str(str)
+        visit_os_str(OsStr)
+        visit_raw(str)
+    }
+
+    fn visit_unit(&self) -> Result<String> {
+        self.written(|writer| writer.visit_unit())
+    }
+
+    fn visit_option<T: Serialize>(&self, input: &Option<T>) -> Result<String> {
+        match input {
+            Some(value) => value.accept(self),
+            None => self.visit_unit(),
+        }
+    }
+
+    fn visit_seq<'a, T, I>(&self, input: I) -> Result<String>
+    where
+        T: Serialize + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        self.written(|writer| {
+            let elements = input.into_iter();
+            writer.array(elements.map(|element| writer.text(&element.accept(self)?, 0)))
+        })
+    }
+
+    fn visit_tuple<T: Tuple + ?Sized>(&self, input: &T) -> Result<String> {
+        self.written(|writer| {
+            let mut array = writer.open('[');
+            // The first element to fail the output limit, if any.
+            let mut written = Ok(());
+            input.serialize_elements(self, |element| {
+                if written.is_ok() {
+                    written = writer
+                        .text(&element, 0)
+                        .map(|element| writer.element(&mut array, element));
+                }
+            })?;
+            written?;
+            writer.close(array, ']')
+        })
+    }
+
+    fn visit_struct<T: Struct + ?Sized>(&self, input: &T) -> Result<String> {
+        self.written(|writer| {
+            writer.structure(input, |index| {
+                writer.text(&input.serialize_field(index, self)?, 0)
+            })
+        })
+    }
+
+    fn visit_variant(&self, variant: &'static str, content: String) -> Result<String> {
+        self.written(|writer| writer.variant(variant, || writer.text(&content, 0)))
+    }
+
+    fn visit_map<'a, K, V, I>(&self, input: I) -> Result<String>
+    where
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        I: IntoIterator<Item = (&'a K, &'a V)>,
+    {
+        self.written(|writer| {
+            writer.map(
+                input,
+                false,
+                |key| writer.text(&key.accept(self)?, 0),
+                |value| writer.text(&value.accept(self)?, 0),
+            )
+        })
+    }
+
+    fn visit_unordered_map<'a, K, V, I>(&self, input: I) -> Result<String>
+    where
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        I: IntoIterator<Item = (&'a K, &'a V)>,
+    {
+        self.written(|writer| {
+            writer.map(
+                input,
+                self.sort_maps,
+                |key| writer.text(&key.accept(self)?, 0),
+                |value| writer.text(&value.accept(self)?, 0),
+            )
+        })
+    }
+
+    /// Fails in [`SharedMode::Reference`], whose ids need the whole
+    /// document written by one [`Writer`].
+    fn visit_shared<T: Serialize + ?Sized>(&self, id: usize, input: &T) -> Result<String> {
+        match self.shared {
+            SharedMode::Inline => self.write_shared(id, || input.accept(self)),
+            SharedMode::Reference => Err(Error::custom(
+                "shared values can only be written as references by `Json::serialize` and the \
+                 methods like it",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::collections::{BTreeMap, BinaryHeap, HashMap, LinkedList, VecDeque};
+    use std::convert::Infallible;
+    use std::marker::PhantomData;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex, RwLock};
+
+    use super::*;
+    use crate::allocations;
+
+    #[test]
+    fn scalars() {
+        let json = Json::new();
+        assert_eq!(json.serialize(&true).unwrap(), "true");
+        assert_eq!(json.serialize(&-12i8).unwrap(), "-12");
+        assert_eq!(
+            json.serialize(&u128::MAX).unwrap(),
+            "340282366920938463463374607431768211455"
+        );
+        assert_eq!(json.serialize(&1.5f64).unwrap(), "1.5");
+        assert_eq!(json.serialize(&'x').unwrap(), "\"x\"");
+        assert_eq!(json.serialize(&()).unwrap(), "null");
+        assert_eq!(json.serialize(&PhantomData::<str>).unwrap(), "null");
+    }
+
+    #[test]
+    fn integers_match_display() {
+        let json = Json::new();
+        for value in [
+            0,
+            7,
+            -7,
+            10,
+            99,
+            100,
+            -101,
+            i64::MIN as i128,
+            i128::MIN,
+            i128::MAX,
+        ] {
+            assert_eq!(json.serialize(&value).unwrap(), value.to_string());
+        }
+        for value in [u64::MAX as u128, u64::MAX as u128 + 1, u128::MAX] {
+            assert_eq!(json.serialize(&value).unwrap(), value.to_string());
+        }
+        assert_eq!(json.serialize(&i8::MIN).unwrap(), "-128");
+        assert_eq!(json.serialize(&usize::MAX).unwrap(), usize::MAX.to_string());
+    }
+
+    #[test]
+    fn wide_integers_can_be_quoted() {
+        let json = Json::new().quote_wide_integers(true);
+        let value = (i128::MIN, u128::MAX, -1i64);
+        assert_eq!(
+            json.serialize(&value).unwrap(),
+            r#"["-170141183460469231731687303715884105728", "340282366920938463463374607431768211455", -1]"#
+        );
+        let map = BTreeMap::from([(7u128, 8i128)]);
+        assert_eq!(json.serialize(&map).unwrap(), r#"{"7": "8"}"#);
+    }
+
+    #[test]
+    fn separators() {
+        let value = (vec![1, 2], BTreeMap::from([("a", ()), ("b", ())]));
+        let compact = Json::new().compact(true);
+        assert_eq!(
+            compact.serialize(&value).unwrap(),
+            r#"[[1,2],{"a":null,"b":null}]"#
+        );
+        assert_eq!(
+            compact.compact(false).serialize(&value).unwrap(),
+            r#"[[1, 2], {"a": null, "b": null}]"#
+        );
+        let custom = Json::new().compact(true).separators(" ,", "\t:");
+        assert_eq!(
+            custom.serialize(&value).unwrap(),
+            "[[1 ,2] ,{\"a\"\t:null ,\"b\"\t:null}]"
+        );
+        let canonical = custom.canonical(true);
+        assert_eq!(
+            canonical.serialize(&value).unwrap(),
+            r#"[[1,2],{"a":null,"b":null}]"#
+        );
+    }
+
+    #[test]
+    fn pretty() {
+        let value = BTreeMap::from([("a", vec![vec![1, 2], vec![]]), ("b", vec![])]);
+        let json = Json::new().pretty(true);
+        assert_eq!(
+            json.serialize(&value).unwrap(),
+            "{\n  \"a\": [\n    [\n      1,\n      2\n    ],\n    []\n  ],\n  \"b\": []\n}"
+        );
+        assert_eq!(json.document(&"x\ny").unwrap(), r#""x\ny""#);
+        let json = json
+            .newline_mode(NewlineMode::CrLf)
+            .trailing_newline(true)
+            .separators(", ", " = ");
+        assert_eq!(
+            json.document(&BTreeMap::from([("a", [1])])).unwrap(),
+            "{\r\n  \"a\" = [\r\n    1\r\n  ]\r\n}\r\n"
+        );
+        assert_eq!(json.canonical(true).document(&[1, 2]).unwrap(), "[1,2]\r\n");
+    }
+
+    #[test]
+    fn values_written_out_of_order() {
+        // Serializes every element before passing any of them on, last
+        // first, so each is written before its place in the output is.
+        struct Reversed(Vec<Vec<u8>>);
+        impl Tuple for Reversed {
+            fn len(&self) -> usize {
+                self.0.len()
+            }
+            fn serialize_elements<S: Serializer>(
+                &self,
+                serializer: &S,
+                element: impl FnMut(S::Output),
+            ) -> Result<()> {
+                let outputs: Vec<_> = self.0.iter().map(|e| e.accept(serializer)).collect();
+                outputs
+                    .into_iter()
+                    .rev()
+                    .collect::<Result<Vec<_>>>()?
+                    .into_iter()
+                    .for_each(element);
+                Ok(())
+            }
+        }
+        struct Variant(Reversed);
+        impl Serialize for Variant {
+            fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+                let content = serializer.visit_tuple(&self.0)?;
+                serializer.visit_variant("V", content)
+            }
+        }
+        let value = Variant(Reversed(vec![vec![1], vec![], vec![2, 3]]));
+        let json = Json::new().pretty(true);
+        assert_eq!(
+            json.serialize(&value).unwrap(),
+            "{\n  \"V\": [\n    [\n      2,\n      3\n    ],\n    [],\n    [\n      1\n    ]\n  ]\n}"
+        );
+        let mut output = String::from("v = ");
+        let json = Json::new().compact(true);
+        json.serialize_into(&[value], &mut output).unwrap();
+        assert_eq!(output, r#"v = [{"V":[[2,3],[],[1]]}]"#);
+    }
+
+    #[test]
+    fn floats() {
+        let json = Json::new().compact(true);
+        let floats = [
+            1.0, -0.0, 123.0, 0.1, 1e15, 1e16, 1.5e16, 1e-5, 1.5e-6, 1e300, -1e-300,
+        ];
+        assert_eq!(
+            json.serialize(&floats).unwrap(),
+            "[1.0,-0.0,123.0,0.1,1000000000000000.0,1e16,1.5e16,0.00001,1.5e-6,1e300,-1e-300]"
+        );
+        let extremes = (
+            f64::MAX,
+            f64::MIN_POSITIVE,
+            5e-324,
+            0.1f32,
+            f32::MAX,
+            16777216f32,
+        );
+        assert_eq!(
+            json.serialize(&extremes).unwrap(),
+            "[1.7976931348623157e308,2.2250738585072014e-308,5e-324,0.1,3.4028235e38,16777216.0]"
+        );
+
+        // Every float reads back as itself, and as a float.
+        let read = deserialize::Json::new();
+        let mut bits = 0x0123_4567_89ab_cdefu64;
+        for _ in 0..2000 {
+            bits = bits
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let value = f64::from_bits(bits);
+            if !value.is_finite() {
+                continue;
+            }
+            let text = json.serialize(&value).unwrap();
+            assert!(text.len() <= 24, "{text}");
+            assert_eq!(read.deserialize::<f64>(&text).unwrap(), value, "{text}");
+            let value = read.deserialize::<crate::Value>(&text).unwrap();
+            assert!(!value.as_number().unwrap().is_integer(), "{text}");
+        }
+        let whole = crate::Value::Number(crate::value::Number::from_f64(1.0).unwrap());
+        let pair = (Json::new(), read);
+        crate::round_trip::check(&whole, &pair, &pair).unwrap();
+        let text = pair.0.serialize(&whole).unwrap();
+        let value = pair.1.deserialize::<crate::Value>(&text).unwrap();
+        assert!(!value.as_number().unwrap().is_integer());
+    }
+
+    #[test]
+    fn canonical() {
+        let json = Json::new().canonical(true).ascii(true).html_safe(true);
+        let map = HashMap::from([("z", 1), ("\u{1F600}", 2), ("\u{fb01}", 3), ("a\n", 4)]);
+        assert_eq!(
+            json.serialize(&map).unwrap(),
+            "{\"a\\n\":4,\"z\":1,\"\u{1F600}\":2,\"\u{fb01}\":3}"
+        );
+        let floats = [1e21, 1e-7, 123.0, 0.1, -1.5e-6, 1e20, 5e-324, -0.0];
+        assert_eq!(
+            json.serialize(&floats).unwrap(),
+            "[1e+21,1e-7,123,0.1,-0.0000015,100000000000000000000,5e-324,0]"
+        );
+        let integers = (u64::MAX, i64::MIN + 1, 9007199254740992u64, -7i8);
+        assert_eq!(
+            json.serialize(&integers).unwrap(),
+            "[18446744073709552000,-9223372036854776000,9007199254740992,-7]"
+        );
+        assert_eq!(
+            json.serialize("<\u{7f}\u{1f}>").unwrap(),
+            "\"<\u{7f}\\u001f>\""
+        );
+        let error = json
+            .non_finite_mode(NonFiniteMode::Null)
+            .serialize(&f64::NAN);
+        assert_eq!(
+            error.unwrap_err().kind(),
+            &ErrorKind::NonFinite { value: "NaN" }
+        );
+    }
+
+    #[test]
+    fn non_finite_floats() {
+        let error = Json::new().serialize(&[0.5, f64::NAN]).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::NonFinite { value: "NaN" });
+        assert_eq!(error.to_string(), "NaN cannot be written as a number");
+        let floats = (f32::INFINITY, f64::NEG_INFINITY, f64::NAN, -0.0f32);
+        let null = Json::new().non_finite_mode(NonFiniteMode::Null);
+        assert_eq!(null.serialize(&floats).unwrap(), "[null, null, null, -0.0]");
+        let string = Json::new().non_finite_mode(NonFiniteMode::String);
+        assert_eq!(
+            string.serialize(&floats).unwrap(),
+            r#"["Infinity", "-Infinity", "NaN", -0.0]"#
+        );
+    }
+
+    #[test]
+    fn strings_are_escaped() {
+        let json = Json::new();
+        assert_eq!(
+            json.serialize("say \"hi\" \\o/").unwrap(),
+            r#""say \"hi\" \\o/""#
+        );
+        assert_eq!(
+            json.serialize("a\nb\r\tc\u{8}\u{c}/").unwrap(),
+            r#""a\nb\r\tc\b\f/""#
+        );
+        assert_eq!(
+            json.serialize("\u{1}\u{1f}\u{7f}é").unwrap(),
+            r#""\u0001\u001f\u007fé""#
+        );
+        let map = BTreeMap::from([("\0", 'é')]);
+        let json = Json::new().ascii(true);
+        assert_eq!(json.serialize(&map).unwrap(), r#"{"\u0000": "\u00e9"}"#);
+        assert_eq!(json.serialize("a𝄞").unwrap(), r#""a\ud834\udd1e""#);
+        let map = BTreeMap::from([("<b>", "x\u{2028}y\u{2029}&")]);
+        let json = Json::new().html_safe(true);
+        assert_eq!(
+            json.serialize(&map).unwrap(),
+            r#"{"\u003cb\u003e": "x\u2028y\u2029\u0026"}"#
+        );
+    }
+
+    #[test]
+    fn options() {
+        let json = Json::new();
+        assert_eq!(json.serialize(&Some(3u8)).unwrap(), "3");
+        assert_eq!(json.serialize(&None::<u8>).unwrap(), "null");
+    }
+
+    #[test]
+    fn bounds_and_control_flow() {
+        use std::ops::{Bound, ControlFlow};
+
+        let json = Json::new();
+        let bounds = [Bound::Included(1u8), Bound::Excluded(2), Bound::Unbounded];
+        assert_eq!(
+            json.serialize(&bounds).unwrap(),
+            r#"[{"Included": 1}, {"Excluded": 2}, "Unbounded"]"#
+        );
+        let flows: [ControlFlow<&str, u8>; 2] = [ControlFlow::Continue(3), ControlFlow::Break("x")];
+        assert_eq!(
+            json.serialize(&flows).unwrap(),
+            r#"[{"Continue": 3}, {"Break": "x"}]"#
+        );
+    }
+
+    #[test]
+    fn sequences_and_tuples() {
+        let json = Json::new();
+        assert_eq!(json.serialize(&Vec::<Infallible>::new()).unwrap(), "[]");
+        assert_eq!(
+            json.serialize(&vec![vec![1, 2], vec![3]]).unwrap(),
+            "[[1, 2], [3]]"
+        );
+        assert_eq!(json.serialize(&(1u8,)).unwrap(), "[1]");
+        assert_eq!(
+            json.serialize(&(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, String::from("twelve")))
+                .unwrap(),
+            r#"[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, "twelve"]"#
+        );
+        assert_eq!(
+            json.serialize(&(1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 'p'))
+                .unwrap(),
+            r#"[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, "p"]"#
+        );
+    }
+
+    #[test]
+    fn os_strings() {
+        let json = Json::new();
+        assert_eq!(json.serialize(OsStr::new("dir/é")).unwrap(), r#""dir/é""#);
+        #[cfg(unix)]
+        {
+            use std::os::unix::ffi::OsStrExt;
+            let input = OsStr::from_bytes(b"a\xff");
+            assert_eq!(json.serialize(input).unwrap(), "[97, 255]");
+            let json = Json::new().os_str_mode(OsStrMode::Lossy);
+            assert_eq!(json.serialize(input).unwrap(), "\"a\u{fffd}\"");
+        }
+    }
+
+    #[test]
+    fn references() {
+        let json = Json::new();
+        let value = 5u8;
+        assert_eq!(json.serialize(&&value).unwrap(), "5");
+        assert_eq!(
+            json.serialize(&(&mut 6u8, "str", &[&7u8])).unwrap(),
+            r#"[6, "str", [7]]"#
+        );
+    }
+
+    #[test]
+    fn smart_pointers() {
+        let json = Json::new();
+        assert_eq!(json.serialize(&Box::new(1u8)).unwrap(), "1");
+        assert_eq!(json.serialize(&Rc::<str>::from("rc")).unwrap(), r#""rc""#);
+        assert_eq!(
+            json.serialize(&Arc::new(vec![Box::new(true)])).unwrap(),
+            "[true]"
+        );
+    }
+
+    #[test]
+    fn shared_values() {
+        /// A node that can point back to itself.
+        struct Node(RefCell<Option<Rc<Node>>>);
+
+        impl Serialize for Node {
+            fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+                self.0.accept(serializer)
+            }
+        }
+
+        let json = Json::new();
+        let leaf = Arc::new("leaf");
+        let tree = vec![(leaf.clone(), Arc::new(1)), (leaf.clone(), Arc::new(2))];
+        let inline = r#"[["leaf", 1], ["leaf", 2]]"#;
+        assert_eq!(json.serialize(&tree).unwrap(), inline);
+        assert_eq!(Serializer::serialize(&json, &tree).unwrap(), inline);
+        let node = Rc::new(Node(RefCell::new(None)));
+        *node.0.borrow_mut() = Some(node.clone());
+        for _ in 0..2 {
+            let error = json.serialize(&node).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::Cycle);
+            let error = Serializer::serialize(&json, &node).unwrap_err();
+            assert_eq!(error.kind(), &ErrorKind::Cycle);
+        }
+
+        let json = Json::new().shared_mode(SharedMode::Reference);
+        assert_eq!(
+            json.serialize(&tree).unwrap(),
+            r#"[[{"$id": 1, "$value": "leaf"}, 1], [{"$ref": 1}, 2]]"#
+        );
+        assert_eq!(
+            json.serialize(&(node.clone(), leaf.clone(), node.clone()))
+                .unwrap(),
+            r#"[{"$id": 1, "$value": {"$ref": 1}}, {"$id": 2, "$value": "leaf"}, {"$ref": 1}]"#
+        );
+        let erased: &dyn super::super::ErasedSerialize = &node;
+        assert_eq!(
+            json.serialize(erased).unwrap(),
+            r#"{"$id": 1, "$value": {"$ref": 1}}"#
+        );
+        assert!(Serializer::serialize(&json, &tree).is_err());
+        let pretty = json
+            .clone()
+            .pretty(true)
+            .serialize(&[&leaf, &leaf])
+            .unwrap();
+        assert_eq!(
+            pretty,
+            "[\n  {\n    \"$id\": 1,\n    \"$value\": \"leaf\"\n  },\n  {\n    \"$ref\": 1\n  }\n]"
+        );
+
+        *node.0.borrow_mut() = None;
+        assert_eq!(Json::new().serialize(&node).unwrap(), "null");
+    }
+
+    #[test]
+    fn interior_mutability() {
+        let json = Json::new();
+        assert_eq!(json.serialize(&Cell::new(1u8)).unwrap(), "1");
+        let cell = RefCell::new(vec![2u8]);
+        assert_eq!(json.serialize(&cell).unwrap(), "[2]");
+        let borrowed = cell.borrow_mut();
+        let error = json.serialize(&cell).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "value mutably borrowed while serializing"
+        );
+        drop(borrowed);
+        assert_eq!(json.serialize(&RwLock::new(true)).unwrap(), "true");
+        let mutex = Arc::new(Mutex::new(3u8));
+        let poisoner = Arc::clone(&mutex);
+        std::thread::spawn(move || {
+            let _guard = poisoner.lock();
+            panic!("poison the lock");
+        })
+        .join()
+        .unwrap_err();
+        assert!(mutex.is_poisoned());
+        let error = json.serialize(&mutex).unwrap_err();
+        assert_eq!(error.to_string(), "lock poisoned while serializing");
+    }
+
+    #[test]
+    fn collections() {
+        let json = Json::new();
+        let deque: VecDeque<u8> = (1..=3).collect();
+        assert_eq!(json.serialize(&deque).unwrap(), "[1, 2, 3]");
+        let list: LinkedList<bool> = [true, false].into_iter().collect();
+        assert_eq!(json.serialize(&list).unwrap(), "[true, false]");
+        assert_eq!(json.serialize(&BinaryHeap::from([7u8])).unwrap(), "[7]");
+    }
+
+    #[test]
+    fn maps() {
+        let json = Json::new();
+        let map = BTreeMap::from([("a\"b", vec![1u8]), ("c", vec![])]);
+        assert_eq!(json.serialize(&map).unwrap(), r#"{"a\"b": [1], "c": []}"#);
+        let nested = BTreeMap::from([(2u8, BTreeMap::from([(false, ())]))]);
+        assert_eq!(
+            json.serialize(&nested).unwrap(),
+            r#"{"2": {"false": null}}"#
+        );
+        assert_eq!(json.serialize(&HashMap::<u8, u8>::new()).unwrap(), "{}");
+        let single = HashMap::from([('x', 1.5)]);
+        assert_eq!(json.serialize(&single).unwrap(), r#"{"x": 1.5}"#);
+    }
+
+    #[test]
+    fn map_keys_round_trip() {
+        fn round_trip<K, V>(map: &BTreeMap<K, V>)
+        where
+            K: Serialize + deserialize::Deserialize + Ord + std::fmt::Debug,
+            V: Serialize + deserialize::Deserialize + PartialEq + std::fmt::Debug,
+        {
+            let text = Json::new().serialize(map).unwrap();
+            let read: BTreeMap<K, V> = deserialize::Json::new().deserialize(&text).unwrap();
+            assert_eq!(&read, map, "{text}");
+        }
+
+        round_trip(&BTreeMap::from([(-3i64, 'a'), (u32::MAX.into(), 'b')]));
+        round_trip(&BTreeMap::from([(false, 1u8), (true, 2)]));
+        round_trip(&BTreeMap::from([('"', 1u8), ('\n', 2)]));
+        round_trip(&BTreeMap::from([(String::from("k"), 1u8)]));
+        round_trip(&BTreeMap::from([(i128::MIN, BTreeMap::from([(7u8, ())]))]));
+
+        // Keys that would be quoted into strings that read back as
+        // something else fail instead.
+        let json = Json::new();
+        let unsupported = |text: Result<String>| text.unwrap_err().kind().clone();
+        assert_eq!(
+            unsupported(json.serialize(&BTreeMap::from([(vec![1u8], 2u8)]))),
+            ErrorKind::UnsupportedKey { found: "an array" }
+        );
+        assert_eq!(
+            unsupported(json.serialize(&BTreeMap::from([(None::<u8>, 3u8)]))),
+            ErrorKind::UnsupportedKey { found: "null" }
+        );
+        assert_eq!(
+            unsupported(json.serialize(&BTreeMap::from([((), 3u8)]))),
+            ErrorKind::UnsupportedKey { found: "null" }
+        );
+        let nested = BTreeMap::from([(1u8, BTreeMap::from([(BTreeMap::<u8, u8>::new(), 1u8)]))]);
+        assert_eq!(
+            unsupported(json.clone().pretty(true).serialize(&nested)),
+            ErrorKind::UnsupportedKey { found: "an object" }
+        );
+        let error = json
+            .serialize(&BTreeMap::from([(None::<u8>, 2u8)]))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "map keys must be strings, numbers or bools, not null"
+        );
+    }
+
+    #[test]
+    fn structs() {
+        struct Pair {
+            name: &'static str,
+            inner: Option<Box<Pair>>,
+        }
+
+        impl Struct for Pair {
+            fn name(&self) -> &'static str {
+                "Pair"
+            }
+
+            fn fields(&self) -> &'static [Field] {
+                const FIELDS: &[Field] = &[
+                    Field::new("name"),
+                    Field {
+                        name: "inner",
+                        views: &["deep"],
+                    },
+                ];
+                FIELDS
+            }
+
+            fn serialize_field<S: Serializer>(
+                &self,
+                index: usize,
+                serializer: &S,
+            ) -> Result<S::Output> {
+                match index {
+                    0 => serializer.serialize(self.name),
+                    _ => serializer.serialize(&self.inner),
+                }
+            }
+        }
+
+        impl Serialize for Pair {
+            fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+                serializer.visit_struct(self)
+            }
+        }
+
+        let inner = Pair {
+            name: "b",
+            inner: None,
+        };
+        let pair = Pair {
+            name: "a",
+            inner: Some(Box::new(inner)),
+        };
+        assert_eq!(
+            Json::new().serialize(&pair).unwrap(),
+            r#"{"name": "a", "inner": {"name": "b", "inner": null}}"#
+        );
+        let shallow = Json::new().field_filter(|field| field.in_view("shallow"));
+        assert_eq!(shallow.serialize(&pair).unwrap(), r#"{"name": "a"}"#);
+        let deep = Json::new().field_filter(|field| field.in_view("deep"));
+        assert_eq!(
+            deep.serialize(&pair).unwrap(),
+            Json::new().serialize(&pair).unwrap()
+        );
+    }
+
+    #[test]
+    fn into_buffers() {
+        let json = Json::new();
+        let mut output = String::from("values: ");
+        json.serialize_into(&[1, 2], &mut output).unwrap();
+        json.serialize_into("x", &mut output).unwrap();
+        assert_eq!(output, r#"values: [1, 2]"x""#);
+        assert!(json.serialize_into(&f64::NAN, &mut output).is_err());
+        assert_eq!(output, r#"values: [1, 2]"x""#);
+
+        struct Closed;
+        impl Write for Closed {
+            fn write_str(&mut self, _: &str) -> std::fmt::Result {
+                Err(std::fmt::Error)
+            }
+        }
+        let error = json.write_to(&1, &mut Closed).unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::Custom(_)));
+    }
+
+    #[test]
+    fn output_limit() {
+        let limit = ErrorKind::OutputLimit { limit: 12 };
+        let json = Json::new().max_output_len(12);
+        let map = BTreeMap::from([("a", vec![1, 2]), ("b", vec![])]);
+        // `{"a": [1, 2], "b": []}` takes 22 bytes.
+        assert_eq!(json.serialize(&map).unwrap_err().kind(), &limit);
+        assert_eq!(json.serialize("0123456789").unwrap(), r#""0123456789""#);
+        assert_eq!(json.serialize("0123456789a").unwrap_err().kind(), &limit);
+        // Exceeded only by the closing bracket.
+        assert_eq!(json.serialize(&[123456, 12]).unwrap(), "[123456, 12]");
+        assert_eq!(json.serialize(&[1234567, 12]).unwrap_err().kind(), &limit);
+        // Through the generic methods, which write each value on its own.
+        let tuple = ("abcd", "efgh");
+        assert_eq!(
+            Serializer::visit_tuple(&json, &tuple).unwrap_err().kind(),
+            &limit
+        );
+        let pretty = json.clone().pretty(true);
+        assert_eq!(pretty.serialize(&[1, 2]).unwrap(), "[\n  1,\n  2\n]");
+        assert_eq!(pretty.serialize(&[1, 2, 3]).unwrap_err().kind(), &limit);
+
+        let mut output = String::from("text before the value");
+        assert_eq!(
+            json.serialize_into(&[1, 2, 3, 4, 5], &mut output)
+                .unwrap_err()
+                .kind(),
+            &limit
+        );
+        assert_eq!(output, "text before the value");
+        json.serialize_into(&[1, 2], &mut output).unwrap();
+        assert_eq!(output, "text before the value[1, 2]");
+        let mut text = String::new();
+        assert_eq!(json.write_to(&map, &mut text).unwrap_err().kind(), &limit);
+        assert_eq!(text, "");
+    }
+
+    #[test]
+    fn write_to_reuses_its_buffer() {
+        /// Written as the text of its JSON, itself written with `write_to`.
+        struct Embedded(Vec<u8>);
+        impl Serialize for Embedded {
+            fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+                let mut text = String::new();
+                Json::new().write_to(&self.0, &mut text)?;
+                serializer.visit_str(&text)
+            }
+        }
+
+        let json = Json::new();
+        let value = (7u8, "seven", [7.5, -7.0], Some(true), ["a", "b\n"]);
+        let mut output = String::with_capacity(4096);
+        json.write_to(&value, &mut output).unwrap();
+        let first = output.clone();
+        let allocations = allocations::count(|| {
+            for _ in 0..10 {
+                output.clear();
+                json.write_to(&value, &mut output).unwrap();
+            }
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(output, first);
+        assert_eq!(output, json.serialize(&value).unwrap());
+
+        // A value written with `write_to` inside `write_to` gets a buffer
+        // of its own.
+        let mut nested = String::new();
+        json.write_to(&(Embedded(vec![1]), 2), &mut nested).unwrap();
+        assert_eq!(nested, r#"["[1]", 2]"#);
+    }
+
+    #[test]
+    fn allocations_per_document() {
+        let json = Json::new();
+        let tuple = (
+            1u8, 2u16, 3u32, 4u64, -5i8, -6i16, -7i32, -8i64, 9usize, 10isize, 11u128, 1.5,
+        );
+        let mut output = json.serialize(&tuple).unwrap();
+        assert_eq!(output, "[1, 2, 3, 4, -5, -6, -7, -8, 9, 10, 11, 1.5]");
+        let allocations = allocations::count(|| output = json.serialize(&tuple).unwrap());
+        assert_eq!(allocations, 1);
+        let allocations = allocations::count(|| {
+            output.clear();
+            json.serialize_into(&tuple, &mut output).unwrap();
+        });
+        assert_eq!(allocations, 0);
+
+        // Sorting and rewriting the entries reuses the writer's buffers.
+        let json = Json::new().pretty(true).sort_maps(true);
+        let map = HashMap::from([
+            (
+                "b",
+                HashMap::from([(2u8, vec![true, false]), (1, Vec::new())]),
+            ),
+            ("a", HashMap::new()),
+        ]);
+        let mut output = json.serialize(&map).unwrap();
+        let first = output.clone();
+        let allocations = allocations::count(|| output = json.serialize(&map).unwrap());
+        assert_eq!(allocations, 1);
+        let allocations = allocations::count(|| {
+            output.clear();
+            json.serialize_into(&map, &mut output).unwrap();
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(output, first);
+        assert!(output.starts_with("{\n  \"a\": {},\n  \"b\": {\n    \"1\": [],"));
+    }
+
+    #[test]
+    fn sorted_maps() {
+        let json = Json::new().sort_maps(true);
+        let map: HashMap<u8, bool> = (0..20).map(|key| (key, key % 2 == 0)).collect();
+        let output = json.serialize(&map).unwrap();
+        assert!(output.starts_with(r#"{"0": true, "1": false, "10": true, "11": false, "#));
+        assert_eq!(output, json.serialize(&map.clone()).unwrap());
+        let ordered = BTreeMap::from([(2u8, ()), (10, ())]);
+        assert_eq!(
+            json.serialize(&ordered).unwrap(),
+            r#"{"2": null, "10": null}"#
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Rewriting JSON text after some of the values in it changed.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+use super::Json;
+use crate::deserialize::{self, lexer::TokenKind, Deserializer};
+use crate::error::{Error, ErrorKind, Result};
+use crate::value::Value;
+
+impl Json {
+    /// Rewrite `original`, the JSON text of a document, to hold `value`, the
+    /// document with the values at the JSON pointers in `changes` changed,
+    /// writing only those values anew.
+    ///
+    /// The rest of the text is kept as it was, with its layout, comments
+    /// and the spelling of its numbers, so a large document edited a value
+    /// at a time need not be written out whole. A value is written with
+    /// this configuration, each line after its first indented like the line
+    /// it starts on. Where an entry was added or removed, its whole object
+    /// or array is written anew, as is an array whose length changed, since
+    /// the elements after the change moved.
+    ///
+    /// Fails with [`ErrorKind::Syntax`] if `original` is not JSON, which
+    /// may have comments, or a change is not a JSON pointer.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    /// use shallot::{deserialize, Value};
+    ///
+    /// let original = "{\n  \"name\": \"demo\", // the title\n  \"size\": 1e3,\n  \"tags\": [\"a\"]\n}";
+    /// let mut value: Value = deserialize::Json::new().comments(true).deserialize(original)?;
+    /// *value.pointer_mut("/name").unwrap() = Value::from("renamed");
+    /// value.pointer_mut("/tags").and_then(Value::as_array_mut).unwrap().push(Value::from("b"));
+    /// let rewritten = Json::new().pretty(true).reserialize(original, &value, ["/name", "/tags/1"])?;
+    /// assert_eq!(
+    ///     rewritten,
+    ///     "{\n  \"name\": \"renamed\", // the title\n  \"size\": 1e3,\n  \"tags\": [\n    \"a\",\n    \"b\"\n  ]\n}",
+    /// );
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn reserialize<I>(&self, original: &str, value: &Value, changes: I) -> Result<String>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let reader = deserialize::Json::new().comments(true);
+        let mut parser = reader.parser(original);
+        parser.visit_raw(|_| Ok(()))?;
+        parser.finish()?;
+        let changes: Vec<String> = changes
+            .into_iter()
+            .map(|change| change.as_ref().to_owned())
+            .collect();
+        let mut wanted = HashSet::new();
+        for change in &changes {
+            check_pointer(change)?;
+            wanted.extend(prefixes(change));
+        }
+        let found = find(&reader, original, &wanted)?;
+        let mut targets: Vec<&str> = changes
+            .iter()
+            .map(|change| target(change, &found, value))
+            .collect();
+        // Sorted, values come after the ones they are inside, which cover
+        // them.
+        targets.sort_unstable();
+        targets.dedup();
+        let mut rewritten: Vec<(Range<usize>, &Value)> = Vec::new();
+        let mut kept: Vec<&str> = Vec::new();
+        for target in targets {
+            if kept.iter().any(|outer| within(target, outer)) {
+                continue;
+            }
+            kept.push(target);
+            let value = value.pointer(target).expect("targets are in the new value");
+            rewritten.push((found[target].span.clone(), value));
+        }
+        rewritten.sort_unstable_by_key(|(span, _)| span.start);
+
+        let mut output = String::with_capacity(original.len());
+        let mut copied = 0;
+        for (span, value) in rewritten {
+            output.push_str(&original[copied..span.start]);
+            let line = original[..span.start]
+                .rfind('\n')
+                .map_or(0, |newline| newline + 1);
+            let indent_len = original[line..span.start]
+                .find(|char| char != ' ' && char != '\t')
+                .unwrap_or(span.start - line);
+            let indent = &original[line..line + indent_len];
+            let text = self.serialize(value)?;
+            for (index, text_line) in text.split('\n').enumerate() {
+                if index > 0 {
+                    output.push('\n');
+                    output.push_str(indent);
+                }
+                output.push_str(text_line);
+            }
+            copied = span.end;
+        }
+        output.push_str(&original[copied..]);
+        Ok(output)
+    }
+}
+
+/// Where a value is in the original text, and its shape.
+struct Found {
+    span: Range<usize>,
+    shape: Shape,
+}
+
+/// What a value in the original text is, as far as finding the values
+/// inside it goes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    Array(usize),
+    Object,
+    Scalar,
+}
+
+/// Fail unless `pointer` is a JSON pointer.
+fn check_pointer(pointer: &str) -> Result<()> {
+    let mut escapes = pointer.match_indices('~');
+    let escaped =
+        escapes.all(|(at, _)| matches!(pointer.as_bytes().get(at + 1), Some(b'0' | b'1')));
+    match (pointer.is_empty() || pointer.starts_with('/')) && escaped {
+        true => Ok(()),
+        false => Err(Error::new(ErrorKind::Syntax {
+            expected: "JSON pointer",
+        })),
+    }
+}
+
+/// The pointers of the values `pointer` passes through, from the whole
+/// document to the value itself.
+fn prefixes(pointer: &str) -> impl Iterator<Item = &str> {
+    let ends = pointer.match_indices('/').map(|(at, _)| at);
+    ends.chain([pointer.len()]).map(|end| &pointer[..end])
+}
+
+/// Whether the value at `inner` is `outer` or inside it.
+fn within(inner: &str, outer: &str) -> bool {
+    inner
+        .strip_prefix(outer)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// The pointer of the value to write anew for `change`: the value itself,
+/// or the nearest value around it that does not have the shape it had.
+fn target<'c>(change: &'c str, found: &HashMap<String, Found>, value: &Value) -> &'c str {
+    let mut outer = "";
+    for prefix in prefixes(change) {
+        let (Some(old), Some(new)) = (found.get(prefix), value.pointer(prefix)) else {
+            return outer;
+        };
+        let same_shape = match old.shape {
+            Shape::Array(len) => new.as_array().is_some_and(|array| array.len() == len),
+            Shape::Object => new.as_object().is_some(),
+            Shape::Scalar => true,
+        };
+        if !same_shape {
+            return prefix;
+        }
+        outer = prefix;
+    }
+    change
+}
+
+/// Where the values at the pointers in `wanted` are in `text`, a valid
+/// document, found in one pass over its tokens.
+fn find(
+    reader: &deserialize::Json,
+    text: &str,
+    wanted: &HashSet<&str>,
+) -> Result<HashMap<String, Found>> {
+    /// An array or object the pass is within.
+    struct Open {
+        start: usize,
+        array: bool,
+        len: usize,
+        /// The length of the pointer outside it.
+        outer_len: usize,
+    }
+
+    let mut found = HashMap::new();
+    let mut record = |pointer: &str, span: Range<usize>, shape: Shape| {
+        if wanted.contains(pointer) {
+            found.insert(pointer.to_owned(), Found { span, shape });
+        }
+    };
+    let mut pointer = String::new();
+    let mut open: Vec<Open> = Vec::new();
+    // The key of the entry whose value is next.
+    let mut key: Option<String> = None;
+    for token in reader.lexer(text) {
+        let token = token?;
+        let start = token.position.offset;
+        let end = start + token.text.len();
+        let in_object = open.last().is_some_and(|open| !open.array);
+        match token.kind {
+            TokenKind::Comment | TokenKind::Colon | TokenKind::Comma => continue,
+            TokenKind::String if in_object && key.is_none() => {
+                key = Some(reader.deserialize(token.text)?);
+                continue;
+            }
+            TokenKind::EndArray | TokenKind::EndObject => {
+                let closed = open.pop().expect("the document is valid");
+                let shape = match closed.array {
+                    true => Shape::Array(closed.len),
+                    false => Shape::Object,
+                };
+                record(&pointer, closed.start..end, shape);
+                pointer.truncate(closed.outer_len);
+                continue;
+            }
+            _ => {}
+        }
+        let outer_len = pointer.len();
+        if let Some(outer) = open.last_mut() {
+            pointer.push('/');
+            match key.take() {
+                Some(key) => {
+                    pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+                }
+                None => {
+                    let _ = write!(pointer, "{}", outer.len);
+                }
+            }
+            outer.len += 1;
+        }
+        match token.kind {
+            TokenKind::BeginArray | TokenKind::BeginObject => open.push(Open {
+                start,
+                array: token.kind == TokenKind::BeginArray,
+                len: 0,
+                outer_len,
+            }),
+            _ => {
+                record(&pointer, start..end, Shape::Scalar);
+                pointer.truncate(outer_len);
+            }
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(text: &str) -> Value {
+        deserialize::Json::new()
+            .comments(true)
+            .deserialize(text)
+            .unwrap()
+    }
+
+    #[test]
+    fn only_changes_are_written() {
+        let json = Json::new();
+        let original = r#"{"a": 1.50, "b": {"c~/d": [1,2], "e": true}, /* kept */ "f": "x"}"#;
+        let mut changed = value(original);
+        *changed.pointer_mut("/b/c~0~1d/1").unwrap() = Value::from("two");
+        *changed.pointer_mut("/f").unwrap() = Value::from(7);
+        let rewritten = json
+            .reserialize(original, &changed, ["/f", "/b/c~0~1d/1"])
+            .unwrap();
+        assert_eq!(
+            rewritten,
+            r#"{"a": 1.50, "b": {"c~/d": [1,"two"], "e": true}, /* kept */ "f": 7}"#
+        );
+        assert_eq!(value(&rewritten), changed);
+        let unchanged = json.reserialize(original, &changed, [""; 0]).unwrap();
+        assert_eq!(unchanged, original);
+        let whole = json.reserialize(original, &changed, ["/f", ""]).unwrap();
+        assert_eq!(whole, json.serialize(&changed).unwrap());
+    }
+
+    #[test]
+    fn shapes_that_changed_are_written_whole() {
+        let json = Json::new();
+        let original = "[ {\"a\": 1}, [1, 2], 3 ]";
+        let mut changed = value(original);
+        changed
+            .pointer_mut("/0")
+            .and_then(Value::as_object_mut)
+            .unwrap()
+            .insert("b".into(), Value::from(2));
+        changed
+            .pointer_mut("/1")
+            .and_then(Value::as_array_mut)
+            .unwrap()
+            .remove(0);
+        let rewritten = json
+            .reserialize(original, &changed, ["/0/b", "/1/0"])
+            .unwrap();
+        assert_eq!(rewritten, "[ {\"a\": 1, \"b\": 2}, [2], 3 ]");
+        *changed.pointer_mut("/2").unwrap() = value("{\"deep\": [3]}");
+        let rewritten = json.reserialize(original, &changed, ["/2/deep/0"]).unwrap();
+        assert_eq!(rewritten, "[ {\"a\": 1}, [1, 2], {\"deep\": [3]} ]");
+        changed.as_array_mut().unwrap().pop();
+        let rewritten = json.reserialize(original, &changed, ["/2"]).unwrap();
+        assert_eq!(rewritten, json.serialize(&changed).unwrap());
+    }
+
+    #[test]
+    fn written_values_are_indented_like_their_line() {
+        let json = Json::new().pretty(true);
+        let original = "{\n    \"list\": [],\n\t\"x\": null\n}\n";
+        let mut changed = value(original);
+        *changed.pointer_mut("/list").unwrap() = value("[1, {\"y\": 2}]");
+        let rewritten = json.reserialize(original, &changed, ["/list"]).unwrap();
+        assert_eq!(
+            rewritten,
+            "{\n    \"list\": [\n      1,\n      {\n        \"y\": 2\n      }\n    ],\n\t\"x\": null\n}\n"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let json = Json::new();
+        let original = r#"{"a": 1}"#;
+        let changed = value(original);
+        let syntax = |expected| ErrorKind::Syntax { expected };
+        for pointer in ["a", "/a~2"] {
+            let error = json.reserialize(original, &changed, [pointer]).unwrap_err();
+            assert_eq!(error.kind(), &syntax("JSON pointer"));
+        }
+        let error = json.reserialize("{\"a\": }", &changed, ["/a"]).unwrap_err();
+        assert_eq!(error.to_string(), "expected value at line 1, column 7");
+        let error = json.reserialize("[1] 2", &changed, ["/a"]).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::TrailingCharacters);
+    }
+}
@@ -0,0 +1,674 @@
+//! Writing JSON text through into a single buffer.
+
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::{Entry as MapEntry, HashMap};
+use std::ffi::OsStr;
+
+use super::{utf16_key, Json, OsStrMode, SharedMode};
+use crate::deserialize;
+use crate::error::{Error, ErrorKind, Result};
+use crate::os_str;
+use crate::serialize::{FieldOrder, Serialize, Serializer, Struct, Tuple};
+use crate::value::Value;
+
+/// Spare buffers beyond this many bytes are dropped rather than kept.
+const MAX_SPARE_CAPACITY: usize = 1 << 20;
+
+thread_local! {
+    /// The entries and scratch buffers of the last [`Writer`] finished on
+    /// this thread, for the next one to reuse.
+    static SPARE: RefCell<(Vec<Entry>, String)> = const { RefCell::new((Vec::new(), String::new())) };
+}
+
+/// Where a value was written in the buffer of a [`Writer`].
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Span {
+    start: usize,
+    end: usize,
+    /// The nesting level the text is indented for.
+    depth: usize,
+}
+
+/// A serializer appending the text of every value to one buffer, for
+/// [`Json`] to write whole documents with.
+///
+/// Values are normally written where they belong, each after the ones
+/// before it. A value can also be written first and only then passed to the
+/// method that places it, such as the content given to
+/// [`Serializer::visit_variant`] or the parts serde hands over one at a
+/// time; it is then copied into place. Text is only ever dropped while no
+/// value written is still waiting to be placed, so values can be placed in
+/// any order.
+pub(super) struct Writer<'a> {
+    json: &'a Json,
+    output: RefCell<String>,
+    /// Where the text of this writer starts in `output`.
+    start: usize,
+    /// How many arrays and objects are open.
+    depth: Cell<usize>,
+    /// How many values have been written but not yet placed.
+    pending: Cell<usize>,
+    /// The entries written so far of each open object, innermost last.
+    entries: RefCell<Vec<Entry>>,
+    /// Text set aside while it is moved or rewritten.
+    scratch: RefCell<String>,
+    /// The ids given to shared values in [`SharedMode::Reference`], by
+    /// address.
+    shared: RefCell<HashMap<usize, u64>>,
+}
+
+/// An array or object being written.
+pub(super) struct Open {
+    start: usize,
+    /// The nesting level of its items.
+    depth: usize,
+    /// How many items have been written.
+    len: usize,
+    /// Where the next item starts, after the separator before it.
+    next: usize,
+    /// Where the last item ends, or the opening bracket if there is none.
+    end: usize,
+    /// Where the object's entries start in [`Writer::entries`].
+    entries: usize,
+}
+
+/// An object entry written, as positions in the buffer.
+struct Entry {
+    start: usize,
+    key_end: usize,
+    value_start: usize,
+    end: usize,
+}
+
+impl<'a> Writer<'a> {
+    /// Create a writer appending to `output`.
+    pub(super) fn new(json: &'a Json, output: String) -> Self {
+        // A writer started while another is writing gets buffers of its own.
+        let (entries, scratch) = SPARE.with_borrow_mut(std::mem::take);
+        Self {
+            json,
+            start: output.len(),
+            output: RefCell::new(output),
+            depth: Cell::new(0),
+            pending: Cell::new(0),
+            entries: RefCell::new(entries),
+            scratch: RefCell::new(scratch),
+            shared: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Take the buffer back, keeping the text of `written` after `start`,
+    /// or nothing if writing failed.
+    pub(super) fn finish(self, start: usize, written: &Result<Span>) -> String {
+        let (mut entries, mut scratch) = (self.entries.into_inner(), self.scratch.into_inner());
+        if scratch.capacity() <= MAX_SPARE_CAPACITY {
+            entries.clear();
+            scratch.clear();
+            SPARE.with_borrow_mut(|spare| *spare = (entries, scratch));
+        }
+        let mut output = self.output.into_inner();
+        match written {
+            Ok(span) => {
+                output.truncate(span.end);
+                output.drain(start..span.start);
+            }
+            Err(_) => output.truncate(start),
+        }
+        output
+    }
+
+    /// Whether arrays and objects are laid out over several lines.
+    fn pretty(&self) -> bool {
+        self.json.pretty && !self.json.canonical
+    }
+
+    /// Write a scalar with `write`.
+    fn scalar(&self, write: impl FnOnce(&mut String) -> Result<()>) -> Result<Span> {
+        let mut output = self.output.borrow_mut();
+        let start = output.len();
+        write(&mut output)?;
+        self.span(start, &output)
+    }
+
+    /// The span of the value written from `start` to the end of `output`,
+    /// once it is checked against [`Json::max_output_len`].
+    fn span(&self, start: usize, output: &str) -> Result<Span> {
+        if let Some(limit) = self.json.max_output_len {
+            if output.len() - self.start > limit {
+                return Err(Error::new(ErrorKind::OutputLimit { limit }));
+            }
+        }
+        self.pending.set(self.pending.get() + 1);
+        Ok(Span {
+            start,
+            end: output.len(),
+            depth: self.depth.get(),
+        })
+    }
+
+    /// Write `text` produced at nesting level `depth`, indenting its lines
+    /// for the current level.
+    pub(super) fn text(&self, text: &str, depth: usize) -> Result<Span> {
+        let mut output = self.output.borrow_mut();
+        let start = output.len();
+        self.indented(&mut output, text, depth);
+        self.span(start, &output)
+    }
+
+    fn indented(&self, output: &mut String, text: &str, depth: usize) {
+        let levels = self.depth.get().saturating_sub(depth);
+        if !self.pretty() || levels == 0 {
+            output.push_str(text);
+            return;
+        }
+        // Strings never contain a raw line ending, so any in the text was
+        // written by pretty output.
+        let newline = self.json.newline.as_str();
+        for (index, line) in text.split(newline).enumerate() {
+            if index > 0 {
+                output.push_str(newline);
+                push_indent(output, levels);
+            }
+            output.push_str(line);
+        }
+    }
+
+    /// Add the text of `span` to `open` at `at`, after the text of `open`
+    /// already there, returning where it was placed.
+    fn place(&self, open: &mut Open, at: usize, span: Span) -> usize {
+        assert_eq!(
+            open.depth,
+            self.depth.get(),
+            "a value was added to an array or object while one inside it was open"
+        );
+        self.pending.set(self.pending.get() - 1);
+        let mut output = self.output.borrow_mut();
+        let in_place = span.start >= at && span.end == output.len() && span.depth == open.depth;
+        if in_place && (span.start == at || self.pending.get() == 0) {
+            output.drain(at..span.start);
+            return at;
+        }
+        let at = match self.pending.get() {
+            0 => {
+                self.set_aside(&output[span.start..span.end]);
+                output.truncate(at);
+                at
+            }
+            _ => {
+                let at = at + self.settle(open, at, &mut output);
+                self.set_aside(&output[span.start..span.end]);
+                at
+            }
+        };
+        self.indented(&mut output, &self.scratch.borrow(), span.depth);
+        at
+    }
+
+    fn set_aside(&self, text: &str) {
+        let mut scratch = self.scratch.borrow_mut();
+        scratch.clear();
+        scratch.push_str(text);
+    }
+
+    /// Make the text of `open` up to `end` the last in the buffer, dropping
+    /// what follows if no value waits to be placed, or else copying it to the
+    /// end. Returns how far it moved.
+    fn settle(&self, open: &mut Open, end: usize, output: &mut String) -> usize {
+        if end == output.len() {
+            return 0;
+        }
+        if self.pending.get() == 0 {
+            output.truncate(end);
+            return 0;
+        }
+        self.set_aside(&output[open.start..end]);
+        let shift = output.len() - open.start;
+        output.push_str(&self.scratch.borrow());
+        open.start += shift;
+        open.next += shift;
+        open.end += shift;
+        for entry in &mut self.entries.borrow_mut()[open.entries..] {
+            entry.start += shift;
+            entry.key_end += shift;
+            entry.value_start += shift;
+            entry.end += shift;
+        }
+        shift
+    }
+
+    /// Write the separator between items, and the line break and
+    /// indentation before the next.
+    fn separator(&self, output: &mut String) {
+        match self.pretty() {
+            true => {
+                output.push(',');
+                self.line_break(output);
+            }
+            false => output.push_str(self.json.separators_or_default().0),
+        }
+    }
+
+    fn line_break(&self, output: &mut String) {
+        output.push_str(self.json.newline.as_str());
+        push_indent(output, self.depth.get());
+    }
+
+    /// Start an array or object with `bracket`.
+    pub(super) fn open(&self, bracket: char) -> Open {
+        let mut output = self.output.borrow_mut();
+        let start = output.len();
+        output.push(bracket);
+        self.depth.set(self.depth.get() + 1);
+        if self.pretty() {
+            self.line_break(&mut output);
+        }
+        Open {
+            start,
+            depth: self.depth.get(),
+            len: 0,
+            next: output.len(),
+            end: start + 1,
+            entries: self.entries.borrow().len(),
+        }
+    }
+
+    /// Add the array element written at `span` to `array`.
+    pub(super) fn element(&self, array: &mut Open, span: Span) {
+        self.place(array, array.next, span);
+        self.item_written(array);
+    }
+
+    /// Write an entry of `object` with `key` and `value`, quoting the key
+    /// unless it is a string.
+    ///
+    /// Fails with [`ErrorKind::UnsupportedKey`] for keys written as null,
+    /// an array or an object, which could not be read back.
+    pub(super) fn entry(
+        &self,
+        object: &mut Open,
+        key: impl FnOnce() -> Result<Span>,
+        value: impl FnOnce() -> Result<Span>,
+    ) -> Result<()> {
+        let key = key()?;
+        let start = self.place(object, object.next, key);
+        self.quote_key(start)?;
+        let (key_end, value_start) = {
+            let mut output = self.output.borrow_mut();
+            let key_end = output.len();
+            output.push_str(self.json.separators_or_default().1);
+            (key_end, output.len())
+        };
+        let value = value()?;
+        let placed = self.place(object, value_start, value);
+        let moved = placed - value_start;
+        let (start, key_end, value_start) = (start + moved, key_end + moved, placed);
+        let end = self.output.borrow().len();
+        self.entries.borrow_mut().push(Entry {
+            start,
+            key_end,
+            value_start,
+            end,
+        });
+        self.item_written(object);
+        Ok(())
+    }
+
+    /// Quote the key written from `start`, unless it is a string.
+    fn quote_key(&self, start: usize) -> Result<()> {
+        let mut output = self.output.borrow_mut();
+        let found = match output[start..].as_bytes() {
+            [b'"', ..] => return Ok(()),
+            b"null" => Some("null"),
+            [b'[', ..] => Some("an array"),
+            [b'{', ..] => Some("an object"),
+            _ => None,
+        };
+        if let Some(found) = found {
+            return Err(Error::new(ErrorKind::UnsupportedKey { found }));
+        }
+        let mut scratch = self.scratch.borrow_mut();
+        scratch.clear();
+        scratch.push_str(&output[start..]);
+        output.truncate(start);
+        output.push('"');
+        self.json.encode_string(&scratch, &mut output);
+        output.push('"');
+        Ok(())
+    }
+
+    fn item_written(&self, open: &mut Open) {
+        let mut output = self.output.borrow_mut();
+        open.len += 1;
+        open.end = output.len();
+        self.separator(&mut output);
+        open.next = output.len();
+    }
+
+    /// Finish `array` with `bracket`.
+    pub(super) fn close(&self, mut array: Open, bracket: char) -> Result<Span> {
+        let mut output = self.output.borrow_mut();
+        // Unless only the separator follows the last item.
+        if array.next != output.len() {
+            let end = array.end;
+            self.settle(&mut array, end, &mut output);
+        }
+        output.truncate(array.end);
+        self.depth.set(self.depth.get() - 1);
+        if self.pretty() && array.len > 0 {
+            self.line_break(&mut output);
+        }
+        output.push(bracket);
+        self.span(array.start, &output)
+    }
+
+    /// Finish `object`, first sorting its entries by their text if `sort`
+    /// is set, and as canonical output requires if it is on.
+    pub(super) fn close_object(&self, mut object: Open, sort: bool) -> Result<Span> {
+        let next = object.next;
+        self.settle(&mut object, next, &mut self.output.borrow_mut());
+        let mut entries = self.entries.borrow_mut();
+        let entries_start = object.entries;
+        let entries_written = &mut entries[entries_start..];
+        if (sort || self.json.canonical) && entries_written.len() > 1 {
+            let mut output = self.output.borrow_mut();
+            if sort {
+                entries_written.sort_unstable_by(|entry, other| {
+                    let text = |entry: &Entry| {
+                        let key = &output[entry.start..entry.key_end];
+                        (key, &output[entry.value_start..entry.end])
+                    };
+                    text(entry).cmp(&text(other))
+                });
+            }
+            if self.json.canonical {
+                entries_written
+                    .sort_by_cached_key(|entry| utf16_key(&output[entry.start..entry.key_end]));
+            }
+            // Rewrite the entries in their new order.
+            let first = entries_written
+                .iter()
+                .map(|entry| entry.start)
+                .min()
+                .expect("entries were written");
+            let mut scratch = self.scratch.borrow_mut();
+            scratch.clear();
+            scratch.push_str(&output[first..object.end]);
+            output.truncate(first);
+            for (index, entry) in entries_written.iter().enumerate() {
+                if index > 0 {
+                    self.separator(&mut output);
+                }
+                output.push_str(&scratch[entry.start - first..entry.end - first]);
+            }
+            object.end = output.len();
+        }
+        entries.truncate(entries_start);
+        drop(entries);
+        self.close(object, '}')
+    }
+
+    /// Write the elements read from `elements` as an array.
+    pub(super) fn array(&self, elements: impl Iterator<Item = Result<Span>>) -> Result<Span> {
+        let mut array = self.open('[');
+        for element in elements {
+            self.element(&mut array, element?);
+        }
+        self.close(array, ']')
+    }
+
+    /// Write the fields of `input` as an object, the value of each written
+    /// by `field` from its index.
+    pub(super) fn structure<T: Struct + ?Sized>(
+        &self,
+        input: &T,
+        field: impl Fn(usize) -> Result<Span>,
+    ) -> Result<Span> {
+        let mut object = self.open('{');
+        let fields = input.fields();
+        // The indices of the fields by name, or empty in declaration order,
+        // which needs no allocation.
+        let mut sorted = Vec::new();
+        if self.json.field_order.unwrap_or_else(|| input.field_order()) == FieldOrder::Alphabetical
+        {
+            sorted.extend(0..fields.len());
+            sorted.sort_by_key(|&index| fields[index].name);
+        }
+        let indices = (0..fields.len()).map(|index| sorted.get(index).copied().unwrap_or(index));
+        for (index, field_info) in indices
+            .map(|index| (index, &fields[index]))
+            .filter(|(_, field)| self.json.field_filter.is_none_or(|filter| filter(field)))
+        {
+            self.entry(
+                &mut object,
+                || self.visit_str(field_info.name),
+                || field(index),
+            )?;
+        }
+        self.close_object(object, false)
+    }
+
+    /// Write `entries` as an object, each key written by `key` and each
+    /// value by `value`.
+    pub(super) fn map<'e, K: 'e, V: 'e>(
+        &self,
+        entries: impl IntoIterator<Item = (&'e K, &'e V)>,
+        sort: bool,
+        key: impl Fn(&K) -> Result<Span>,
+        value: impl Fn(&V) -> Result<Span>,
+    ) -> Result<Span> {
+        let mut object = self.open('{');
+        for (entry_key, entry_value) in entries {
+            self.entry(&mut object, || key(entry_key), || value(entry_value))?;
+        }
+        self.close_object(object, sort)
+    }
+
+    /// Write an object holding the `content` of `variant`.
+    pub(super) fn variant(
+        &self,
+        variant: &'static str,
+        content: impl FnOnce() -> Result<Span>,
+    ) -> Result<Span> {
+        let mut object = self.open('{');
+        self.entry(&mut object, || self.visit_str(variant), content)?;
+        self.close_object(object, false)
+    }
+}
+
+/// Indent by two spaces per level.
+fn push_indent(output: &mut String, levels: usize) {
+    for _ in 0..levels {
+        output.push_str("  ");
+    }
+}
+
+macro_rules! visit_integer {
+    (signed: $($method:ident($ty:ty))+) => {
+        $(
+            fn $method(&self, input: &$ty) -> Result<Span> {
+                let magnitude = input.unsigned_abs() as u128;
+                self.scalar(|output| Ok(self.json.integer(*input < 0, magnitude, output)))
+            }
+        )+
+    };
+    (unsigned: $($method:ident($ty:ty))+) => {
+        $(
+            fn $method(&self, input: &$ty) -> Result<Span> {
+                self.scalar(|output| Ok(self.json.integer(false, *input as u128, output)))
+            }
+        )+
+    };
+}
+
+impl Serializer for Writer<'_> {
+    type Output = Span;
+
+    fn visit_bool(&self, input: &bool) -> Result<Span> {
+        self.scalar(|output| {
+            output.push_str(if *input { "true" } else { "false" });
+            Ok(())
+        })
+    }
+
+    visit_integer! {
+        signed:
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_isize(isize)
+    }
+
+    visit_integer! {
+        unsigned:
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_usize(usize)
+    }
+
+    fn visit_i128(&self, input: &i128) -> Result<Span> {
+        self.scalar(|output| {
+            self.json.wide_integer(output, |output| {
+                self.json.integer(*input < 0, input.unsigned_abs(), output)
+            });
+            Ok(())
+        })
+    }
+
+    fn visit_u128(&self, input: &u128) -> Result<Span> {
+        self.scalar(|output| {
+            self.json
+                .wide_integer(output, |output| self.json.integer(false, *input, output));
+            Ok(())
+        })
+    }
+
+    fn visit_f32(&self, input: &f32) -> Result<Span> {
+        self.scalar(|output| self.json.float(*input, output))
+    }
+
+    fn visit_f64(&self, input: &f64) -> Result<Span> {
+        self.scalar(|output| self.json.float(*input, output))
+    }
+
+    fn visit_char(&self, input: &char) -> Result<Span> {
+        self.visit_str(input.encode_utf8(&mut [0; 4]))
+    }
+
+    fn visit_str(&self, input: &str) -> Result<Span> {
+        self.scalar(|output| {
+            output.push('"');
+            self.json.encode_string(input, output);
+            output.push('"');
+            Ok(())
+        })
+    }
+
+    fn visit_os_str(&self, input: &OsStr) -> Result<Span> {
+        match (input.to_str(), self.json.os_str) {
+            (Some(input), _) => self.visit_str(input),
+            (None, OsStrMode::CodeUnits) => self.visit_array(&os_str::code_units(input)),
+            (None, OsStrMode::Lossy) => self.visit_str(&input.to_string_lossy()),
+        }
+    }
+
+    fn visit_raw(&self, input: &str) -> Result<Span> {
+        if self.json.canonical {
+            let value: Value = deserialize::Json::new().deserialize(input)?;
+            return value.accept(self);
+        }
+        self.text(input, 0)
+    }
+
+    fn visit_unit(&self) -> Result<Span> {
+        self.scalar(|output| {
+            output.push_str("null");
+            Ok(())
+        })
+    }
+
+    fn visit_option<T: Serialize>(&self, input: &Option<T>) -> Result<Span> {
+        match input {
+            Some(value) => value.accept(self),
+            None => self.visit_unit(),
+        }
+    }
+
+    fn visit_seq<'e, T, I>(&self, input: I) -> Result<Span>
+    where
+        T: Serialize + 'e,
+        I: IntoIterator<Item = &'e T>,
+    {
+        self.array(input.into_iter().map(|element| element.accept(self)))
+    }
+
+    fn visit_tuple<T: Tuple + ?Sized>(&self, input: &T) -> Result<Span> {
+        let mut array = self.open('[');
+        input.serialize_elements(self, |element| self.element(&mut array, element))?;
+        self.close(array, ']')
+    }
+
+    fn visit_struct<T: Struct + ?Sized>(&self, input: &T) -> Result<Span> {
+        self.structure(input, |index| input.serialize_field(index, self))
+    }
+
+    fn visit_variant(&self, variant: &'static str, content: Span) -> Result<Span> {
+        self.variant(variant, || Ok(content))
+    }
+
+    fn visit_map<'e, K, V, I>(&self, input: I) -> Result<Span>
+    where
+        K: Serialize + 'e,
+        V: Serialize + 'e,
+        I: IntoIterator<Item = (&'e K, &'e V)>,
+    {
+        self.map(
+            input,
+            false,
+            |key| key.accept(self),
+            |value| value.accept(self),
+        )
+    }
+
+    fn visit_unordered_map<'e, K, V, I>(&self, input: I) -> Result<Span>
+    where
+        K: Serialize + 'e,
+        V: Serialize + 'e,
+        I: IntoIterator<Item = (&'e K, &'e V)>,
+    {
+        let sort = self.json.sort_maps;
+        self.map(
+            input,
+            sort,
+            |key| key.accept(self),
+            |value| value.accept(self),
+        )
+    }
+
+    fn visit_shared<T: Serialize + ?Sized>(&self, id: usize, input: &T) -> Result<Span> {
+        if self.json.shared == SharedMode::Inline {
+            return self.json.write_shared(id, || input.accept(self));
+        }
+        let (id, first) = {
+            let mut ids = self.shared.borrow_mut();
+            let next = ids.len() as u64 + 1;
+            match ids.entry(id) {
+                MapEntry::Occupied(entry) => (*entry.get(), false),
+                MapEntry::Vacant(entry) => (*entry.insert(next), true),
+            }
+        };
+        let mut object = self.open('{');
+        let key = if first { "$id" } else { "$ref" };
+        self.entry(&mut object, || self.visit_str(key), || self.visit_u64(&id))?;
+        if first {
+            self.entry(
+                &mut object,
+                || self.visit_str("$value"),
+                || input.accept(self),
+            )?;
+        }
+        self.close_object(object, false)
+    }
+}
@@ -0,0 +1,101 @@
+//! Line-delimited JSON output, also known as NDJSON or JSON Lines.
+
+use std::io::Write;
+
+use super::{Json, Serialize};
+use crate::error::Result;
+
+impl Json {
+    /// Write each of `items` to `writer` as compact JSON on a line of its
+    /// own, returning how many were written.
+    ///
+    /// Every line, the last included, ends with the line ending of
+    /// [`Json::newline_mode`]; [`Json::pretty`] and [`Json::separators`]
+    /// are ignored so that each value stays on one line. Each line reaches
+    /// `writer` in a single write, so an unbuffered `writer` never holds
+    /// part of one; on error, the items before the failing one have been
+    /// written.
+    ///
+    /// ```
+    /// use shallot::serialize::Json;
+    ///
+    /// let mut output = Vec::new();
+    /// let written = Json::new().write_lines(&mut output, [vec![1, 2], vec![], vec![3]])?;
+    /// assert_eq!(written, 3);
+    /// assert_eq!(output, b"[1,2]\n[]\n[3]\n");
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn write_lines<W, I>(&self, mut writer: W, items: I) -> Result<usize>
+    where
+        W: Write,
+        I: IntoIterator,
+        I::Item: Serialize,
+    {
+        let json = self
+            .clone()
+            .pretty(false)
+            .compact(true)
+            .trailing_newline(true);
+        // One buffer holds each line in turn.
+        let mut line = String::new();
+        let mut written = 0;
+        for item in items {
+            line.clear();
+            json.document_into(&item, &mut line)?;
+            writer.write_all(line.as_bytes())?;
+            written += 1;
+        }
+        writer.flush()?;
+        Ok(written)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::serialize::NewlineMode;
+    use crate::ErrorKind;
+
+    #[test]
+    fn lines() {
+        let json = Json::new()
+            .pretty(true)
+            .separators(",\n", ":\n")
+            .newline_mode(NewlineMode::CrLf);
+        let mut output = Vec::new();
+        let items = [("a", vec![1]), ("b\nc", vec![])];
+        assert_eq!(json.write_lines(&mut output, &items).unwrap(), 2);
+        assert_eq!(output, b"[\"a\",[1]]\r\n[\"b\\nc\",[]]\r\n");
+
+        let mut output = Vec::new();
+        let error = json
+            .write_lines(&mut output, [1.0, f64::NAN, 2.0])
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::NonFinite { value: "NaN" });
+        assert_eq!(output, b"1.0\r\n");
+    }
+
+    #[test]
+    fn io_errors() {
+        struct Full;
+        impl Write for Full {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::new(io::ErrorKind::StorageFull, "disk full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let error = Json::new().write_lines(Full, [1]).unwrap_err();
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::Io {
+                kind: io::ErrorKind::StorageFull,
+                ..
+            }
+        ));
+        assert_eq!(Json::new().write_lines(Full, [0u8; 0]).unwrap(), 0);
+    }
+}
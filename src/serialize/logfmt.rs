@@ -0,0 +1,323 @@
+//! logfmt output: a line of `key=value` pairs.
+
+use std::cell::Cell;
+use std::fmt::Write;
+
+use super::{Serialize, Serializer, Struct, Tuple};
+use crate::deserialize::{self, Kind};
+use crate::error::{Error, ErrorKind, Result};
+
+/// Serializes structs and maps into logfmt, a single line of `key=value`
+/// pairs as read by many log processors.
+///
+/// The fields of a struct, or the entries of a map, become the pairs in
+/// order, and their values must be scalars: logfmt has no arrays or nested
+/// objects. Fields that are `None` or `()` are left out. Strings are quoted
+/// when they are empty, contain spaces, `=`, quotes or control characters,
+/// or would otherwise read back as a number or bool.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use shallot::serialize::Logfmt;
+///
+/// let pairs = BTreeMap::from([("level", "info"), ("msg", "request done"), ("status", "200")]);
+/// assert_eq!(
+///     Logfmt::new().serialize(&pairs)?,
+///     r#"level=info msg="request done" status="200""#,
+/// );
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct Logfmt {}
+
+impl Logfmt {
+    /// Create a logfmt serializer.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serialize `input`, a struct or map, into a logfmt line without a
+    /// line ending.
+    pub fn serialize<T: Serialize + ?Sized>(&self, input: &T) -> Result<String> {
+        let writer = Writer {
+            pairs: Cell::new(false),
+            key: Cell::new(false),
+        };
+        let line = input.accept(&writer)?;
+        match writer.pairs.get() {
+            true => Ok(line),
+            false => Err(not_pairs()),
+        }
+    }
+}
+
+/// The serializer behind [`Logfmt`]. Its output for a scalar is the text
+/// of its value, empty for one that is left out.
+struct Writer {
+    /// Whether the struct or map holding the pairs has been started.
+    pairs: Cell<bool>,
+    /// Whether a map key is being written, which is never quoted.
+    key: Cell<bool>,
+}
+
+fn not_pairs() -> Error {
+    Error::new(ErrorKind::Syntax {
+        expected: "a struct or map",
+    })
+}
+
+fn not_scalar() -> Error {
+    Error::custom("logfmt values must be scalars")
+}
+
+/// Whether `key` can be written as a logfmt key.
+fn valid_key(key: &str) -> bool {
+    !key.is_empty() && !key.contains(|c: char| c == ' ' || c == '=' || c == '"' || c.is_control())
+}
+
+impl Writer {
+    /// Start the pairs, which can only happen once.
+    fn begin_pairs(&self) -> Result<()> {
+        match self.pairs.replace(true) {
+            true => Err(not_scalar()),
+            false => Ok(()),
+        }
+    }
+
+    /// Add the pair of `key` and the text of `value` to `line`, unless the
+    /// value is left out.
+    fn pair(&self, line: &mut String, key: &str, value: &str) -> Result<()> {
+        if !valid_key(key) {
+            return Err(Error::custom(format!(
+                "logfmt keys cannot be empty or contain spaces, `=`, quotes or control \
+                 characters: {key:?}"
+            )));
+        }
+        if value.is_empty() {
+            return Ok(());
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        let _ = write!(line, "{key}={value}");
+        Ok(())
+    }
+}
+
+macro_rules! visit_display {
+    ($($method:ident($ty:ty))+) => {
+        $(
+            fn $method(&self, input: &$ty) -> Result<String> {
+                Ok(input.to_string())
+            }
+        )+
+    };
+}
+
+impl Serializer for Writer {
+    type Output = String;
+
+    visit_display! {
+        visit_bool(bool)
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_i128(i128)
+        visit_isize(isize)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_u128(u128)
+        visit_usize(usize)
+    }
+
+    fn visit_f32(&self, input: &f32) -> Result<String> {
+        self.visit_f64(&f64::from(*input))
+    }
+
+    /// Floats keep a fractional part or exponent, as in `1.0` and `1e300`,
+    /// so that they read back as floats.
+    fn visit_f64(&self, input: &f64) -> Result<String> {
+        let value = match *input {
+            input if input.is_finite() => return Ok(format!("{input:?}")),
+            input if input.is_nan() => "NaN",
+            input if input > 0.0 => "Infinity",
+            _ => "-Infinity",
+        };
+        Err(Error::new(ErrorKind::NonFinite { value }))
+    }
+
+    fn visit_char(&self, input: &char) -> Result<String> {
+        self.visit_str(input.encode_utf8(&mut [0; 4]))
+    }
+
+    fn visit_str(&self, input: &str) -> Result<String> {
+        if self.key.get() {
+            return Ok(input.to_owned());
+        }
+        let plain = !input.is_empty()
+            && !input.contains(|c: char| matches!(c, ' ' | '=' | '"' | '\\') || c.is_control())
+            && deserialize::logfmt_kind(input) == Kind::String;
+        if plain {
+            return Ok(input.to_owned());
+        }
+        let mut output = String::with_capacity(input.len() + 2);
+        output.push('"');
+        for c in input.chars() {
+            match c {
+                '"' => output.push_str("\\\""),
+                '\\' => output.push_str("\\\\"),
+                '\n' => output.push_str("\\n"),
+                '\r' => output.push_str("\\r"),
+                '\t' => output.push_str("\\t"),
+                c if c.is_control() => {
+                    let _ = write!(output, "\\u{:04x}", u32::from(c));
+                }
+                c => output.push(c),
+            }
+        }
+        output.push('"');
+        Ok(output)
+    }
+
+    fn visit_unit(&self) -> Result<String> {
+        Ok(String::new())
+    }
+
+    fn visit_option<T: Serialize>(&self, input: &Option<T>) -> Result<String> {
+        match input {
+            Some(input) => input.accept(self),
+            None => self.visit_unit(),
+        }
+    }
+
+    fn visit_seq<'a, T, I>(&self, input: I) -> Result<String>
+    where
+        T: Serialize + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let _ = input;
+        Err(not_scalar())
+    }
+
+    fn visit_tuple<T: Tuple + ?Sized>(&self, input: &T) -> Result<String> {
+        let _ = input;
+        Err(not_scalar())
+    }
+
+    fn visit_struct<T: Struct + ?Sized>(&self, input: &T) -> Result<String> {
+        self.begin_pairs()?;
+        let mut line = String::new();
+        for (index, field) in input.fields().iter().enumerate() {
+            self.pair(&mut line, field.name, &input.serialize_field(index, self)?)?;
+        }
+        Ok(line)
+    }
+
+    fn visit_variant(&self, variant: &'static str, content: String) -> Result<String> {
+        let _ = (variant, content);
+        Err(not_scalar())
+    }
+
+    fn visit_map<'a, K, V, I>(&self, input: I) -> Result<String>
+    where
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        I: IntoIterator<Item = (&'a K, &'a V)>,
+    {
+        self.begin_pairs()?;
+        let mut line = String::new();
+        for (key, value) in input {
+            self.key.set(true);
+            let key = key.accept(self);
+            self.key.set(false);
+            self.pair(&mut line, &key?, &value.accept(self)?)?;
+        }
+        Ok(line)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::Value;
+
+    #[test]
+    fn pairs() {
+        let logfmt = Logfmt::new();
+        let map = BTreeMap::from([
+            ("a", Value::from(1)),
+            ("b", crate::value::to_value(&-2.0).unwrap()),
+            ("c", Value::from(true)),
+            ("d", Value::Null),
+            ("e", Value::from("x")),
+        ]);
+        assert_eq!(logfmt.serialize(&map).unwrap(), "a=1 b=-2.0 c=true e=x");
+        let map = BTreeMap::from([(1, Some('x')), (2, None), (3, Some(' '))]);
+        assert_eq!(logfmt.serialize(&map).unwrap(), r#"1=x 3=" ""#);
+        let map: BTreeMap<&str, &str> = BTreeMap::new();
+        assert_eq!(logfmt.serialize(&map).unwrap(), "");
+    }
+
+    #[test]
+    fn quoting() {
+        let logfmt = Logfmt::new();
+        for (value, written) in [
+            ("", r#""""#),
+            ("a b", r#""a b""#),
+            ("a=b", r#""a=b""#),
+            ("say \"hi\"", r#""say \"hi\"""#),
+            ("back\\slash", r#""back\\slash""#),
+            ("line\nbreak\t\u{1}", r#""line\nbreak\t\u0001""#),
+            ("true", r#""true""#),
+            ("-12", r#""-12""#),
+            ("1e5", r#""1e5""#),
+            ("inf", "inf"),
+            ("é", "é"),
+        ] {
+            let line = logfmt.serialize(&BTreeMap::from([("k", value)])).unwrap();
+            assert_eq!(line, format!("k={written}"));
+            let read: BTreeMap<String, Value> = crate::deserialize::Logfmt::new()
+                .deserialize(&line)
+                .unwrap();
+            assert_eq!(read["k"], Value::from(value), "{line}");
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let logfmt = Logfmt::new();
+        let error = logfmt
+            .serialize(&BTreeMap::from([("a", vec![1])]))
+            .unwrap_err();
+        assert_eq!(error.to_string(), "logfmt values must be scalars");
+        let nested = BTreeMap::from([("a", BTreeMap::from([("b", 1)]))]);
+        let error = logfmt.serialize(&nested).unwrap_err();
+        assert_eq!(error.to_string(), "logfmt values must be scalars");
+        let error = logfmt.serialize(&1).unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Syntax {
+                expected: "a struct or map"
+            }
+        );
+        let error = logfmt.serialize(&vec![1]).unwrap_err();
+        assert_eq!(error.to_string(), "logfmt values must be scalars");
+        for key in ["", "a b", "a=b", "\"a\"", "a\nb"] {
+            let error = logfmt.serialize(&BTreeMap::from([(key, 1)])).unwrap_err();
+            assert!(
+                error.to_string().starts_with("logfmt keys cannot"),
+                "{key:?}"
+            );
+        }
+        let error = logfmt
+            .serialize(&BTreeMap::from([("a", f64::NAN)]))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::NonFinite { value: "NaN" });
+    }
+}
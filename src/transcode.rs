@@ -0,0 +1,293 @@
+//! Reading one format and writing another without an intermediate type.
+//!
+//! [`transcode`] reads each value from a [`Deserializer`] as the
+//! [`Serializer`] asks for it, so converting between formats needs neither a
+//! type describing the document nor a [`Value`](crate::Value) holding all of
+//! it. The deserializer must describe its values through
+//! [`Deserializer::peek_kind`].
+//!
+//! ```
+//! use shallot::{deserialize, serialize, transcode};
+//!
+//! let json = deserialize::Json::new();
+//! let mut parser = json.parser(r#"{"id": 7, "tags": ["a", "b"]}"#);
+//! let text = transcode(&mut parser, &serialize::Json::new().pretty(true))?;
+//! parser.finish()?;
+//! assert_eq!(text, "{\n  \"id\": 7,\n  \"tags\": [\n    \"a\",\n    \"b\"\n  ]\n}");
+//! # Ok::<(), shallot::Error>(())
+//! ```
+
+use std::cell::{Cell, RefCell};
+
+use crate::deserialize::{DeserializeSeed, Deserializer, Kind, Map, Seq};
+use crate::error::{Error, Result};
+use crate::serialize::{Serialize, Serializer};
+
+/// Read the value at the deserializer's current position and write it with
+/// `serializer`.
+///
+/// Sequences and maps are passed to the serializer as iterators that read
+/// each element when it is reached, so the serializer must write elements as
+/// it iterates; one that collects them first, like
+/// [`ErasedSerializer`](crate::serialize::ErasedSerializer), fails.
+/// Numbers are read as `u64`, `i64`, `u128`, `i128` or `f64` according to
+/// their [`Kind`], so whole numbers keep every digit, and map keys as
+/// strings.
+pub fn transcode<D: Deserializer, S: Serializer>(
+    deserializer: &mut D,
+    serializer: &S,
+) -> Result<S::Output> {
+    match deserializer.peek_kind()? {
+        Kind::Null => {
+            deserializer.visit_unit()?;
+            serializer.visit_unit()
+        }
+        Kind::Bool => serializer.visit_bool(&deserializer.visit_bool()?),
+        Kind::Unsigned => serializer.visit_u64(&deserializer.visit_u64()?),
+        Kind::Signed => serializer.visit_i64(&deserializer.visit_i64()?),
+        Kind::WideUnsigned => serializer.visit_u128(&deserializer.visit_u128()?),
+        Kind::WideSigned => serializer.visit_i128(&deserializer.visit_i128()?),
+        Kind::Float => serializer.visit_f64(&deserializer.visit_f64()?),
+        Kind::String => serializer.visit_str(&deserializer.visit_string()?),
+        Kind::Seq => {
+            let seq = deserializer.begin_seq(None)?;
+            let reader = Reader::new(deserializer, seq);
+            let output = serializer.visit_seq(Elements(&reader));
+            let (deserializer, seq) = reader.finish()?;
+            let output = output?;
+            deserializer.end_seq(seq)?;
+            Ok(output)
+        }
+        Kind::Map => {
+            let map = deserializer.begin_map()?;
+            let reader = Reader::new(deserializer, map);
+            let output = serializer.visit_map(Entries(&reader));
+            let (deserializer, map) = reader.finish()?;
+            let output = output?;
+            deserializer.end_map(map)?;
+            Ok(output)
+        }
+    }
+}
+
+/// A [`DeserializeSeed`] that writes the value it reads with a serializer,
+/// for formats whose entry points take a seed rather than exposing their
+/// [`Deserializer`].
+///
+/// ```
+/// use shallot::{deserialize, serialize, Transcode};
+///
+/// let compact = serialize::Json::new().compact(true);
+/// let text = deserialize::Json::new().deserialize_seed("[1, [true]]", Transcode(&compact))?;
+/// assert_eq!(text, "[1,[true]]");
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Transcode<'a, S>(pub &'a S);
+
+impl<S: Serializer> DeserializeSeed for Transcode<'_, S> {
+    type Value = S::Output;
+
+    fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<S::Output> {
+        transcode(deserializer, self.0)
+    }
+}
+
+/// A sequence or map being read while the serializer writes it.
+struct Reader<'a, D, A> {
+    deserializer: RefCell<&'a mut D>,
+    access: RefCell<A>,
+    /// The key of the current map entry.
+    key: Key,
+    /// Whether the serializer has been given an element it has not written.
+    pending: Cell<bool>,
+    /// The error that stopped iteration, reported once the serializer
+    /// returns.
+    error: RefCell<Option<Error>>,
+}
+
+impl<'a, D: Deserializer, A> Reader<'a, D, A> {
+    fn new(deserializer: &'a mut D, access: A) -> Self {
+        Self {
+            deserializer: RefCell::new(deserializer),
+            access: RefCell::new(access),
+            key: Key(RefCell::new(String::new())),
+            pending: Cell::new(false),
+            error: RefCell::new(None),
+        }
+    }
+
+    /// Whether iteration can move on, failing if the serializer asked for
+    /// the next element before writing the current one.
+    fn advance(&self) -> bool {
+        if self.error.borrow().is_some() {
+            return false;
+        }
+        if self.pending.get() {
+            self.fail(Error::custom(
+                "the serializer must write each element before the next",
+            ));
+            return false;
+        }
+        true
+    }
+
+    /// Record `result` of moving to the next element, returning whether
+    /// there is one.
+    fn moved(&self, result: Result<bool>) -> bool {
+        match result {
+            Ok(more) => {
+                self.pending.set(more);
+                more
+            }
+            Err(error) => {
+                self.fail(error);
+                false
+            }
+        }
+    }
+
+    fn fail(&self, error: Error) {
+        *self.error.borrow_mut() = Some(error);
+    }
+
+    /// Take back the deserializer once the serializer returns, failing
+    /// with the error that stopped iteration if there was one.
+    fn finish(self) -> Result<(&'a mut D, A)> {
+        if let Some(error) = self.error.into_inner() {
+            return Err(error);
+        }
+        Ok((self.deserializer.into_inner(), self.access.into_inner()))
+    }
+}
+
+impl<D: Deserializer> Serialize for Reader<'_, D, Seq> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        self.pending.set(false);
+        transcode(&mut **self.deserializer.borrow_mut(), serializer)
+    }
+}
+
+impl<D: Deserializer> Serialize for Reader<'_, D, Map> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        self.pending.set(false);
+        let mut map = self.access.borrow_mut();
+        self.deserializer
+            .borrow_mut()
+            .next_value_seed(&mut map, Transcode(serializer))
+    }
+}
+
+/// The elements of a sequence, each read when the serializer writes it.
+struct Elements<'r, 'a, D>(&'r Reader<'a, D, Seq>);
+
+impl<'r, 'a, D: Deserializer> Iterator for Elements<'r, 'a, D> {
+    type Item = &'r Reader<'a, D, Seq>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = self.0;
+        if !reader.advance() {
+            return None;
+        }
+        let result = reader
+            .deserializer
+            .borrow_mut()
+            .next_element(&mut reader.access.borrow_mut());
+        reader.moved(result).then_some(reader)
+    }
+}
+
+/// The entries of a map, whose keys are read as the iterator reaches them
+/// and whose values are read when the serializer writes them.
+struct Entries<'r, 'a, D>(&'r Reader<'a, D, Map>);
+
+impl<'r, 'a, D: Deserializer> Iterator for Entries<'r, 'a, D> {
+    type Item = (&'r Key, &'r Reader<'a, D, Map>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let reader = self.0;
+        if !reader.advance() {
+            return None;
+        }
+        let result = reader
+            .deserializer
+            .borrow_mut()
+            .next_key::<String>(&mut reader.access.borrow_mut());
+        let result = result.map(|key| key.map(|key| *reader.key.0.borrow_mut() = key).is_some());
+        reader.moved(result).then_some((&reader.key, reader))
+    }
+}
+
+/// The key of the map entry being written.
+struct Key(RefCell<String>);
+
+impl Serialize for Key {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_str(&self.0.borrow())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serialize::ErasedSerializer;
+    use crate::{deserialize, serialize, ErrorKind};
+
+    #[test]
+    fn between_formats() {
+        let json = deserialize::Json::new();
+        let input = r#"{"a": [null, true, 1, -2, 2.5, "x"], "b": {}, "c": [[]]}"#;
+        let mut parser = json.parser(input);
+        let text = transcode(&mut parser, &serialize::Json::new().compact(true)).unwrap();
+        parser.finish().unwrap();
+        assert_eq!(text, r#"{"a":[null,true,1,-2,2.5,"x"],"b":{},"c":[[]]}"#);
+
+        let text = deserialize::Json::new()
+            .json5(true)
+            .deserialize_seed(
+                "{a: [0x10, 'b',], // c\n}",
+                Transcode(&serialize::Json::new()),
+            )
+            .unwrap();
+        assert_eq!(text, r#"{"a": [16, "b"]}"#);
+
+        // Whole numbers beyond 64 bits keep their digits rather than
+        // passing through an `f64`.
+        let wide = "[340282366920938463463374607431768211455, 18446744073709551616, \
+                    -170141183460469231731687303715884105728, -9223372036854775809, \
+                    340282366920938463463374607431768211456]";
+        let text = json
+            .deserialize_seed(wide, Transcode(&serialize::Json::new().compact(true)))
+            .unwrap();
+        assert_eq!(
+            text,
+            "[340282366920938463463374607431768211455,18446744073709551616,\
+             -170141183460469231731687303715884105728,-9223372036854775809,\
+             3.402823669209385e38]"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let json = deserialize::Json::new();
+        let error = json
+            .deserialize_seed(r#"{"a": [1, 2 3]}"#, Transcode(&serialize::Json::new()))
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Syntax {
+                expected: "`,` or `]`"
+            }
+        );
+        assert_eq!(error.position().map(|position| position.col), Some(13));
+
+        let erased: &dyn ErasedSerializer = &serialize::Json::new();
+        let error = json
+            .deserialize_seed("[1, 2]", Transcode(&erased))
+            .unwrap_err();
+        assert_eq!(
+            error.kind(),
+            &ErrorKind::Custom("the serializer must write each element before the next".into())
+        );
+    }
+}
@@ -0,0 +1,532 @@
+//! A dynamically typed document, for data without a fixed schema.
+//!
+//! [`Value`] can hold anything a self-describing format such as JSON can
+//! express, and implements both [`Serialize`] and [`Deserialize`], so it
+//! can be read from one document, inspected or edited, and written out
+//! again. [`to_value`] and [`from_value`] convert typed data to and from
+//! the tree directly, without going through text.
+//!
+//! ```
+//! use shallot::{deserialize, serialize, Serializer, Value};
+//!
+//! let value: Value = deserialize::Json::new().deserialize(r#"{"id": 7, "tags": ["a"]}"#)?;
+//! assert_eq!(value.get("id").and_then(Value::as_u64), Some(7));
+//! assert_eq!(value.get("tags").and_then(Value::as_array).map(Vec::len), Some(1));
+//! assert_eq!(serialize::Json::new().serialize(&value)?, r#"{"id": 7, "tags": ["a"]}"#);
+//! # Ok::<(), shallot::Error>(())
+//! ```
+
+#[cfg(feature = "bumpalo")]
+mod arena;
+mod de;
+mod dot;
+mod patch;
+mod raw;
+mod ser;
+#[cfg(feature = "serde_json")]
+mod serde_json;
+mod tree;
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::deserialize::{Deserialize, Deserializer, Kind};
+use crate::error::Result;
+use crate::serialize::{Serialize, Serializer};
+
+#[cfg(feature = "bumpalo")]
+pub use arena::{ArenaValue, InArena};
+pub use de::from_value;
+pub use patch::{Operation, Patch};
+pub use raw::RawValue;
+pub use ser::to_value;
+
+/// Any value of a self-describing format.
+///
+/// Objects are kept sorted by key, so a document read and written again
+/// may list its keys in a different order; duplicate keys keep the last
+/// value.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Value {
+    /// JSON's `null`, or a unit value.
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(String),
+    /// A sequence of values.
+    Array(Vec<Value>),
+    /// A map from string keys to values.
+    Object(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Whether the value is [`Value::Null`].
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    /// The boolean, if this is a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The number as a `u64`, if it is a whole number in its range.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number().and_then(Number::as_u64)
+    }
+
+    /// The number as an `i64`, if it is a whole number in its range.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number().and_then(Number::as_i64)
+    }
+
+    /// The number as an `f64`, rounding whole numbers beyond 2^53.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().map(Number::as_f64)
+    }
+
+    /// The number, if this is a number.
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            Self::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    /// The string, if this is a string.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// The elements, if this is an array.
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match self {
+            Self::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// The elements for editing, if this is an array.
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Self::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// The entries, if this is an object.
+    pub fn as_object(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Self::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    /// The entries for editing, if this is an object.
+    pub fn as_object_mut(&mut self) -> Option<&mut BTreeMap<String, Value>> {
+        match self {
+            Self::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    /// The value of `key`, if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.as_object()?.get(key)
+    }
+
+    /// The value of `key` for editing, if this is an object that has it.
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        self.as_object_mut()?.get_mut(key)
+    }
+
+    /// The value at a JSON pointer (RFC 6901) such as `/items/0/name`, where
+    /// `~1` stands for `/` and `~0` for `~` in keys. The empty pointer is
+    /// the value itself.
+    ///
+    /// ```
+    /// use shallot::{deserialize, Value};
+    ///
+    /// let value: Value = deserialize::Json::new().deserialize(r#"{"a/b": [1, 2]}"#)?;
+    /// assert_eq!(value.pointer("/a~1b/1"), Some(&Value::from(2)));
+    /// assert_eq!(value.pointer("/a~1b/2"), None);
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn pointer(&self, pointer: &str) -> Option<&Value> {
+        patch::tokens(pointer)
+            .ok()?
+            .iter()
+            .try_fold(self, |value, token| value.child(token))
+    }
+
+    /// The value at a JSON pointer for editing; see [`Value::pointer`].
+    pub fn pointer_mut(&mut self, pointer: &str) -> Option<&mut Value> {
+        patch::tokens(pointer)
+            .ok()?
+            .iter()
+            .try_fold(self, |value, token| value.child_mut(token))
+    }
+
+    /// The entry named by one reference token of a JSON pointer.
+    fn child(&self, token: &str) -> Option<&Value> {
+        match self {
+            Self::Object(object) => object.get(token),
+            Self::Array(array) => array.get(patch::index(token)?),
+            _ => None,
+        }
+    }
+
+    fn child_mut(&mut self, token: &str) -> Option<&mut Value> {
+        match self {
+            Self::Object(object) => object.get_mut(token),
+            Self::Array(array) => array.get_mut(patch::index(token)?),
+            _ => None,
+        }
+    }
+}
+
+/// A number, kept as read: unsigned or negative whole numbers that fit in
+/// 64 bits stay exact, and anything else is an `f64`.
+///
+/// Numbers compare by value, so `1` equals `1.0`.
+#[derive(Debug, Clone, Copy)]
+pub struct Number(N);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum N {
+    Unsigned(u64),
+    /// Always negative, so each number has one representation.
+    Signed(i64),
+    Float(f64),
+}
+
+impl Number {
+    /// A number holding `value`, unless it is NaN or infinite.
+    pub fn from_f64(value: f64) -> Option<Self> {
+        value.is_finite().then_some(Self(N::Float(value)))
+    }
+
+    /// Whether the number is whole and held exactly.
+    pub fn is_integer(&self) -> bool {
+        !matches!(self.0, N::Float(_))
+    }
+
+    /// The number as a `u64`, if it is a whole number in its range.
+    pub fn as_u64(&self) -> Option<u64> {
+        match self.0 {
+            N::Unsigned(value) => Some(value),
+            N::Signed(_) | N::Float(_) => None,
+        }
+    }
+
+    /// The number as an `i64`, if it is a whole number in its range.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self.0 {
+            N::Unsigned(value) => value.try_into().ok(),
+            N::Signed(value) => Some(value),
+            N::Float(_) => None,
+        }
+    }
+
+    /// The number as an `f64`, rounding whole numbers beyond 2^53.
+    pub fn as_f64(&self) -> f64 {
+        match self.0 {
+            N::Unsigned(value) => value as f64,
+            N::Signed(value) => value as f64,
+            N::Float(value) => value,
+        }
+    }
+}
+
+impl PartialEq for Number {
+    /// A float equals a whole number only if it holds exactly that integer,
+    /// so equality stays transitive beyond 2^53.
+    fn eq(&self, other: &Self) -> bool {
+        match (self.0, other.0) {
+            (N::Float(float), integer) | (integer, N::Float(float)) => match integer {
+                N::Float(other) => float == other,
+                N::Unsigned(value) => {
+                    float.fract() == 0.0
+                        && (0.0..18446744073709551616.0).contains(&float)
+                        && float as u64 == value
+                }
+                N::Signed(value) => {
+                    float.fract() == 0.0
+                        && (-9223372036854775808.0..9223372036854775808.0).contains(&float)
+                        && float as i64 == value
+                }
+            },
+            (number, other) => number == other,
+        }
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            N::Unsigned(value) => value.fmt(f),
+            N::Signed(value) => value.fmt(f),
+            N::Float(value) => value.fmt(f),
+        }
+    }
+}
+
+macro_rules! impl_from_unsigned {
+    ($($ty:ty)+) => {
+        $(
+            impl From<$ty> for Number {
+                fn from(value: $ty) -> Self {
+                    Self(N::Unsigned(value.into()))
+                }
+            }
+        )+
+    };
+}
+
+macro_rules! impl_from_signed {
+    ($($ty:ty)+) => {
+        $(
+            impl From<$ty> for Number {
+                fn from(value: $ty) -> Self {
+                    match u64::try_from(value) {
+                        Ok(value) => Self(N::Unsigned(value)),
+                        Err(_) => Self(N::Signed(value.into())),
+                    }
+                }
+            }
+        )+
+    };
+}
+
+impl_from_unsigned!(u8 u16 u32 u64);
+impl_from_signed!(i8 i16 i32 i64);
+
+macro_rules! impl_value_from {
+    ($($ty:ty => $variant:ident,)+) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(value: $ty) -> Self {
+                    Self::$variant(value.into())
+                }
+            }
+        )+
+    };
+}
+
+impl_value_from! {
+    bool => Bool,
+    u8 => Number,
+    u16 => Number,
+    u32 => Number,
+    u64 => Number,
+    i8 => Number,
+    i16 => Number,
+    i32 => Number,
+    i64 => Number,
+    Number => Number,
+    String => String,
+    &str => String,
+    Vec<Value> => Array,
+    BTreeMap<String, Value> => Object,
+}
+
+impl From<()> for Value {
+    fn from((): ()) -> Self {
+        Self::Null
+    }
+}
+
+impl Serialize for Number {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        match &self.0 {
+            N::Unsigned(value) => serializer.visit_u64(value),
+            N::Signed(value) => serializer.visit_i64(value),
+            N::Float(value) => serializer.visit_f64(value),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        match self {
+            Self::Null => serializer.visit_unit(),
+            Self::Bool(value) => serializer.visit_bool(value),
+            Self::Number(number) => number.accept(serializer),
+            Self::String(string) => serializer.visit_str(string),
+            Self::Array(array) => serializer.visit_array(array),
+            Self::Object(object) => serializer.visit_map(object),
+        }
+    }
+}
+
+impl Deserialize for Value {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        Ok(match deserializer.peek_kind()? {
+            Kind::Null => deserializer.visit_unit().map(|()| Self::Null)?,
+            Kind::Bool => Self::Bool(deserializer.visit_bool()?),
+            Kind::Unsigned => Self::Number(Number(N::Unsigned(deserializer.visit_u64()?))),
+            Kind::Signed => Self::Number(Number::from(deserializer.visit_i64()?)),
+            Kind::Float | Kind::WideUnsigned | Kind::WideSigned => {
+                Self::Number(Number(N::Float(deserializer.visit_f64()?)))
+            }
+            Kind::String => Self::String(deserializer.visit_string()?),
+            Kind::Seq => Self::Array(deserializer.visit_array()?),
+            Kind::Map => {
+                let entries: Vec<(String, Value)> = deserializer.visit_map()?;
+                Self::Object(entries.into_iter().collect())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize, serialize, ErrorKind};
+
+    #[test]
+    fn reads_any_document() {
+        let input =
+            r#"{"a": [null, true, 1, -2, 2.5, 1e2, "x"], "b": {}, "a": 18446744073709551616}"#;
+        let value: Value = deserialize::Json::new().deserialize(input).unwrap();
+        let big = Value::Number(Number::from_f64(18446744073709551616.0).unwrap());
+        assert_eq!(
+            value,
+            Value::Object(BTreeMap::from([
+                ("a".into(), big),
+                ("b".into(), Value::Object(BTreeMap::new())),
+            ]))
+        );
+        let array: Value = deserialize::Json::new()
+            .deserialize(r#"[null, true, 1, -2, 2.5, 1e2, "x"]"#)
+            .unwrap();
+        assert_eq!(
+            array,
+            Value::Array(vec![
+                Value::Null,
+                true.into(),
+                1u8.into(),
+                (-2).into(),
+                Value::Number(Number::from_f64(2.5).unwrap()),
+                Value::Number(Number::from_f64(100.0).unwrap()),
+                "x".into(),
+            ])
+        );
+        assert_eq!(array.as_array().unwrap()[3].as_i64(), Some(-2));
+        assert_eq!(array.as_array().unwrap()[2].as_i64(), Some(1));
+        assert_eq!(array.as_array().unwrap()[2].as_f64(), Some(1.0));
+        assert_eq!(array.as_array().unwrap()[4].as_u64(), None);
+        let zero: Value = deserialize::Json::new().deserialize("-0").unwrap();
+        assert_eq!(zero, Value::from(0u8));
+        assert_eq!(zero.as_u64(), Some(0));
+    }
+
+    #[test]
+    fn writes_back_out() {
+        let input =
+            r#"{"id": 18446744073709551615, "n": -9223372036854775808, "x": [1.5, "é", null]}"#;
+        let value: Value = deserialize::Json::new().deserialize(input).unwrap();
+        assert_eq!(serialize::Json::new().serialize(&value).unwrap(), input);
+        let mut value = value;
+        value.get_mut("x").unwrap().as_array_mut().unwrap().clear();
+        assert_eq!(value.get("x"), Some(&Value::Array(Vec::new())));
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(Number::from(5i32), Number::from(5u8));
+        assert_eq!(Number::from(-5i8).as_u64(), None);
+        assert_eq!(Number::from(u64::MAX).as_i64(), None);
+        assert!(Number::from_f64(f64::NAN).is_none());
+        assert!(!Number::from_f64(1.0).unwrap().is_integer());
+        assert_eq!(Number::from(-7i64).to_string(), "-7");
+        assert_eq!(Number::from_f64(3.0).unwrap(), Number::from(3u8));
+        assert_ne!(Number::from_f64(-3.5).unwrap(), Number::from(-3i8));
+        let float = Number::from_f64(9007199254740992.0).unwrap();
+        assert_eq!(float, Number::from(9007199254740992u64));
+        assert_ne!(float, Number::from(9007199254740993u64));
+        assert_ne!(
+            Number::from_f64(-9007199254740992.0).unwrap(),
+            Number::from(-9007199254740993i64)
+        );
+        assert_ne!(Number::from_f64(1e20).unwrap(), Number::from(u64::MAX));
+        assert_eq!(Number::from_f64(-0.0).unwrap(), Number::from(0u8));
+    }
+
+    #[test]
+    fn converts_typed_data() {
+        let data = (7u8, -3i64, 0.5f32, 'x', Some("a"), None::<bool>, [(), ()]);
+        let value = to_value(&data).unwrap();
+        assert_eq!(
+            serialize::Json::new().serialize(&value).unwrap(),
+            r#"[7, -3, 0.5, "x", "a", null, [null, null]]"#
+        );
+        let read: (u8, i64, f32, char, Option<String>, Option<bool>, [(); 2]) =
+            from_value(value).unwrap();
+        assert_eq!(read, (7, -3, 0.5, 'x', Some("a".into()), None, [(), ()]));
+
+        let map = BTreeMap::from([(true, vec![1.5]), (false, vec![])]);
+        let value = to_value(&map).unwrap();
+        assert_eq!(
+            value.get("true"),
+            Some(&Value::Array(vec![to_value(&1.5).unwrap()]))
+        );
+        assert_eq!(from_value::<BTreeMap<bool, Vec<f64>>>(value).unwrap(), map);
+        assert_eq!(
+            from_value::<Value>(Value::from(5u8)).unwrap(),
+            Value::from(5u8)
+        );
+    }
+
+    #[test]
+    fn conversion_errors() {
+        let kind = |error: crate::Error| error.kind().clone();
+        assert_eq!(
+            kind(to_value(&u128::MAX).unwrap_err()),
+            ErrorKind::Overflow { target: "Value" }
+        );
+        assert_eq!(
+            kind(to_value(&f64::NAN).unwrap_err()),
+            ErrorKind::NonFinite { value: "NaN" }
+        );
+        assert_eq!(
+            kind(to_value(&BTreeMap::from([((), 1)])).unwrap_err()),
+            ErrorKind::UnsupportedKey { found: "null" }
+        );
+        let error = to_value(&BTreeMap::from([(vec![1], 1)])).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "map keys must be strings, numbers or bools, not an array"
+        );
+        assert_eq!(
+            kind(from_value::<u8>(Value::from(300u16)).unwrap_err()),
+            ErrorKind::Overflow { target: "u8" }
+        );
+        assert_eq!(
+            kind(from_value::<u8>(Value::from("1")).unwrap_err()),
+            ErrorKind::Syntax { expected: "u8" }
+        );
+        assert_eq!(
+            kind(from_value::<(u8, u8)>(Value::Array(vec![1u8.into()])).unwrap_err()),
+            ErrorKind::Length {
+                expected: 2,
+                found: 1
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_input_fails() {
+        let error = deserialize::Json::new()
+            .deserialize::<Value>("[1, ?]")
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Syntax { expected: "value" });
+    }
+}
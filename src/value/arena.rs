@@ -0,0 +1,340 @@
+//! A [`Value`] tree allocated in a `bumpalo` arena.
+
+use bumpalo::Bump;
+
+use super::{Number, Value, N};
+use crate::deserialize::{DeserializeSeed, Deserializer, Kind};
+use crate::error::Result;
+use crate::serialize::{Serialize, Serializer};
+
+/// Any value of a self-describing format, like [`Value`], with its strings,
+/// arrays and objects allocated in a [`Bump`] arena.
+///
+/// Reading a document with [`InArena`] then takes no allocation of its own
+/// per string or container, and the whole tree is freed at once with the
+/// arena. Objects are kept sorted by key, with the last value of duplicate
+/// keys, as in [`Value`].
+///
+/// ```
+/// use bumpalo::Bump;
+/// use shallot::deserialize::Json;
+/// use shallot::value::{ArenaValue, InArena};
+///
+/// let arena = Bump::new();
+/// let value = Json::new().deserialize_seed(r#"{"id": 7, "tags": ["a"]}"#, InArena(&arena))?;
+/// assert_eq!(value.get("id").and_then(ArenaValue::as_u64), Some(7));
+/// assert_eq!(value.get("tags").and_then(ArenaValue::as_array).map(<[_]>::len), Some(1));
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ArenaValue<'a> {
+    /// JSON's `null`, or a unit value.
+    #[default]
+    Null,
+    Bool(bool),
+    Number(Number),
+    String(&'a str),
+    /// A sequence of values.
+    Array(&'a [ArenaValue<'a>]),
+    /// Entries sorted by key, with no key repeated.
+    Object(&'a [(&'a str, ArenaValue<'a>)]),
+}
+
+impl<'a> ArenaValue<'a> {
+    /// Whether the value is [`ArenaValue::Null`].
+    pub fn is_null(&self) -> bool {
+        matches!(self, Self::Null)
+    }
+
+    /// The boolean, if this is a boolean.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The number as a `u64`, if it is a whole number in its range.
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_number().and_then(Number::as_u64)
+    }
+
+    /// The number as an `i64`, if it is a whole number in its range.
+    pub fn as_i64(&self) -> Option<i64> {
+        self.as_number().and_then(Number::as_i64)
+    }
+
+    /// The number as an `f64`, rounding whole numbers beyond 2^53.
+    pub fn as_f64(&self) -> Option<f64> {
+        self.as_number().map(Number::as_f64)
+    }
+
+    /// The number, if this is a number.
+    pub fn as_number(&self) -> Option<&Number> {
+        match self {
+            Self::Number(number) => Some(number),
+            _ => None,
+        }
+    }
+
+    /// The string, if this is a string.
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            Self::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    /// The elements, if this is an array.
+    pub fn as_array(&self) -> Option<&'a [ArenaValue<'a>]> {
+        match self {
+            Self::Array(array) => Some(array),
+            _ => None,
+        }
+    }
+
+    /// The entries, if this is an object.
+    pub fn as_object(&self) -> Option<&'a [(&'a str, ArenaValue<'a>)]> {
+        match self {
+            Self::Object(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    /// The value of `key`, if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&'a ArenaValue<'a>> {
+        let object = self.as_object()?;
+        let index = object.binary_search_by(|(name, _)| (*name).cmp(key)).ok()?;
+        Some(&object[index].1)
+    }
+}
+
+/// Copies the tree out of the arena.
+impl From<ArenaValue<'_>> for Value {
+    fn from(value: ArenaValue<'_>) -> Self {
+        match value {
+            ArenaValue::Null => Self::Null,
+            ArenaValue::Bool(value) => Self::Bool(value),
+            ArenaValue::Number(number) => Self::Number(number),
+            ArenaValue::String(string) => Self::String(string.to_owned()),
+            ArenaValue::Array(array) => {
+                Self::Array(array.iter().map(|&value| value.into()).collect())
+            }
+            ArenaValue::Object(object) => Self::Object(
+                object
+                    .iter()
+                    .map(|&(key, value)| (key.to_owned(), value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for ArenaValue<'_> {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        match self {
+            Self::Null => serializer.visit_unit(),
+            Self::Bool(value) => serializer.visit_bool(value),
+            Self::Number(number) => number.accept(serializer),
+            Self::String(string) => serializer.visit_str(string),
+            Self::Array(array) => serializer.visit_array(array),
+            Self::Object(object) => {
+                serializer.visit_map(object.iter().map(|(key, value)| (key, value)))
+            }
+        }
+    }
+}
+
+/// A [`DeserializeSeed`] reading an [`ArenaValue`] allocated in the arena it
+/// holds.
+///
+/// Elements and entries are gathered in buffers shared by the whole
+/// document and copied into the arena once each container is complete, so
+/// the only allocations outside the arena are those buffers.
+#[derive(Debug, Clone, Copy)]
+pub struct InArena<'a>(pub &'a Bump);
+
+impl<'a> DeserializeSeed for InArena<'a> {
+    type Value = ArenaValue<'a>;
+
+    fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<ArenaValue<'a>> {
+        let mut builder = Builder {
+            arena: self.0,
+            elements: Vec::new(),
+            entries: Vec::new(),
+            text: String::new(),
+        };
+        Node(&mut builder).accept(deserializer)
+    }
+}
+
+/// The state of reading a tree into an arena.
+struct Builder<'a> {
+    arena: &'a Bump,
+    /// The elements read so far of each open array, innermost last.
+    elements: Vec<ArenaValue<'a>>,
+    /// The entries read so far of each open object, innermost last, with
+    /// their index in the object.
+    entries: Vec<(&'a str, usize, ArenaValue<'a>)>,
+    /// The string being read.
+    text: String,
+}
+
+impl<'a> Builder<'a> {
+    fn string<D: Deserializer>(&mut self, deserializer: &mut D) -> Result<&'a str> {
+        deserializer.visit_string_into(&mut self.text)?;
+        Ok(self.arena.alloc_str(&self.text))
+    }
+
+    // Both containers leave the buffers as they found them, even when they
+    // fail and the format skips them to carry on.
+
+    fn array<D: Deserializer>(&mut self, deserializer: &mut D) -> Result<&'a [ArenaValue<'a>]> {
+        let start = self.elements.len();
+        let read = self.read_elements(deserializer);
+        let array = read.map(|()| &*self.arena.alloc_slice_copy(&self.elements[start..]));
+        self.elements.truncate(start);
+        array
+    }
+
+    fn read_elements<D: Deserializer>(&mut self, deserializer: &mut D) -> Result<()> {
+        let mut seq = deserializer.begin_seq(None)?;
+        while deserializer.next_element(&mut seq)? {
+            let element = deserializer.element_seed(&mut seq, Node(self))?;
+            self.elements.extend(element);
+        }
+        deserializer.end_seq(seq)
+    }
+
+    fn object<D: Deserializer>(
+        &mut self,
+        deserializer: &mut D,
+    ) -> Result<&'a [(&'a str, ArenaValue<'a>)]> {
+        let start = self.entries.len();
+        let read = self.read_entries(deserializer, start);
+        let object = read.map(|()| self.sorted_entries(start));
+        self.entries.truncate(start);
+        object
+    }
+
+    fn read_entries<D: Deserializer>(&mut self, deserializer: &mut D, start: usize) -> Result<()> {
+        let mut map = deserializer.begin_map()?;
+        while let Some(key) = deserializer.next_key_seed(&mut map, Key(self))? {
+            let value = deserializer.next_value_seed(&mut map, Node(self))?;
+            let index = self.entries.len() - start;
+            self.entries.push((key, index, value));
+        }
+        deserializer.end_map(map)
+    }
+
+    /// Copy the entries from `start` into the arena, sorted by key and
+    /// keeping the last of those with the same key.
+    fn sorted_entries(&mut self, start: usize) -> &'a [(&'a str, ArenaValue<'a>)] {
+        let entries = &mut self.entries[start..];
+        // The last entry of each key sorts first among them.
+        entries.sort_unstable_by(|(key, index, _), (other, other_index, _)| {
+            key.cmp(other).then(other_index.cmp(index))
+        });
+        let mut len = 0;
+        for next in 0..entries.len() {
+            if len == 0 || entries[len - 1].0 != entries[next].0 {
+                entries[len] = entries[next];
+                len += 1;
+            }
+        }
+        let entries = entries[..len].iter().map(|&(key, _, value)| (key, value));
+        self.arena.alloc_slice_fill_iter(entries)
+    }
+}
+
+/// A seed reading one value of the tree.
+struct Node<'b, 'a>(&'b mut Builder<'a>);
+
+impl<'a> DeserializeSeed for Node<'_, 'a> {
+    type Value = ArenaValue<'a>;
+
+    fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<ArenaValue<'a>> {
+        let builder = self.0;
+        Ok(match deserializer.peek_kind()? {
+            Kind::Null => deserializer.visit_unit().map(|()| ArenaValue::Null)?,
+            Kind::Bool => ArenaValue::Bool(deserializer.visit_bool()?),
+            Kind::Unsigned => ArenaValue::Number(Number(N::Unsigned(deserializer.visit_u64()?))),
+            Kind::Signed => ArenaValue::Number(Number::from(deserializer.visit_i64()?)),
+            Kind::Float | Kind::WideUnsigned | Kind::WideSigned => {
+                ArenaValue::Number(Number(N::Float(deserializer.visit_f64()?)))
+            }
+            Kind::String => ArenaValue::String(builder.string(deserializer)?),
+            Kind::Seq => ArenaValue::Array(builder.array(deserializer)?),
+            Kind::Map => ArenaValue::Object(builder.object(deserializer)?),
+        })
+    }
+}
+
+/// A seed reading an object key into the arena.
+struct Key<'b, 'a>(&'b mut Builder<'a>);
+
+impl<'a> DeserializeSeed for Key<'_, 'a> {
+    type Value = &'a str;
+
+    fn accept<D: Deserializer>(self, deserializer: &mut D) -> Result<&'a str> {
+        self.0.string(deserializer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize, serialize, ErrorKind};
+
+    #[test]
+    fn reads_any_document() {
+        let arena = Bump::new();
+        let input = r#"{"b": [null, true, -2, 2.5, "x\n", [[]]], "a": {}, "b": 1}"#;
+        let json = deserialize::Json::new();
+        let value = json.deserialize_seed(input, InArena(&arena)).unwrap();
+        assert_eq!(
+            value,
+            ArenaValue::Object(&[
+                ("a", ArenaValue::Object(&[])),
+                ("b", ArenaValue::Number(1u8.into()))
+            ])
+        );
+        let zero = json.deserialize_seed("-0", InArena(&arena)).unwrap();
+        assert_eq!(zero.as_u64(), Some(0));
+        let value = json
+            .deserialize_seed(
+                r#"[null, true, -2, 2.5, "x\n", [[]], {"k": [1]}]"#,
+                InArena(&arena),
+            )
+            .unwrap();
+        let owned: Value = json
+            .deserialize(r#"[null, true, -2, 2.5, "x\n", [[]], {"k": [1]}]"#)
+            .unwrap();
+        assert_eq!(Value::from(value), owned);
+        let array = value.as_array().unwrap();
+        assert_eq!(array[2].as_i64(), Some(-2));
+        assert_eq!(array[4].as_str(), Some("x\n"));
+        assert_eq!(
+            array[6].get("k"),
+            Some(&ArenaValue::Array(&[ArenaValue::Number(1u8.into())]))
+        );
+        assert_eq!(array[6].get("j"), None);
+        assert_eq!(
+            serialize::Json::new()
+                .compact(true)
+                .serialize(&value)
+                .unwrap(),
+            r#"[null,true,-2,2.5,"x\n",[[]],{"k":[1]}]"#
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let arena = Bump::new();
+        let json = deserialize::Json::new();
+        let error = json
+            .deserialize_seed(r#"{"a": [1, ?]}"#, InArena(&arena))
+            .unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::Syntax { expected: "value" });
+    }
+}
@@ -0,0 +1,307 @@
+//! Deserializing out of a [`Value`].
+
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use super::Value;
+use crate::deserialize::{Deserialize, DeserializeSeed, Deserializer, Kind, Map, Seq, Variant};
+use crate::error::{Error, ErrorKind, Result, WarningKind};
+use crate::serialize;
+
+/// Read a `T` out of a [`Value`] tree, as if from the JSON text the tree
+/// would be written as, but without producing it.
+///
+/// Map keys that are strings are parsed when the key type is a number or a
+/// boolean.
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use shallot::{value, Value};
+///
+/// let value = Value::Object(BTreeMap::from([("7".into(), Value::from(-1))]));
+/// let map: BTreeMap<u8, i32> = value::from_value(value)?;
+/// assert_eq!(map, BTreeMap::from([(7, -1)]));
+/// # Ok::<(), shallot::Error>(())
+/// ```
+pub fn from_value<T: Deserialize>(value: Value) -> Result<T> {
+    let mut reader = Reader {
+        pending: vec![value],
+        remaining: Vec::new(),
+        key: false,
+    };
+    PhantomData::<T>
+        .accept(&mut reader)
+        .map_err(Error::in_type::<T>)
+}
+
+/// A deserializer taking values apart from the top of a stack.
+///
+/// Starting a sequence or map pushes its contents, keys above their values,
+/// so they are read in order.
+struct Reader {
+    pending: Vec<Value>,
+    /// How many elements or entries of each open sequence or map are left.
+    remaining: Vec<usize>,
+    /// Whether a map key is being read, so strings may stand for scalars.
+    key: bool,
+}
+
+fn syntax(expected: &'static str) -> Error {
+    Error::new(ErrorKind::Syntax { expected })
+}
+
+impl Reader {
+    fn next(&mut self) -> Result<Value> {
+        self.pending.pop().ok_or_else(|| Error::new(ErrorKind::Eof))
+    }
+
+    /// Move to the next element or entry of the innermost open container.
+    fn advance(&mut self) -> bool {
+        match self.remaining.last_mut() {
+            Some(0) | None => false,
+            Some(remaining) => {
+                *remaining -= 1;
+                true
+            }
+        }
+    }
+
+    /// Close the innermost container, dropping what was left unread.
+    fn close(&mut self, values_per_entry: usize) -> usize {
+        let left = self.remaining.pop().unwrap_or_default();
+        let len = self.pending.len().saturating_sub(left * values_per_entry);
+        self.pending.truncate(len);
+        left
+    }
+
+    /// Read a scalar parsed from a string when it is a map key.
+    fn scalar<T: FromStr>(
+        &mut self,
+        expected: &'static str,
+        convert: impl FnOnce(Value) -> Option<Result<T>>,
+    ) -> Result<T> {
+        match self.next()? {
+            Value::String(key) if self.key => key.parse().map_err(|_| syntax(expected)),
+            value => convert(value).unwrap_or_else(|| Err(syntax(expected))),
+        }
+    }
+
+    fn integer<T>(&mut self, expected: &'static str) -> Result<T>
+    where
+        T: FromStr + TryFrom<u64> + TryFrom<i64>,
+    {
+        let overflow = || Error::new(ErrorKind::Overflow { target: expected });
+        self.scalar(expected, |value| {
+            let number = *value.as_number()?;
+            match (number.as_u64(), number.as_i64()) {
+                (Some(value), _) => Some(T::try_from(value).map_err(|_| overflow())),
+                (None, Some(value)) => Some(T::try_from(value).map_err(|_| overflow())),
+                (None, None) => None,
+            }
+        })
+    }
+}
+
+macro_rules! visit_integer {
+    ($($method:ident($ty:ident))+) => {
+        $(
+            fn $method(&mut self) -> Result<$ty> {
+                self.integer(stringify!($ty))
+            }
+        )+
+    };
+}
+
+impl Deserializer for Reader {
+    fn visit_bool(&mut self) -> Result<bool> {
+        self.scalar("bool", |value| value.as_bool().map(Ok))
+    }
+
+    visit_integer! {
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_i128(i128)
+        visit_isize(isize)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+        visit_u128(u128)
+        visit_usize(usize)
+    }
+
+    fn visit_f32(&mut self) -> Result<f32> {
+        self.scalar("f32", |value| value.as_f64().map(|value| Ok(value as f32)))
+    }
+
+    fn visit_f64(&mut self) -> Result<f64> {
+        self.scalar("f64", |value| value.as_f64().map(Ok))
+    }
+
+    fn visit_char(&mut self) -> Result<char> {
+        let string = self.visit_string()?;
+        let mut chars = string.chars();
+        match (chars.next(), chars.next()) {
+            (Some(char), None) => Ok(char),
+            _ => Err(syntax("char")),
+        }
+    }
+
+    fn visit_string(&mut self) -> Result<String> {
+        match self.next()? {
+            Value::String(string) => Ok(string),
+            _ => Err(syntax("string")),
+        }
+    }
+
+    /// The text is the value written as compact JSON.
+    fn visit_raw<T>(&mut self, read: impl FnOnce(&str) -> Result<T>) -> Result<T> {
+        let value = self.next()?;
+        read(&serialize::Json::new().compact(true).serialize(&value)?)
+    }
+
+    fn peek_kind(&mut self) -> Result<Kind> {
+        let value = self
+            .pending
+            .last()
+            .ok_or_else(|| Error::new(ErrorKind::Eof))?;
+        Ok(match value {
+            Value::Null => Kind::Null,
+            Value::Bool(_) => Kind::Bool,
+            Value::Number(number) if number.as_u64().is_some() => Kind::Unsigned,
+            Value::Number(number) if number.as_i64().is_some() => Kind::Signed,
+            Value::Number(_) => Kind::Float,
+            Value::String(_) => Kind::String,
+            Value::Array(_) => Kind::Seq,
+            Value::Object(_) => Kind::Map,
+        })
+    }
+
+    fn visit_unit(&mut self) -> Result<()> {
+        match self.next()? {
+            Value::Null => Ok(()),
+            _ => Err(syntax("null")),
+        }
+    }
+
+    fn visit_option<T: Deserialize>(&mut self) -> Result<Option<T>> {
+        if let Some(Value::Null) = self.pending.last() {
+            self.pending.pop();
+            return Ok(None);
+        }
+        T::accept(self).map(Some).map_err(Error::in_type::<T>)
+    }
+
+    fn begin_seq(&mut self, len: Option<usize>) -> Result<Seq> {
+        let Value::Array(elements) = self.next()? else {
+            return Err(syntax("array"));
+        };
+        if let Some(expected) = len.filter(|len| *len != elements.len()) {
+            let found = elements.len();
+            return Err(Error::new(ErrorKind::Length { expected, found }));
+        }
+        self.remaining.push(elements.len());
+        self.pending.extend(elements.into_iter().rev());
+        Ok(Seq::new(len, 0))
+    }
+
+    fn next_element(&mut self, seq: &mut Seq) -> Result<bool> {
+        let more = self.advance();
+        seq.index += usize::from(more);
+        Ok(more)
+    }
+
+    fn end_seq(&mut self, seq: Seq) -> Result<()> {
+        match self.close(1) {
+            0 => Ok(()),
+            left => Err(Error::new(ErrorKind::Length {
+                expected: seq.index,
+                found: seq.index + left,
+            })),
+        }
+    }
+
+    fn begin_map(&mut self) -> Result<Map> {
+        let Value::Object(entries) = self.next()? else {
+            return Err(syntax("object"));
+        };
+        self.remaining.push(entries.len());
+        for (key, value) in entries.into_iter().rev() {
+            self.pending.push(value);
+            self.pending.push(Value::String(key));
+        }
+        Ok(Map::new(0))
+    }
+
+    fn next_key_seed<S: DeserializeSeed>(
+        &mut self,
+        map: &mut Map,
+        seed: S,
+    ) -> Result<Option<S::Value>> {
+        if !self.advance() {
+            return Ok(None);
+        }
+        map.index += 1;
+        self.key = true;
+        let key = seed.accept(self).map_err(Error::in_type::<S::Value>);
+        self.key = false;
+        key.map(Some)
+    }
+
+    fn end_map(&mut self, map: Map) -> Result<()> {
+        let _ = map;
+        match self.close(2) {
+            0 => Ok(()),
+            _ => Err(Error::custom("not every entry of the map was read")),
+        }
+    }
+
+    fn next_field(
+        &mut self,
+        map: &mut Map,
+        fields: &'static [&'static str],
+    ) -> Result<Option<usize>> {
+        while self.advance() {
+            map.index += 1;
+            let name = self.visit_string()?;
+            if let Some(index) = fields.iter().position(|field| *field == name) {
+                return Ok(Some(index));
+            }
+            self.warn(WarningKind::UnknownField { field: name });
+            self.next()?;
+        }
+        Ok(None)
+    }
+
+    /// Unit variants are strings, other variants objects with the name as
+    /// their only key, as written by [`to_value`](super::to_value).
+    fn begin_variant(&mut self, variants: &'static [Variant]) -> Result<usize> {
+        let (name, content) = match self.next()? {
+            Value::String(name) => (name, None),
+            Value::Object(entries) if entries.len() == 1 => {
+                let (name, content) = entries.into_iter().next().expect("one entry");
+                (name, Some(content))
+            }
+            _ => return Err(syntax("variant")),
+        };
+        let Some(index) = variants.iter().position(|variant| variant.name == name) else {
+            return Err(Error::new(ErrorKind::UnknownVariant { variant: name }));
+        };
+        match (content, variants[index].unit) {
+            (None, true) => Ok(index),
+            (Some(content), false) => {
+                self.pending.push(content);
+                Ok(index)
+            }
+            (None, false) => Err(syntax("object")),
+            (Some(_), true) => Err(syntax("string")),
+        }
+    }
+
+    fn end_variant(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
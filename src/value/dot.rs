@@ -0,0 +1,126 @@
+//! Graphviz drawings of the shape of a [`Value`].
+
+use std::fmt::Write;
+
+use super::{Number, Value, N};
+
+impl Value {
+    /// Describe the value as a Graphviz DOT graph, for drawing the shape of
+    /// a large document with a tool such as `dot -Tsvg`.
+    ///
+    /// Each value is a node labeled with its type, and scalars also with
+    /// their value. Each entry of an object or array is an edge from it,
+    /// labeled with the key or index. Nodes are named `n0`, `n1`, and so on
+    /// in depth-first order, starting from this value.
+    ///
+    /// ```
+    /// use shallot::{deserialize, Value};
+    ///
+    /// let value: Value = deserialize::Json::new().deserialize(r#"{"tags": ["a"]}"#)?;
+    /// assert_eq!(
+    ///     value.to_dot(),
+    ///     "digraph value {\n  n0 [label=\"object\"];\n  n0 -> n1 [label=\"tags\"];\n  \
+    ///      n1 [label=\"array\"];\n  n1 -> n2 [label=\"0\"];\n  \
+    ///      n2 [label=\"string \\\"a\\\"\"];\n}\n",
+    /// );
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn to_dot(&self) -> String {
+        let mut output = String::from("digraph value {\n");
+        self.dot_node(&mut 0, &mut output);
+        output.push_str("}\n");
+        output
+    }
+
+    /// Write the node for this value, named after `next`, then the edges to
+    /// and nodes of its entries, advancing `next` past every node written.
+    fn dot_node(&self, next: &mut usize, output: &mut String) {
+        let id = *next;
+        *next += 1;
+        let label = match self {
+            Self::Null => String::from("null"),
+            Self::Bool(value) => format!("bool {value}"),
+            Self::Number(Number(N::Float(value))) => format!("float {value:?}"),
+            Self::Number(number) => format!("integer {number}"),
+            Self::String(string) => format!("string {string:?}"),
+            Self::Array(_) => String::from("array"),
+            Self::Object(_) => String::from("object"),
+        };
+        writeln!(output, "  n{id} [label=\"{}\"];", escape(&label)).expect("writing to a String");
+        let entries: Box<dyn Iterator<Item = (String, &Value)>> = match self {
+            Self::Array(array) => Box::new(
+                array
+                    .iter()
+                    .enumerate()
+                    .map(|(index, element)| (index.to_string(), element)),
+            ),
+            Self::Object(object) => {
+                Box::new(object.iter().map(|(key, value)| (key.clone(), value)))
+            }
+            _ => return,
+        };
+        for (key, value) in entries {
+            writeln!(
+                output,
+                "  n{id} -> n{} [label=\"{}\"];",
+                *next,
+                escape(&key)
+            )
+            .expect("writing to a String");
+            value.dot_node(next, output);
+        }
+    }
+}
+
+/// `text` escaped for a double-quoted DOT string.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(char);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize;
+
+    #[test]
+    fn to_dot() {
+        let value: Value = deserialize::Json::new()
+            .deserialize(r#"{"a\"\\": [1.5, -2], "b\nc": {"d": null}, "e": "x\ny"}"#)
+            .unwrap();
+        let expected = [
+            "digraph value {",
+            "  n0 [label=\"object\"];",
+            "  n0 -> n1 [label=\"a\\\"\\\\\"];",
+            "  n1 [label=\"array\"];",
+            "  n1 -> n2 [label=\"0\"];",
+            "  n2 [label=\"float 1.5\"];",
+            "  n1 -> n3 [label=\"1\"];",
+            "  n3 [label=\"integer -2\"];",
+            "  n0 -> n4 [label=\"b\\nc\"];",
+            "  n4 [label=\"object\"];",
+            "  n4 -> n5 [label=\"d\"];",
+            "  n5 [label=\"null\"];",
+            "  n0 -> n6 [label=\"e\"];",
+            "  n6 [label=\"string \\\"x\\\\ny\\\"\"];",
+            "}",
+            "",
+        ];
+        assert_eq!(value.to_dot(), expected.join("\n"));
+        assert_eq!(
+            Value::from(true).to_dot(),
+            "digraph value {\n  n0 [label=\"bool true\"];\n}\n"
+        );
+    }
+}
@@ -0,0 +1,451 @@
+//! JSON Patch (RFC 6902) documents and their application to a [`Value`].
+
+use super::Value;
+use crate::deserialize::{Deserialize, Deserializer};
+use crate::error::{Error, ErrorKind, Result};
+use crate::serialize::{Serialize, Serializer};
+
+/// A JSON Patch: a list of operations applied in order by
+/// [`Patch::apply`].
+///
+/// ```
+/// use shallot::value::Patch;
+/// use shallot::{deserialize, ErrorKind, Value};
+///
+/// let json = deserialize::Json::new();
+/// let mut value: Value = json.deserialize(r#"{"tags": ["a"], "draft": true}"#)?;
+/// let patch: Patch = json.deserialize(
+///     r#"[
+///         {"op": "test", "path": "/draft", "value": true},
+///         {"op": "remove", "path": "/draft"},
+///         {"op": "add", "path": "/tags/-", "value": "b"}
+///     ]"#,
+/// )?;
+/// patch.apply(&mut value)?;
+/// assert_eq!(value, json.deserialize::<Value>(r#"{"tags": ["a", "b"]}"#)?);
+///
+/// let error = patch.apply(&mut value).unwrap_err();
+/// assert_eq!(error.kind(), &ErrorKind::NoSuchPath { path: "/draft".into() });
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Patch(pub Vec<Operation>);
+
+/// One operation of a [`Patch`]; `path` and `from` are JSON pointers, as
+/// taken by [`Value::pointer`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    /// Insert `value` into an object or array, or replace an object entry.
+    /// The last token `-` appends to an array.
+    Add { path: String, value: Value },
+    /// Remove the value at `path`, which must exist.
+    Remove { path: String },
+    /// Replace the value at `path`, which must exist.
+    Replace { path: String, value: Value },
+    /// Remove the value at `from` and add it at `path`.
+    Move { from: String, path: String },
+    /// Add a copy of the value at `from` at `path`.
+    Copy { from: String, path: String },
+    /// Fail with [`ErrorKind::TestFailed`] unless the value at `path`
+    /// equals `value`.
+    Test { path: String, value: Value },
+}
+
+impl Patch {
+    /// Apply every operation to `target` in order.
+    ///
+    /// The patch applies as a whole or not at all: if an operation fails,
+    /// `target` is left unchanged.
+    pub fn apply(&self, target: &mut Value) -> Result<()> {
+        let mut patched = target.clone();
+        for operation in &self.0 {
+            operation.apply(&mut patched)?;
+        }
+        *target = patched;
+        Ok(())
+    }
+}
+
+impl Operation {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Add { .. } => "add",
+            Self::Remove { .. } => "remove",
+            Self::Replace { .. } => "replace",
+            Self::Move { .. } => "move",
+            Self::Copy { .. } => "copy",
+            Self::Test { .. } => "test",
+        }
+    }
+
+    fn apply(&self, target: &mut Value) -> Result<()> {
+        // Check every pointer up front so a malformed one is reported as
+        // such whichever operation carries it.
+        let (from, path) = match self {
+            Self::Move { from, path } | Self::Copy { from, path } => (Some(from), path),
+            Self::Add { path, .. }
+            | Self::Remove { path }
+            | Self::Replace { path, .. }
+            | Self::Test { path, .. } => (None, path),
+        };
+        for pointer in from.into_iter().chain([path]) {
+            tokens(pointer)?;
+        }
+        match self {
+            Self::Add { path, value } => add(target, path, value.clone()),
+            Self::Remove { path } => remove(target, path).map(drop),
+            Self::Replace { path, value } => {
+                *target.pointer_mut(path).ok_or_else(|| no_such_path(path))? = value.clone();
+                Ok(())
+            }
+            Self::Move { from, path } => {
+                // A value cannot be moved into one of its own children.
+                if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                    return Err(no_such_path(path));
+                }
+                let value = remove(target, from)?;
+                add(target, path, value)
+            }
+            Self::Copy { from, path } => {
+                let value = target.pointer(from).ok_or_else(|| no_such_path(from))?;
+                add(target, path, value.clone())
+            }
+            Self::Test { path, value } => match target.pointer(path) {
+                Some(found) if found == value => Ok(()),
+                Some(_) => Err(Error::new(ErrorKind::TestFailed { path: path.clone() })),
+                None => Err(no_such_path(path)),
+            },
+        }
+    }
+}
+
+fn no_such_path(path: &str) -> Error {
+    Error::new(ErrorKind::NoSuchPath {
+        path: path.to_owned(),
+    })
+}
+
+/// Split a JSON pointer into its unescaped reference tokens.
+pub(super) fn tokens(pointer: &str) -> Result<Vec<String>> {
+    let syntax = || {
+        Error::new(ErrorKind::Syntax {
+            expected: "JSON pointer",
+        })
+    };
+    if pointer.is_empty() {
+        return Ok(Vec::new());
+    }
+    let pointer = pointer.strip_prefix('/').ok_or_else(syntax)?;
+    pointer
+        .split('/')
+        .map(|token| {
+            let mut unescaped = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(char) = chars.next() {
+                unescaped.push(match char {
+                    '~' => match chars.next() {
+                        Some('0') => '~',
+                        Some('1') => '/',
+                        _ => return Err(syntax()),
+                    },
+                    char => char,
+                });
+            }
+            Ok(unescaped)
+        })
+        .collect()
+}
+
+/// Parse an array index token, which has no sign or leading zeros.
+pub(super) fn index(token: &str) -> Option<usize> {
+    match token.as_bytes() {
+        [b'0'] => Some(0),
+        [b'1'..=b'9', rest @ ..] if rest.iter().all(u8::is_ascii_digit) => token.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Resolve all but the last token of `path`, returning the container and
+/// that token, or `None` for the empty pointer.
+fn parent<'a>(target: &'a mut Value, path: &str) -> Result<Option<(&'a mut Value, String)>> {
+    let mut tokens = tokens(path)?;
+    let Some(last) = tokens.pop() else {
+        return Ok(None);
+    };
+    let parent = tokens
+        .iter()
+        .try_fold(target, |value, token| value.child_mut(token))
+        .ok_or_else(|| no_such_path(path))?;
+    Ok(Some((parent, last)))
+}
+
+fn add(target: &mut Value, path: &str, value: Value) -> Result<()> {
+    let Some((parent, last)) = parent(target, path)? else {
+        *target = value;
+        return Ok(());
+    };
+    match parent {
+        Value::Object(object) => {
+            object.insert(last, value);
+        }
+        Value::Array(array) => {
+            let index = match last.as_str() {
+                "-" => array.len(),
+                _ => index(&last)
+                    .filter(|index| *index <= array.len())
+                    .ok_or_else(|| no_such_path(path))?,
+            };
+            array.insert(index, value);
+        }
+        _ => return Err(no_such_path(path)),
+    }
+    Ok(())
+}
+
+fn remove(target: &mut Value, path: &str) -> Result<Value> {
+    let removed = match parent(target, path)? {
+        Some((Value::Object(object), last)) => object.remove(&last),
+        Some((Value::Array(array), last)) => index(&last)
+            .filter(|index| *index < array.len())
+            .map(|index| array.remove(index)),
+        // The whole document cannot be removed.
+        _ => None,
+    };
+    removed.ok_or_else(|| no_such_path(path))
+}
+
+impl Serialize for Operation {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        let mut entries = vec![("op", Value::from(self.name()))];
+        match self {
+            Self::Add { path, value }
+            | Self::Replace { path, value }
+            | Self::Test { path, value } => {
+                entries.push(("path", Value::from(path.as_str())));
+                entries.push(("value", value.clone()));
+            }
+            Self::Remove { path } => entries.push(("path", Value::from(path.as_str()))),
+            Self::Move { from, path } | Self::Copy { from, path } => {
+                entries.push(("from", Value::from(from.as_str())));
+                entries.push(("path", Value::from(path.as_str())));
+            }
+        }
+        serializer.visit_map(entries.iter().map(|(key, value)| (key, value)))
+    }
+}
+
+/// Members other than the ones the operation uses are ignored.
+impl Deserialize for Operation {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        let Value::Object(mut members) = <Value as Deserialize>::accept(deserializer)? else {
+            return Err(Error::new(ErrorKind::Syntax { expected: "object" }));
+        };
+        let mut value = |field| {
+            members
+                .remove(field)
+                .ok_or_else(|| Error::new(ErrorKind::MissingField { field }))
+        };
+        let string = |value: Value| match value {
+            Value::String(string) => Ok(string),
+            _ => Err(Error::new(ErrorKind::Syntax { expected: "string" })),
+        };
+        let op = string(value("op")?)?;
+        Ok(match op.as_str() {
+            "add" => Self::Add {
+                path: string(value("path")?)?,
+                value: value("value")?,
+            },
+            "remove" => Self::Remove {
+                path: string(value("path")?)?,
+            },
+            "replace" => Self::Replace {
+                path: string(value("path")?)?,
+                value: value("value")?,
+            },
+            "move" => Self::Move {
+                from: string(value("from")?)?,
+                path: string(value("path")?)?,
+            },
+            "copy" => Self::Copy {
+                from: string(value("from")?)?,
+                path: string(value("path")?)?,
+            },
+            "test" => Self::Test {
+                path: string(value("path")?)?,
+                value: value("value")?,
+            },
+            _ => return Err(Error::new(ErrorKind::UnknownVariant { variant: op })),
+        })
+    }
+}
+
+impl Serialize for Patch {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_array(&self.0)
+    }
+}
+
+impl Deserialize for Patch {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_array().map(Self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{deserialize, serialize};
+
+    fn json(text: &str) -> Value {
+        deserialize::Json::new().deserialize(text).unwrap()
+    }
+
+    fn patch(text: &str) -> Patch {
+        deserialize::Json::new().deserialize(text).unwrap()
+    }
+
+    #[test]
+    fn operations() {
+        let mut value = json(r#"{"a": {"b": [1, 2]}, "c": "x", "~/": 0}"#);
+        patch(
+            r#"[
+                {"op": "add", "path": "/a/b/1", "value": 9},
+                {"op": "replace", "path": "/c", "value": null},
+                {"op": "move", "from": "/~0~1", "path": "/d"},
+                {"op": "copy", "from": "/a/b", "path": "/e"},
+                {"op": "remove", "path": "/a/b/0"},
+                {"op": "test", "path": "/e", "value": [1, 9, 2.0]}
+            ]"#,
+        )
+        .apply(&mut value)
+        .unwrap();
+        assert_eq!(
+            value,
+            json(r#"{"a": {"b": [9, 2]}, "c": null, "d": 0, "e": [1, 9, 2]}"#)
+        );
+        patch(r#"[{"op": "add", "path": "", "value": [true]}]"#)
+            .apply(&mut value)
+            .unwrap();
+        assert_eq!(value, json("[true]"));
+    }
+
+    #[test]
+    fn failures_leave_the_target_unchanged() {
+        let original = json(r#"{"a": [1], "b": {"c": 2}}"#);
+        let failures = [
+            (
+                r#"{"op": "test", "path": "/a/0", "value": 2}"#,
+                ErrorKind::TestFailed {
+                    path: "/a/0".into(),
+                },
+            ),
+            (
+                r#"{"op": "remove", "path": "/a/1"}"#,
+                ErrorKind::NoSuchPath {
+                    path: "/a/1".into(),
+                },
+            ),
+            (
+                r#"{"op": "add", "path": "/a/01", "value": 0}"#,
+                ErrorKind::NoSuchPath {
+                    path: "/a/01".into(),
+                },
+            ),
+            (
+                r#"{"op": "add", "path": "/x/y", "value": 0}"#,
+                ErrorKind::NoSuchPath {
+                    path: "/x/y".into(),
+                },
+            ),
+            (
+                r#"{"op": "replace", "path": "/z", "value": 0}"#,
+                ErrorKind::NoSuchPath { path: "/z".into() },
+            ),
+            (
+                r#"{"op": "move", "from": "/b", "path": "/b/d"}"#,
+                ErrorKind::NoSuchPath {
+                    path: "/b/d".into(),
+                },
+            ),
+            (
+                r#"{"op": "remove", "path": "b"}"#,
+                ErrorKind::Syntax {
+                    expected: "JSON pointer",
+                },
+            ),
+        ];
+        for (operation, kind) in failures {
+            let mut value = original.clone();
+            let patch = patch(&format!(
+                r#"[{{"op": "add", "path": "/n", "value": 1}}, {operation}]"#
+            ));
+            assert_eq!(
+                patch.apply(&mut value).unwrap_err().kind(),
+                &kind,
+                "{operation}"
+            );
+            assert_eq!(value, original);
+        }
+    }
+
+    #[test]
+    fn malformed_pointers() {
+        let operations = [
+            r#"{"op": "add", "path": "{}", "value": 0}"#,
+            r#"{"op": "remove", "path": "{}"}"#,
+            r#"{"op": "replace", "path": "{}", "value": 0}"#,
+            r#"{"op": "move", "from": "{}", "path": "/b"}"#,
+            r#"{"op": "move", "from": "/a", "path": "{}"}"#,
+            r#"{"op": "copy", "from": "{}", "path": "/b"}"#,
+            r#"{"op": "copy", "from": "/a", "path": "{}"}"#,
+            r#"{"op": "test", "path": "{}", "value": 1}"#,
+        ];
+        for pointer in ["a", "/a~2", "/a~"] {
+            for operation in operations {
+                let operation = operation.replace("{}", pointer);
+                let mut value = json(r#"{"a": 1}"#);
+                let error = patch(&format!("[{operation}]"))
+                    .apply(&mut value)
+                    .unwrap_err();
+                assert_eq!(
+                    error.kind(),
+                    &ErrorKind::Syntax {
+                        expected: "JSON pointer"
+                    },
+                    "{operation}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn documents() {
+        let text = r#"[{"op": "move", "from": "/a", "path": "/b"}, {"op": "test", "path": "", "value": 1}]"#;
+        let read = patch(text);
+        assert_eq!(
+            read.0[0],
+            Operation::Move {
+                from: "/a".into(),
+                path: "/b".into()
+            }
+        );
+        assert_eq!(serialize::Json::new().serialize(&read).unwrap(), text);
+        let error = |text| {
+            deserialize::Json::new()
+                .deserialize::<Patch>(text)
+                .unwrap_err()
+                .kind()
+                .clone()
+        };
+        assert_eq!(
+            error(r#"[{"op": "copy", "path": "/a"}]"#),
+            ErrorKind::MissingField { field: "from" }
+        );
+        assert_eq!(
+            error(r#"[{"op": "delete", "path": "/a"}]"#),
+            ErrorKind::UnknownVariant {
+                variant: "delete".into()
+            }
+        );
+    }
+}
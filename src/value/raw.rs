@@ -0,0 +1,111 @@
+//! Values kept as the text they were read from.
+
+use std::fmt;
+
+use crate::deserialize::{self, Deserialize, Deserializer};
+use crate::error::Result;
+use crate::serialize::{Serialize, Serializer};
+
+/// A JSON value kept as text: captured as it is when deserialized, and
+/// written back unchanged when serialized.
+///
+/// A subtree that is only passed along is then neither parsed into typed
+/// data nor produced again, and can still be read later with
+/// [`RawValue::parse`].
+///
+/// ```
+/// use shallot::value::RawValue;
+/// use shallot::{deserialize, serialize, Serializer};
+///
+/// let (id, payload): (u32, RawValue) =
+///     deserialize::Json::new().deserialize(r#"[7, {"big": [1, 2, 3]}]"#)?;
+/// assert_eq!(payload.as_str(), r#"{"big": [1, 2, 3]}"#);
+/// let output = serialize::Json::new().compact(true).serialize(&(id + 1, payload))?;
+/// assert_eq!(output, r#"[8,{"big": [1, 2, 3]}]"#);
+/// # Ok::<(), shallot::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RawValue(String);
+
+impl RawValue {
+    /// Check that `json` holds a single JSON value and wrap it, without the
+    /// whitespace around it.
+    pub fn from_string(json: String) -> Result<Self> {
+        deserialize::Json::new().deserialize(&json)
+    }
+
+    /// The text of the value.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Unwrap the text of the value.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// Deserialize the value as a `T`.
+    pub fn parse<T: Deserialize>(&self) -> Result<T> {
+        deserialize::Json::new().deserialize(&self.0)
+    }
+}
+
+impl fmt::Display for RawValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Serialize for RawValue {
+    fn accept<S: Serializer>(&self, serializer: &S) -> Result<S::Output> {
+        serializer.visit_raw(&self.0)
+    }
+}
+
+impl Deserialize for RawValue {
+    fn accept<D: Deserializer>(deserializer: &mut D) -> Result<Self> {
+        deserializer.visit_raw(|text| Ok(RawValue(text.to_owned())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::value::{from_value, to_value, Value};
+    use crate::{serialize, ErrorKind};
+
+    #[test]
+    fn passes_text_through() {
+        let text = r#"{"keep": [1,   2.50, "A"], "n": 3}"#;
+        let map: BTreeMap<String, RawValue> = deserialize::Json::new().deserialize(text).unwrap();
+        assert_eq!(map["keep"].as_str(), r#"[1,   2.50, "A"]"#);
+        let json = serialize::Json::new();
+        assert_eq!(
+            json.serialize(&map).unwrap(),
+            r#"{"keep": [1,   2.50, "A"], "n": 3}"#
+        );
+        let canonical = json.canonical(true).serialize(&map["keep"]).unwrap();
+        assert_eq!(canonical, r#"[1,2.5,"A"]"#);
+        assert_eq!(map["n"].parse::<u8>().unwrap(), 3);
+    }
+
+    #[test]
+    fn validates_text() {
+        let raw = RawValue::from_string(" [true] ".into()).unwrap();
+        assert_eq!(raw.to_string(), "[true]");
+        let error = RawValue::from_string("[true] false".into()).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::TrailingCharacters);
+        assert!(RawValue::from_string("{".into()).is_err());
+    }
+
+    #[test]
+    fn converts_to_and_from_values() {
+        let raw = RawValue::from_string(r#"{"a": [null]}"#.into()).unwrap();
+        let value = to_value(&raw).unwrap();
+        assert_eq!(value.pointer("/a/0"), Some(&Value::Null));
+        let raw: RawValue = from_value(value).unwrap();
+        assert_eq!(raw.as_str(), r#"{"a":[null]}"#);
+    }
+}
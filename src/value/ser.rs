@@ -0,0 +1,185 @@
+//! Serializing into a [`Value`].
+
+use std::collections::BTreeMap;
+
+use super::{Number, Value};
+use crate::deserialize;
+use crate::error::{Error, ErrorKind, Result};
+use crate::serialize::{Serialize, Serializer, Struct, Tuple};
+
+/// Convert `value` into a [`Value`] tree, as it would be written to JSON
+/// but without producing text.
+///
+/// Struct fields are all kept, enum variants with data become single-entry
+/// objects, and map keys that serialize to numbers or booleans are turned
+/// into strings. Integers beyond 64 bits, non-finite floats, and other map
+/// keys fail, the keys with [`ErrorKind::UnsupportedKey`].
+///
+/// ```
+/// use std::collections::BTreeMap;
+///
+/// use shallot::{value, Value};
+///
+/// let value = value::to_value(&BTreeMap::from([(1, vec!["a"])]))?;
+/// assert_eq!(value.get("1"), Some(&Value::Array(vec!["a".into()])));
+/// # Ok::<(), shallot::Error>(())
+/// ```
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+    Writer.serialize(value)
+}
+
+/// A serializer whose output is a [`Value`].
+struct Writer;
+
+impl Writer {
+    /// Serialize a map key into a string.
+    fn key<K: Serialize>(&self, key: &K) -> Result<String> {
+        match self.serialize(key)? {
+            Value::String(key) => Ok(key),
+            Value::Number(key) => Ok(key.to_string()),
+            Value::Bool(key) => Ok(key.to_string()),
+            Value::Null => Err(unsupported_key("null")),
+            Value::Array(_) => Err(unsupported_key("an array")),
+            Value::Object(_) => Err(unsupported_key("an object")),
+        }
+    }
+}
+
+/// The error for a map key that serialized to `found`, as the JSON
+/// serializer reports it.
+fn unsupported_key(found: &'static str) -> Error {
+    Error::new(ErrorKind::UnsupportedKey { found })
+}
+
+/// The error for integers that do not fit in a [`Number`].
+fn out_of_range() -> Error {
+    Error::new(ErrorKind::Overflow { target: "Value" })
+}
+
+macro_rules! visit_number {
+    ($($method:ident($ty:ty))+) => {
+        $(
+            fn $method(&self, input: &$ty) -> Result<Value> {
+                Ok(Value::Number(Number::from(*input)))
+            }
+        )+
+    };
+}
+
+impl Serializer for Writer {
+    type Output = Value;
+
+    fn visit_bool(&self, input: &bool) -> Result<Value> {
+        Ok(Value::Bool(*input))
+    }
+
+    visit_number! {
+        visit_i8(i8)
+        visit_i16(i16)
+        visit_i32(i32)
+        visit_i64(i64)
+        visit_u8(u8)
+        visit_u16(u16)
+        visit_u32(u32)
+        visit_u64(u64)
+    }
+
+    fn visit_i128(&self, input: &i128) -> Result<Value> {
+        match u64::try_from(*input) {
+            Ok(input) => self.visit_u64(&input),
+            Err(_) => self.visit_i64(&i64::try_from(*input).map_err(|_| out_of_range())?),
+        }
+    }
+
+    fn visit_isize(&self, input: &isize) -> Result<Value> {
+        self.visit_i64(&i64::try_from(*input).map_err(|_| out_of_range())?)
+    }
+
+    fn visit_u128(&self, input: &u128) -> Result<Value> {
+        self.visit_u64(&u64::try_from(*input).map_err(|_| out_of_range())?)
+    }
+
+    fn visit_usize(&self, input: &usize) -> Result<Value> {
+        self.visit_u64(&u64::try_from(*input).map_err(|_| out_of_range())?)
+    }
+
+    fn visit_f32(&self, input: &f32) -> Result<Value> {
+        self.visit_f64(&f64::from(*input))
+    }
+
+    fn visit_f64(&self, input: &f64) -> Result<Value> {
+        if let Some(number) = Number::from_f64(*input) {
+            return Ok(Value::Number(number));
+        }
+        let value = match *input {
+            input if input.is_nan() => "NaN",
+            input if input > 0.0 => "Infinity",
+            _ => "-Infinity",
+        };
+        Err(Error::new(ErrorKind::NonFinite { value }))
+    }
+
+    fn visit_char(&self, input: &char) -> Result<Value> {
+        Ok(Value::String(input.to_string()))
+    }
+
+    fn visit_str(&self, input: &str) -> Result<Value> {
+        Ok(Value::String(input.to_owned()))
+    }
+
+    fn visit_raw(&self, input: &str) -> Result<Value> {
+        deserialize::Json::new().deserialize(input)
+    }
+
+    fn visit_unit(&self) -> Result<Value> {
+        Ok(Value::Null)
+    }
+
+    fn visit_option<T: Serialize>(&self, input: &Option<T>) -> Result<Value> {
+        match input {
+            Some(input) => self.serialize(input),
+            None => Ok(Value::Null),
+        }
+    }
+
+    fn visit_seq<'a, T, I>(&self, input: I) -> Result<Value>
+    where
+        T: Serialize + 'a,
+        I: IntoIterator<Item = &'a T>,
+    {
+        let elements = input.into_iter().map(|element| self.serialize(element));
+        elements.collect::<Result<_>>().map(Value::Array)
+    }
+
+    fn visit_tuple<T: Tuple + ?Sized>(&self, input: &T) -> Result<Value> {
+        let mut elements = Vec::with_capacity(input.len());
+        input.serialize_elements(self, |element| elements.push(element))?;
+        Ok(Value::Array(elements))
+    }
+
+    fn visit_struct<T: Struct + ?Sized>(&self, input: &T) -> Result<Value> {
+        let fields = input.fields().iter().enumerate();
+        let entries = fields
+            .map(|(index, field)| Ok((field.name.to_owned(), input.serialize_field(index, self)?)));
+        entries.collect::<Result<_>>().map(Value::Object)
+    }
+
+    fn visit_variant(&self, variant: &'static str, content: Value) -> Result<Value> {
+        Ok(Value::Object(BTreeMap::from([(
+            variant.to_owned(),
+            content,
+        )])))
+    }
+
+    fn visit_map<'a, K, V, I>(&self, input: I) -> Result<Value>
+    where
+        K: Serialize + 'a,
+        V: Serialize + 'a,
+        I: IntoIterator<Item = (&'a K, &'a V)>,
+    {
+        let entries = input
+            .into_iter()
+            .map(|(key, value)| Ok((self.key(key)?, self.serialize(value)?)));
+        entries.collect::<Result<_>>().map(Value::Object)
+    }
+}
@@ -0,0 +1,119 @@
+//! Conversions between [`Value`] and `serde_json::Value`, for passing
+//! documents between code using either library.
+
+use super::{Number, Value, N};
+use crate::error::{Error, ErrorKind};
+
+/// Strings, arrays and objects are moved rather than copied.
+///
+/// ```
+/// use shallot::Value;
+///
+/// let value = Value::Array(vec![Value::from(-1), Value::from("a")]);
+/// assert_eq!(serde_json::Value::from(value), serde_json::json!([-1, "a"]));
+/// ```
+impl From<Value> for serde_json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::Null => Self::Null,
+            Value::Bool(value) => Self::Bool(value),
+            Value::Number(number) => Self::Number(number.into()),
+            Value::String(string) => Self::String(string),
+            Value::Array(array) => Self::Array(array.into_iter().map(Self::from).collect()),
+            Value::Object(object) => Self::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl From<Number> for serde_json::Number {
+    fn from(number: Number) -> Self {
+        match number.0 {
+            N::Unsigned(value) => value.into(),
+            N::Signed(value) => value.into(),
+            N::Float(value) => Self::from_f64(value).expect("numbers are finite"),
+        }
+    }
+}
+
+/// Objects are sorted by key. Only numbers beyond what a [`Number`] holds,
+/// which `serde_json` keeps with its `arbitrary_precision` feature, fail,
+/// with [`ErrorKind::Overflow`].
+///
+/// ```
+/// use shallot::Value;
+///
+/// let value = Value::try_from(serde_json::json!({"b": [1.5], "a": null}))?;
+/// assert_eq!(value.get("b").and_then(Value::as_array).unwrap()[0].as_f64(), Some(1.5));
+/// assert_eq!(value.get("a"), Some(&Value::Null));
+/// # Ok::<(), shallot::Error>(())
+/// ```
+impl TryFrom<serde_json::Value> for Value {
+    type Error = Error;
+
+    fn try_from(value: serde_json::Value) -> Result<Self, Error> {
+        Ok(match value {
+            serde_json::Value::Null => Self::Null,
+            serde_json::Value::Bool(value) => Self::Bool(value),
+            serde_json::Value::Number(number) => Self::Number(number.try_into()?),
+            serde_json::Value::String(string) => Self::String(string),
+            serde_json::Value::Array(array) => Self::Array(
+                array
+                    .into_iter()
+                    .map(Self::try_from)
+                    .collect::<Result<_, _>>()?,
+            ),
+            serde_json::Value::Object(object) => Self::Object(
+                object
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, value.try_into()?)))
+                    .collect::<Result<_, Error>>()?,
+            ),
+        })
+    }
+}
+
+impl TryFrom<serde_json::Number> for Number {
+    type Error = Error;
+
+    fn try_from(number: serde_json::Number) -> Result<Self, Error> {
+        if let Some(value) = number.as_u64() {
+            return Ok(value.into());
+        }
+        if let Some(value) = number.as_i64() {
+            return Ok(value.into());
+        }
+        number
+            .as_f64()
+            .and_then(Number::from_f64)
+            .ok_or_else(|| Error::new(ErrorKind::Overflow { target: "Number" }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let original = json!({
+            "null": null,
+            "flags": [true, false],
+            "numbers": [0, u64::MAX, i64::MIN, -0.5, 1e300],
+            "nested": {"text": "é\n", "empty": {}},
+        });
+        let value = Value::try_from(original.clone()).unwrap();
+        let numbers = value.get("numbers").and_then(Value::as_array).unwrap();
+        assert_eq!(numbers[1].as_u64(), Some(u64::MAX));
+        assert_eq!(numbers[2].as_i64(), Some(i64::MIN));
+        assert_eq!(numbers[3].as_f64(), Some(-0.5));
+        assert_eq!(serde_json::Value::from(value), original);
+        assert_eq!(serde_json::Number::from(Number::from(-3)), (-3).into());
+    }
+}
@@ -0,0 +1,114 @@
+//! An indented outline of a [`Value`], for reading nested documents in logs.
+
+use std::fmt::Write;
+
+use super::{Number, Value, N};
+
+impl Value {
+    /// Render the value as an indented tree with one line per value, each
+    /// naming its type, rather than as JSON.
+    ///
+    /// Entries are listed under their object or array two spaces further
+    /// in, led by the key or index. Strings and keys are quoted and escaped,
+    /// so each line holds exactly one value.
+    ///
+    /// ```
+    /// use shallot::{deserialize, Value};
+    ///
+    /// let value: Value =
+    ///     deserialize::Json::new().deserialize(r#"{"id": 7, "tags": ["a", null], "x": 0.5}"#)?;
+    /// assert_eq!(
+    ///     value.render_tree(),
+    ///     "object, 3 entries\n  \"id\": integer 7\n  \"tags\": array, 2 elements\n    \
+    ///      [0]: string \"a\"\n    [1]: null\n  \"x\": float 0.5",
+    /// );
+    /// # Ok::<(), shallot::Error>(())
+    /// ```
+    pub fn render_tree(&self) -> String {
+        let mut output = String::new();
+        self.render_node(0, &mut output);
+        output
+    }
+
+    /// Write the line for this value, which the caller has started with its
+    /// indentation and label, and the lines of its entries at `depth + 1`.
+    fn render_node(&self, depth: usize, output: &mut String) {
+        match self {
+            Self::Null => output.push_str("null"),
+            Self::Bool(value) => write!(output, "bool {value}").expect("writing to a String"),
+            Self::Number(Number(N::Float(value))) => {
+                write!(output, "float {value:?}").expect("writing to a String")
+            }
+            Self::Number(number) => {
+                write!(output, "integer {number}").expect("writing to a String")
+            }
+            Self::String(string) => {
+                write!(output, "string {string:?}").expect("writing to a String")
+            }
+            Self::Array(array) => {
+                write!(output, "array, {}", count(array.len(), "element"))
+                    .expect("writing to a String");
+                for (index, element) in array.iter().enumerate() {
+                    line(depth + 1, output);
+                    write!(output, "[{index}]: ").expect("writing to a String");
+                    element.render_node(depth + 1, output);
+                }
+            }
+            Self::Object(object) => {
+                write!(output, "object, {}", count(object.len(), "entry"))
+                    .expect("writing to a String");
+                for (key, value) in object {
+                    line(depth + 1, output);
+                    write!(output, "{key:?}: ").expect("writing to a String");
+                    value.render_node(depth + 1, output);
+                }
+            }
+        }
+    }
+}
+
+/// Start a new line indented for `depth`.
+fn line(depth: usize, output: &mut String) {
+    output.push('\n');
+    output.extend(std::iter::repeat_n("  ", depth));
+}
+
+/// `len` followed by `noun`, made plural unless `len` is one.
+fn count(len: usize, noun: &str) -> String {
+    match (len, noun.strip_suffix('y')) {
+        (1, _) => format!("1 {noun}"),
+        (_, Some(stem)) => format!("{len} {stem}ies"),
+        (_, None) => format!("{len} {noun}s"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deserialize;
+
+    #[test]
+    fn render_tree() {
+        let json = deserialize::Json::new();
+        let value: Value = json
+            .deserialize(
+                r#"{"a\nb": [[], {}, {"k": true}], "n": [-2, 1e300, 18446744073709551615]}"#,
+            )
+            .unwrap();
+        let expected = [
+            "object, 2 entries",
+            "  \"a\\nb\": array, 3 elements",
+            "    [0]: array, 0 elements",
+            "    [1]: object, 0 entries",
+            "    [2]: object, 1 entry",
+            "      \"k\": bool true",
+            "  \"n\": array, 3 elements",
+            "    [0]: integer -2",
+            "    [1]: float 1e300",
+            "    [2]: integer 18446744073709551615",
+        ];
+        assert_eq!(value.render_tree(), expected.join("\n"));
+        assert_eq!(Value::from("é\"").render_tree(), "string \"é\\\"\"");
+        assert_eq!(Value::Null.render_tree(), "null");
+    }
+}